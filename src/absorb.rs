@@ -0,0 +1,295 @@
+// Swap partition absorption for systemd-swap
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// `systemd-swap absorb-partition DEV` retires a static swap partition without
+// ever reducing the swap available to the system:
+//   1. Check the partition is active and measure how much it holds. The kernel
+//      cannot change the priority of an active area, so new pages may still
+//      land on it until step 3; ABSORB_MARGIN covers them.
+//   2. If the other swap areas cannot take that much, write WORK_DIR/absorb.
+//      While it exists the daemon's swap file monitor adds one file per
+//      emergency cooldown; we wait until there is room, then remove it.
+//   3. swapoff the partition; the kernel moves its pages to the other areas.
+//   4. Optionally wipe the swap signature, then report how to keep the
+//      partition from being activated again at boot.
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use thiserror::Error;
+
+use crate::context::work_dir;
+use crate::exec::TimedCommand;
+use crate::helpers::format_size;
+use crate::state::DaemonState;
+use crate::{info, warn};
+
+/// Extra free space required beyond what the partition holds
+const ABSORB_MARGIN: u64 = 256 * 1024 * 1024;
+
+/// How long to wait for the daemon to add replacement capacity
+const ABSORB_WAIT: Duration = Duration::from_secs(300);
+
+#[derive(Error, Debug)]
+pub enum AbsorbError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Systemd error: {0}")]
+    Systemd(#[from] crate::systemd::SystemdError),
+    #[error("{0} is not an active swap area")]
+    NotActive(String),
+    #[error("{0} is not a swap partition (type {1})")]
+    NotPartition(String, String),
+    #[error("not enough swap elsewhere to drain {device}: need {needed}, {available} free")]
+    InsufficientCapacity {
+        device: String,
+        needed: String,
+        available: String,
+    },
+    #[error("wipefs {0} failed")]
+    WipeFailed(String),
+}
+
+pub type Result<T> = std::result::Result<T, AbsorbError>;
+
+fn request_file() -> String {
+    format!("{}/absorb", work_dir())
+}
+
+/// Whether `absorb-partition` is waiting for the daemon to add capacity
+pub fn capacity_requested() -> bool {
+    Path::new(&request_file()).exists()
+}
+
+/// One line of /proc/swaps, sizes in bytes
+#[derive(Debug, Clone, PartialEq)]
+pub struct SwapArea {
+    pub path: String,
+    pub kind: String,
+    pub size: u64,
+    pub used: u64,
+    pub priority: i32,
+}
+
+impl SwapArea {
+    fn free(&self) -> u64 {
+        self.size.saturating_sub(self.used)
+    }
+}
+
+fn parse_swaps(content: &str) -> Vec<SwapArea> {
+    content
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let [path, kind, size, used, priority, ..] = fields[..] else {
+                return None;
+            };
+            Some(SwapArea {
+                path: path.to_string(),
+                kind: kind.to_string(),
+                size: size.parse::<u64>().ok()? * 1024,
+                used: used.parse::<u64>().ok()? * 1024,
+                priority: priority.parse().ok()?,
+            })
+        })
+        .collect()
+}
+
+/// Active swap areas from /proc/swaps
+pub fn read_swaps() -> Vec<SwapArea> {
+    parse_swaps(&fs::read_to_string("/proc/swaps").unwrap_or_default())
+}
+
+/// Free space on every active swap area except `device`
+fn free_elsewhere(device: &str) -> u64 {
+    read_swaps()
+        .iter()
+        .filter(|a| a.path != device)
+        .map(SwapArea::free)
+        .sum()
+}
+
+/// Identifiers fstab may use for a device (UUID=, PARTUUID=, LABEL=)
+fn device_tags(device: &str) -> Vec<String> {
+    let Ok(output) = Command::new("blkid")
+        .args(["-o", "export", device])
+        .stderr(Stdio::null())
+        .output_timed()
+    else {
+        return Vec::new();
+    };
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter(|l| ["UUID=", "PARTUUID=", "LABEL="].iter().any(|p| l.starts_with(p)))
+        .map(str::to_string)
+        .collect()
+}
+
+/// fstab swap lines that refer to `device` by path, tag or /dev/disk/by-* link
+fn fstab_entries(fstab: &str, device: &str, tags: &[String]) -> Vec<String> {
+    let by_link = |spec: &str| {
+        fs::canonicalize(spec)
+            .map(|p| p.to_string_lossy() == device)
+            .unwrap_or(false)
+    };
+    fstab
+        .lines()
+        .filter(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let [spec, _, fstype, ..] = fields[..] else {
+                return false;
+            };
+            !spec.starts_with('#')
+                && fstype == "swap"
+                && (spec == device
+                    || tags.iter().any(|t| *t == spec.replace('"', ""))
+                    || (spec.starts_with("/dev/disk/") && by_link(spec)))
+        })
+        .map(str::to_string)
+        .collect()
+}
+
+fn swap_unit_name(device: &str) -> String {
+    Command::new("systemd-escape")
+        .args(["-p", "--suffix=swap", device])
+        .output_timed()
+        .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+        .unwrap_or_default()
+}
+
+/// Outcome of absorbing a partition
+#[derive(Debug, Clone)]
+pub struct AbsorbReport {
+    pub device: String,
+    /// Bytes that were swapped out to the partition when it was drained
+    pub drained: u64,
+    pub wiped: bool,
+    /// fstab lines that would activate the partition again at boot
+    pub fstab_lines: Vec<String>,
+    /// Swap unit to mask if the partition is activated by systemd-gpt-auto-generator
+    pub unit: String,
+}
+
+/// Wait for `needed` bytes of free swap outside `device`, asking the daemon for files
+fn ensure_capacity(device: &str, needed: u64) -> Result<()> {
+    let available = free_elsewhere(device);
+    if available >= needed {
+        return Ok(());
+    }
+    let insufficient = |available: u64| AbsorbError::InsufficientCapacity {
+        device: device.to_string(),
+        needed: format_size(needed),
+        available: format_size(available),
+    };
+    let manages_files = DaemonState::load()
+        .is_some_and(|d| d.mode.contains("swapfc") || d.mode == "manual");
+    if !manages_files {
+        return Err(insufficient(available));
+    }
+
+    info!(
+        "Absorb: {} free elsewhere, {} needed - asking the daemon for swap files",
+        format_size(available),
+        format_size(needed)
+    );
+    fs::write(request_file(), format!("{}\n", needed))?;
+    let deadline = Instant::now() + ABSORB_WAIT;
+    let result = loop {
+        let available = free_elsewhere(device);
+        if available >= needed {
+            break Ok(());
+        }
+        if Instant::now() >= deadline || crate::is_shutdown() {
+            break Err(insufficient(available));
+        }
+        thread::sleep(Duration::from_secs(2));
+    };
+    let _ = fs::remove_file(request_file());
+    result
+}
+
+/// Drain and deactivate a swap partition, moving its pages to managed swap
+pub fn absorb_partition(device: &str, wipe: bool) -> Result<AbsorbReport> {
+    let device = fs::canonicalize(device)?.to_string_lossy().into_owned();
+    let area = read_swaps()
+        .into_iter()
+        .find(|a| a.path == device)
+        .ok_or_else(|| AbsorbError::NotActive(device.clone()))?;
+    if area.kind != "partition" || device.starts_with("/dev/zram") || device.starts_with("/dev/loop") {
+        return Err(AbsorbError::NotPartition(device, area.kind));
+    }
+
+    let higher: Vec<String> = read_swaps()
+        .into_iter()
+        .filter(|a| a.path != device && a.priority > area.priority)
+        .map(|a| a.path)
+        .collect();
+    if higher.is_empty() {
+        warn!(
+            "Absorb: {} has the highest swap priority ({}); new pages go to it until it is drained",
+            device, area.priority
+        );
+    }
+
+    ensure_capacity(&device, area.used + ABSORB_MARGIN)?;
+
+    // Tags must be read while the swap signature still exists
+    let tags = device_tags(&device);
+
+    info!("Absorb: draining {} ({} in use)", device, format_size(area.used));
+    crate::systemd::swapoff(&device)?;
+    info!("Absorb: {} deactivated", device);
+
+    if wipe {
+        let status = Command::new("wipefs").args(["-a", &device]).stdout(Stdio::null()).status_timed()?;
+        if !status.success() {
+            return Err(AbsorbError::WipeFailed(device));
+        }
+        info!("Absorb: swap signature removed from {}", device);
+    }
+
+    Ok(AbsorbReport {
+        fstab_lines: fstab_entries(&fs::read_to_string("/etc/fstab").unwrap_or_default(), &device, &tags),
+        unit: swap_unit_name(&device),
+        drained: area.used,
+        wiped: wipe,
+        device,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_proc_swaps() {
+        let swaps = "Filename\t\t\t\tType\t\tSize\t\tUsed\t\tPriority\n\
+                     /dev/sda2                               partition\t8388604\t\t1024\t\t-2\n\
+                     /dev/zram0                              partition\t16777212\t0\t\t32767\n";
+        let areas = parse_swaps(swaps);
+        assert_eq!(areas.len(), 2);
+        assert_eq!(areas[0].path, "/dev/sda2");
+        assert_eq!(areas[0].used, 1024 * 1024);
+        assert_eq!(areas[0].priority, -2);
+        assert_eq!(areas[1].free(), 16777212 * 1024);
+    }
+
+    #[test]
+    fn finds_fstab_lines_by_path_and_tag() {
+        let fstab = "UUID=abcd / btrfs defaults 0 0\n\
+                     UUID=1234-5678 none swap defaults 0 0\n\
+                     #/dev/sda2 none swap defaults 0 0\n\
+                     /dev/sda2 none swap sw 0 0\n\
+                     /dev/sdb1 none swap sw 0 0\n";
+        let tags = vec!["UUID=1234-5678".to_string()];
+        assert_eq!(
+            fstab_entries(fstab, "/dev/sda2", &tags),
+            ["UUID=1234-5678 none swap defaults 0 0", "/dev/sda2 none swap sw 0 0"]
+        );
+    }
+}
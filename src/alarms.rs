@@ -0,0 +1,338 @@
+// Swap usage alarms with user hooks for systemd-swap
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+use crate::defaults;
+use crate::exec::TimedCommand;
+use crate::{info, is_shutdown, warn};
+
+/// Seconds between alarm evaluations
+const ALARM_INTERVAL: u64 = 10;
+
+/// D-Bus object and interface used for alarm signals
+const DBUS_PATH: &str = "/org/biglinux/SystemdSwap";
+const DBUS_INTERFACE: &str = "org.biglinux.SystemdSwap";
+
+/// Alarm conditions the daemon can detect
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AlarmKind {
+    /// Swap usage above the threshold for the configured duration
+    SwapUsage,
+    /// Zswap rejecting pages faster than the configured rate
+    ZswapRejects,
+}
+
+impl AlarmKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            AlarmKind::SwapUsage => "swap_usage",
+            AlarmKind::ZswapRejects => "zswap_rejects",
+        }
+    }
+}
+
+/// A raised or cleared alarm
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlarmEvent {
+    pub kind: AlarmKind,
+    pub raised: bool,
+    /// Value that triggered the transition (percent or rejects/minute)
+    pub value: u64,
+}
+
+/// One measurement of the watched values
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AlarmSample {
+    /// Swap used, percent of total
+    pub swap_used_percent: u8,
+    /// Cumulative zswap reject counter (None without debugfs access)
+    pub zswap_rejects: Option<u64>,
+}
+
+impl AlarmSample {
+    /// Read current values from /proc/meminfo and zswap debugfs
+    pub fn read() -> Self {
+        let swap_used_percent = crate::meminfo::get_mem_stats(&["SwapTotal", "SwapFree"])
+            .ok()
+            .filter(|s| s["SwapTotal"] > 0)
+            .map(|s| {
+                let used = s["SwapTotal"].saturating_sub(s["SwapFree"]);
+                (used * 100 / s["SwapTotal"]).min(100) as u8
+            })
+            .unwrap_or(0);
+        let zswap_rejects = crate::zswap::get_status().filter(|z| z.enabled).map(|z| {
+            z.reject_compress_fail + z.reject_compress_poor + z.reject_reclaim_fail
+        });
+        Self {
+            swap_used_percent,
+            zswap_rejects,
+        }
+    }
+}
+
+/// Alarm thresholds and delivery settings
+#[derive(Debug, Clone)]
+pub struct AlarmConfig {
+    /// Executable run on every alarm transition
+    pub hook: Option<PathBuf>,
+    /// Also emit a D-Bus signal through busctl
+    pub dbus: bool,
+    /// Swap usage threshold in percent (0 = disabled)
+    pub swap_used_perc: u8,
+    /// How long swap usage must stay above the threshold
+    pub swap_used_duration: Duration,
+    /// Zswap rejects per minute threshold (0 = disabled)
+    pub zswap_reject_rate: u64,
+}
+
+impl AlarmConfig {
+    pub fn from_config(config: &Config) -> Self {
+        let hook = config
+            .get("alarm_hook")
+            .unwrap_or(defaults::ALARM_HOOK)
+            .trim()
+            .to_string();
+        Self {
+            hook: (!hook.is_empty()).then(|| PathBuf::from(hook)),
+            dbus: config.get_bool("alarm_dbus"),
+            swap_used_perc: config
+                .get_as::<u32>("alarm_swap_used_perc")
+                .unwrap_or(defaults::ALARM_SWAP_USED_PERC as u32)
+                .min(100) as u8,
+            swap_used_duration: Duration::from_secs(
+                config
+                    .get_as("alarm_swap_used_duration")
+                    .unwrap_or(defaults::ALARM_SWAP_USED_DURATION),
+            ),
+            zswap_reject_rate: config
+                .get_as("alarm_zswap_reject_rate")
+                .unwrap_or(defaults::ALARM_ZSWAP_REJECT_RATE),
+        }
+    }
+
+    /// Whether any delivery channel is configured
+    pub fn enabled(&self) -> bool {
+        self.hook.is_some() || self.dbus
+    }
+}
+
+/// Tracks alarm state across samples and reports transitions
+pub struct AlarmMonitor {
+    config: AlarmConfig,
+    swap_high_since: Option<Instant>,
+    swap_alarm: bool,
+    last_rejects: Option<(Instant, u64)>,
+    reject_alarm: bool,
+}
+
+impl AlarmMonitor {
+    pub fn new(config: AlarmConfig) -> Self {
+        Self {
+            config,
+            swap_high_since: None,
+            swap_alarm: false,
+            last_rejects: None,
+            reject_alarm: false,
+        }
+    }
+
+    /// Feed one sample taken at `now`; returns alarms that were raised or cleared
+    pub fn evaluate(&mut self, sample: AlarmSample, now: Instant) -> Vec<AlarmEvent> {
+        let mut events = Vec::new();
+
+        if self.config.swap_used_perc > 0 {
+            let value = sample.swap_used_percent as u64;
+            if sample.swap_used_percent > self.config.swap_used_perc {
+                let since = *self.swap_high_since.get_or_insert(now);
+                if !self.swap_alarm && now.duration_since(since) >= self.config.swap_used_duration {
+                    self.swap_alarm = true;
+                    events.push(AlarmEvent {
+                        kind: AlarmKind::SwapUsage,
+                        raised: true,
+                        value,
+                    });
+                }
+            } else {
+                self.swap_high_since = None;
+                if self.swap_alarm {
+                    self.swap_alarm = false;
+                    events.push(AlarmEvent {
+                        kind: AlarmKind::SwapUsage,
+                        raised: false,
+                        value,
+                    });
+                }
+            }
+        }
+
+        if self.config.zswap_reject_rate > 0 {
+            if let Some(rejects) = sample.zswap_rejects {
+                if let Some((then, prev)) = self.last_rejects {
+                    let secs = now.duration_since(then).as_secs().max(1);
+                    let per_minute = rejects.saturating_sub(prev) * 60 / secs;
+                    let high = per_minute > self.config.zswap_reject_rate;
+                    if high != self.reject_alarm {
+                        self.reject_alarm = high;
+                        events.push(AlarmEvent {
+                            kind: AlarmKind::ZswapRejects,
+                            raised: high,
+                            value: per_minute,
+                        });
+                    }
+                }
+                self.last_rejects = Some((now, rejects));
+            }
+        }
+
+        events
+    }
+
+    /// Deliver an event to the hook and/or D-Bus
+    fn dispatch(&self, event: &AlarmEvent) {
+        let state = if event.raised { "raised" } else { "cleared" };
+        let value = event.value.to_string();
+        if event.raised {
+            warn!("Alarm: {} {} (value {})", event.kind.name(), state, value);
+        } else {
+            info!("Alarm: {} {} (value {})", event.kind.name(), state, value);
+        }
+
+        if let Some(ref hook) = self.config.hook {
+            let spawned = Command::new(hook)
+                .args([event.kind.name(), state, &value])
+                .env("SWAP_ALARM", event.kind.name())
+                .env("SWAP_ALARM_STATE", state)
+                .env("SWAP_ALARM_VALUE", &value)
+                .stdin(Stdio::null())
+                .spawn();
+            match spawned {
+                // Reap in the background so a slow hook never blocks monitoring
+                Ok(mut child) => {
+                    crate::context::spawn(move || {
+                        let _ = child.wait();
+                    });
+                }
+                Err(e) => warn!("Alarm: failed to run hook {}: {}", hook.display(), e),
+            }
+        }
+
+        if self.config.dbus {
+            let status = Command::new("busctl")
+                .args([
+                    "--system",
+                    "emit",
+                    DBUS_PATH,
+                    DBUS_INTERFACE,
+                    "Alarm",
+                    "sst",
+                    event.kind.name(),
+                    state,
+                    &value,
+                ])
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status_timed();
+            if !matches!(status, Ok(s) if s.success()) {
+                warn!("Alarm: failed to emit D-Bus signal for {}", event.kind.name());
+            }
+        }
+    }
+
+    /// Evaluate alarms in a background thread until shutdown
+    pub fn spawn(mut self) {
+        crate::context::spawn(move || {
+            while !is_shutdown() {
+                let events = self.evaluate(AlarmSample::read(), Instant::now());
+                for event in &events {
+                    self.dispatch(event);
+                }
+                thread::sleep(Duration::from_secs(ALARM_INTERVAL));
+            }
+        });
+    }
+}
+
+/// Start the alarm monitor if a hook or D-Bus delivery is configured
+pub fn start(config: &Config) {
+    let alarm_config = AlarmConfig::from_config(config);
+    if !alarm_config.enabled() {
+        return;
+    }
+    info!(
+        "Alarm: watching swap > {}% for {}s, zswap rejects > {}/min",
+        alarm_config.swap_used_perc,
+        alarm_config.swap_used_duration.as_secs(),
+        alarm_config.zswap_reject_rate
+    );
+    AlarmMonitor::new(alarm_config).spawn();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn monitor() -> AlarmMonitor {
+        AlarmMonitor::new(AlarmConfig {
+            hook: None,
+            dbus: false,
+            swap_used_perc: 85,
+            swap_used_duration: Duration::from_secs(300),
+            zswap_reject_rate: 100,
+        })
+    }
+
+    fn swap(percent: u8) -> AlarmSample {
+        AlarmSample {
+            swap_used_percent: percent,
+            zswap_rejects: None,
+        }
+    }
+
+    #[test]
+    fn swap_alarm_needs_sustained_usage() {
+        let mut m = monitor();
+        let t0 = Instant::now();
+        assert!(m.evaluate(swap(90), t0).is_empty());
+        assert!(m.evaluate(swap(90), t0 + Duration::from_secs(200)).is_empty());
+        let events = m.evaluate(swap(92), t0 + Duration::from_secs(300));
+        assert_eq!(events.len(), 1);
+        assert!(events[0].raised);
+        // Already raised: no repeat
+        assert!(m.evaluate(swap(95), t0 + Duration::from_secs(400)).is_empty());
+        let events = m.evaluate(swap(50), t0 + Duration::from_secs(410));
+        assert_eq!(events.len(), 1);
+        assert!(!events[0].raised);
+    }
+
+    #[test]
+    fn swap_dip_resets_timer() {
+        let mut m = monitor();
+        let t0 = Instant::now();
+        m.evaluate(swap(90), t0);
+        m.evaluate(swap(80), t0 + Duration::from_secs(200));
+        assert!(m.evaluate(swap(90), t0 + Duration::from_secs(310)).is_empty());
+    }
+
+    #[test]
+    fn zswap_reject_rate() {
+        let mut m = monitor();
+        let t0 = Instant::now();
+        let rejects = |n| AlarmSample {
+            swap_used_percent: 0,
+            zswap_rejects: Some(n),
+        };
+        assert!(m.evaluate(rejects(1000), t0).is_empty());
+        // 300 rejects in 60s = 300/min > 100/min
+        let events = m.evaluate(rejects(1300), t0 + Duration::from_secs(60));
+        assert_eq!(events[0].kind, AlarmKind::ZswapRejects);
+        assert_eq!(events[0].value, 300);
+        assert!(events[0].raised);
+        let events = m.evaluate(rejects(1310), t0 + Duration::from_secs(120));
+        assert!(!events[0].raised);
+    }
+}
@@ -0,0 +1,165 @@
+// Size alignment of loop-backed swap files for systemd-swap
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// A sparse swap file gets its blocks from the filesystem as swap writes them,
+// and its loop device passes discards back as hole punches. A size that is
+// not a multiple of the filesystem block (the cluster on ext4 bigalloc)
+// leaves a partial block at the end of the file. A size that is not a
+// multiple of the disk's discard granularity leaves ranges the device cannot
+// trim; it keeps them mapped and rewrites them later (write amplification).
+// At creation each file's size is rounded down to the least common multiple
+// of the page size, the filesystem block and the largest discard granularity
+// among the disks under the filesystem (through disks.rs, so LVM and md count).
+// A partition that does not start on the granularity (discard_alignment) cannot
+// be fixed from here and is only reported.
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::helpers::format_size;
+use crate::swapfile::source_block_device;
+
+/// Swap pages are this large on every architecture systemd-swap runs on
+const PAGE_SIZE: u64 = 4096;
+
+/// What sizes of files in a directory are aligned to
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Alignment {
+    /// Filesystem block or cluster size
+    pub fs_block: u64,
+    /// Largest discard granularity of the disks below; 0 without discard
+    pub discard_granularity: u64,
+    /// Bytes the partition start is off the granularity; 0 when aligned
+    pub discard_offset: u64,
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+fn lcm(a: u64, b: u64) -> u64 {
+    match (a, b) {
+        (0, x) | (x, 0) => x,
+        _ => a / gcd(a, b) * b,
+    }
+}
+
+fn read_u64(path: &str) -> u64 {
+    fs::read_to_string(path).ok().and_then(|s| s.trim().parse().ok()).unwrap_or(0)
+}
+
+impl Alignment {
+    /// Alignment for files in `dir`, from its filesystem and the disks below
+    pub fn detect(dir: &Path) -> Self {
+        let existing = dir.ancestors().find(|p| p.exists()).unwrap_or(Path::new("/"));
+        let fs_block = nix::sys::statvfs::statvfs(existing)
+            .map(|s| s.block_size().max(s.fragment_size()))
+            .unwrap_or(0);
+        let Some(device) = source_block_device(dir) else {
+            return Self {
+                fs_block,
+                ..Self::default()
+            };
+        };
+        // Device-mapper devices have a queue of their own; partitions only
+        // through their disk
+        let mut queues = crate::disks::whole_disks(&device);
+        queues.push(device.clone());
+        let discard_granularity = queues
+            .iter()
+            .map(|name| read_u64(&format!("/sys/class/block/{}/queue/discard_granularity", name)))
+            .max()
+            .unwrap_or(0);
+        Self {
+            fs_block,
+            discard_granularity,
+            discard_offset: read_u64(&format!("/sys/class/block/{}/discard_alignment", device)),
+        }
+    }
+
+    /// Sizes are multiples of this
+    pub fn unit(&self) -> u64 {
+        lcm(lcm(PAGE_SIZE, self.fs_block), self.discard_granularity)
+    }
+
+    pub fn is_aligned(&self, size: u64) -> bool {
+        size.is_multiple_of(self.unit())
+    }
+
+    /// `size` rounded down to a multiple of the unit; sizes below one unit
+    /// are left as they are
+    pub fn align(&self, size: u64) -> u64 {
+        let unit = self.unit();
+        if size < unit {
+            size
+        } else {
+            size / unit * unit
+        }
+    }
+
+    /// Files directly in `dir` whose size is not a multiple of the unit
+    pub fn misaligned_files(&self, dir: &Path) -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = fs::read_dir(dir)
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .filter(|e| e.file_type().is_ok_and(|t| t.is_file()))
+                    .filter(|e| e.metadata().is_ok_and(|m| !self.is_aligned(m.len())))
+                    .map(|e| e.path())
+                    .collect()
+            })
+            .unwrap_or_default();
+        files.sort();
+        files
+    }
+}
+
+impl fmt::Display for Alignment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} multiples ({} blocks", format_size(self.unit()), format_size(self.fs_block))?;
+        match self.discard_granularity {
+            0 => write!(f, ", no discard)")?,
+            granularity => write!(f, ", {} discard granularity)", format_size(granularity))?,
+        }
+        if self.discard_offset > 0 {
+            write!(f, ", partition start {} off the discard granularity", format_size(self.discard_offset))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::{KB, MB};
+
+    #[test]
+    fn sizes_round_down_to_common_multiple() {
+        let ssd = Alignment {
+            fs_block: 4 * KB,
+            discard_granularity: 512 * KB,
+            discard_offset: 0,
+        };
+        assert_eq!(ssd.unit(), 512 * KB);
+        assert!(ssd.is_aligned(512 * MB));
+        // 5% of a 15.5 GiB machine
+        assert_eq!(ssd.align(812_646_400), 812_646_400 / (512 * KB) * 512 * KB);
+        assert_eq!(ssd.align(100 * KB), 100 * KB);
+
+        // bigalloc cluster of 64K, discard of 3 x 128K (RAID stripe)
+        let odd = Alignment {
+            fs_block: 64 * KB,
+            discard_granularity: 384 * KB,
+            discard_offset: 0,
+        };
+        assert_eq!(odd.unit(), 384 * KB);
+        let none = Alignment::default();
+        assert_eq!(none.unit(), PAGE_SIZE);
+        assert_eq!(none.to_string(), "4 KiB multiples (0 B blocks, no discard)");
+    }
+}
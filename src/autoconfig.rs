@@ -1,28 +1,118 @@
 // Automatic system detection and configuration for systemd-swap
 // SPDX-License-Identifier: GPL-3.0-or-later
 
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
 use std::path::Path;
 
+use crate::context::work_dir;
 use crate::defaults;
-use crate::helpers::{get_fstype, MB, GB};
+use crate::disks::SharedArea;
+use crate::helpers::{get_fstype, now_secs, MB, GB};
 use crate::meminfo::get_ram_size;
+use crate::workload::WorkloadClass;
 use crate::{debug, info};
 
+/// Zram disksize on diskless systems (% of RAM); nothing backs it on overflow
+const DISKLESS_ZRAM_SIZE_PERCENT: u32 = 100;
+/// Physical RAM the zram pool may use on diskless systems
+const DISKLESS_ZRAM_MEM_LIMIT: &str = "25%";
+
+/// Swap file chunk on build hosts, where a parallel link outgrows 512M at once
+const BUILD_CHUNK_SIZE: &str = "1G";
+/// Free RAM (%) below which build hosts add a swap file: bursts outrun 20%
+const BUILD_FREE_RAM_PERC: u8 = 30;
+/// Free swap (%) above which build hosts remove files; kept between bursts
+const BUILD_REMOVE_FREE_SWAP_PERC: u8 = 85;
+
+/// Cached detection results are reused for this long
+const CAPS_CACHE_TTL: u64 = 600;
+
+fn caps_cache_file() -> String {
+    format!("{}/capabilities", work_dir())
+}
+
+/// Changes when block devices, RAM or CPUs are hot-plugged, or the binary is upgraded
+fn hardware_fingerprint() -> u64 {
+    let mut hasher = DefaultHasher::new();
+    env!("CARGO_PKG_VERSION").hash(&mut hasher);
+    fs::read_to_string("/proc/partitions").unwrap_or_default().hash(&mut hasher);
+    get_ram_size().unwrap_or(0).hash(&mut hasher);
+    std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1).hash(&mut hasher);
+    hasher.finish()
+}
 
 /// Full system capabilities
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct SystemCapabilities {
     pub swap_path_fstype: Option<String>,
     pub free_disk_space_bytes: u64,
     pub total_ram_bytes: u64,
     pub is_live_system: bool,
+    /// Read-only root/usr (ostree, composefs and similar image-based systems)
+    pub immutable_root: bool,
+    /// Where swapfiles should go on this system
+    pub swap_path: String,
+    /// No writable persistent storage at all (network boot, thin clients)
+    pub diskless: bool,
     pub cpu_count: usize,
 }
 
+/// Detect an image-based system whose root or /usr is read-only at runtime
+pub fn is_immutable_root() -> bool {
+    if Path::new("/run/ostree-booted").exists() {
+        return true;
+    }
+    if matches!(get_fstype("/").as_deref(), Some("composefs") | Some("erofs")) {
+        return true;
+    }
+    ["/", "/usr"].iter().any(|path| {
+        nix::sys::statvfs::statvfs(*path)
+            .map(|st| st.flags().contains(nix::sys::statvfs::FsFlags::ST_RDONLY))
+            .unwrap_or(false)
+    })
+}
+
+/// Filesystems that never hold writable persistent data
+const READ_ONLY_FS: &[&str] = &["squashfs", "iso9660", "erofs", "udf"];
+
+/// Whether any writable mount in /proc/mounts is backed by a real block device
+fn has_writable_disk(mounts: &str) -> bool {
+    mounts.lines().any(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let [source, _target, fstype, options, ..] = fields[..] else {
+            return false;
+        };
+        let volatile = ["/dev/loop", "/dev/zram", "/dev/ram"]
+            .iter()
+            .any(|prefix| source.starts_with(prefix));
+        source.starts_with("/dev/")
+            && !volatile
+            && !READ_ONLY_FS.contains(&fstype)
+            && options.split(',').any(|opt| opt == "rw")
+    })
+}
+
+/// Detect a system with no writable persistent storage (network root, tmpfs only)
+pub fn is_diskless() -> bool {
+    std::fs::read_to_string("/proc/mounts")
+        .map(|mounts| !has_writable_disk(&mounts))
+        .unwrap_or(false)
+}
+
 impl SystemCapabilities {
     /// Detect system capabilities
     pub fn detect() -> Self {
-        let swap_path = "/swapfile";
+        // On immutable roots /swapfile can't be created; /var stays writable
+        let immutable_root = is_immutable_root();
+        let swap_path = if immutable_root {
+            info!("Autoconfig: Detected immutable root - swapfiles go to {}",
+                defaults::SWAPFILE_PATH_IMMUTABLE);
+            defaults::SWAPFILE_PATH_IMMUTABLE
+        } else {
+            defaults::SWAPFILE_PATH
+        };
         let swap_path_fstype = get_fstype(swap_path).or_else(|| get_fstype("/"));
         let total_ram = get_ram_size().unwrap_or(0);
         let free_space = Self::get_free_disk_space(swap_path).unwrap_or(0);
@@ -32,7 +122,10 @@ impl SystemCapabilities {
             Some("tmpfs") | Some("squashfs") | Some("overlay")
         );
 
-        if is_live {
+        let diskless = is_diskless();
+        if diskless {
+            info!("Autoconfig: Detected diskless system - will use capped zram only");
+        } else if is_live {
             info!("Autoconfig: Detected LiveCD/Live system - will use zram only");
         }
 
@@ -47,18 +140,99 @@ impl SystemCapabilities {
             free_disk_space_bytes: free_space,
             total_ram_bytes: total_ram,
             is_live_system: is_live,
+            immutable_root,
+            swap_path: swap_path.to_string(),
+            diskless,
             cpu_count: std::thread::available_parallelism()
                 .map(|n| n.get())
                 .unwrap_or(1),
         }
     }
 
+    /// Detection result from WORK_DIR if it is recent and the hardware is unchanged,
+    /// otherwise a fresh detection (which is cached for the next caller).
+    ///
+    /// Free disk space is always re-read; it is cheap and changes constantly.
+    pub fn detect_cached() -> Self {
+        let fingerprint = hardware_fingerprint();
+        let cached = fs::read_to_string(caps_cache_file())
+            .ok()
+            .and_then(|content| Self::from_cache(&content, fingerprint, now_secs()));
+        if let Some(mut caps) = cached {
+            debug!("Autoconfig: using cached system capabilities");
+            caps.free_disk_space_bytes = Self::get_free_disk_space(&caps.swap_path).unwrap_or(0);
+            return caps;
+        }
+        let caps = Self::detect();
+        caps.save_cache(fingerprint);
+        caps
+    }
+
+    /// Store this detection for detect_cached(); skipped when WORK_DIR is absent
+    pub fn save_cache(&self, fingerprint: u64) {
+        if Path::new(&work_dir()).is_dir() && !crate::readonly::is_enabled() {
+            let _ = fs::write(caps_cache_file(), self.to_cache(fingerprint, now_secs()));
+        }
+    }
+
+    /// Store this detection under the current hardware fingerprint
+    pub fn refresh_cache(&self) {
+        self.save_cache(hardware_fingerprint());
+    }
+
+    fn to_cache(&self, fingerprint: u64, now: u64) -> String {
+        format!(
+            "cached={}\nfingerprint={}\nfstype={}\nfree_disk={}\nram={}\nlive={}\nimmutable={}\n\
+             swap_path={}\ndiskless={}\ncpus={}\n",
+            now,
+            fingerprint,
+            self.swap_path_fstype.as_deref().unwrap_or(""),
+            self.free_disk_space_bytes,
+            self.total_ram_bytes,
+            self.is_live_system as u8,
+            self.immutable_root as u8,
+            self.swap_path,
+            self.diskless as u8,
+            self.cpu_count
+        )
+    }
+
+    /// Parse a cache file; None if it is stale, incomplete or for other hardware
+    fn from_cache(content: &str, fingerprint: u64, now: u64) -> Option<Self> {
+        let values: std::collections::HashMap<&str, &str> = content
+            .lines()
+            .filter_map(|l| l.split_once('='))
+            .collect();
+        let number = |key: &str| values.get(key)?.parse::<u64>().ok();
+        let flag = |key: &str| number(key).map(|v| v == 1);
+
+        let cached = number("cached")?;
+        if number("fingerprint")? != fingerprint || now.saturating_sub(cached) > CAPS_CACHE_TTL {
+            return None;
+        }
+        let fstype = values.get("fstype")?;
+        Some(Self {
+            swap_path_fstype: (!fstype.is_empty()).then(|| fstype.to_string()),
+            free_disk_space_bytes: number("free_disk")?,
+            total_ram_bytes: number("ram")?,
+            is_live_system: flag("live")?,
+            immutable_root: flag("immutable")?,
+            swap_path: values.get("swap_path")?.to_string(),
+            diskless: flag("diskless")?,
+            cpu_count: number("cpus")? as usize,
+        })
+    }
+
     /// Get free disk space for a path using statvfs
     fn get_free_disk_space(path: &str) -> Option<u64> {
         let check_path = if Path::new(path).exists() {
             path.to_string()
         } else {
-            "/".to_string()
+            Path::new(path)
+                .parent()
+                .filter(|p| p.exists())
+                .map(|p| p.to_string_lossy().into_owned())
+                .unwrap_or_else(|| "/".to_string())
         };
 
         nix::sys::statvfs::statvfs(check_path.as_str())
@@ -92,6 +266,14 @@ pub struct RecommendedConfig {
     pub swapfc_free_ram_perc: u8,
     pub swapfc_free_swap_perc: u8,
     pub swapfc_remove_free_swap_perc: u8,
+    /// Swapfile location, only injected when it differs from the default
+    pub swapfc_path: Option<String>,
+    /// Zram physical memory cap, only injected when set (diskless systems)
+    pub zram_mem_limit: Option<String>,
+    /// Workload class the thresholds were tuned for
+    pub workload: WorkloadClass,
+    /// Swap area of others on the spinning disk of the swap file directory
+    pub shared_disk: Option<SharedArea>,
 }
 
 impl Default for RecommendedConfig {
@@ -112,6 +294,22 @@ impl RecommendedConfig {
             swapfc_free_ram_perc: defaults::SWAPFILE_FREE_RAM_PERC,
             swapfc_free_swap_perc: defaults::SWAPFILE_FREE_SWAP_PERC,
             swapfc_remove_free_swap_perc: defaults::SWAPFILE_REMOVE_FREE_SWAP_PERC,
+            swapfc_path: None,
+            zram_mem_limit: None,
+            workload: WorkloadClass::default(),
+            shared_disk: None,
+        }
+    }
+
+    /// Capped zram-only config for systems without persistent storage.
+    ///
+    /// There is no disk to overflow to, so the pool is kept smaller and its
+    /// physical RAM use is limited to leave room for the page cache.
+    fn zram_diskless() -> Self {
+        Self {
+            zram_size_percent: DISKLESS_ZRAM_SIZE_PERCENT,
+            zram_mem_limit: Some(DISKLESS_ZRAM_MEM_LIMIT.to_string()),
+            ..Self::zram_only()
         }
     }
 
@@ -129,6 +327,10 @@ impl RecommendedConfig {
             swapfc_free_ram_perc: defaults::SWAPFILE_FREE_RAM_PERC,
             swapfc_free_swap_perc: defaults::SWAPFILE_FREE_SWAP_PERC,
             swapfc_remove_free_swap_perc: defaults::SWAPFILE_REMOVE_FREE_SWAP_PERC,
+            swapfc_path: None,
+            zram_mem_limit: None,
+            workload: WorkloadClass::default(),
+            shared_disk: None,
         }
     }
 
@@ -137,6 +339,40 @@ impl RecommendedConfig {
         Self::build_config(caps)
     }
 
+    /// Tune the swap file thresholds for a workload class. Desktops and
+    /// servers keep the defaults; build hosts add larger files earlier and
+    /// keep them between bursts, so parallel jobs do not outrun expansion
+    /// and the pool does not churn files from one build to the next.
+    pub fn with_workload(mut self, workload: WorkloadClass) -> Self {
+        self.workload = workload;
+        if workload == WorkloadClass::Build && self.swap_mode == SwapMode::ZramSwapfc {
+            self.swapfc_chunk_size = BUILD_CHUNK_SIZE.to_string();
+            self.swapfc_free_ram_perc = BUILD_FREE_RAM_PERC;
+            self.swapfc_remove_free_swap_perc = BUILD_REMOVE_FREE_SWAP_PERC;
+        }
+        self
+    }
+
+    /// Leave swap files out when their directory shares a spinning disk with
+    /// another swap area (see disks.rs): that area already is the disk
+    /// overflow, and more swap on the same heads thrashes both
+    pub fn with_shared_disk(mut self, shared: Option<SharedArea>) -> Self {
+        if let Some(area) = shared.filter(SharedArea::is_slow) {
+            if self.swap_mode == SwapMode::ZramSwapfc {
+                info!(
+                    "Autoconfig: swap area {} is on the same spinning disk {}, using zram only",
+                    area.area, area.disk
+                );
+                self = Self {
+                    workload: self.workload,
+                    ..Self::zram_only()
+                };
+            }
+            self.shared_disk = Some(area);
+        }
+        self
+    }
+
     /// All config key-value pairs that auto mode injects.
     ///
     /// This is the **single source of truth** for auto-mode defaults.
@@ -149,6 +385,9 @@ impl RecommendedConfig {
             ("zram_size", format!("{}%", self.zram_size_percent)),
             ("zram_prio", defaults::ZRAM_PRIO.to_string()),
         ];
+        if let Some(ref limit) = self.zram_mem_limit {
+            pairs.push(("zram_mem_limit", limit.clone()));
+        }
 
         if self.swap_mode == SwapMode::ZramSwapfc {
             pairs.extend([
@@ -158,6 +397,9 @@ impl RecommendedConfig {
                 ("swapfile_free_swap_perc", self.swapfc_free_swap_perc.to_string()),
                 ("swapfile_remove_free_swap_perc", self.swapfc_remove_free_swap_perc.to_string()),
             ]);
+            if let Some(ref path) = self.swapfc_path {
+                pairs.push(("swapfile_path", path.clone()));
+            }
         }
 
         pairs
@@ -166,11 +408,17 @@ impl RecommendedConfig {
     /// Select swap mode: zram+swapfc when disk available, zram-only otherwise.
     ///
     /// Decision logic:
+    /// 0. Diskless (no writable block device) → capped zram only
     /// 1. Live system (tmpfs/squashfs/overlay) → zram only
     /// 2. FS doesn't support swapfiles (not btrfs/ext4/xfs) → zram only
     /// 3. Free disk space < total RAM → zram only
     /// 4. Otherwise → zram + pre-allocated swapfiles
     fn build_config(caps: &SystemCapabilities) -> Self {
+        if caps.diskless {
+            debug!("Autoconfig: Diskless system detected, using capped zram only");
+            return Self::zram_diskless();
+        }
+
         if caps.is_live_system {
             debug!("Autoconfig: Live system detected, using zram only");
             return Self::zram_only();
@@ -201,6 +449,111 @@ impl RecommendedConfig {
             caps.total_ram_bytes as f64 / GB as f64,
             caps.swap_path_fstype,
         );
-        Self::zram_swapfc()
+        let mut config = Self::zram_swapfc();
+        if caps.swap_path != defaults::SWAPFILE_PATH {
+            config.swapfc_path = Some(caps.swap_path.clone());
+        }
+        config
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::swapfile::StorageType;
+
+    #[test]
+    fn network_root_is_diskless() {
+        let mounts = "proc /proc proc rw,nosuid 0 0\n\
+                      10.0.0.1:/srv/root / nfs4 rw,relatime 0 0\n\
+                      tmpfs /tmp tmpfs rw 0 0\n\
+                      /dev/loop0 /run/media squashfs ro 0 0\n\
+                      /dev/zram0 /var/tmp ext4 rw 0 0\n";
+        assert!(!has_writable_disk(mounts));
+    }
+
+    #[test]
+    fn writable_block_device_is_a_disk() {
+        let mounts = "/dev/sr0 /run/media iso9660 ro 0 0\n\
+                      /dev/nvme0n1p2 / btrfs rw,relatime,ssd 0 0\n";
+        assert!(has_writable_disk(mounts));
+        let read_only = "/dev/nvme0n1p2 / ext4 ro,relatime 0 0\n";
+        assert!(!has_writable_disk(read_only));
+    }
+
+    fn caps() -> SystemCapabilities {
+        SystemCapabilities {
+            swap_path_fstype: Some("nfs4".into()),
+            free_disk_space_bytes: 0,
+            total_ram_bytes: 8 * GB,
+            is_live_system: false,
+            immutable_root: false,
+            swap_path: defaults::SWAPFILE_PATH.into(),
+            diskless: true,
+            cpu_count: 4,
+        }
+    }
+
+    #[test]
+    fn diskless_profile_caps_zram() {
+        let config = RecommendedConfig::from_capabilities(&caps());
+        assert_eq!(config.swap_mode, SwapMode::ZramOnly);
+        let pairs = config.config_pairs();
+        assert!(pairs.contains(&("zram_size", "100%".to_string())));
+        assert!(pairs.contains(&("zram_mem_limit", "25%".to_string())));
+        assert!(!pairs.iter().any(|(k, _)| k.starts_with("swapfile_")));
+        // Without swap files there is nothing to tune for builds
+        let build = RecommendedConfig::from_capabilities(&caps()).with_workload(WorkloadClass::Build);
+        assert_eq!(build.config_pairs(), pairs);
+    }
+
+    #[test]
+    fn build_hosts_expand_earlier_with_larger_files() {
+        let disk = SystemCapabilities {
+            swap_path_fstype: Some("ext4".into()),
+            free_disk_space_bytes: 64 * GB,
+            diskless: false,
+            ..caps()
+        };
+        let desktop = RecommendedConfig::from_capabilities(&disk).with_workload(WorkloadClass::Desktop);
+        assert!(desktop.config_pairs().contains(&("swapfile_chunk_size", defaults::SWAPFILE_CHUNK_SIZE.to_string())));
+        let build = RecommendedConfig::from_capabilities(&disk).with_workload(WorkloadClass::Build);
+        let pairs = build.config_pairs();
+        assert!(pairs.contains(&("swapfile_chunk_size", "1G".to_string())));
+        assert!(pairs.contains(&("swapfile_free_ram_perc", "30".to_string())));
+        assert!(pairs.contains(&("swapfile_remove_free_swap_perc", "85".to_string())));
+    }
+
+    #[test]
+    fn shared_spinning_disk_leaves_swap_files_out() {
+        let disk = SystemCapabilities {
+            swap_path_fstype: Some("ext4".into()),
+            free_disk_space_bytes: 64 * GB,
+            diskless: false,
+            ..caps()
+        };
+        let shared = |storage| SharedArea {
+            area: "/dev/sda3".to_string(),
+            disk: "sda".to_string(),
+            storage,
+        };
+        let hdd = RecommendedConfig::from_capabilities(&disk).with_shared_disk(Some(shared(StorageType::Hdd)));
+        assert_eq!(hdd.swap_mode, SwapMode::ZramOnly);
+        assert!(!hdd.config_pairs().iter().any(|(k, _)| k.starts_with("swapfile_")));
+        assert!(hdd.shared_disk.is_some());
+        let ssd = RecommendedConfig::from_capabilities(&disk).with_shared_disk(Some(shared(StorageType::Ssd)));
+        assert_eq!(ssd.swap_mode, SwapMode::ZramSwapfc);
+    }
+
+    #[test]
+    fn capability_cache_roundtrip_and_invalidation() {
+        let caps = caps();
+        let content = caps.to_cache(42, 1000);
+        assert_eq!(SystemCapabilities::from_cache(&content, 42, 1100), Some(caps));
+        // Hotplug changes the fingerprint
+        assert_eq!(SystemCapabilities::from_cache(&content, 43, 1100), None);
+        // Too old
+        assert_eq!(SystemCapabilities::from_cache(&content, 42, 1000 + CAPS_CACHE_TTL + 1), None);
+        assert_eq!(SystemCapabilities::from_cache("cached=1000\n", 42, 1000), None);
     }
 }
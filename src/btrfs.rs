@@ -0,0 +1,289 @@
+// Btrfs layout handling for systemd-swap
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// Where the swap subvolume belongs depends on how the distribution laid out
+// btrfs, as seen from the subvolume the swap directory is mounted from:
+//   flat       the top-level subvolume itself (subvolid=5): the swap
+//              subvolume is nested at swapfile_path
+//   @-prefixed /@, /@home, ... (Ubuntu, Arch, Timeshift): a subvolume nested
+//              in @ stays behind in the old root when Timeshift or snapper
+//              restores a snapshot, so a top-level @swap sibling is created
+//              and mounted at swapfile_path instead
+//   snapshot   a snapper snapshot (/@/.snapshots/N/snapshot, openSUSE):
+//              rollbacks switch the root, so @swap goes to the top level too
+//   other      unknown naming: nested, as for flat
+// An existing subvolume at swapfile_path is always kept as it is.
+//
+// The same mount information resolves loop backing files: the kernel may
+// report them relative to the top level or to the mounted subvolume.
+//
+// While a balance, resize or device add/remove/replace runs, the kernel
+// refuses to activate swap files on the filesystem, and a new file competes
+// with the relocation for space and I/O. Since 5.10 the running operation is
+// published in /sys/fs/btrfs/<uuid>/exclusive_operation; the swap file
+// monitor defers expansion while it is set.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::context::work_dir;
+use crate::exec::TimedCommand;
+use crate::helpers::makedirs;
+use crate::info;
+
+/// Name of the top-level swap subvolume for @-prefixed and snapshot layouts
+pub const TOPLEVEL_SUBVOLUME: &str = "@swap";
+
+/// A mounted btrfs subvolume
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct BtrfsMount {
+    /// Block device, without findmnt's [/subvol] suffix
+    pub device: String,
+    /// Path of the mounted subvolume below the top level, e.g. "/@"
+    pub fsroot: String,
+    /// Where it is mounted
+    pub target: PathBuf,
+    /// Filesystem UUID, names its directory in /sys/fs/btrfs
+    pub uuid: String,
+}
+
+impl BtrfsMount {
+    /// Parse a findmnt -P line (SOURCE, FSROOT, TARGET, FSTYPE, UUID); None unless btrfs
+    fn parse(line: &str) -> Option<Self> {
+        let mut mount = Self::default();
+        let mut is_btrfs = false;
+        let mut rest = line.trim();
+        while let Some((key, tail)) = rest.split_once("=\"") {
+            let (value, tail) = tail.split_once('"')?;
+            match key.trim() {
+                "SOURCE" => mount.device = value.split('[').next().unwrap_or(value).to_string(),
+                "FSROOT" => mount.fsroot = value.to_string(),
+                "TARGET" => mount.target = PathBuf::from(value),
+                "FSTYPE" => is_btrfs = value == "btrfs",
+                "UUID" => mount.uuid = value.to_string(),
+                _ => {}
+            }
+            rest = tail;
+        }
+        (is_btrfs && !mount.device.is_empty()).then_some(mount)
+    }
+
+    /// The btrfs mount a path lives on
+    pub fn of(path: &Path) -> Option<Self> {
+        let target = path.ancestors().find(|p| p.exists()).unwrap_or(Path::new("/"));
+        let output = Command::new("findmnt")
+            .args(["-n", "-P", "-o", "SOURCE,FSROOT,TARGET,FSTYPE,UUID", "--target"])
+            .arg(target)
+            .stderr(Stdio::null())
+            .output_timed()
+            .ok()?;
+        Self::parse(String::from_utf8_lossy(&output.stdout).lines().next()?)
+    }
+
+    /// Path of `path` (below this mount) as seen from the top-level subvolume
+    pub fn toplevel_path(&self, path: &Path) -> PathBuf {
+        let relative = path.strip_prefix(&self.target).unwrap_or(path);
+        Path::new("/").join(self.fsroot.trim_start_matches('/')).join(relative)
+    }
+
+    /// Exclusive operation running on the filesystem (balance, resize,
+    /// device add/remove/replace); None when idle or the kernel does not say
+    pub fn exclusive_operation(&self) -> Option<String> {
+        if self.uuid.is_empty() {
+            return None;
+        }
+        let content = fs::read_to_string(format!("/sys/fs/btrfs/{}/exclusive_operation", self.uuid)).ok()?;
+        parse_exclusive_operation(&content)
+    }
+}
+
+/// Operation named in exclusive_operation. "swap activate" is a swapon in
+/// progress, ours or another one, and holds the filesystem only for a moment.
+fn parse_exclusive_operation(content: &str) -> Option<String> {
+    match content.trim() {
+        "" | "none" | "swap activate" => None,
+        op => Some(op.to_string()),
+    }
+}
+
+/// Subvolume layout of a btrfs installation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Layout {
+    Flat,
+    AtPrefixed,
+    Snapshot,
+    Other,
+}
+
+/// Parent of the swap subvolume
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SwapParent {
+    /// Nested at swapfile_path, inside the subvolume mounted there
+    Nested,
+    /// @swap at the top level, mounted at swapfile_path
+    TopLevel,
+}
+
+impl Layout {
+    pub fn detect(mount: &BtrfsMount) -> Self {
+        let fsroot = mount.fsroot.trim_end_matches('/');
+        if fsroot.is_empty() {
+            Layout::Flat
+        } else if fsroot.contains("/.snapshots/") {
+            Layout::Snapshot
+        } else if fsroot.starts_with("/@") {
+            Layout::AtPrefixed
+        } else {
+            Layout::Other
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Layout::Flat => "flat",
+            Layout::AtPrefixed => "@-prefixed",
+            Layout::Snapshot => "snapshot root",
+            Layout::Other => "other",
+        }
+    }
+
+    /// Known-good place for the swap subvolume in this layout
+    pub fn swap_parent(self) -> SwapParent {
+        match self {
+            Layout::Flat | Layout::Other => SwapParent::Nested,
+            Layout::AtPrefixed | Layout::Snapshot => SwapParent::TopLevel,
+        }
+    }
+}
+
+impl SwapParent {
+    /// `swapfile_btrfs_parent`: auto follows the layout
+    pub fn from_setting(value: &str, layout: Layout) -> Self {
+        match value.trim() {
+            "nested" => SwapParent::Nested,
+            "toplevel" => SwapParent::TopLevel,
+            _ => layout.swap_parent(),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SwapParent::Nested => "nested",
+            SwapParent::TopLevel => "top-level @swap",
+        }
+    }
+}
+
+fn run(cmd: &mut Command) -> io::Result<()> {
+    let output = cmd.stdout(Stdio::null()).stderr(Stdio::piped()).output_timed()?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(String::from_utf8_lossy(&output.stderr).trim().to_string()))
+    }
+}
+
+/// Create @swap at the top level of `mount`'s filesystem (if missing) and
+/// mount it at `dest`. The top level is mounted only briefly to create it.
+pub fn mount_toplevel_subvolume(mount: &BtrfsMount, dest: &Path, nocow: bool) -> io::Result<()> {
+    let top = PathBuf::from(format!("{}/btrfs-top", work_dir()));
+    makedirs(&top).map_err(io::Error::other)?;
+    run(Command::new("mount").args(["-o", "subvolid=5", &mount.device]).arg(&top))?;
+    let subvolume = top.join(TOPLEVEL_SUBVOLUME);
+    let created = if subvolume.exists() {
+        Ok(())
+    } else {
+        run(Command::new("btrfs").args(["subvolume", "create"]).arg(&subvolume)).inspect(|_| {
+            if nocow {
+                let _ = Command::new("chattr").arg("+C").arg(&subvolume).status_timed();
+            }
+            info!("swapFC: created top-level btrfs subvolume {}", TOPLEVEL_SUBVOLUME);
+        })
+    };
+    let _ = run(Command::new("umount").arg(&top));
+    let _ = fs::remove_dir(&top);
+    created?;
+
+    fs::create_dir_all(dest)?;
+    run(Command::new("mount")
+        .args(["-o", &format!("subvol=/{}", TOPLEVEL_SUBVOLUME), &mount.device])
+        .arg(dest))
+}
+
+/// Whether a loop device's reported backing file is `file`. The kernel may
+/// report it relative to the top level ("/@swap/1") or to the mounted
+/// subvolume ("/1"), so any trailing part of the file's top-level path matches.
+pub fn backing_matches(reported: &Path, file: &Path, mount: Option<&BtrfsMount>) -> bool {
+    if reported == file {
+        return true;
+    }
+    let Some(mount) = mount else {
+        return false;
+    };
+    let reported = reported.strip_prefix("/").unwrap_or(reported);
+    reported.components().next().is_some() && mount.toplevel_path(file).ends_with(reported)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mount(fsroot: &str, target: &str) -> BtrfsMount {
+        BtrfsMount {
+            device: "/dev/nvme0n1p2".to_string(),
+            fsroot: fsroot.to_string(),
+            target: PathBuf::from(target),
+            uuid: String::new(),
+        }
+    }
+
+    #[test]
+    fn parses_findmnt_pairs() {
+        let line = r#"SOURCE="/dev/nvme0n1p2[/@]" FSROOT="/@" TARGET="/" FSTYPE="btrfs""#;
+        assert_eq!(BtrfsMount::parse(line), Some(mount("/@", "/")));
+        assert_eq!(BtrfsMount::parse(r#"SOURCE="/dev/vda" FSROOT="/" TARGET="/" FSTYPE="ext4""#), None);
+        let with_uuid = BtrfsMount::parse(&format!(r#"{} UUID="5c1e2a7d-0b1f""#, line)).unwrap();
+        assert_eq!(with_uuid.uuid, "5c1e2a7d-0b1f");
+    }
+
+    #[test]
+    fn exclusive_operations_that_block_swap_files() {
+        assert_eq!(parse_exclusive_operation("balance\n").as_deref(), Some("balance"));
+        assert_eq!(parse_exclusive_operation("balance paused\n").as_deref(), Some("balance paused"));
+        assert_eq!(parse_exclusive_operation("resize\n").as_deref(), Some("resize"));
+        assert_eq!(parse_exclusive_operation("none\n"), None);
+        assert_eq!(parse_exclusive_operation("swap activate\n"), None);
+    }
+
+    #[test]
+    fn layouts_pick_known_good_parent() {
+        assert_eq!(Layout::detect(&mount("/", "/")), Layout::Flat);
+        assert_eq!(Layout::detect(&mount("/@", "/")), Layout::AtPrefixed);
+        assert_eq!(Layout::detect(&mount("/@home", "/home")), Layout::AtPrefixed);
+        assert_eq!(Layout::detect(&mount("/@/.snapshots/1/snapshot", "/")), Layout::Snapshot);
+        assert_eq!(Layout::detect(&mount("/root", "/")), Layout::Other);
+        assert_eq!(Layout::AtPrefixed.swap_parent(), SwapParent::TopLevel);
+        assert_eq!(Layout::Flat.swap_parent(), SwapParent::Nested);
+        assert_eq!(SwapParent::from_setting("nested", Layout::AtPrefixed), SwapParent::Nested);
+        assert_eq!(SwapParent::from_setting("auto", Layout::Snapshot), SwapParent::TopLevel);
+    }
+
+    #[test]
+    fn backing_paths_resolve_across_subvolumes() {
+        let file = Path::new("/swapfile/1");
+        // Nested subvolume inside @, mounted at /
+        let root = mount("/@", "/");
+        assert_eq!(root.toplevel_path(file), PathBuf::from("/@/swapfile/1"));
+        assert!(backing_matches(Path::new("/swapfile/1"), file, Some(&root)));
+        assert!(backing_matches(Path::new("/@/swapfile/1"), file, Some(&root)));
+        assert!(backing_matches(Path::new("/1"), file, Some(&root)));
+        // Top-level @swap mounted at /swapfile
+        let swap = mount("/@swap", "/swapfile");
+        assert!(backing_matches(Path::new("/@swap/1"), file, Some(&swap)));
+        assert!(!backing_matches(Path::new("/home/user/1"), file, Some(&swap)));
+        assert!(!backing_matches(Path::new("/11"), file, Some(&swap)));
+        assert!(!backing_matches(Path::new("/1"), file, None));
+    }
+}
@@ -0,0 +1,161 @@
+// Combined compressed-memory budget for systemd-swap
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// In manual mode zswap and zram can run side by side, each with its own RAM
+// limit (zswap_max_pool_percent, zram_mem_limit) and nothing bounding the
+// sum. compressed_budget_perc caps that sum: zram gets what the zswap pool
+// leaves over when it has no limit, and both are scaled down together when
+// the configured limits add up to more than the budget.
+
+use crate::config::Config;
+use crate::defaults;
+use crate::helpers::format_size;
+use crate::{info, warn};
+
+/// Smallest share of the budget left to zram when it has no explicit limit
+const MIN_ZRAM_SHARE_PERC: u64 = 10;
+
+/// Zswap pool and zram limit that fit the budget
+#[derive(Debug, Clone, PartialEq)]
+pub struct BudgetSplit {
+    pub budget: u64,
+    pub zswap_pool_percent: u8,
+    pub zram_mem_limit: u64,
+    /// Why the configured values were changed or could not be honoured
+    pub warnings: Vec<String>,
+}
+
+/// Fit zswap's pool and zram's mem_limit (0 = unlimited) into budget_perc of RAM
+pub fn split(ram: u64, budget_perc: u8, zswap_pool_percent: u8, zram_mem_limit: u64) -> Option<BudgetSplit> {
+    if budget_perc == 0 || ram == 0 {
+        return None;
+    }
+    let budget = ram * budget_perc.min(100) as u64 / 100;
+    let mut warnings = Vec::new();
+
+    let zswap_req = ram * zswap_pool_percent as u64 / 100;
+    let (mut zswap, mut zram) = if zram_mem_limit == 0 {
+        let min_zram = budget * MIN_ZRAM_SHARE_PERC / 100;
+        let zram = budget.saturating_sub(zswap_req).max(min_zram);
+        if zswap_req + zram > budget {
+            warnings.push(format!(
+                "zswap_max_pool_percent={} leaves zram no room in the {} budget; zswap pool reduced",
+                zswap_pool_percent,
+                format_size(budget)
+            ));
+            (budget - min_zram, min_zram)
+        } else {
+            (zswap_req, zram)
+        }
+    } else {
+        (zswap_req, zram_mem_limit)
+    };
+
+    let total = zswap + zram;
+    if total > budget {
+        warnings.push(format!(
+            "zswap_max_pool_percent={} and zram_mem_limit={} add up to {}, more than the {} budget; both scaled down",
+            zswap_pool_percent,
+            format_size(zram_mem_limit),
+            format_size(total),
+            format_size(budget)
+        ));
+        zswap = (zswap as u128 * budget as u128 / total as u128) as u64;
+        zram = (zram as u128 * budget as u128 / total as u128) as u64;
+    }
+
+    // Round down only when the pool was actually reduced
+    let pool_percent = if zswap == zswap_req {
+        zswap_pool_percent
+    } else {
+        (zswap * 100 / ram) as u8
+    };
+    if pool_percent == 0 && zswap_req > 0 {
+        warnings.push(format!(
+            "compressed_budget_perc={} is too small to give zswap 1% of RAM next to zram; settings left unchanged",
+            budget_perc
+        ));
+        return Some(BudgetSplit {
+            budget,
+            zswap_pool_percent,
+            zram_mem_limit,
+            warnings,
+        });
+    }
+
+    Some(BudgetSplit {
+        budget,
+        zswap_pool_percent: pool_percent,
+        zram_mem_limit: zram,
+        warnings,
+    })
+}
+
+/// Budget split for this config, None unless manual mode runs both zswap and zram
+pub fn for_config(config: &Config, ram: u64) -> crate::config::Result<Option<BudgetSplit>> {
+    if !(config.get_bool("zswap_enabled") && config.get_bool("zram_enabled")) {
+        return Ok(None);
+    }
+    let budget_perc = config
+        .get_as::<u32>("compressed_budget_perc")
+        .unwrap_or(defaults::COMPRESSED_BUDGET_PERC as u32)
+        .min(100) as u8;
+    let zswap_pool_percent = config
+        .get_as::<u32>("zswap_max_pool_percent")
+        .unwrap_or(defaults::ZSWAP_MAX_POOL_PERCENT)
+        .min(100) as u8;
+    let zram_mem_limit = config.get_size_or("zram_mem_limit", "0")?;
+    Ok(split(ram, budget_perc, zswap_pool_percent, zram_mem_limit))
+}
+
+/// Rewrite zswap_max_pool_percent and zram_mem_limit to fit the budget
+pub fn apply(config: &mut Config, ram: u64) -> crate::config::Result<()> {
+    let Some(split) = for_config(config, ram)? else {
+        return Ok(());
+    };
+    for warning in &split.warnings {
+        warn!("Budget: {}", warning);
+    }
+    info!(
+        "Budget: {} for compressed memory - zswap pool {}%, zram mem_limit {}",
+        format_size(split.budget),
+        split.zswap_pool_percent,
+        format_size(split.zram_mem_limit)
+    );
+    config.set("zswap_max_pool_percent", split.zswap_pool_percent.to_string());
+    config.set("zram_mem_limit", split.zram_mem_limit.to_string());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GB: u64 = 1024 * 1024 * 1024;
+
+    #[test]
+    fn unlimited_zram_gets_the_remainder() {
+        let split = split(16 * GB, 50, 20, 0).unwrap();
+        assert_eq!(split.zswap_pool_percent, 20);
+        assert_eq!(split.zram_mem_limit, 8 * GB - 16 * GB / 5);
+        assert!(split.warnings.is_empty());
+    }
+
+    #[test]
+    fn oversubscribed_limits_scale_down() {
+        let split = split(16 * GB, 50, 40, 8 * GB).unwrap();
+        assert_eq!(split.warnings.len(), 1);
+        let zswap = 16 * GB * split.zswap_pool_percent as u64 / 100;
+        assert!(zswap + split.zram_mem_limit <= 8 * GB);
+        assert!(split.zram_mem_limit > 0 && split.zswap_pool_percent > 0);
+    }
+
+    #[test]
+    fn zswap_filling_the_budget_leaves_zram_a_share() {
+        let split = split(10 * GB, 20, 45, 0).unwrap();
+        assert_eq!(split.zram_mem_limit, 2 * GB / 10);
+        assert_eq!(split.zswap_pool_percent, 18);
+        assert!(!split.warnings.is_empty());
+        assert!(super::split(10 * GB, 0, 45, 0).is_none());
+    }
+}
@@ -0,0 +1,91 @@
+// Time-bounded data collection for systemd-swap status
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// `status` is most often run when the system is struggling, which is also
+// when systemctl (D-Bus), swapon, du or findmnt can stall for a long time.
+// Each such source runs on its own thread as soon as status starts, and is
+// waited for at most SOURCE_TIMEOUT from that moment: sources are collected
+// in parallel, so status returns within about SOURCE_TIMEOUT overall, and a
+// source that has not answered by then is reported as unavailable. Stalled
+// threads are left behind; they end with the process.
+
+use std::sync::mpsc::{self, Receiver};
+use std::time::{Duration, Instant};
+
+/// How long status waits for any one source
+pub const SOURCE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// A source being collected in the background
+pub struct Pending<T> {
+    pub name: &'static str,
+    started: Instant,
+    timeout: Duration,
+    rx: Receiver<T>,
+}
+
+/// Start collecting `name` on its own thread
+pub fn spawn<T, F>(name: &'static str, f: F) -> Pending<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    let (tx, rx) = mpsc::channel();
+    crate::context::spawn(move || {
+        let _ = tx.send(f());
+    });
+    Pending {
+        name,
+        started: Instant::now(),
+        timeout: SOURCE_TIMEOUT,
+        rx,
+    }
+}
+
+impl<T> Pending<T> {
+    /// The collected value, or None if the source did not answer within its
+    /// timeout (counted from `spawn`, so waiting for others does not add up)
+    pub fn wait(self) -> Option<T> {
+        let remaining = self.timeout.saturating_sub(self.started.elapsed());
+        self.rx.recv_timeout(remaining).ok()
+    }
+
+    /// Like `wait`, adding the source name to `missing` on timeout
+    pub fn wait_or_record(self, missing: &mut Vec<&'static str>) -> Option<T> {
+        let name = self.name;
+        let value = self.wait();
+        if value.is_none() {
+            missing.push(name);
+        }
+        value
+    }
+
+    #[cfg(test)]
+    fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+/// Status line for a source that timed out
+pub fn unavailable(name: &str) -> String {
+    format!("{} unavailable (no answer within {}s)", name, SOURCE_TIMEOUT.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fast_source_is_collected() {
+        assert_eq!(spawn("fast", || 42).wait(), Some(42));
+    }
+
+    #[test]
+    fn stalled_source_times_out() {
+        let started = Instant::now();
+        let pending = spawn("slow", || std::thread::sleep(Duration::from_secs(5)))
+            .with_timeout(Duration::from_millis(50));
+        assert_eq!(pending.wait(), None);
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+}
@@ -7,7 +7,8 @@
 
 use std::collections::HashMap;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 
 use glob::glob;
 use thiserror::Error;
@@ -33,17 +34,135 @@ pub const VEN_SYSD: &str = "/usr/lib/systemd";
 pub const RUN_SYSD: &str = "/run/systemd";
 pub const ETC_SYSD: &str = "/etc/systemd";
 pub const WORK_DIR: &str = "/run/systemd/swap";
+/// Persistent, writable state (kept outside /etc and /usr for image-based systems)
+pub const STATE_DIR: &str = "/var/lib/systemd-swap";
+
+/// Environment overrides for the paths above
+pub const ENV_DEFAULT_CONFIG: &str = "SYSTEMD_SWAP_DEFAULT_CONFIG";
+pub const ENV_ETC_CONFIG: &str = "SYSTEMD_SWAP_ETC_CONFIG";
+/// Colon-separated base directories searched for swap.conf.d/, lowest priority first
+pub const ENV_CONF_DIRS: &str = "SYSTEMD_SWAP_CONF_DIRS";
+pub const ENV_STATE_DIR: &str = "SYSTEMD_SWAP_STATE_DIR";
+/// Alternate configuration: a file used instead of /etc/systemd/swap.conf and
+/// its fragments, or a directory laid out like / holding the whole tree
+pub const ENV_CONF: &str = "SYSTEMD_SWAP_CONF";
+
+/// `--config`, which takes precedence over SYSTEMD_SWAP_CONF
+static CONFIG_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+
+/// Built-in copy of swap-default.conf, used when the installed file is missing
+/// (e.g. images that ship /usr without /usr/share/systemd-swap)
+const EMBEDDED_DEFAULT_CONFIG: &str = include_str!("../include/swap-default.conf");
+
+fn env_path(var: &str) -> Option<PathBuf> {
+    std::env::var_os(var)
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Load the configuration from `path` for the rest of the process, as
+/// SYSTEMD_SWAP_CONF would (`--config`); call before the first load
+pub fn set_config_override(path: PathBuf) {
+    let _ = CONFIG_OVERRIDE.set(path);
+}
+
+/// `--config`, else SYSTEMD_SWAP_CONF
+pub fn config_override() -> Option<PathBuf> {
+    CONFIG_OVERRIDE.get().cloned().or_else(|| env_path(ENV_CONF))
+}
+
+/// `path` below an alternate root, or as it is without one
+fn under(root: Option<&Path>, path: &str) -> PathBuf {
+    match root {
+        Some(root) => root.join(path.trim_start_matches('/')),
+        None => PathBuf::from(path),
+    }
+}
+
+/// The override if it is a directory (a root), else None
+fn override_root(config: Option<&Path>) -> Option<&Path> {
+    config.filter(|p| p.is_dir())
+}
+
+/// Vendor default config file
+pub fn default_config_path() -> PathBuf {
+    default_config_path_for(config_override().as_deref())
+}
+
+fn default_config_path_for(config: Option<&Path>) -> PathBuf {
+    env_path(ENV_DEFAULT_CONFIG).unwrap_or_else(|| under(override_root(config), DEF_CONFIG))
+}
+
+/// Admin config file
+pub fn etc_config_path() -> PathBuf {
+    etc_config_path_for(config_override().as_deref())
+}
+
+fn etc_config_path_for(config: Option<&Path>) -> PathBuf {
+    if let Some(path) = env_path(ENV_ETC_CONFIG) {
+        return path;
+    }
+    match config {
+        Some(file) if !file.is_dir() => file.to_path_buf(),
+        root => under(root, ETC_CONFIG),
+    }
+}
+
+/// Base directories holding swap.conf.d/, lowest priority first
+pub fn conf_dirs() -> Vec<PathBuf> {
+    conf_dirs_for(config_override().as_deref())
+}
+
+fn conf_dirs_for(config: Option<&Path>) -> Vec<PathBuf> {
+    match std::env::var(ENV_CONF_DIRS) {
+        Ok(dirs) if !dirs.is_empty() => dirs
+            .split(':')
+            .filter(|d| !d.is_empty())
+            .map(PathBuf::from)
+            .collect(),
+        // A single alternate file stands alone
+        _ if config.is_some_and(|p| !p.is_dir()) => Vec::new(),
+        _ => [VEN_SYSD, RUN_SYSD, ETC_SYSD]
+            .iter()
+            .map(|dir| under(override_root(config), dir))
+            .collect(),
+    }
+}
+
+/// Writable persistent state directory: environment, then `state_dir`, then STATE_DIR
+pub fn state_dir(config: &Config) -> PathBuf {
+    env_path(ENV_STATE_DIR)
+        .or_else(|| config.get_opt("state_dir").filter(|v| !v.is_empty()).map(PathBuf::from))
+        .unwrap_or_else(|| PathBuf::from(STATE_DIR))
+}
 
 /// Configuration holder
 #[derive(Debug, Clone)]
 pub struct Config {
     values: HashMap<String, String>,
+    /// Files loaded, lowest precedence first
+    sources: Vec<String>,
 }
 
 impl Config {
     /// Load configuration from all sources
     pub fn load() -> Result<Self> {
+        Self::load_from(config_override().as_deref())
+    }
+
+    /// Load with `config` as the alternate file or root (None: the system's)
+    fn load_from(config: Option<&Path>) -> Result<Self> {
         let mut values = HashMap::new();
+        let mut sources = Vec::new();
+        if let Some(path) = config {
+            if !path.exists() {
+                return Err(ConfigError::Io(std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    format!("{} does not exist", path.display()),
+                )));
+            }
+            info!("Config: using {}", path.display());
+        }
 
         // Inject system-derived values without unsafe env::set_var.
         // expand_value uses this map before falling back to std::env::vars().
@@ -57,25 +176,35 @@ impl Config {
             crate::meminfo::get_ram_size().unwrap_or(0).to_string(),
         );
 
-        // Load default config
-        if Path::new(DEF_CONFIG).exists() {
-            if let Ok(cfg) = Self::parse_config(DEF_CONFIG, &system_vars) {
+        // Load default config (built-in copy if the vendor file is missing)
+        let def_config = default_config_path_for(config);
+        if def_config.exists() {
+            if let Ok(cfg) = Self::parse_config(&def_config, &system_vars) {
                 values.extend(cfg);
+                sources.push(def_config.display().to_string());
             }
+        } else {
+            debug!("{} not found, using built-in defaults", def_config.display());
+            values.extend(Self::parse_str(EMBEDDED_DEFAULT_CONFIG, &system_vars));
+            sources.push("built-in defaults".to_string());
         }
 
         // Load /etc/systemd/swap.conf
-        if Path::new(ETC_CONFIG).exists() {
-            match Self::parse_config(ETC_CONFIG, &system_vars) {
-                Ok(cfg) => values.extend(cfg),
-                Err(e) => warn!("Could not load {}: {}", ETC_CONFIG, e),
+        let etc_config = etc_config_path_for(config);
+        if etc_config.exists() {
+            match Self::parse_config(&etc_config, &system_vars) {
+                Ok(cfg) => {
+                    values.extend(cfg);
+                    sources.push(etc_config.display().to_string());
+                }
+                Err(e) => warn!("Could not load {}: {}", etc_config.display(), e),
             }
         }
 
         // Load conf.d fragments (etc > run > lib for same basename)
         let mut config_files: HashMap<String, String> = HashMap::new();
-        for base_path in [VEN_SYSD, RUN_SYSD, ETC_SYSD] {
-            let pattern = format!("{}/swap.conf.d/*.conf", base_path);
+        for base_path in conf_dirs_for(config) {
+            let pattern = format!("{}/swap.conf.d/*.conf", base_path.display());
             if let Ok(entries) = glob(&pattern) {
                 for entry in entries.flatten() {
                     if entry.is_file() {
@@ -101,10 +230,16 @@ impl Config {
             info!("Load: {}", path);
             if let Ok(cfg) = Self::parse_config(&path, &system_vars) {
                 values.extend(cfg);
+                sources.push(path);
             }
         }
 
-        Ok(Self { values })
+        Ok(Self { values, sources })
+    }
+
+    /// Files the configuration was loaded from, lowest precedence first
+    pub fn sources(&self) -> &[String] {
+        &self.sources
     }
 
     /// Helper: set a config key only if the user hasn't explicitly set it
@@ -131,7 +266,10 @@ impl Config {
         &mut self,
         recommended: &crate::autoconfig::RecommendedConfig,
     ) {
-        info!("Autoconfig: applying recommended configuration for detected hardware");
+        info!(
+            "Autoconfig: applying recommended configuration for detected hardware ({} workload)",
+            recommended.workload
+        );
 
         for (key, value) in recommended.config_pairs() {
             self.set_if_missing(key, &value);
@@ -140,13 +278,24 @@ impl Config {
         info!("Autoconfig: injection complete");
     }
 
+    /// Replace a value derived at startup (e.g. by the compressed-memory budget)
+    pub fn set(&mut self, key: &str, value: String) {
+        debug!("Config: {}={}", key, value);
+        self.values.insert(key.to_string(), value);
+    }
+
     /// Parse a single config file
     fn parse_config<P: AsRef<Path>>(
         path: P,
         extra_vars: &HashMap<String, String>,
     ) -> Result<HashMap<String, String>> {
-        let mut config = HashMap::new();
         let content = fs::read_to_string(path)?;
+        Ok(Self::parse_str(&content, extra_vars))
+    }
+
+    /// Parse config file content
+    fn parse_str(content: &str, extra_vars: &HashMap<String, String>) -> HashMap<String, String> {
+        let mut config = HashMap::new();
 
         for line in content.lines() {
             let line = line.trim();
@@ -168,7 +317,7 @@ impl Config {
             }
         }
 
-        Ok(config)
+        config
     }
 
     /// Safely expand environment variables and simple arithmetic in config values
@@ -273,10 +422,29 @@ impl Config {
             .unwrap_or(false)
     }
 
+    /// Get a size in bytes (see `helpers::parse_size`), parsing `default` when
+    /// the key is unset or empty. Errors name the offending key.
+    pub fn get_size_or(&self, key: &str, default: &str) -> Result<u64> {
+        let value = match self.get_opt(key).map(str::trim) {
+            Some(v) if !v.is_empty() => v,
+            _ => default,
+        };
+        crate::helpers::parse_size(value).map_err(|e| ConfigError::ParseError(key.to_string(), e))
+    }
+
     /// Get optional value
     pub fn get_opt(&self, key: &str) -> Option<&str> {
         self.values.get(key).map(|s| s.as_str())
     }
+
+    /// Build a config from already-parsed values (tests)
+    #[cfg(test)]
+    pub(crate) fn from_values(values: HashMap<String, String>) -> Self {
+        Config {
+            values,
+            sources: Vec::new(),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -294,7 +462,7 @@ mod tests {
                 values.insert(k.trim().to_string(), v.trim().to_string());
             }
         }
-        Config { values }
+        Config::from_values(values)
     }
 
     // ── evaluate_simple_arithmetic ────────────────────────────────────────────
@@ -398,4 +566,70 @@ mod tests {
         let cfg = config_from_str("count=notanint");
         assert!(cfg.get_as::<u32>("count").is_err());
     }
+
+    // ── Paths ────────────────────────────────────────────────────────────────
+
+    #[test]
+    fn embedded_default_config_parses() {
+        let values = Config::parse_str(EMBEDDED_DEFAULT_CONFIG, &HashMap::new());
+        assert_eq!(values.get("swap_mode").map(String::as_str), Some("auto"));
+    }
+
+    #[test]
+    fn state_dir_from_config() {
+        if std::env::var_os(ENV_STATE_DIR).is_some() {
+            return;
+        }
+        assert_eq!(state_dir(&config_from_str("")), PathBuf::from(STATE_DIR));
+        let cfg = config_from_str("state_dir=/var/lib/swap-state");
+        assert_eq!(state_dir(&cfg), PathBuf::from("/var/lib/swap-state"));
+    }
+
+    #[test]
+    fn alternate_file_or_root() {
+        let scoped = [ENV_DEFAULT_CONFIG, ENV_ETC_CONFIG, ENV_CONF_DIRS];
+        if scoped.iter().any(|var| std::env::var_os(var).is_some()) {
+            return;
+        }
+        let root = std::env::temp_dir().join(format!("config-root-{}", std::process::id()));
+        let fragments = root.join("etc/systemd/swap.conf.d");
+        fs::create_dir_all(&fragments).unwrap();
+        fs::write(root.join("etc/systemd/swap.conf"), "swap_mode=manual\nzram_prio=100\n").unwrap();
+        fs::write(fragments.join("10-test.conf"), "zram_prio=200\n").unwrap();
+
+        // A root: built-in defaults, then its swap.conf and fragments
+        let cfg = Config::load_from(Some(&root)).unwrap();
+        assert_eq!(cfg.get("swap_mode").unwrap(), "manual");
+        assert_eq!(cfg.get("zram_prio").unwrap(), "200");
+        assert_eq!(cfg.sources()[0], "built-in defaults");
+        assert!(cfg.sources()[2].ends_with("swap.conf.d/10-test.conf"));
+
+        // A file stands alone, without the fragments
+        let cfg = Config::load_from(Some(&root.join("etc/systemd/swap.conf"))).unwrap();
+        assert_eq!(cfg.get("zram_prio").unwrap(), "100");
+        assert_eq!(cfg.sources().len(), 2);
+
+        assert!(Config::load_from(Some(&root.join("missing.conf"))).is_err());
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    // ── Config::get_size_or ──────────────────────────────────────────────────
+
+    #[test]
+    fn get_size_or_parses_value_and_default() {
+        let cfg = config_from_str("chunk=512M\nempty=");
+        assert_eq!(cfg.get_size_or("chunk", "1G").unwrap(), 512 * 1024 * 1024);
+        assert_eq!(cfg.get_size_or("empty", "1G").unwrap(), 1024 * 1024 * 1024);
+        assert_eq!(cfg.get_size_or("missing", "0").unwrap(), 0);
+    }
+
+    #[test]
+    fn get_size_or_error_names_key() {
+        let cfg = config_from_str("swapfile_chunk_size=12X");
+        let err = cfg.get_size_or("swapfile_chunk_size", "512M").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Parse error for swapfile_chunk_size: invalid size '12X': unknown unit 'X'"
+        );
+    }
 }
@@ -0,0 +1,257 @@
+// Instance context for systemd-swap
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// The standalone daemon runs one swap manager per process, with its working
+// directory at WORK_DIR, shutdown driven by SIGTERM and log lines on
+// stdout/stderr for the journal. A manager embedded in another process (see
+// manager.rs) brings its own working directory, shutdown flag and log sink
+// instead. The context is bound to the manager's threads: `enter` binds it to
+// the current thread and `spawn` carries it into new ones, so monitors,
+// wipes, hooks and collectors of one manager all see the same instance.
+// Threads without a context use the process defaults.
+//
+// The log level is one per process: lines below it are dropped before they
+// reach any sink. It starts at Info (Debug with DEBUG set in the environment)
+// and can be changed at runtime through the control socket (control.rs).
+
+use std::cell::RefCell;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+use crate::config::WORK_DIR;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl LogLevel {
+    /// Prefix of the standalone daemon's log lines
+    pub fn prefix(self) -> &'static str {
+        match self {
+            LogLevel::Debug => "DEBUG",
+            LogLevel::Info => "INFO",
+            LogLevel::Warn => "WARN",
+            LogLevel::Error => "ERRO",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            LogLevel::Debug => "debug",
+            LogLevel::Info => "info",
+            LogLevel::Warn => "warn",
+            LogLevel::Error => "error",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "debug" => Some(LogLevel::Debug),
+            "info" => Some(LogLevel::Info),
+            "warn" | "warning" => Some(LogLevel::Warn),
+            "error" => Some(LogLevel::Error),
+            _ => None,
+        }
+    }
+
+    fn from_u8(v: u8) -> Self {
+        match v {
+            0 => LogLevel::Debug,
+            1 => LogLevel::Info,
+            2 => LogLevel::Warn,
+            _ => LogLevel::Error,
+        }
+    }
+}
+
+/// Lowest level logged; UNSET until first read or set
+static LOG_LEVEL: AtomicU8 = AtomicU8::new(UNSET);
+const UNSET: u8 = u8::MAX;
+
+/// Current log level of the process
+pub fn log_level() -> LogLevel {
+    match LOG_LEVEL.load(Ordering::Relaxed) {
+        UNSET => {
+            let initial = if std::env::var_os("DEBUG").is_some() {
+                LogLevel::Debug
+            } else {
+                LogLevel::Info
+            };
+            let _ = LOG_LEVEL.compare_exchange(UNSET, initial as u8, Ordering::Relaxed, Ordering::Relaxed);
+            LogLevel::from_u8(LOG_LEVEL.load(Ordering::Relaxed))
+        }
+        v => LogLevel::from_u8(v),
+    }
+}
+
+/// Change the log level of the running process
+pub fn set_log_level(level: LogLevel) {
+    LOG_LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+/// Whether lines of `level` are logged
+pub fn log_enabled(level: LogLevel) -> bool {
+    level >= log_level()
+}
+
+/// Receives every log line of a manager instance
+pub type LogSink = Arc<dyn Fn(LogLevel, &str) + Send + Sync>;
+
+/// Where an instance keeps its runtime state, when it stops and where it logs
+#[derive(Clone)]
+pub struct Context {
+    pub work_dir: PathBuf,
+    pub shutdown: Arc<AtomicBool>,
+    pub log: Option<LogSink>,
+    /// Report readiness and status to systemd (sd_notify); off when embedded,
+    /// where the host process owns the service's notifications
+    pub notify_systemd: bool,
+}
+
+impl fmt::Debug for Context {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Context")
+            .field("work_dir", &self.work_dir)
+            .field("shutdown", &self.shutdown)
+            .field("log", &self.log.as_ref().map(|_| "custom"))
+            .field("notify_systemd", &self.notify_systemd)
+            .finish()
+    }
+}
+
+impl Default for Context {
+    fn default() -> Self {
+        Self {
+            work_dir: PathBuf::from(WORK_DIR),
+            shutdown: Arc::new(AtomicBool::new(false)),
+            log: None,
+            notify_systemd: true,
+        }
+    }
+}
+
+thread_local! {
+    static CURRENT: RefCell<Option<Context>> = const { RefCell::new(None) };
+}
+
+/// Bind `ctx` to the current thread
+pub fn enter(ctx: Context) {
+    CURRENT.with(|c| *c.borrow_mut() = Some(ctx));
+}
+
+/// Unbind the current thread's context
+pub fn leave() {
+    CURRENT.with(|c| *c.borrow_mut() = None);
+}
+
+/// Context of the current thread, if any
+pub fn current() -> Option<Context> {
+    CURRENT.with(|c| c.borrow().clone())
+}
+
+fn with_current<T>(f: impl FnOnce(Option<&Context>) -> T) -> T {
+    CURRENT.with(|c| f(c.borrow().as_ref()))
+}
+
+/// `thread::spawn` that carries the current context into the new thread
+pub fn spawn<T, F>(f: F) -> JoinHandle<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    let ctx = current();
+    thread::spawn(move || {
+        if let Some(ctx) = ctx {
+            enter(ctx);
+        }
+        f()
+    })
+}
+
+/// Runtime state directory of the current instance
+pub fn work_dir() -> String {
+    with_current(|ctx| match ctx {
+        Some(ctx) => ctx.work_dir.to_string_lossy().into_owned(),
+        None => WORK_DIR.to_string(),
+    })
+}
+
+/// Whether the current instance was asked to stop
+pub fn shutdown_requested() -> bool {
+    with_current(|ctx| ctx.is_some_and(|ctx| ctx.shutdown.load(Ordering::Acquire)))
+}
+
+pub fn notify_systemd() -> bool {
+    with_current(|ctx| ctx.is_none_or(|ctx| ctx.notify_systemd))
+}
+
+/// Log a line through the instance's sink, or to stdout (info) and stderr
+pub fn log(level: LogLevel, message: &str) {
+    if !log_enabled(level) {
+        return;
+    }
+    let sink = with_current(|ctx| ctx.and_then(|ctx| ctx.log.clone()));
+    match sink {
+        Some(sink) => sink(level, message),
+        None if level == LogLevel::Info => println!("{}: {}", level.prefix(), message),
+        None => eprintln!("{}: {}", level.prefix(), message),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn spawned_threads_inherit_the_context() {
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        let sink_lines = lines.clone();
+        let dir = std::env::temp_dir().join(format!("embedded-swap-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let swap_file = dir.with_extension("swapfile");
+        std::fs::write(&swap_file, "swap").unwrap();
+        let ctx = Context {
+            work_dir: dir.clone(),
+            log: Some(Arc::new(move |level, msg: &str| {
+                sink_lines.lock().unwrap().push(format!("{:?} {}", level, msg));
+            })),
+            notify_systemd: false,
+            ..Context::default()
+        };
+        let shutdown = ctx.shutdown.clone();
+        let plan = crate::plan::StopPlan {
+            files: vec![(swap_file.clone(), 4)],
+            ..Default::default()
+        };
+        thread::spawn(move || {
+            enter(ctx);
+            spawn(|| {
+                crate::info!("from {}", work_dir());
+                assert!(!notify_systemd());
+            })
+            .join()
+            .unwrap();
+            // Teardown output goes to the sink too
+            plan.execute();
+        })
+        .join()
+        .unwrap();
+        let lines = lines.lock().unwrap();
+        assert_eq!(lines[0], format!("Info from {}", dir.display()));
+        assert!(lines.contains(&format!("Info Removed {}", swap_file.display())));
+        assert!(!swap_file.exists() && !dir.exists());
+        let _ = std::fs::remove_file(dir.with_extension("stopping"));
+
+        shutdown.store(true, Ordering::Release);
+        assert!(!shutdown_requested(), "threads without a context are not affected");
+        assert_eq!(work_dir(), WORK_DIR);
+    }
+}
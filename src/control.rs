@@ -0,0 +1,160 @@
+// Control socket of the running daemon for systemd-swap
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// The daemon listens on WORK_DIR/control, a Unix stream socket only root can
+// reach (WORK_DIR is root's). A client sends one request line and reads one
+// reply line, "ok <value>" or "error <reason>":
+//   log-level            current log level
+//   log-level <level>    switch to debug, info, warn or error
+// Settings changed here hold until the daemon restarts; swap stays as it is.
+// The listener re-binds when the working directory is re-created under it
+// (see recovery.rs), so the socket path keeps working.
+
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+use thiserror::Error;
+
+use crate::context::{self, work_dir, LogLevel};
+use crate::{info, is_shutdown, warn};
+
+/// How long either side waits for the other's line
+const IO_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Poll interval of the listener (accept is non-blocking)
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Error, Debug)]
+pub enum ControlError {
+    #[error("IO error: {0}")]
+    Io(#[from] io::Error),
+    #[error("daemon is not running (no control socket at {0})")]
+    NotRunning(String),
+    #[error("daemon refused: {0}")]
+    Refused(String),
+}
+
+pub type Result<T> = std::result::Result<T, ControlError>;
+
+pub fn socket_path() -> PathBuf {
+    PathBuf::from(work_dir()).join("control")
+}
+
+/// Answer one request line
+pub fn handle(request: &str) -> String {
+    let mut words = request.split_whitespace();
+    match (words.next(), words.next(), words.next()) {
+        (Some("log-level"), None, _) => format!("ok {}", context::log_level().name()),
+        (Some("log-level"), Some(level), None) => match LogLevel::parse(level) {
+            Some(level) => {
+                if level != context::log_level() {
+                    // Logged at the level being left, so it shows either way
+                    context::log(
+                        context::log_level().max(LogLevel::Info),
+                        &format!("Control: log level set to {}", level.name()),
+                    );
+                    context::set_log_level(level);
+                }
+                format!("ok {}", level.name())
+            }
+            None => format!("error unknown log level {} (debug, info, warn, error)", level),
+        },
+        _ => format!("error unknown request: {}", request.trim()),
+    }
+}
+
+fn bind() -> io::Result<UnixListener> {
+    let path = socket_path();
+    // A socket left by an earlier instance refuses connections; replace it
+    let _ = fs::remove_file(&path);
+    let listener = UnixListener::bind(&path)?;
+    listener.set_nonblocking(true)?;
+    Ok(listener)
+}
+
+fn serve(stream: UnixStream) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(IO_TIMEOUT))?;
+    stream.set_write_timeout(Some(IO_TIMEOUT))?;
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line)?;
+    let mut stream = stream;
+    writeln!(stream, "{}", handle(&line))
+}
+
+/// Listen on the control socket until shutdown
+pub fn spawn_listener() {
+    let mut listener = match bind() {
+        Ok(l) => l,
+        Err(e) => {
+            warn!("Control: cannot listen on {}: {}", socket_path().display(), e);
+            return;
+        }
+    };
+    info!("Control: listening on {}", socket_path().display());
+    context::spawn(move || {
+        while !is_shutdown() {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    if let Err(e) = serve(stream) {
+                        warn!("Control: request failed: {}", e);
+                    }
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    if !socket_path().exists() {
+                        match bind() {
+                            Ok(l) => listener = l,
+                            Err(e) => warn!("Control: cannot listen again: {}", e),
+                        }
+                    }
+                    thread::sleep(ACCEPT_INTERVAL);
+                }
+                Err(e) => {
+                    warn!("Control: accept failed: {}", e);
+                    thread::sleep(ACCEPT_INTERVAL);
+                }
+            }
+        }
+        let _ = fs::remove_file(socket_path());
+    });
+}
+
+/// Send `request` to the running daemon and return the value of its reply
+pub fn request(request: &str) -> Result<String> {
+    let path = socket_path();
+    let mut stream = UnixStream::connect(&path).map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound | io::ErrorKind::ConnectionRefused => {
+            ControlError::NotRunning(path.display().to_string())
+        }
+        _ => ControlError::Io(e),
+    })?;
+    stream.set_read_timeout(Some(IO_TIMEOUT))?;
+    stream.set_write_timeout(Some(IO_TIMEOUT))?;
+    writeln!(stream, "{}", request)?;
+    let mut reply = String::new();
+    BufReader::new(&stream).read_line(&mut reply)?;
+    let reply = reply.trim();
+    match reply.split_once(' ') {
+        Some(("ok", value)) => Ok(value.to_string()),
+        Some(("error", reason)) => Err(ControlError::Refused(reason.to_string())),
+        _ => Err(ControlError::Refused(format!("unexpected reply {:?}", reply))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_unknown_requests() {
+        assert!(handle("log-level loud").starts_with("error unknown log level loud"));
+        assert!(handle("reload\n").starts_with("error unknown request: reload"));
+        assert!(handle("log-level").starts_with("ok "));
+        assert_eq!(LogLevel::parse(" Warning "), Some(LogLevel::Warn));
+        assert_eq!(LogLevel::parse("debug").map(LogLevel::name), Some("debug"));
+    }
+}
@@ -8,6 +8,8 @@
 // ── Zram ─────────────────────────────────────────────────────────────────────
 
 pub const ZRAM_SIZE: &str = "125%";
+/// zram_size of the dynamic pool, which grows past it on demand
+pub const ZRAM_POOL_SIZE: &str = "50%";
 pub const ZRAM_ALG: &str = "zstd";
 pub const ZRAM_PRIO: i32 = 32767;
 pub const ZRAM_MAX_DEVICES: u8 = 8;
@@ -18,21 +20,32 @@ pub const ZRAM_CONTRACT_STABILITY: u64 = 120;
 pub const ZRAM_MIN_FREE_RAM: u8 = 15;
 pub const ZRAM_CHECK_INTERVAL: u64 = 5;
 pub const ZRAM_EXPAND_MIN_RATIO: f64 = 2.0;
+pub const ZRAM_SIZE_MODE: &str = "ram";
+pub const ZRAM_EXPECTED_RATIO: f64 = 3.0;
+pub const ZRAM_WRITEBACK_INTERVAL: u64 = 600;
+pub const ZRAM_WRITEBACK_IDLE_AGE: u64 = 3600;
+pub const ZRAM_WRITEBACK_MIN_HUGE: &str = "16M";
+pub const ZRAM_EXEC_WATCH: bool = false;
+pub const ZRAM_EXEC_WATCH_APPS: &str = "firefox,chrome,chromium,code,blender,steam,java";
+pub const ZRAM_EXEC_WATCH_GRACE: u64 = 120;
 
 // ── Zswap ────────────────────────────────────────────────────────────────────
 
 pub const ZSWAP_COMPRESSOR: &str = "zstd";
 pub const ZSWAP_ZPOOL: &str = "zsmalloc";
+pub const COMPRESSED_BUDGET_PERC: u8 = 50;
 pub const ZSWAP_MAX_POOL_PERCENT: u32 = 45;
 pub const ZSWAP_SHRINKER_ENABLED: &str = "1";
 pub const ZSWAP_ACCEPT_THRESHOLD: &str = "80";
+pub const ZSWAP_PERSIST: bool = false;
 
 // ── SwapFile ─────────────────────────────────────────────────────────────────
 
 pub const SWAPFILE_PATH: &str = "/swapfile";
+pub const SWAPFILE_PATH_IMMUTABLE: &str = "/var/swap";
 pub const SWAPFILE_CHUNK_SIZE: &str = "512M";
 pub const SWAPFILE_MAX_COUNT: u32 = 28;
-pub const SWAPFILE_MIN_COUNT: u32 = 1;
+pub const SWAPFILE_MIN_COUNT: &str = "auto";
 pub const SWAPFILE_FREE_RAM_PERC: u8 = 20;
 pub const SWAPFILE_FREE_SWAP_PERC: u8 = 40;
 pub const SWAPFILE_REMOVE_FREE_SWAP_PERC: u8 = 70;
@@ -40,3 +53,57 @@ pub const SWAPFILE_FREQUENCY: u32 = 1;
 pub const SWAPFILE_SHRINK_THRESHOLD: u8 = 30;
 pub const SWAPFILE_SAFE_HEADROOM: u8 = 40;
 pub const SWAPFILE_NOCOW: &str = "1";
+pub const SWAPFILE_LAYOUT: &str = "";
+pub const SWAPFILE_PRIORITY: &str = "auto";
+pub const SWAPFILE_GROW_MAX_SIZE: &str = "8G";
+pub const SWAPFILE_WIPE_ON_REMOVE: &str = "0";
+pub const SWAPFILE_TRIM_WINDOW: u64 = 1800;
+pub const SWAPFILE_BTRFS_PARENT: &str = "auto";
+pub const SWAPFILE_BURST_MAX_COUNT: u32 = 0;
+pub const SWAPFILE_BURST_FREE_SWAP_PERC: u8 = 50;
+pub const SWAPFILE_BURST_QUIET: u64 = 120;
+
+// ── Kernel tuning ────────────────────────────────────────────────────────────
+
+pub const MIN_FREE_KBYTES_AUTO: bool = false;
+pub const HIBERNATE_IMAGE_SIZE: &str = "";
+
+// ── Alarms ───────────────────────────────────────────────────────────────────
+
+pub const ALARM_HOOK: &str = "";
+pub const ALARM_SWAP_USED_PERC: u8 = 85;
+pub const ALARM_SWAP_USED_DURATION: u64 = 300;
+pub const ALARM_ZSWAP_REJECT_RATE: u64 = 1000;
+
+// ── Hooks ────────────────────────────────────────────────────────────────────
+
+pub const EXEC_AFTER_START: &str = "";
+pub const EXEC_BEFORE_STOP: &str = "";
+
+// ── External commands ────────────────────────────────────────────────────────
+
+pub const CMD_TIMEOUT: u64 = 60;
+pub const CMD_TIMEOUT_SYSTEMCTL: u64 = 120;
+pub const CMD_TIMEOUT_MKSWAP: u64 = 120;
+pub const CMD_TIMEOUT_SWAPOFF: u64 = 600;
+pub const CMD_TIMEOUT_BTRFS: u64 = 300;
+
+// ── History ──────────────────────────────────────────────────────────────────
+
+pub const SWAP_HISTORY: bool = true;
+
+// ── Latency probe ────────────────────────────────────────────────────────────
+
+pub const SWAP_LATENCY_PROBE: bool = false;
+pub const SWAP_LATENCY_INTERVAL: u64 = 3600;
+
+// ── systemd-oomd ─────────────────────────────────────────────────────────────
+
+pub const OOMD_ALIGN: &str = "0";
+
+// ── PSI ──────────────────────────────────────────────────────────────────────
+
+pub const PSI_TRIGGER: bool = false;
+pub const PSI_STALL_US: u64 = 150_000;
+pub const PSI_WINDOW_US: u64 = 2_000_000;
+pub const PSI_IDLE_INTERVAL: u64 = 30;
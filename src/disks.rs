@@ -0,0 +1,165 @@
+// Disks shared between the swap file directory and other swap for systemd-swap
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// A swap partition (or a swap file set up by someone else) on the disk that
+// also holds swapfile_path means both compete for the same heads: on a
+// spinning disk, every file added there makes thrashing worse instead of
+// relieving it. Both sides are resolved to whole disks through sysfs
+// (partition to its disk, device-mapper and md devices through their slaves),
+// so LVM and LUKS volumes on the same disk count as shared too.
+// On a rotational disk the sharing changes policy: autoconfig leaves swap
+// files out (zram, with the partition as overflow), and in explicit modes only
+// the emergency triggers add files (SwapFileConfig::shared_slow_disk).
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use crate::absorb::read_swaps;
+use crate::swapfile::{source_block_device, StorageType};
+
+/// A swap area set up by others on a disk under the swap file directory
+#[derive(Debug, Clone, PartialEq)]
+pub struct SharedArea {
+    /// As listed in /proc/swaps
+    pub area: String,
+    /// Whole disk both live on, e.g. "sda"
+    pub disk: String,
+    pub storage: StorageType,
+}
+
+impl SharedArea {
+    /// A spinning disk, where the two thrash each other
+    pub fn is_slow(&self) -> bool {
+        self.storage == StorageType::Hdd
+    }
+}
+
+impl fmt::Display for SharedArea {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} also holds swap area {} ({})", self.disk, self.area, self.storage.name())
+    }
+}
+
+/// Whole disks under a block device given by kernel name ("sda2", "dm-0")
+pub fn whole_disks(name: &str) -> Vec<String> {
+    whole_disks_in(Path::new("/sys/class/block"), name)
+}
+
+fn whole_disks_in(sys: &Path, name: &str) -> Vec<String> {
+    let dev = sys.join(name);
+    let mut slaves: Vec<String> = fs::read_dir(dev.join("slaves"))
+        .map(|entries| entries.flatten().map(|e| e.file_name().to_string_lossy().into_owned()).collect())
+        .unwrap_or_default();
+    if !slaves.is_empty() {
+        slaves.sort();
+        let mut disks: Vec<String> = slaves.iter().flat_map(|s| whole_disks_in(sys, s)).collect();
+        disks.sort();
+        disks.dedup();
+        return disks;
+    }
+    if dev.join("partition").exists() {
+        if let Some(disk) = fs::canonicalize(&dev)
+            .ok()
+            .and_then(|p| p.parent().and_then(|d| d.file_name()).map(|n| n.to_string_lossy().into_owned()))
+        {
+            return vec![disk];
+        }
+    }
+    vec![name.to_string()]
+}
+
+/// Whole disks under the filesystem holding `dir`
+fn disks_of_dir(dir: &Path) -> Vec<String> {
+    source_block_device(dir).map(|name| whole_disks(&name)).unwrap_or_default()
+}
+
+/// Whole disks under a /proc/swaps entry; empty for zram and for areas
+/// inside `own` (the daemon's swap files, directly or through a loop device)
+fn disks_of_area(path: &str, own: &Path) -> Vec<String> {
+    let Some(dev) = path.strip_prefix("/dev/") else {
+        if Path::new(path).starts_with(own) {
+            return Vec::new();
+        }
+        return disks_of_dir(Path::new(path).parent().unwrap_or(Path::new("/")));
+    };
+    if dev.starts_with("zram") {
+        return Vec::new();
+    }
+    if dev.starts_with("loop") {
+        let backing = fs::read_to_string(format!("/sys/block/{}/loop/backing_file", dev)).unwrap_or_default();
+        let backing = Path::new(backing.trim());
+        if backing.starts_with(own) {
+            return Vec::new();
+        }
+        return disks_of_dir(backing.parent().unwrap_or(Path::new("/")));
+    }
+    let name = fs::canonicalize(path)
+        .ok()
+        .and_then(|p| p.file_name().map(|n| n.to_string_lossy().into_owned()))
+        .unwrap_or_else(|| dev.to_string());
+    whole_disks(&name)
+}
+
+/// Active swap areas set up by others that share a disk with `dir`
+pub fn shared_areas(dir: &Path) -> Vec<SharedArea> {
+    let ours = disks_of_dir(dir);
+    if ours.is_empty() {
+        return Vec::new();
+    }
+    read_swaps()
+        .into_iter()
+        .filter_map(|area| {
+            let disk = disks_of_area(&area.path, dir).into_iter().find(|d| ours.contains(d))?;
+            Some(SharedArea {
+                storage: StorageType::of_device(disk.clone()),
+                area: area.path,
+                disk,
+            })
+        })
+        .collect()
+}
+
+/// The first area sharing a spinning disk with `dir`, if any
+pub fn slow_shared_area(dir: &Path) -> Option<SharedArea> {
+    shared_areas(dir).into_iter().find(SharedArea::is_slow)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::symlink;
+
+    #[test]
+    fn resolves_partitions_and_mapped_devices_to_disks() {
+        let root = std::env::temp_dir().join(format!("disks-{}", std::process::id()));
+        let sys = root.join("class");
+        let devices = root.join("devices");
+        for part in ["sda/sda2", "sda/sda3", "sdb/sdb1"] {
+            fs::create_dir_all(devices.join(part)).unwrap();
+            fs::write(devices.join(part).join("partition"), "1\n").unwrap();
+        }
+        fs::create_dir_all(devices.join("dm-0/slaves/sda3")).unwrap();
+        fs::create_dir_all(devices.join("md0/slaves/sda2")).unwrap();
+        fs::create_dir_all(devices.join("md0/slaves/sdb1")).unwrap();
+        fs::create_dir_all(&sys).unwrap();
+        for (name, target) in [
+            ("sda2", "sda/sda2"),
+            ("sda3", "sda/sda3"),
+            ("sdb1", "sdb/sdb1"),
+            ("dm-0", "dm-0"),
+            ("md0", "md0"),
+        ] {
+            symlink(devices.join(target), sys.join(name)).unwrap();
+        }
+
+        assert_eq!(whole_disks_in(&sys, "sda2"), ["sda"]);
+        assert_eq!(whole_disks_in(&sys, "dm-0"), ["sda"]);
+        assert_eq!(whole_disks_in(&sys, "md0"), ["sda", "sdb"]);
+        assert_eq!(whole_disks_in(&sys, "nvme0n1"), ["nvme0n1"]);
+        let _ = fs::remove_dir_all(&root);
+
+        assert!(disks_of_area("/dev/zram0", Path::new("/swapfile")).is_empty());
+        assert!(disks_of_area("/swapfile/3", Path::new("/swapfile")).is_empty());
+    }
+}
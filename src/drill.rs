@@ -0,0 +1,287 @@
+//! Emergency readiness drill for systemd-swap (`simulate-oom`).
+//!
+//! Walks the swap file monitor's emergency path without allocating memory
+//! or creating anything. A synthetic critical sample (free RAM far below
+//! EMERGENCY_FREE_RAM, swap files nearly full, zswap slots exhausted) goes
+//! through the same expansion triggers the monitor runs each tick, and the
+//! preconditions of creating the file are checked on the live system: the
+//! daemon and its mode, the swap directory, free space, the file limit, the
+//! cooldowns the daemon last published and the programs creation runs. The
+//! report ends with the expected timeline from the pressure to usable swap.
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use nix::unistd::{access, AccessFlags};
+
+use crate::absorb::read_swaps;
+use crate::config::Config;
+use crate::helpers::format_size;
+use crate::meminfo::get_free_swap_percent_effective;
+use crate::selftest::{Check, Outcome};
+use crate::state::DaemonState;
+use crate::swapfile::{
+    Expansion, ExpansionSample, StorageType, SwapFileConfig, SwapFilePacing, EMERGENCY_FREE_RAM, Result,
+};
+
+/// Seconds for mkswap, the unit start and swapon after the file exists
+const ACTIVATION_SECS: u64 = 1;
+
+/// Sequential write rate assumed for preallocating a file, bytes/s
+fn write_rate(storage: StorageType) -> u64 {
+    match storage {
+        StorageType::Nvme => 1000 * 1024 * 1024,
+        StorageType::Ssd => 400 * 1024 * 1024,
+        StorageType::Hdd | StorageType::Unknown => 100 * 1024 * 1024,
+    }
+}
+
+/// Report of a drill: checks, the trigger that fired and the timeline
+#[derive(Debug, Clone, Default)]
+pub struct DrillReport {
+    pub checks: Vec<Check>,
+    pub trigger: Option<Expansion>,
+    /// Seconds after the pressure appears, and what happens then
+    pub timeline: Vec<(u64, String)>,
+}
+
+impl DrillReport {
+    /// True when no check failed
+    pub fn ready(&self) -> bool {
+        self.checks.iter().all(|c| c.outcome != Outcome::Fail)
+    }
+
+    fn push(&mut self, name: &'static str, outcome: Outcome, detail: String) {
+        self.checks.push(Check {
+            name,
+            outcome,
+            detail,
+        });
+    }
+
+    pub fn print(&self) {
+        println!("Emergency drill (simulated: nothing is allocated or created):");
+        for check in &self.checks {
+            println!("  [{}] {:<12} {}", check.outcome, check.name, check.detail);
+        }
+        if !self.timeline.is_empty() {
+            println!("Expected timeline:");
+            for (at, step) in &self.timeline {
+                println!("  +{:<5} {}", format!("{}s", at), step);
+            }
+        }
+        println!("Result: {}", if self.ready() { "READY" } else { "NOT READY" });
+    }
+}
+
+/// Swap files of the pool in /proc/swaps (loop devices by their backing
+/// file), as (total, unused)
+fn pool_files(dir: &Path) -> (u32, usize) {
+    let burst = dir.join("burst");
+    let files: Vec<u64> = read_swaps()
+        .into_iter()
+        .filter(|area| {
+            let backing = match area.path.strip_prefix("/dev/") {
+                Some(dev) if dev.starts_with("loop") => {
+                    fs::read_to_string(format!("/sys/block/{}/loop/backing_file", dev)).unwrap_or_default()
+                }
+                _ => area.path.clone(),
+            };
+            let backing = Path::new(backing.trim());
+            backing.starts_with(dir) && !backing.starts_with(&burst)
+        })
+        .map(|area| area.used)
+        .collect();
+    (files.len() as u32, files.iter().filter(|used| **used == 0).count())
+}
+
+fn in_path(program: &str) -> bool {
+    env::var_os("PATH")
+        .is_some_and(|path| env::split_paths(&path).any(|dir| dir.join(program).is_file()))
+}
+
+/// Whether the daemon's swap mode `mode` manages swap files
+fn manages_swap_files(mode: &str, config: &Config) -> bool {
+    mode.contains("swapfc") || (mode == "manual" && config.get_bool("swapfile_enabled"))
+}
+
+/// Run the drill against the live system and `config`
+pub fn run(config: &Config) -> Result<DrillReport> {
+    let swapfile = SwapFileConfig::from_config(config)?;
+    let mut report = DrillReport::default();
+
+    // The daemon and whether its mode manages swap files at all
+    let daemon = DaemonState::load();
+    let zswap = match &daemon {
+        Some(d) => d.mode.starts_with("zswap") || (d.mode == "manual" && config.get_bool("zswap_enabled")),
+        None => crate::zswap::is_enabled(),
+    };
+    match &daemon {
+        None => report.push("daemon", Outcome::Fail, "not running, nothing would react".to_string()),
+        Some(d) if manages_swap_files(&d.mode, config) => {
+            report.push("daemon", Outcome::Pass, format!("running in {} mode (pid {})", d.mode, d.pid))
+        }
+        Some(d) => report.push(
+            "daemon",
+            Outcome::Fail,
+            format!("{} mode manages no swap files, zram is all the swap there is", d.mode),
+        ),
+    }
+
+    // Where the file would go
+    let dir = &swapfile.path;
+    let writable_dir = dir.ancestors().find(|p| p.exists()).unwrap_or(Path::new("/"));
+    match access(writable_dir, AccessFlags::W_OK) {
+        Ok(()) => report.push("directory", Outcome::Pass, format!("{} is writable", dir.display())),
+        Err(e) => report.push("directory", Outcome::Fail, format!("{}: {}", writable_dir.display(), e)),
+    }
+
+    // Capacity left under swapfile_max_count
+    let (allocated, unused) = pool_files(dir);
+    let at_max_count = allocated >= swapfile.max_count;
+    report.push(
+        "file limit",
+        if at_max_count { Outcome::Fail } else { Outcome::Pass },
+        format!("{} of {} swap files in use", allocated, swapfile.max_count),
+    );
+
+    // Free space for the next file, with the monitor's 2x margin
+    let size = if swapfile.sparse_loop_backing && zswap {
+        swapfile.growth_size()
+    } else if swapfile.layout.is_empty() {
+        swapfile.chunk_size
+    } else {
+        swapfile.size_for_file(allocated + 1)
+    };
+    let free = nix::sys::statvfs::statvfs(writable_dir)
+        .map(|s| s.blocks_available() * s.block_size())
+        .unwrap_or(0);
+    let space_ok = free >= size * 2;
+    report.push(
+        "disk space",
+        if space_ok { Outcome::Pass } else { Outcome::Fail },
+        format!("{} free, a {} file needs {}", format_size(free), format_size(size), format_size(size * 2)),
+    );
+
+    // Cooldowns as the daemon last published them
+    let pacing = SwapFilePacing::load();
+    let wait = pacing.as_ref().map_or(0, |p| p.emergency_in);
+    let disk_full = pacing.as_ref().is_some_and(|p| p.disk_full);
+    let fs_busy = pacing.as_ref().is_some_and(|p| p.fs_busy);
+    match &pacing {
+        None => report.push("cooldown", Outcome::Skip, "no pacing published by the monitor".to_string()),
+        Some(_) if disk_full => report.push(
+            "cooldown",
+            Outcome::Fail,
+            "expansion paused: the monitor found the filesystem full".to_string(),
+        ),
+        Some(_) if fs_busy => report.push(
+            "cooldown",
+            Outcome::Fail,
+            "expansion deferred: a btrfs balance, resize or device change is running".to_string(),
+        ),
+        Some(_) if wait > 0 => report.push("cooldown", Outcome::Pass, format!("emergency creation allowed in {}s", wait)),
+        Some(_) => report.push("cooldown", Outcome::Pass, "emergency creation allowed now".to_string()),
+    }
+
+    // Programs creation runs
+    let mut programs = vec!["mkswap", "systemctl"];
+    if swapfile.sparse_loop_backing {
+        programs.extend(["truncate", "losetup"]);
+    }
+    let missing: Vec<&str> = programs.iter().copied().filter(|p| !in_path(p)).collect();
+    if missing.is_empty() {
+        report.push("programs", Outcome::Pass, programs.join(", "));
+    } else {
+        report.push("programs", Outcome::Fail, format!("not found: {}", missing.join(", ")));
+    }
+
+    // The synthetic critical sample through the monitor's triggers
+    let sample = ExpansionSample {
+        free_ram: EMERGENCY_FREE_RAM / 2,
+        free_swap: get_free_swap_percent_effective().unwrap_or(100).min(50),
+        disk_free_swap: 10,
+        unused_files: unused,
+        files_stressed: false,
+        slots_exhausted: true,
+        absorb_requested: false,
+        cooldown_ok: true,
+        emergency_cooldown_ok: true,
+        zswap,
+        disk_full: disk_full || !space_ok,
+        fs_busy,
+        at_max_count,
+    };
+    report.trigger = swapfile.expansion_trigger(&sample);
+    match report.trigger {
+        Some(trigger) => report.push(
+            "decision",
+            Outcome::Pass,
+            format!("{} trigger adds a {} file", trigger.name(), format_size(size)),
+        ),
+        None if !sample.disk_full && !at_max_count && !zswap && unused >= 2 => report.push(
+            "decision",
+            Outcome::Pass,
+            format!("{} unused swap files take the first pages, a new file follows once they fill", unused),
+        ),
+        None => report.push("decision", Outcome::Fail, "no trigger would add a swap file".to_string()),
+    }
+
+    if report.trigger.is_some() {
+        let interval = pacing
+            .as_ref()
+            .map(|p| p.interval)
+            .filter(|i| *i > 0)
+            .unwrap_or(swapfile.frequency);
+        let storage = StorageType::detect(dir);
+        let mut at = 0;
+        report.timeline.push((at, format!("critical pressure, free RAM {}%", sample.free_ram)));
+        at += interval;
+        report.timeline.push((at, "monitor tick sees it (at the latest)".to_string()));
+        if wait > interval {
+            at = wait;
+            report.timeline.push((at, "emergency cooldown over".to_string()));
+        }
+        if swapfile.sparse_loop_backing {
+            report.timeline.push((at, format!("sparse {} file attached to a loop device", format_size(size))));
+        } else {
+            at += size / write_rate(storage);
+            report.timeline.push((
+                at,
+                format!("{} file written on {} storage (estimate)", format_size(size), storage.name()),
+            ));
+        }
+        at += ACTIVATION_SECS;
+        report.timeline.push((at, "mkswap and swapon done, new swap usable".to_string()));
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ready_unless_a_check_failed() {
+        let mut report = DrillReport::default();
+        report.push("daemon", Outcome::Pass, String::new());
+        report.push("cooldown", Outcome::Skip, String::new());
+        assert!(report.ready());
+        report.push("disk space", Outcome::Fail, String::new());
+        assert!(!report.ready());
+        assert!(write_rate(StorageType::Nvme) > write_rate(StorageType::Hdd));
+    }
+
+    #[test]
+    fn manual_mode_with_swap_files_enabled() {
+        let config = |pairs: &[(&str, &str)]| {
+            Config::from_values(pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect())
+        };
+        assert!(manages_swap_files("manual", &config(&[("swapfile_enabled", "1")])));
+        assert!(!manages_swap_files("manual", &config(&[("swapfile_enabled", "0")])));
+        assert!(manages_swap_files("zram+swapfc", &config(&[])));
+        assert!(!manages_swap_files("zram", &config(&[])));
+    }
+}
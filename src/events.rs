@@ -0,0 +1,284 @@
+// Event history and OOM-kill tracking for systemd-swap
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// Events are appended to a per-boot log under /run so they survive daemon
+// restarts (which wipe WORK_DIR) but not reboots. One event per line:
+//   <unix time> <kind> key=value key=value ...
+
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use thiserror::Error;
+
+use crate::config::RUN_SYSD;
+use crate::helpers::now_secs;
+use crate::{is_shutdown, warn};
+
+/// Oldest events are dropped beyond this many lines
+const MAX_EVENTS: usize = 500;
+
+/// Writes between checks of the history's length
+const TRIM_EVERY: usize = MAX_EVENTS / 10;
+
+/// Events written by this process, to trim every TRIM_EVERY of them
+static WRITES: AtomicUsize = AtomicUsize::new(0);
+
+/// Serializes appends and trims of the daemon's threads
+static RECORD_LOCK: Mutex<()> = Mutex::new(());
+
+/// Seconds between /proc/vmstat checks
+const OOM_POLL_INTERVAL: u64 = 5;
+
+#[derive(Error, Debug)]
+pub enum EventError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+pub type Result<T> = std::result::Result<T, EventError>;
+
+fn events_file() -> String {
+    format!("{}/swap-events", RUN_SYSD)
+}
+
+/// Kinds of recorded events
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EventKind {
+    /// Kernel OOM killer ran
+    OomKill,
+    /// Swap file expansion held back by a btrfs balance, resize or device change
+    ExpansionDeferred,
+    /// That operation finished, expansion runs again
+    ExpansionResumed,
+    /// A zram device counted failed reads, writes or invalid requests
+    ZramIoFailure,
+    /// A swap area was activated by others (swapwatch.rs)
+    SwapAdded,
+    /// A swap area set up by others was deactivated
+    SwapRemoved,
+}
+
+impl EventKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EventKind::OomKill => "oom_kill",
+            EventKind::ExpansionDeferred => "expansion_deferred",
+            EventKind::ExpansionResumed => "expansion_resumed",
+            EventKind::ZramIoFailure => "zram_io_failure",
+            EventKind::SwapAdded => "swap_added",
+            EventKind::SwapRemoved => "swap_removed",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "oom_kill" => Some(EventKind::OomKill),
+            "expansion_deferred" => Some(EventKind::ExpansionDeferred),
+            "expansion_resumed" => Some(EventKind::ExpansionResumed),
+            "zram_io_failure" => Some(EventKind::ZramIoFailure),
+            "swap_added" => Some(EventKind::SwapAdded),
+            "swap_removed" => Some(EventKind::SwapRemoved),
+            _ => None,
+        }
+    }
+}
+
+/// A recorded event with its state snapshot
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event {
+    pub timestamp: u64,
+    pub kind: EventKind,
+    pub details: Vec<(String, String)>,
+}
+
+impl Event {
+    pub fn new(kind: EventKind, details: Vec<(String, String)>) -> Self {
+        Self {
+            timestamp: now_secs(),
+            kind,
+            details,
+        }
+    }
+
+    /// Look up a detail value
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.details
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    fn to_line(&self) -> String {
+        let mut line = format!("{} {}", self.timestamp, self.kind.as_str());
+        for (key, value) in &self.details {
+            // Spaces in values become underscores to keep the line splittable
+            line.push_str(&format!(" {}={}", key, value.replace(' ', "_")));
+        }
+        line
+    }
+
+    fn from_line(line: &str) -> Option<Self> {
+        let mut fields = line.split_whitespace();
+        let timestamp = fields.next()?.parse().ok()?;
+        let kind = EventKind::parse(fields.next()?)?;
+        let details = fields
+            .filter_map(|f| f.split_once('='))
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        Some(Self {
+            timestamp,
+            kind,
+            details,
+        })
+    }
+}
+
+/// Append an event to the history
+pub fn record(event: &Event) -> Result<()> {
+    // A trim rewriting the file would drop a line appended meanwhile
+    let _guard = RECORD_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let path = events_file();
+    let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+    writeln!(file, "{}", event.to_line())?;
+    drop(file);
+
+    // Trim on the first write of the process and every TRIM_EVERY after it
+    if !WRITES.fetch_add(1, Ordering::Relaxed).is_multiple_of(TRIM_EVERY) {
+        return Ok(());
+    }
+    let content = fs::read_to_string(&path)?;
+    let lines: Vec<&str> = content.lines().collect();
+    if lines.len() > MAX_EVENTS + MAX_EVENTS / 10 {
+        let keep = lines[lines.len() - MAX_EVENTS..].join("\n");
+        fs::write(&path, keep + "\n")?;
+    }
+    Ok(())
+}
+
+/// All recorded events, oldest first
+pub fn load() -> Vec<Event> {
+    fs::read_to_string(events_file())
+        .map(|c| c.lines().filter_map(Event::from_line).collect())
+        .unwrap_or_default()
+}
+
+/// Read a counter from /proc/vmstat content
+fn vmstat_counter(content: &str, key: &str) -> Option<u64> {
+    content.lines().find_map(|line| {
+        let (k, v) = line.split_once(' ')?;
+        if k == key {
+            v.trim().parse().ok()
+        } else {
+            None
+        }
+    })
+}
+
+/// OOM kills since boot (oom_kill in /proc/vmstat, kernel 4.13+)
+pub fn oom_kill_count() -> Option<u64> {
+    let content = fs::read_to_string("/proc/vmstat").ok()?;
+    vmstat_counter(&content, "oom_kill")
+}
+
+/// Swap and pool state at this moment, for correlating events
+pub(crate) fn snapshot() -> Vec<(String, String)> {
+    let mut details = Vec::new();
+    if let Ok(mem) = crate::meminfo::get_mem_stats(&[
+        "MemTotal",
+        "MemAvailable",
+        "SwapTotal",
+        "SwapFree",
+    ]) {
+        details.push(("mem_available".into(), mem["MemAvailable"].to_string()));
+        details.push(("mem_total".into(), mem["MemTotal"].to_string()));
+        details.push(("swap_total".into(), mem["SwapTotal"].to_string()));
+        details.push((
+            "swap_used".into(),
+            mem["SwapTotal"].saturating_sub(mem["SwapFree"]).to_string(),
+        ));
+    }
+    if let Some(zram) = crate::zram::get_zram_stats() {
+        details.push(("zram_stored".into(), zram.orig_data_size.to_string()));
+        details.push(("zram_used".into(), zram.mem_used_total.to_string()));
+    }
+    if let Ok(usage) = crate::meminfo::get_effective_swap_usage() {
+        if usage.zswap_active {
+            details.push(("zswap_stored".into(), usage.zswapped_original_bytes.to_string()));
+            details.push(("zswap_pool".into(), usage.zswap_pool_bytes.to_string()));
+        }
+    }
+    let swap_areas = fs::read_to_string("/proc/swaps")
+        .map(|s| s.lines().count().saturating_sub(1))
+        .unwrap_or(0);
+    details.push(("swap_areas".into(), swap_areas.to_string()));
+    details
+}
+
+/// Watch the OOM-kill counter and record a state snapshot for every new kill
+pub fn spawn_oom_watcher() {
+    let Some(mut last) = oom_kill_count() else {
+        warn!("Events: /proc/vmstat has no oom_kill counter, OOM tracking disabled");
+        return;
+    };
+    crate::context::spawn(move || {
+        while !is_shutdown() {
+            thread::sleep(Duration::from_secs(OOM_POLL_INTERVAL));
+            let Some(count) = oom_kill_count() else {
+                continue;
+            };
+            if count > last {
+                let mut details = vec![
+                    ("kills".to_string(), (count - last).to_string()),
+                    ("total".to_string(), count.to_string()),
+                ];
+                details.extend(snapshot());
+                warn!(
+                    "Events: {} OOM kill(s) detected ({} since boot)",
+                    count - last,
+                    count
+                );
+                if let Err(e) = record(&Event::new(EventKind::OomKill, details)) {
+                    warn!("Events: failed to record OOM kill: {}", e);
+                }
+            }
+            last = count;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn event_line_roundtrip() {
+        let event = Event {
+            timestamp: 1_700_000_000,
+            kind: EventKind::OomKill,
+            details: vec![
+                ("kills".into(), "1".into()),
+                ("swap_used".into(), "1048576".into()),
+            ],
+        };
+        let line = event.to_line();
+        assert_eq!(line, "1700000000 oom_kill kills=1 swap_used=1048576");
+        assert_eq!(Event::from_line(&line), Some(event));
+    }
+
+    #[test]
+    fn unknown_event_lines_are_skipped() {
+        assert_eq!(Event::from_line("1700000000 reboot"), None);
+        assert_eq!(Event::from_line("garbage"), None);
+    }
+
+    #[test]
+    fn vmstat_oom_counter() {
+        let vmstat = "pgfault 123\noom_kill 7\npswpin 0\n";
+        assert_eq!(vmstat_counter(vmstat, "oom_kill"), Some(7));
+        assert_eq!(vmstat_counter(vmstat, "missing"), None);
+    }
+}
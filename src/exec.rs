@@ -0,0 +1,228 @@
+// External command execution for systemd-swap
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// Every external program the daemon waits for (systemctl, mkswap, swapoff,
+// btrfs, losetup, ...) runs through `status` or `output` (also available as
+// TimedCommand methods), which kill it once its timeout expires. A hung D-Bus
+// or a stuck block device then fails one step with a TimedOut error instead
+// of blocking a monitor loop forever; the callers treat that like any other
+// failed attempt and retry on a later tick.
+//
+// Timeouts come from the cmd_timeout* keys, per program for the slow ones.
+// Processes that run without the daemon waiting (hooks, the selftest worker)
+// manage their own lifetime and do not go through here.
+//
+// In read-only mode (readonly.rs) programs that may change the system are
+// refused here with a PermissionDenied error instead of being started.
+
+use std::ffi::OsStr;
+use std::io::{self, Read};
+use std::path::Path;
+use std::process::{Child, Command, ExitStatus, Output, Stdio};
+use std::sync::OnceLock;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+use crate::defaults;
+use crate::readonly;
+use crate::warn;
+
+/// Timeouts in seconds per program class; 0 = wait indefinitely
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Timeouts {
+    pub default: u64,
+    pub systemctl: u64,
+    pub mkswap: u64,
+    pub swapoff: u64,
+    pub btrfs: u64,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Self {
+            default: defaults::CMD_TIMEOUT,
+            systemctl: defaults::CMD_TIMEOUT_SYSTEMCTL,
+            mkswap: defaults::CMD_TIMEOUT_MKSWAP,
+            swapoff: defaults::CMD_TIMEOUT_SWAPOFF,
+            btrfs: defaults::CMD_TIMEOUT_BTRFS,
+        }
+    }
+}
+
+impl Timeouts {
+    pub fn from_config(config: &Config) -> Self {
+        // Below a few seconds even a healthy system would hit the limit
+        let read = |key: &str, default: u64| match config.get_as::<u64>(key).unwrap_or(default) {
+            0 => 0,
+            secs => secs.max(5),
+        };
+        Self {
+            default: read("cmd_timeout", defaults::CMD_TIMEOUT),
+            systemctl: read("cmd_timeout_systemctl", defaults::CMD_TIMEOUT_SYSTEMCTL),
+            mkswap: read("cmd_timeout_mkswap", defaults::CMD_TIMEOUT_MKSWAP),
+            swapoff: read("cmd_timeout_swapoff", defaults::CMD_TIMEOUT_SWAPOFF),
+            btrfs: read("cmd_timeout_btrfs", defaults::CMD_TIMEOUT_BTRFS),
+        }
+    }
+
+    /// Timeout for `program`, None to wait indefinitely
+    fn for_program(&self, program: &OsStr) -> Option<Duration> {
+        let name = Path::new(program).file_name().unwrap_or(program);
+        let secs = match name.to_str() {
+            Some("systemctl") => self.systemctl,
+            Some("mkswap") => self.mkswap,
+            Some("swapoff") => self.swapoff,
+            Some("btrfs") => self.btrfs,
+            _ => self.default,
+        };
+        (secs > 0).then(|| Duration::from_secs(secs))
+    }
+}
+
+static TIMEOUTS: OnceLock<Timeouts> = OnceLock::new();
+
+/// Apply the configured timeouts; before this (CLI commands) the defaults hold
+pub fn init(config: &Config) {
+    let _ = TIMEOUTS.set(Timeouts::from_config(config));
+}
+
+fn timeouts() -> Timeouts {
+    TIMEOUTS.get().copied().unwrap_or_default()
+}
+
+/// Whether `err` comes from a command killed at its timeout
+pub fn is_timeout(err: &io::Error) -> bool {
+    err.kind() == io::ErrorKind::TimedOut
+}
+
+/// Wait for `child`, killing it once `timeout` has passed
+fn wait(child: &mut Child, program: &OsStr, timeout: Option<Duration>) -> io::Result<ExitStatus> {
+    let Some(timeout) = timeout else {
+        return child.wait();
+    };
+    let deadline = Instant::now() + timeout;
+    let mut poll = Duration::from_millis(5);
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(status);
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            let message = format!(
+                "{} did not finish within {}s, killed",
+                program.to_string_lossy(),
+                timeout.as_secs()
+            );
+            warn!("{}", message);
+            return Err(io::Error::new(io::ErrorKind::TimedOut, message));
+        }
+        thread::sleep(poll);
+        poll = (poll * 2).min(Duration::from_millis(100));
+    }
+}
+
+/// Refuse `cmd` in read-only mode unless it only observes the system
+fn check_read_only(cmd: &Command) -> io::Result<()> {
+    if readonly::is_enabled() && !readonly::permits(cmd.get_program(), &cmd.get_args().collect::<Vec<_>>()) {
+        return Err(readonly::refused(&format!("run {}", cmd.get_program().to_string_lossy())));
+    }
+    Ok(())
+}
+
+/// `Command::status` with the program's timeout
+pub fn status(cmd: &mut Command) -> io::Result<ExitStatus> {
+    check_read_only(cmd)?;
+    let timeout = timeouts().for_program(cmd.get_program());
+    let mut child = cmd.spawn()?;
+    wait(&mut child, cmd.get_program(), timeout)
+}
+
+/// `Command::output` with the program's timeout; stdout and stderr are
+/// always captured
+pub fn output(cmd: &mut Command) -> io::Result<Output> {
+    check_read_only(cmd)?;
+    let timeout = timeouts().for_program(cmd.get_program());
+    let mut child = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    // Drain both pipes while waiting, so a chatty command cannot block on a
+    // full pipe; they close once the command exits or is killed
+    fn drain(pipe: Option<impl Read + Send + 'static>) -> thread::JoinHandle<Vec<u8>> {
+        thread::spawn(move || {
+            let mut buf = Vec::new();
+            if let Some(mut pipe) = pipe {
+                let _ = pipe.read_to_end(&mut buf);
+            }
+            buf
+        })
+    }
+    let stdout = drain(child.stdout.take());
+    let stderr = drain(child.stderr.take());
+
+    let status = wait(&mut child, cmd.get_program(), timeout)?;
+    Ok(Output {
+        status,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    })
+}
+
+/// `status`/`output` as methods, so command chains read as before
+pub trait TimedCommand {
+    fn status_timed(&mut self) -> io::Result<ExitStatus>;
+    fn output_timed(&mut self) -> io::Result<Output>;
+}
+
+impl TimedCommand for Command {
+    fn status_timed(&mut self) -> io::Result<ExitStatus> {
+        status(self)
+    }
+
+    fn output_timed(&mut self) -> io::Result<Output> {
+        output(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn timeouts_per_program() {
+        let config = Config::from_values(HashMap::from([
+            ("cmd_timeout".to_string(), "1".to_string()),
+            ("cmd_timeout_swapoff".to_string(), "0".to_string()),
+        ]));
+        let t = Timeouts::from_config(&config);
+        assert_eq!(t.for_program(OsStr::new("losetup")), Some(Duration::from_secs(5)));
+        assert_eq!(t.for_program(OsStr::new("/usr/bin/swapoff")), None);
+        assert_eq!(
+            t.for_program(OsStr::new("systemctl")),
+            Some(Duration::from_secs(defaults::CMD_TIMEOUT_SYSTEMCTL))
+        );
+    }
+
+    #[test]
+    fn hung_command_is_killed() {
+        let mut child = Command::new("sleep").arg("10").spawn().unwrap();
+        let started = Instant::now();
+        let err = wait(&mut child, OsStr::new("sleep"), Some(Duration::from_millis(50))).unwrap_err();
+        assert!(is_timeout(&err));
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        if readonly::is_enabled() {
+            let err = output(Command::new("echo").arg("ok")).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+            return;
+        }
+        let out = output(Command::new("echo").arg("ok")).unwrap();
+        assert!(out.status.success());
+        assert_eq!(out.stdout, b"ok\n");
+    }
+}
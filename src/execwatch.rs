@@ -0,0 +1,138 @@
+// Launch hints for the zram pool (zram_exec_watch)
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// Browsers, IDEs, games and the like claim gigabytes within seconds of
+// starting, faster than the pool notices its utilization rising and adds a
+// device. With zram_exec_watch=1 each pool monitor tick scans /proc for
+// processes named in zram_exec_watch_apps; one with no process at the
+// previous scan is a launch (further processes of a running browser are not),
+// and the pool adds one device ahead of the demand.
+// If the pool's data never reaches what would have triggered a normal
+// expansion within zram_exec_watch_grace seconds, that device is removed again.
+//
+// /proc is scanned instead of listening to the kernel's proc connector, which
+// needs netlink socket calls outside what this crate does without unsafe code.
+// A launch is seen within one check interval, early enough for a spike that
+// takes seconds to build.
+
+use std::collections::HashSet;
+use std::fs;
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::defaults;
+
+/// Length the kernel truncates process names (comm) to
+const COMM_LEN: usize = 15;
+
+/// Watches for launches of the configured applications
+#[derive(Debug)]
+pub struct ExecWatch {
+    /// Process names to watch for, truncated like comm
+    apps: Vec<String>,
+    /// How long a pre-expansion waits for the demand
+    pub grace: Duration,
+    /// Applications running at the last scan
+    running: HashSet<String>,
+    /// A first scan has run; processes found by it are not launches
+    primed: bool,
+}
+
+impl ExecWatch {
+    /// None unless zram_exec_watch is on and names at least one application
+    pub fn from_config(config: &Config) -> Option<Self> {
+        let enabled = match config.get_opt("zram_exec_watch") {
+            Some(_) => config.get_bool("zram_exec_watch"),
+            None => defaults::ZRAM_EXEC_WATCH,
+        };
+        if !enabled {
+            return None;
+        }
+        let apps: Vec<String> = config
+            .get("zram_exec_watch_apps")
+            .unwrap_or(defaults::ZRAM_EXEC_WATCH_APPS)
+            .split(',')
+            .map(|name| name.trim().chars().take(COMM_LEN).collect::<String>())
+            .filter(|name| !name.is_empty())
+            .collect();
+        if apps.is_empty() {
+            return None;
+        }
+        Some(Self {
+            apps,
+            grace: Duration::from_secs(
+                config
+                    .get_as::<u64>("zram_exec_watch_grace")
+                    .unwrap_or(defaults::ZRAM_EXEC_WATCH_GRACE)
+                    .clamp(30, 1800),
+            ),
+            running: HashSet::new(),
+            primed: false,
+        })
+    }
+
+    /// Scan /proc; returns the names of applications launched since the
+    /// last scan
+    pub fn poll(&mut self) -> Vec<String> {
+        let procs = scan();
+        self.launches(&procs)
+    }
+
+    fn launches(&mut self, procs: &[(i32, String)]) -> Vec<String> {
+        let running: HashSet<String> = procs
+            .iter()
+            .filter(|(_, comm)| self.apps.contains(comm))
+            .map(|(_, comm)| comm.clone())
+            .collect();
+        let mut launched: Vec<String> = if self.primed {
+            running.difference(&self.running).cloned().collect()
+        } else {
+            Vec::new()
+        };
+        launched.sort();
+        self.running = running;
+        self.primed = true;
+        launched
+    }
+}
+
+/// Processes by pid and comm
+fn scan() -> Vec<(i32, String)> {
+    let Ok(entries) = fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| {
+            let pid = entry.file_name().to_str()?.parse::<i32>().ok()?;
+            let comm = fs::read_to_string(entry.path().join("comm")).ok()?;
+            Some((pid, comm.trim_end().to_string()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn new_processes_of_listed_apps_are_launches() {
+        let config = Config::from_values(HashMap::from([
+            ("zram_exec_watch".to_string(), "1".to_string()),
+            ("zram_exec_watch_apps".to_string(), "firefox, blender".to_string()),
+        ]));
+        let mut watch = ExecWatch::from_config(&config).unwrap();
+        let procs = |list: &[(i32, &str)]| list.iter().map(|(p, c)| (*p, c.to_string())).collect::<Vec<_>>();
+
+        // Already running at the first scan: not a launch
+        assert!(watch.launches(&procs(&[(10, "firefox"), (11, "bash")])).is_empty());
+        assert_eq!(watch.launches(&procs(&[(10, "firefox"), (20, "blender")])), ["blender"]);
+        // More processes of a running application are not a launch
+        assert!(watch.launches(&procs(&[(10, "firefox"), (12, "firefox"), (20, "blender")])).is_empty());
+        assert!(watch.launches(&procs(&[(20, "blender")])).is_empty());
+        assert_eq!(watch.launches(&procs(&[(30, "firefox"), (20, "blender")])), ["firefox"]);
+
+        assert!(ExecWatch::from_config(&Config::from_values(HashMap::new())).is_none());
+    }
+}
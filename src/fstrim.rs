@@ -0,0 +1,242 @@
+// Coordination with the scheduled fstrim for systemd-swap
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// Discard wipes punch holes into removed swap files so the filesystem trims
+// their blocks, which is the same work fstrim.timer does for all free space.
+// Run back to back, or at the same time, the SSD sees the same regions
+// trimmed twice and two bursts of maintenance I/O instead of one. So a
+// discard wipe does not start, and one in progress pauses, from
+// swapfile_trim_window seconds before the timer's next run until fstrim.service
+// has finished; it then resumes after a random delay of up to TRIM_JITTER, so
+// it does not land on top of whatever else the same calendar event started.
+// Without a readable fstrim.timer (not installed, not enabled, systemd too old
+// for `show --timestamp=unix`) wipes are not held back.
+
+use std::process::Command;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::exec::TimedCommand;
+use crate::helpers::now_secs;
+
+/// Seconds between queries of the timer while a wipe runs
+const QUERY_INTERVAL: u64 = 60;
+
+/// Upper bound of the random delay after fstrim finishes, in seconds
+pub const TRIM_JITTER: u64 = 300;
+
+/// A run this long overdue is taken as stale (timer stopped meanwhile) and no
+/// longer holds wipes back; covers fstrim.timer's AccuracySec and
+/// RandomizedDelaySec as shipped by util-linux
+const OVERDUE_MAX: u64 = 3 * 3600;
+
+/// What systemd reports about fstrim.timer and fstrim.service
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FstrimTimer {
+    /// Calendar spec of the timer, e.g. "OnCalendar=weekly"
+    pub calendar: Option<String>,
+    /// Next elapse in seconds since the epoch; None if the timer is not active
+    pub next: Option<u64>,
+    /// fstrim.service is running right now
+    pub running: bool,
+}
+
+impl FstrimTimer {
+    /// Parse `systemctl show --timestamp=unix` output for fstrim.timer
+    /// followed by fstrim.service (blocks separated by an empty line)
+    pub fn parse(output: &str) -> Self {
+        let mut timer = Self::default();
+        let mut timer_active = false;
+        for (block, text) in output.split("\n\n").enumerate() {
+            for line in text.lines() {
+                let Some((key, value)) = line.split_once('=') else {
+                    continue;
+                };
+                match (block, key) {
+                    (0, "ActiveState") => timer_active = value == "active",
+                    (0, "NextElapseUSecRealtime") => {
+                        timer.next = value.strip_prefix('@').and_then(|s| s.trim().parse().ok());
+                    }
+                    (0, "TimersCalendar") => {
+                        // { OnCalendar=weekly ; next_elapse=... }
+                        timer.calendar = value
+                            .trim_start_matches(['{', ' '])
+                            .split(" ;")
+                            .next()
+                            .filter(|s| s.contains('='))
+                            .map(str::to_string);
+                    }
+                    (1, "ActiveState") => timer.running = matches!(value, "activating" | "active" | "deactivating"),
+                    _ => {}
+                }
+            }
+        }
+        if !timer_active {
+            timer.next = None;
+        }
+        timer
+    }
+
+    /// Ask systemd; None if it cannot tell
+    pub fn query() -> Option<Self> {
+        let output = Command::new("systemctl")
+            .args([
+                "show",
+                "--timestamp=unix",
+                "-p",
+                "ActiveState",
+                "-p",
+                "NextElapseUSecRealtime",
+                "-p",
+                "TimersCalendar",
+                "fstrim.timer",
+                "fstrim.service",
+            ])
+            .output_timed()
+            .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        Some(Self::parse(&String::from_utf8_lossy(&output.stdout)))
+    }
+
+    /// Whether discards should wait at `now`: fstrim runs, or its next run is
+    /// less than `window` seconds away (or overdue, but not stale)
+    pub fn holds_off(&self, now: u64, window: u64) -> bool {
+        if self.running {
+            return true;
+        }
+        match self.next {
+            Some(next) => now + window >= next && now < next + OVERDUE_MAX,
+            None => false,
+        }
+    }
+
+    /// Human-readable description for the log
+    pub fn describe(&self) -> String {
+        let calendar = self.calendar.as_deref().unwrap_or("unknown calendar");
+        match (self.running, self.next) {
+            (true, _) => format!("fstrim.service is running ({})", calendar),
+            (false, Some(next)) => {
+                let now = now_secs();
+                if next > now {
+                    format!("fstrim.timer is due in {}s ({})", next - now, calendar)
+                } else {
+                    format!("fstrim.timer is overdue by {}s ({})", now - next, calendar)
+                }
+            }
+            (false, None) => "fstrim.timer is not active".to_string(),
+        }
+    }
+}
+
+/// Random delay in [0, TRIM_JITTER] seconds, from the clock's sub-second part
+fn jitter() -> Duration {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos() as u64)
+        .unwrap_or(0);
+    Duration::from_secs((nanos ^ std::process::id() as u64) % (TRIM_JITTER + 1))
+}
+
+/// Per-wipe view of the fstrim schedule, re-queried every QUERY_INTERVAL
+#[derive(Debug)]
+pub struct TrimGate {
+    window: u64,
+    timer: Option<FstrimTimer>,
+    queried: Option<Instant>,
+    /// fstrim was pending or running at the last check
+    held: bool,
+    /// After fstrim finished: when the wipe may continue
+    release_at: Option<Instant>,
+}
+
+/// What a wipe should do before its next discard
+#[derive(Debug, Clone, PartialEq)]
+pub enum Gate {
+    /// Go ahead
+    Open,
+    /// Hold back; the first time carries the reason for the log
+    Wait(Option<String>),
+    /// fstrim finished; continue after this delay (logged once)
+    Released(Duration),
+}
+
+impl TrimGate {
+    /// `window` in seconds; 0 disables the coordination
+    pub fn new(window: u64) -> Self {
+        Self {
+            window,
+            timer: None,
+            queried: None,
+            held: false,
+            release_at: None,
+        }
+    }
+
+    pub fn check(&mut self) -> Gate {
+        if self.window == 0 {
+            return Gate::Open;
+        }
+        if let Some(at) = self.release_at {
+            if Instant::now() < at {
+                return Gate::Wait(None);
+            }
+            self.release_at = None;
+        }
+        if self.queried.is_none_or(|q| q.elapsed() >= Duration::from_secs(QUERY_INTERVAL)) {
+            self.timer = FstrimTimer::query();
+            self.queried = Some(Instant::now());
+        }
+        let holds = self.timer.as_ref().is_some_and(|t| t.holds_off(now_secs(), self.window));
+        match (holds, self.held) {
+            (true, true) => Gate::Wait(None),
+            (true, false) => {
+                self.held = true;
+                Gate::Wait(self.timer.as_ref().map(FstrimTimer::describe))
+            }
+            (false, true) => {
+                self.held = false;
+                let delay = jitter();
+                self.release_at = Some(Instant::now() + delay);
+                Gate::Released(delay)
+            }
+            (false, false) => Gate::Open,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHOW: &str = "ActiveState=active\n\
+                        NextElapseUSecRealtime=@1760918400\n\
+                        TimersCalendar={ OnCalendar=weekly ; next_elapse=Mon 2025-10-20 00:00:00 UTC }\n\
+                        \n\
+                        ActiveState=inactive\n";
+
+    #[test]
+    fn parses_timer_and_service() {
+        let timer = FstrimTimer::parse(SHOW);
+        assert_eq!(timer.next, Some(1_760_918_400));
+        assert_eq!(timer.calendar.as_deref(), Some("OnCalendar=weekly"));
+        assert!(!timer.running);
+
+        let stopped = FstrimTimer::parse(&SHOW.replacen("ActiveState=active", "ActiveState=inactive", 1));
+        assert_eq!(stopped.next, None, "an inactive timer will not run");
+        let running = FstrimTimer::parse(&SHOW.replace("ActiveState=inactive", "ActiveState=activating"));
+        assert!(running.running);
+    }
+
+    #[test]
+    fn holds_off_around_the_next_run() {
+        let timer = FstrimTimer::parse(SHOW);
+        let next = 1_760_918_400;
+        assert!(!timer.holds_off(next - 3600, 1800));
+        assert!(timer.holds_off(next - 600, 1800));
+        assert!(timer.holds_off(next + 600, 1800), "overdue within AccuracySec");
+        assert!(!timer.holds_off(next + OVERDUE_MAX, 1800), "stale");
+        assert!(!timer.holds_off(next - 600, 0));
+        assert!(FstrimTimer { running: true, ..Default::default() }.holds_off(0, 1800));
+    }
+}
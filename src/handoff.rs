@@ -0,0 +1,221 @@
+// Upgrade handoff between daemon instances for systemd-swap
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// Protocol:
+//   1. The running daemon records its version, pid and protocol number in
+//      WORK_DIR/state at start (see state.rs).
+//   2. `systemd-swap handoff` (run after a package upgrade) checks that record,
+//      writes a handoff marker listing the active swap devices and restarts the
+//      service.
+//   3. `stop` finds a fresh marker with a matching protocol and leaves every
+//      swap device active instead of tearing down.
+//   4. The new `start` adopts the devices as on any restart, verifies that
+//      every device in the marker is still active and removes the marker.
+//
+// The marker lives outside WORK_DIR because start wipes WORK_DIR.
+
+use std::fs;
+
+use thiserror::Error;
+
+use crate::config::RUN_SYSD;
+use crate::helpers::{
+    find_swap_units, get_tag_from_swap_unit, get_what_from_swap_unit, now_secs, read_file, tag_subsystem,
+};
+use crate::state::DaemonState;
+use crate::{info, warn};
+
+/// Bumped whenever WORK_DIR layout or adoption rules change incompatibly
+pub const HANDOFF_PROTOCOL: u32 = 1;
+
+/// Markers older than this are ignored (the restart never happened)
+const HANDOFF_TIMEOUT_SECS: u64 = 120;
+
+const SERVICE_UNIT: &str = "systemd-swap.service";
+
+#[derive(Error, Debug)]
+pub enum HandoffError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Systemd error: {0}")]
+    Systemd(#[from] crate::systemd::SystemdError),
+    #[error("systemd-swap is not running")]
+    DaemonNotRunning,
+    #[error("running daemon uses handoff protocol {running}, this binary uses {ours}")]
+    Incompatible { running: u32, ours: u32 },
+}
+
+pub type Result<T> = std::result::Result<T, HandoffError>;
+
+fn marker_file() -> String {
+    format!("{}/swap-handoff", RUN_SYSD)
+}
+
+/// Parse "key=value" lines into (key, value) pairs
+fn parse_kv(content: &str) -> impl Iterator<Item = (&str, &str)> {
+    content
+        .lines()
+        .filter_map(|l| l.split_once('='))
+        .map(|(k, v)| (k.trim(), v.trim()))
+}
+
+/// Pending handoff from one daemon instance to the next
+#[derive(Debug, Clone, Default)]
+pub struct Handoff {
+    pub from_version: String,
+    pub to_version: String,
+    pub protocol: u32,
+    pub created: u64,
+    /// Swap devices that must stay active across the restart
+    pub devices: Vec<String>,
+}
+
+impl Handoff {
+    fn save(&self) -> Result<()> {
+        let content = format!(
+            "from_version={}\nto_version={}\nprotocol={}\ncreated={}\ndevices={}\n",
+            self.from_version,
+            self.to_version,
+            self.protocol,
+            self.created,
+            self.devices.join(",")
+        );
+        fs::write(marker_file(), content)?;
+        Ok(())
+    }
+
+    fn load() -> Option<Self> {
+        let content = read_file(marker_file()).ok()?;
+        let mut handoff = Self::default();
+        for (key, value) in parse_kv(&content) {
+            match key {
+                "from_version" => handoff.from_version = value.to_string(),
+                "to_version" => handoff.to_version = value.to_string(),
+                "protocol" => handoff.protocol = value.parse().unwrap_or(0),
+                "created" => handoff.created = value.parse().unwrap_or(0),
+                "devices" => {
+                    handoff.devices = value
+                        .split(',')
+                        .filter(|d| !d.is_empty())
+                        .map(str::to_string)
+                        .collect()
+                }
+                _ => {}
+            }
+        }
+        Some(handoff)
+    }
+
+    fn is_fresh(&self) -> bool {
+        now_secs().saturating_sub(self.created) <= HANDOFF_TIMEOUT_SECS
+    }
+}
+
+/// Swap devices currently backed by our generated units
+fn managed_devices() -> Vec<String> {
+    find_swap_units()
+        .into_iter()
+        .filter(|unit| {
+            get_tag_from_swap_unit(unit)
+                .is_some_and(|tag| matches!(tag_subsystem(&tag), "swapfile" | "swapfc" | "zram" | "burst"))
+        })
+        .filter_map(get_what_from_swap_unit)
+        .collect()
+}
+
+/// Ask the running daemon to hand over to the installed binary and restart it
+pub fn request() -> Result<Handoff> {
+    let daemon = DaemonState::load().ok_or(HandoffError::DaemonNotRunning)?;
+    if daemon.protocol != HANDOFF_PROTOCOL {
+        return Err(HandoffError::Incompatible {
+            running: daemon.protocol,
+            ours: HANDOFF_PROTOCOL,
+        });
+    }
+
+    let handoff = Handoff {
+        from_version: daemon.version,
+        to_version: env!("CARGO_PKG_VERSION").to_string(),
+        protocol: HANDOFF_PROTOCOL,
+        created: now_secs(),
+        devices: managed_devices(),
+    };
+    handoff.save()?;
+    info!(
+        "Handoff: {} → {}, keeping {} swap device(s) active",
+        handoff.from_version,
+        handoff.to_version,
+        handoff.devices.len()
+    );
+
+    if let Err(e) = crate::systemd::systemctl(crate::systemd::SystemctlAction::Restart, SERVICE_UNIT) {
+        let _ = fs::remove_file(marker_file());
+        return Err(e.into());
+    }
+    Ok(handoff)
+}
+
+/// Whether a valid handoff is pending, without consuming or discarding the marker
+pub fn is_pending() -> bool {
+    Handoff::load().is_some_and(|h| h.is_fresh() && h.protocol == HANDOFF_PROTOCOL)
+}
+
+/// Called by `stop`: true if a valid handoff is pending and teardown must be skipped
+pub fn pending_for_stop() -> bool {
+    let Some(handoff) = Handoff::load() else {
+        return false;
+    };
+    if !handoff.is_fresh() {
+        warn!("Handoff: ignoring stale handoff marker");
+        let _ = fs::remove_file(marker_file());
+        return false;
+    }
+    if handoff.protocol != HANDOFF_PROTOCOL {
+        warn!(
+            "Handoff: marker uses protocol {}, expected {} - doing a full stop",
+            handoff.protocol, HANDOFF_PROTOCOL
+        );
+        let _ = fs::remove_file(marker_file());
+        return false;
+    }
+    info!("Handoff: upgrade in progress, leaving swap devices active");
+    true
+}
+
+/// Called by `start`: finish a pending handoff and check that no device was lost
+pub fn complete() {
+    let Some(handoff) = Handoff::load() else {
+        return;
+    };
+    let _ = fs::remove_file(marker_file());
+    if !handoff.is_fresh() {
+        return;
+    }
+
+    let swaps = fs::read_to_string("/proc/swaps").unwrap_or_default();
+    let active: Vec<&str> = swaps
+        .lines()
+        .skip(1)
+        .filter_map(|l| l.split_whitespace().next())
+        .collect();
+    let missing: Vec<&String> = handoff
+        .devices
+        .iter()
+        .filter(|d| !active.contains(&d.as_str()))
+        .collect();
+
+    if missing.is_empty() {
+        info!(
+            "Handoff: {} → {} complete, {} swap device(s) kept active",
+            handoff.from_version,
+            env!("CARGO_PKG_VERSION"),
+            handoff.devices.len()
+        );
+    } else {
+        warn!(
+            "Handoff: {} device(s) were deactivated during the upgrade: {}",
+            missing.len(),
+            missing.iter().map(|d| d.as_str()).collect::<Vec<_>>().join(", ")
+        );
+    }
+}
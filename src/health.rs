@@ -0,0 +1,193 @@
+// Health check for systemd-swap
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// `systemd-swap health` prints one line and exits with a fixed code, so
+// monitoring plugins (Nagios, Zabbix, ...) need no JSON parsing:
+//   0  healthy
+//   1  degraded: swap works, but the daemon cannot grow it, its monitor
+//      loop has stalled or a zram device reported I/O failures
+//   2  failed: the daemon is not running or no swap is active
+// With swap_mode=disabled a stopped daemon is the expected state.
+// The checks only read what the daemon already exports to WORK_DIR.
+
+use crate::config::Config;
+use crate::modes::SwapMode;
+use crate::state::DaemonState;
+use crate::swapfile::SwapFilePacing;
+
+/// A monitor snapshot this many intervals old means the loop is stuck
+const STALE_INTERVALS: u64 = 3;
+/// Grace period on top of the intervals (slow file creation, PSI waits)
+const STALE_GRACE_SECS: u64 = 60;
+
+/// Overall health, ordered from best to worst
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Health {
+    Healthy,
+    Degraded,
+    Failed,
+}
+
+impl Health {
+    /// Process exit code for this state
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Health::Healthy => 0,
+            Health::Degraded => 1,
+            Health::Failed => 2,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Health::Healthy => "OK",
+            Health::Degraded => "DEGRADED",
+            Health::Failed => "FAILED",
+        }
+    }
+}
+
+/// What the health check looks at
+#[derive(Debug, Clone, Default)]
+pub struct HealthInputs {
+    pub daemon: Option<DaemonState>,
+    /// Active swap areas in /proc/swaps
+    pub swap_areas: usize,
+    pub pacing: Option<SwapFilePacing>,
+    /// swap_mode=disabled: the daemon exits right after start
+    pub disabled: bool,
+    /// Failed requests of the zram devices (io_stat)
+    pub zram_io_failures: u64,
+}
+
+impl HealthInputs {
+    pub fn read() -> Self {
+        let swap_areas = std::fs::read_to_string("/proc/swaps")
+            .map(|s| s.lines().skip(1).filter(|l| !l.trim().is_empty()).count())
+            .unwrap_or(0);
+        let (daemon, pacing, zram) = crate::state::read_consistent(|| {
+            (DaemonState::load(), SwapFilePacing::load(), crate::zram::get_zram_stats())
+        });
+        Self {
+            daemon,
+            swap_areas,
+            pacing,
+            disabled: Config::load()
+                .map(|c| SwapMode::from_config(&c) == SwapMode::Disabled)
+                .unwrap_or(false),
+            zram_io_failures: zram.map_or(0, |z| z.io.failures()),
+        }
+    }
+}
+
+/// Health state with the reasons behind it
+#[derive(Debug, Clone, PartialEq)]
+pub struct HealthReport {
+    pub health: Health,
+    pub reasons: Vec<String>,
+}
+
+impl HealthReport {
+    pub fn evaluate(inputs: &HealthInputs) -> Self {
+        let mut health = Health::Healthy;
+        let mut reasons = Vec::new();
+        let mut raise = |level: Health, reason: String| {
+            health = health.max(level);
+            reasons.push(reason);
+        };
+
+        let Some(daemon) = &inputs.daemon else {
+            if inputs.disabled {
+                reasons.push("swap management disabled".to_string());
+                return Self { health, reasons };
+            }
+            raise(Health::Failed, "daemon not running".to_string());
+            return Self { health, reasons };
+        };
+        if inputs.swap_areas == 0 {
+            raise(Health::Failed, "no swap area active".to_string());
+        }
+        if let Some(pacing) = &inputs.pacing {
+            if pacing.disk_full {
+                raise(Health::Degraded, "swap file expansion paused: filesystem full".to_string());
+            }
+            let stale_after = pacing.interval.max(1) * STALE_INTERVALS + STALE_GRACE_SECS;
+            if pacing.age > stale_after {
+                raise(
+                    Health::Degraded,
+                    format!("swap file monitor last ran {}s ago", pacing.age),
+                );
+            }
+        }
+        if inputs.zram_io_failures > 0 {
+            raise(
+                Health::Degraded,
+                format!("zram reported {} I/O failure(s)", inputs.zram_io_failures),
+            );
+        }
+        if reasons.is_empty() {
+            reasons.push(format!(
+                "{} mode, {} swap area(s) active",
+                daemon.mode, inputs.swap_areas
+            ));
+        }
+        Self { health, reasons }
+    }
+
+    /// Single status line, e.g. "DEGRADED: swap file expansion paused: filesystem full"
+    pub fn line(&self) -> String {
+        format!("{}: {}", self.health.label(), self.reasons.join("; "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn running() -> HealthInputs {
+        HealthInputs {
+            daemon: Some(DaemonState {
+                mode: "zram+swapfc".to_string(),
+                ..DaemonState::default()
+            }),
+            swap_areas: 2,
+            pacing: Some(SwapFilePacing {
+                interval: 1,
+                age: 2,
+                ..SwapFilePacing::default()
+            }),
+            disabled: false,
+            zram_io_failures: 0,
+        }
+    }
+
+    #[test]
+    fn healthy_daemon_exits_zero() {
+        let report = HealthReport::evaluate(&running());
+        assert_eq!(report.health.exit_code(), 0);
+        assert_eq!(report.line(), "OK: zram+swapfc mode, 2 swap area(s) active");
+    }
+
+    #[test]
+    fn degraded_and_failed_states() {
+        let mut inputs = running();
+        inputs.pacing.as_mut().unwrap().disk_full = true;
+        inputs.pacing.as_mut().unwrap().age = 600;
+        let report = HealthReport::evaluate(&inputs);
+        assert_eq!(report.health, Health::Degraded);
+        assert_eq!(report.reasons.len(), 2);
+        inputs.zram_io_failures = 3;
+        assert_eq!(HealthReport::evaluate(&inputs).reasons[2], "zram reported 3 I/O failure(s)");
+
+        inputs.swap_areas = 0;
+        assert_eq!(HealthReport::evaluate(&inputs).health.exit_code(), 2);
+
+        let report = HealthReport::evaluate(&HealthInputs::default());
+        assert_eq!(report.line(), "FAILED: daemon not running");
+        let disabled = HealthInputs {
+            disabled: true,
+            ..HealthInputs::default()
+        };
+        assert_eq!(HealthReport::evaluate(&disabled).health, Health::Healthy);
+    }
+}
@@ -11,9 +11,12 @@ use std::os::unix::fs::symlink;
 use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
 use std::sync::{Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use thiserror::Error;
 
+use crate::exec::TimedCommand;
+
 #[derive(Error, Debug)]
 pub enum HelperError {
     #[error("IO error: {0}")]
@@ -35,6 +38,14 @@ pub fn am_i_root() -> Result<()> {
     }
 }
 
+/// Current Unix time in seconds; 0 if the clock is before the epoch
+pub fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
 /// Read entire file to string
 pub fn read_file<P: AsRef<Path>>(path: P) -> Result<String> {
     Ok(fs::read_to_string(path)?)
@@ -45,6 +56,7 @@ pub fn read_file<P: AsRef<Path>>(path: P) -> Result<String> {
 /// For real filesystem paths, calls sync_all to ensure persistence.
 pub fn write_file<P: AsRef<Path>>(path: P, content: &str) -> Result<()> {
     let path = path.as_ref();
+    crate::readonly::check(&format!("write {}", path.display()))?;
     let mut file = fs::File::create(path)?;
     file.write_all(content.as_bytes())?;
     // Skip fsync for virtual filesystems (sysfs, procfs) where it's meaningless
@@ -55,18 +67,33 @@ pub fn write_file<P: AsRef<Path>>(path: P, content: &str) -> Result<()> {
     Ok(())
 }
 
+/// Write a value to a kernel interface (sysfs, procfs, cgroupfs), without
+/// the fsync of `write_file`
+pub fn write_sysfs<P: AsRef<Path>, C: AsRef<[u8]>>(path: P, value: C) -> io::Result<()> {
+    let path = path.as_ref();
+    crate::readonly::check(&format!("write {}", path.display()))?;
+    fs::write(path, value)
+}
+
+/// Remove a file
+pub fn remove_file<P: AsRef<Path>>(path: P) -> io::Result<()> {
+    let path = path.as_ref();
+    crate::readonly::check(&format!("remove {}", path.display()))?;
+    fs::remove_file(path)
+}
+
 /// Force remove file, ignoring errors
 pub fn force_remove<P: AsRef<Path>>(path: P, verbose: bool) {
     let path = path.as_ref();
-    match fs::remove_file(path) {
+    match remove_file(path) {
         Ok(()) => {
             if verbose {
-                println!("INFO: Removed {}", path.display());
+                crate::info!("Removed {}", path.display());
             }
         }
         Err(e) => {
             if verbose {
-                eprintln!("WARN: Cannot remove {}: {}", path.display(), e);
+                crate::warn!("Cannot remove {}: {}", path.display(), e);
             }
         }
     }
@@ -74,6 +101,7 @@ pub fn force_remove<P: AsRef<Path>>(path: P, verbose: bool) {
 
 /// Create directories recursively
 pub fn makedirs<P: AsRef<Path>>(path: P) -> Result<()> {
+    crate::readonly::check(&format!("create {}", path.as_ref().display()))?;
     fs::create_dir_all(path)?;
     Ok(())
 }
@@ -83,6 +111,7 @@ pub fn relative_symlink<P: AsRef<Path>, Q: AsRef<Path>>(target: P, link_name: Q)
     let link_name = link_name.as_ref();
     let target = target.as_ref();
 
+    crate::readonly::check(&format!("link {}", link_name.display()))?;
     // Remove existing link
     let _ = fs::remove_file(link_name);
 
@@ -100,7 +129,7 @@ pub fn run_cmd_output(cmd: &[&str]) -> Result<String> {
         .args(&cmd[1..])
         .stdout(Stdio::piped())
         .stderr(Stdio::null())
-        .output()?;
+        .output_timed()?;
 
     if output.status.success() {
         Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
@@ -142,6 +171,44 @@ pub fn get_what_from_swap_unit<P: AsRef<Path>>(path: P) -> Option<String> {
     None
 }
 
+/// Key that marks a unit generated by systemd-swap; systemd ignores X- keys
+pub const UNIT_TAG_KEY: &str = "X-SystemdSwap-Tag";
+
+/// Owner tag of a generated swap unit (e.g. "zram", "swapfile_3"), None for
+/// units systemd-swap did not write. Units from older versions carry it as a
+/// "# Tag=" comment.
+pub fn parse_unit_tag(content: &str) -> Option<&str> {
+    content.lines().find_map(|line| {
+        let line = line.trim();
+        let value = line
+            .strip_prefix(UNIT_TAG_KEY)
+            .and_then(|rest| rest.trim_start().strip_prefix('='))
+            .or_else(|| line.strip_prefix("# Tag="))?;
+        Some(value.trim()).filter(|v| !v.is_empty())
+    })
+}
+
+/// Get the owner tag from a swap unit file
+pub fn get_tag_from_swap_unit<P: AsRef<Path>>(path: P) -> Option<String> {
+    let content = read_file(path).ok()?;
+    parse_unit_tag(&content).map(str::to_string)
+}
+
+/// Selected entry of a sysfs choice listing such as "lzo [lz4] zstd"
+pub fn selected_option(listing: &str) -> Option<String> {
+    let start = listing.find('[')? + 1;
+    let end = start + listing[start..].find(']')?;
+    Some(listing[start..end].to_string())
+}
+
+/// Subsystem a unit tag belongs to: "swapfile_3" → "swapfile"
+pub fn tag_subsystem(tag: &str) -> &str {
+    match tag.rsplit_once('_') {
+        Some((name, n)) if n.parse::<u32>().is_ok() => name,
+        _ => tag,
+    }
+}
+
 /// Cache for filesystem type detection (avoids repeated findmnt calls)
 static FS_TYPE_CACHE: OnceLock<Mutex<HashMap<PathBuf, String>>> = OnceLock::new();
 
@@ -178,7 +245,7 @@ pub fn get_fstype<P: AsRef<Path>>(path: P) -> Option<String> {
             &check_path.to_string_lossy(),
         ])
         .stdout(Stdio::piped())
-        .output()
+        .output_timed()
         .ok()?;
 
     let fstype = String::from_utf8_lossy(&output.stdout)
@@ -207,74 +274,276 @@ pub const GB: u64 = 1024 * MB;
 
 /// Parse size string to bytes.
 ///
-/// Accepts: `"512M"`, `"1G"`, `"256K"`, `"2T"`, `"50%"` (percentage of RAM),
-/// or raw bytes `"1073741824"`.
+/// Accepts: `"512M"`, `"1.5G"`, `"256K"`, `"2T"` (an optional `B`/`iB` after the
+/// unit is ignored), `"50%"` (percentage of RAM), raw bytes `"1073741824"`, and
+/// simple arithmetic: terms joined by `+`/`-`, each optionally multiplied or
+/// divided by plain numbers (`"50%+1G"`, `"16G/4"`, `"512M*3"`).
+/// Evaluated left to right with `*`/`/` binding tighter; no parentheses.
 pub fn parse_size(s: &str) -> std::result::Result<u64, String> {
+    parse_size_with_ram(s, || {
+        crate::meminfo::get_ram_size().map_err(|e| format!("failed to get RAM size: {}", e))
+    })
+}
+
+/// `parse_size` with an explicit RAM source (only called for `%` terms)
+pub fn parse_size_with_ram<F>(s: &str, ram: F) -> std::result::Result<u64, String>
+where
+    F: Fn() -> std::result::Result<u64, String>,
+{
     let s = s.trim();
     if s.is_empty() {
-        return Err("Empty size string".to_string());
+        return Err("empty size".to_string());
+    }
+    let invalid = |why: &str| format!("invalid size '{}': {}", s, why);
+
+    let mut total: f64 = 0.0;
+    let mut sign = 1.0;
+    let mut term_start = 0;
+    for (i, c) in s.char_indices().chain(std::iter::once((s.len(), '+'))) {
+        if c != '+' && c != '-' {
+            continue;
+        }
+        let term = s[term_start..i].trim();
+        if term.is_empty() {
+            return Err(invalid("missing term"));
+        }
+        total += sign * parse_size_term(term, &ram).map_err(|why| invalid(&why))?;
+        sign = if c == '-' { -1.0 } else { 1.0 };
+        term_start = i + 1;
     }
 
-    // Handle percentage (e.g., "50%", "100%")
-    if let Some(pct) = s.strip_suffix('%') {
-        let percent: u64 = pct
-            .parse()
-            .map_err(|_| format!("Invalid percentage: {}", s))?;
-        let ram = crate::meminfo::get_ram_size()
-            .map_err(|e| format!("Failed to get RAM size: {}", e))?;
-        return Ok(ram * percent / 100);
+    if total < 0.0 {
+        return Err(invalid("result is negative"));
+    }
+    if total >= u64::MAX as f64 {
+        return Err(invalid("too large"));
     }
+    Ok(total.round() as u64)
+}
 
-    // Handle size with suffix (e.g., "1G", "512M")
-    if s.len() > 1 {
-        let (num_part, suffix) = s.split_at(s.len() - 1);
-        let multiplier = match suffix.to_ascii_uppercase().as_str() {
-            "K" => Some(KB),
-            "M" => Some(MB),
-            "G" => Some(GB),
-            "T" => Some(GB * 1024),
-            _ => None,
-        };
-        if let Some(m) = multiplier {
-            return num_part
-                .parse::<u64>()
-                .map(|n| n * m)
-                .map_err(|_| format!("Invalid size: {}", s));
+/// One term: a size followed by any number of `*N` / `/N`
+fn parse_size_term<F>(term: &str, ram: &F) -> std::result::Result<f64, String>
+where
+    F: Fn() -> std::result::Result<u64, String>,
+{
+    let mut parts = term.split(['*', '/']);
+    let mut value = parse_size_atom(parts.next().unwrap_or("").trim(), ram)?;
+    let mut ops = term.chars().filter(|c| *c == '*' || *c == '/');
+    for factor in parts {
+        let factor = factor.trim();
+        let n: f64 = factor
+            .parse()
+            .ok()
+            .filter(|n: &f64| n.is_finite() && *n >= 0.0)
+            .ok_or_else(|| format!("'{}' is not a plain number", factor))?;
+        match ops.next() {
+            Some('*') => value *= n,
+            _ if n == 0.0 => return Err("division by zero".to_string()),
+            _ => value /= n,
         }
     }
+    Ok(value)
+}
+
+/// A single size: number with optional unit, or percentage of RAM
+fn parse_size_atom<F>(atom: &str, ram: &F) -> std::result::Result<f64, String>
+where
+    F: Fn() -> std::result::Result<u64, String>,
+{
+    let number_end = atom
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(atom.len());
+    let (number, unit) = atom.split_at(number_end);
+    let number: f64 = number
+        .parse()
+        .ok()
+        .filter(|n: &f64| n.is_finite())
+        .ok_or_else(|| format!("'{}' has no number", atom))?;
+
+    let multiplier = match unit.trim().to_ascii_uppercase().as_str() {
+        "%" => return Ok(ram()? as f64 * number / 100.0),
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => KB,
+        "M" | "MB" | "MIB" => MB,
+        "G" | "GB" | "GIB" => GB,
+        "T" | "TB" | "TIB" => GB * 1024,
+        other => return Err(format!("unknown unit '{}'", other)),
+    };
+    Ok(number * multiplier as f64)
+}
 
-    // No suffix — treat as raw bytes
-    s.parse::<u64>()
-        .map_err(|_| format!("Invalid size: {}", s))
+/// Format bytes as human-readable size
+pub fn format_size(bytes: u64) -> String {
+    if bytes >= GB {
+        format!("{:.1} GiB", bytes as f64 / GB as f64)
+    } else if bytes >= MB {
+        format!("{:.1} MiB", bytes as f64 / MB as f64)
+    } else if bytes >= KB {
+        format!("{:.0} KiB", bytes as f64 / KB as f64)
+    } else {
+        format!("{} B", bytes)
+    }
+}
+
+/// Format seconds as a short duration: "45s", "12m 5s", "3h 12m", "2d 4h"
+pub fn format_duration(secs: u64) -> String {
+    let (d, h, m, s) = (secs / 86400, secs / 3600 % 24, secs / 60 % 60, secs % 60);
+    if d > 0 {
+        format!("{}d {}h", d, h)
+    } else if h > 0 {
+        format!("{}h {}m", h, m)
+    } else if m > 0 {
+        format!("{}m {}s", m, s)
+    } else {
+        format!("{}s", s)
+    }
+}
+
+/// Quote a string for JSON output
+pub fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
 }
 
 // Logging macros
 #[macro_export]
 macro_rules! info {
     ($($arg:tt)*) => {
-        println!("INFO: {}", format!($($arg)*))
+        $crate::context::log($crate::context::LogLevel::Info, &format!($($arg)*))
     };
 }
 
 #[macro_export]
 macro_rules! warn {
     ($($arg:tt)*) => {
-        eprintln!("WARN: {}", format!($($arg)*))
+        $crate::context::log($crate::context::LogLevel::Warn, &format!($($arg)*))
     };
 }
 
 #[macro_export]
 macro_rules! error {
     ($($arg:tt)*) => {
-        eprintln!("ERRO: {}", format!($($arg)*))
+        $crate::context::log($crate::context::LogLevel::Error, &format!($($arg)*))
     };
 }
 
 #[macro_export]
 macro_rules! debug {
     ($($arg:tt)*) => {
-        if std::env::var("DEBUG").is_ok() {
-            eprintln!("DEBUG: {}", format!($($arg)*))
+        if $crate::context::log_enabled($crate::context::LogLevel::Debug) {
+            $crate::context::log($crate::context::LogLevel::Debug, &format!($($arg)*))
         }
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RAM: u64 = 8 * GB;
+
+    fn size(s: &str) -> std::result::Result<u64, String> {
+        parse_size_with_ram(s, || Ok(RAM))
+    }
+
+    #[test]
+    fn size_units() {
+        assert_eq!(size("4096"), Ok(4096));
+        assert_eq!(size("256K"), Ok(256 * KB));
+        assert_eq!(size("512M"), Ok(512 * MB));
+        assert_eq!(size("512m"), Ok(512 * MB));
+        assert_eq!(size("1G"), Ok(GB));
+        assert_eq!(size("2T"), Ok(2048 * GB));
+        assert_eq!(size("1GiB"), Ok(GB));
+        assert_eq!(size("512MB"), Ok(512 * MB));
+        assert_eq!(size("100B"), Ok(100));
+        assert_eq!(size(" 1G "), Ok(GB));
+    }
+
+    #[test]
+    fn unit_tags() {
+        let unit = "[Unit]\nDescription=Swap file\n# Generated by systemd-swap\nX-SystemdSwap-Tag=swapfile_10\n\n[Swap]\nWhat=/swapfile/10\n";
+        assert_eq!(parse_unit_tag(unit), Some("swapfile_10"));
+        assert_eq!(parse_unit_tag("# Generated by systemd-swap\n# Tag=zram\n"), Some("zram"));
+        // Mentioning a subsystem is not ownership
+        assert_eq!(parse_unit_tag("[Swap]\nWhat=/dev/zram0\n"), None);
+        assert_eq!(tag_subsystem("swapfile_10"), "swapfile");
+        assert_eq!(tag_subsystem("zram"), "zram");
+    }
+
+    #[test]
+    fn selected_option_from_listing() {
+        assert_eq!(selected_option("lzo lzo-rle [lz4] zstd\n").as_deref(), Some("lz4"));
+        assert_eq!(selected_option("[none] mq-deadline").as_deref(), Some("none"));
+        assert_eq!(selected_option("lzo zstd"), None);
+    }
+
+    #[test]
+    fn size_fractions() {
+        assert_eq!(size("1.5G"), Ok(GB + 512 * MB));
+        assert_eq!(size("0.5M"), Ok(512 * KB));
+    }
+
+    #[test]
+    fn size_percent_of_ram() {
+        assert_eq!(size("50%"), Ok(4 * GB));
+        assert_eq!(size("150%"), Ok(12 * GB));
+        assert_eq!(size("12.5%"), Ok(GB));
+        assert_eq!(size("0%"), Ok(0));
+    }
+
+    #[test]
+    fn size_percent_reads_ram_lazily() {
+        let no_ram = || Err("no meminfo".to_string());
+        assert_eq!(parse_size_with_ram("1G", no_ram), Ok(GB));
+        assert!(parse_size_with_ram("50%", no_ram).is_err());
+    }
+
+    #[test]
+    fn size_arithmetic() {
+        assert_eq!(size("1G+512M"), Ok(GB + 512 * MB));
+        assert_eq!(size("50% - 1G"), Ok(3 * GB));
+        assert_eq!(size("16G/4"), Ok(4 * GB));
+        assert_eq!(size("512M*3"), Ok(1536 * MB));
+        assert_eq!(size("1G+512M*2"), Ok(2 * GB));
+        assert_eq!(size("100%/2+1G"), Ok(5 * GB));
+    }
+
+    #[test]
+    fn size_errors() {
+        assert_eq!(size(""), Err("empty size".to_string()));
+        assert_eq!(size("12X"), Err("invalid size '12X': unknown unit 'X'".to_string()));
+        assert_eq!(size("G"), Err("invalid size 'G': 'G' has no number".to_string()));
+        assert_eq!(size("1G+"), Err("invalid size '1G+': missing term".to_string()));
+        assert_eq!(size("-1G"), Err("invalid size '-1G': missing term".to_string()));
+        assert_eq!(size("1G-2G"), Err("invalid size '1G-2G': result is negative".to_string()));
+        assert_eq!(size("1G/0"), Err("invalid size '1G/0': division by zero".to_string()));
+        assert_eq!(size("1G*2G"), Err("invalid size '1G*2G': '2G' is not a plain number".to_string()));
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(format_duration(45), "45s");
+        assert_eq!(format_duration(12 * 60 + 5), "12m 5s");
+        assert_eq!(format_duration(3 * 3600 + 12 * 60 + 7), "3h 12m");
+        assert_eq!(format_duration(2 * 86400 + 4 * 3600), "2d 4h");
+    }
+
+    #[test]
+    fn json_strings_are_escaped() {
+        assert_eq!(json_string("zram+swapfc"), "\"zram+swapfc\"");
+        assert_eq!(json_string("a\"b\\c\n"), "\"a\\\"b\\\\c\\n\"");
+        assert_eq!(json_string("\u{1}"), "\"\\u0001\"");
+    }
+}
@@ -0,0 +1,242 @@
+// Hibernation image size for systemd-swap
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// The kernel writes the hibernation image to the swap area named by resume=
+// (/sys/power/resume). This module finds that area among the active swaps,
+// checks it can take a worst-case image, and with hibernate_image_size=auto
+// sizes /sys/power/image_size to it: the kernel then frees enough memory
+// before hibernating for the compressed image to fit. zram never holds the
+// image, so a zram-heavy setup still needs a disk swap area large enough.
+
+use std::fs;
+use std::path::Path;
+
+use nix::sys::stat::{major, minor, stat, SFlag};
+
+use crate::absorb::{read_swaps, SwapArea};
+use crate::config::Config;
+use crate::context::work_dir;
+use crate::defaults;
+use crate::helpers::{format_size, parse_size, write_file, write_sysfs};
+use crate::{info, warn};
+
+pub const IMAGE_SIZE: &str = "/sys/power/image_size";
+const RESUME: &str = "/sys/power/resume";
+
+/// Typical ratio of the kernel's lzo-compressed image
+const IMAGE_COMPRESSION_RATIO: u64 = 2;
+
+fn backup_file() -> String {
+    format!("{}/image_size_backup", work_dir())
+}
+
+/// Swap area the hibernation image is written to
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResumeArea {
+    pub path: String,
+    pub size: u64,
+    pub used: u64,
+}
+
+impl ResumeArea {
+    pub fn free(&self) -> u64 {
+        self.size.saturating_sub(self.used)
+    }
+}
+
+/// Whether the resume area can take an image of the current image_size
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Verdict {
+    /// Fits even if nothing compresses
+    Fits,
+    /// Fits only at the usual compression ratio
+    FitsIfCompressible,
+    TooSmall,
+    /// resume= names a device that is not an active swap area
+    NoArea,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HibernationStatus {
+    /// resume device as major:minor
+    pub resume_device: String,
+    pub area: Option<ResumeArea>,
+    /// Current /sys/power/image_size
+    pub image_size: u64,
+}
+
+/// Parse "maj:min" from /sys/power/resume; "0:0" means hibernation is not set up
+fn parse_device(value: &str) -> Option<(u64, u64)> {
+    let (maj, min) = value.trim().split_once(':')?;
+    let dev = (maj.parse().ok()?, min.parse().ok()?);
+    (dev != (0, 0)).then_some(dev)
+}
+
+/// Active swap area on the resume device: the device itself, or the largest
+/// swap file on that filesystem (resume_offset points into one of them)
+fn find_area(areas: Vec<SwapArea>, device: (u64, u64)) -> Option<ResumeArea> {
+    areas
+        .into_iter()
+        .filter(|area| {
+            stat(area.path.as_str()).is_ok_and(|st| {
+                let is_block = SFlag::from_bits_truncate(st.st_mode).contains(SFlag::S_IFBLK);
+                let dev = if is_block { st.st_rdev } else { st.st_dev };
+                (major(dev), minor(dev)) == device
+            })
+        })
+        .max_by_key(|area| area.size)
+        .map(|area| ResumeArea {
+            path: area.path,
+            size: area.size,
+            used: area.used,
+        })
+}
+
+impl HibernationStatus {
+    /// None when no resume device is configured
+    pub fn detect() -> Option<Self> {
+        let device = parse_device(&fs::read_to_string(RESUME).ok()?)?;
+        Some(Self {
+            resume_device: format!("{}:{}", device.0, device.1),
+            area: find_area(read_swaps(), device),
+            image_size: fs::read_to_string(IMAGE_SIZE)
+                .ok()
+                .and_then(|v| v.trim().parse().ok())
+                .unwrap_or(0),
+        })
+    }
+
+    pub fn verdict(&self) -> Verdict {
+        let Some(area) = &self.area else {
+            return Verdict::NoArea;
+        };
+        let free = area.free();
+        if free >= self.image_size {
+            Verdict::Fits
+        } else if free * IMAGE_COMPRESSION_RATIO >= self.image_size {
+            Verdict::FitsIfCompressible
+        } else {
+            Verdict::TooSmall
+        }
+    }
+
+    /// Explanation for status when hibernation may fail
+    pub fn warning(&self) -> Option<String> {
+        match self.verdict() {
+            Verdict::Fits => None,
+            Verdict::FitsIfCompressible => Some(format!(
+                "image up to {} fits in {} free only if it compresses {}x",
+                format_size(self.image_size),
+                format_size(self.area.as_ref().map(ResumeArea::free).unwrap_or(0)),
+                IMAGE_COMPRESSION_RATIO
+            )),
+            Verdict::TooSmall => Some(format!(
+                "image up to {} does not fit in {} free; hibernation will likely fail",
+                format_size(self.image_size),
+                format_size(self.area.as_ref().map(ResumeArea::free).unwrap_or(0))
+            )),
+            Verdict::NoArea => Some(format!(
+                "resume device {} is not an active swap area; hibernation will fail",
+                self.resume_device
+            )),
+        }
+    }
+}
+
+/// image_size for a resume area: what it holds at the usual compression,
+/// never above the kernel default of 2/5 of RAM
+pub fn recommended_image_size(ram: u64, area_size: u64) -> u64 {
+    (ram / 5 * 2).min(area_size * IMAGE_COMPRESSION_RATIO)
+}
+
+/// Set /sys/power/image_size from `hibernate_image_size` (empty: leave it,
+/// auto: fit the resume area, or a size) and check the resume area.
+pub fn apply(config: &Config) {
+    let Some(status) = HibernationStatus::detect() else {
+        return;
+    };
+    let setting = config
+        .get("hibernate_image_size")
+        .unwrap_or(defaults::HIBERNATE_IMAGE_SIZE)
+        .trim();
+    let target = match setting {
+        "" => None,
+        "auto" => match (&status.area, crate::meminfo::get_ram_size()) {
+            (Some(area), Ok(ram)) => Some(recommended_image_size(ram, area.size)),
+            _ => None,
+        },
+        size => match parse_size(size) {
+            Ok(bytes) => Some(bytes),
+            Err(e) => {
+                warn!("Hibernate: invalid hibernate_image_size '{}': {}", size, e);
+                None
+            }
+        },
+    };
+
+    let mut status = status;
+    if let Some(target) = target.filter(|&t| t != status.image_size) {
+        if !Path::new(&backup_file()).exists() {
+            let _ = write_file(backup_file(), &status.image_size.to_string());
+        }
+        match write_sysfs(IMAGE_SIZE, target.to_string()) {
+            Ok(()) => {
+                info!(
+                    "Hibernate: image_size {} → {}",
+                    format_size(status.image_size),
+                    format_size(target)
+                );
+                status.image_size = target;
+            }
+            Err(e) => warn!("Hibernate: failed to set image_size: {}", e),
+        }
+    }
+    if let Some(warning) = status.warning() {
+        warn!("Hibernate: {}", warning);
+    }
+}
+
+/// image_size saved before the daemon changed it
+pub fn backup_value() -> Option<String> {
+    fs::read_to_string(backup_file()).ok().map(|v| v.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GIB: u64 = 1024 * 1024 * 1024;
+
+    #[test]
+    fn resume_device_parsing() {
+        assert_eq!(parse_device("259:2\n"), Some((259, 2)));
+        assert_eq!(parse_device("0:0\n"), None);
+        assert_eq!(parse_device("garbage"), None);
+    }
+
+    #[test]
+    fn verdict_by_free_space() {
+        let mut status = HibernationStatus {
+            resume_device: "8:2".to_string(),
+            area: Some(ResumeArea {
+                path: "/dev/sda2".to_string(),
+                size: 8 * GIB,
+                used: 2 * GIB,
+            }),
+            image_size: 6 * GIB,
+        };
+        assert_eq!(status.verdict(), Verdict::Fits);
+        status.image_size = 10 * GIB;
+        assert_eq!(status.verdict(), Verdict::FitsIfCompressible);
+        status.image_size = 13 * GIB;
+        assert_eq!(status.verdict(), Verdict::TooSmall);
+        status.area = None;
+        assert!(status.warning().unwrap().contains("not an active swap area"));
+    }
+
+    #[test]
+    fn image_size_capped_by_ram_and_area() {
+        assert_eq!(recommended_image_size(16 * GIB, 16 * GIB), 16 * GIB / 5 * 2);
+        assert_eq!(recommended_image_size(16 * GIB, 2 * GIB), 4 * GIB);
+    }
+}
@@ -0,0 +1,237 @@
+// Swap usage history for systemd-swap
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// The daemon samples total swap use and the swap of every top-level cgroup
+// slice (memory.swap.current) once a minute and keeps the daily maxima in
+// state_dir/swap-history, which survives reboots. `stats --history` reads
+// them back for capacity planning: how close the peaks came to the swap the
+// files can provide (swapfile_max_count), and which slice needed it.
+//
+// One line per day and cgroup, days counted in UTC since the epoch:
+//   <day> <cgroup> <peak bytes>
+// The cgroup TOTAL stands for all swap. HISTORY_DAYS days are kept.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::defaults;
+use crate::helpers::now_secs;
+use crate::{is_shutdown, warn};
+
+/// cgroup v2 hierarchy root
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+/// Seconds between samples
+const SAMPLE_INTERVAL: u64 = 60;
+
+/// Days of history kept
+pub const HISTORY_DAYS: u64 = 90;
+
+/// Cgroup name of the total swap use
+pub const TOTAL: &str = "-";
+
+const SECS_PER_DAY: u64 = 86400;
+
+/// History file below the persistent state directory
+pub fn history_file(config: &Config) -> PathBuf {
+    crate::config::state_dir(config).join("swap-history")
+}
+
+/// Today, in days since the epoch (UTC)
+pub fn today() -> u64 {
+    now_secs() / SECS_PER_DAY
+}
+
+/// Calendar date of a day since the epoch, as YYYY-MM-DD
+pub fn format_day(day: u64) -> String {
+    // Civil-from-days (Howard Hinnant), valid for any day after 1970
+    let z = day as i64 + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = yoe + era * 400 + i64::from(m <= 2);
+    format!("{:04}-{:02}-{:02}", y, m, d)
+}
+
+/// Daily swap peaks, keyed by (day, cgroup)
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct History {
+    pub peaks: BTreeMap<(u64, String), u64>,
+}
+
+impl History {
+    /// Read the history file; a missing or unreadable file is an empty history
+    pub fn load(path: &Path) -> Self {
+        let content = fs::read_to_string(path).unwrap_or_default();
+        let peaks = content
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.split_whitespace();
+                let day = fields.next()?.parse().ok()?;
+                let cgroup = fields.next()?.to_string();
+                let peak = fields.next()?.parse().ok()?;
+                Some(((day, cgroup), peak))
+            })
+            .collect();
+        Self { peaks }
+    }
+
+    /// Write the history atomically
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        let mut content = String::new();
+        for ((day, cgroup), peak) in &self.peaks {
+            content.push_str(&format!("{} {} {}\n", day, cgroup, peak));
+        }
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, content)?;
+        fs::rename(&tmp, path)
+    }
+
+    /// Raise `day`'s peaks to `samples` and drop days older than HISTORY_DAYS.
+    /// Returns true if anything changed.
+    pub fn record(&mut self, day: u64, samples: &[(String, u64)]) -> bool {
+        let mut changed = false;
+        for (cgroup, bytes) in samples {
+            let peak = self.peaks.entry((day, cgroup.clone())).or_insert(0);
+            if *bytes > *peak {
+                *peak = *bytes;
+                changed = true;
+            }
+        }
+        let oldest = day.saturating_sub(HISTORY_DAYS - 1);
+        let before = self.peaks.len();
+        self.peaks.retain(|(d, _), _| *d >= oldest);
+        changed || self.peaks.len() != before
+    }
+
+    /// Days with data from `since` on, oldest first
+    pub fn days(&self, since: u64) -> Vec<u64> {
+        let mut days: Vec<u64> = self.peaks.keys().map(|(d, _)| *d).filter(|d| *d >= since).collect();
+        days.dedup();
+        days
+    }
+
+    pub fn peak(&self, day: u64, cgroup: &str) -> Option<u64> {
+        self.peaks.get(&(day, cgroup.to_string())).copied()
+    }
+
+    /// Cgroups (without TOTAL) seen from `since` on, by their highest peak
+    pub fn cgroups(&self, since: u64) -> Vec<(String, u64, u64)> {
+        let mut best: BTreeMap<&str, (u64, u64)> = BTreeMap::new();
+        for ((day, cgroup), peak) in self.peaks.range((since, String::new())..) {
+            if cgroup == TOTAL {
+                continue;
+            }
+            let entry = best.entry(cgroup).or_insert((0, *day));
+            if *peak > entry.0 {
+                *entry = (*peak, *day);
+            }
+        }
+        let mut cgroups: Vec<(String, u64, u64)> =
+            best.into_iter().map(|(c, (peak, day))| (c.to_string(), peak, day)).collect();
+        cgroups.sort_by_key(|(_, peak, _)| std::cmp::Reverse(*peak));
+        cgroups
+    }
+}
+
+/// Swap used by each top-level slice below `root`, from memory.swap.current
+fn slice_usage(root: &Path) -> Vec<(String, u64)> {
+    let Ok(entries) = fs::read_dir(root) else {
+        return Vec::new();
+    };
+    let mut slices: Vec<(String, u64)> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if !name.ends_with(".slice") {
+                return None;
+            }
+            let bytes = fs::read_to_string(entry.path().join("memory.swap.current")).ok()?;
+            Some((name, bytes.trim().parse().ok()?))
+        })
+        .collect();
+    slices.sort();
+    slices
+}
+
+/// Current total swap use and swap use per top-level slice
+pub fn sample() -> Vec<(String, u64)> {
+    let mut samples = Vec::new();
+    if let Ok(mem) = crate::meminfo::get_mem_stats(&["SwapTotal", "SwapFree"]) {
+        samples.push((TOTAL.to_string(), mem["SwapTotal"].saturating_sub(mem["SwapFree"])));
+    }
+    samples.extend(slice_usage(Path::new(CGROUP_ROOT)));
+    samples
+}
+
+/// Whether swap_history is on (default on)
+pub fn enabled(config: &Config) -> bool {
+    match config.get_opt("swap_history") {
+        Some(_) => config.get_bool("swap_history"),
+        None => defaults::SWAP_HISTORY,
+    }
+}
+
+/// Sample once a minute until shutdown; the file is rewritten only when a
+/// daily peak rises, so a steady system writes rarely
+pub fn spawn_recorder(config: &Config) {
+    if !enabled(config) {
+        return;
+    }
+    let path = history_file(config);
+    crate::context::spawn(move || {
+        let mut history = History::load(&path);
+        let mut warned = false;
+        while !is_shutdown() {
+            if history.record(today(), &sample()) {
+                if let Err(e) = history.save(&path) {
+                    if !warned {
+                        warn!("History: cannot write {}: {}", path.display(), e);
+                        warned = true;
+                    }
+                }
+            }
+            thread::sleep(Duration::from_secs(SAMPLE_INTERVAL));
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_daily_maxima_and_prunes() {
+        let mut history = History::default();
+        let user = |bytes| vec![(TOTAL.to_string(), bytes), ("user.slice".to_string(), bytes / 2)];
+        assert!(history.record(100, &user(4 << 30)));
+        assert!(!history.record(100, &user(2 << 30)), "lower samples keep the peak");
+        assert!(history.record(101, &user(6 << 30)));
+        assert_eq!(history.peak(100, "user.slice"), Some(2 << 30));
+        assert_eq!(history.cgroups(0), vec![("user.slice".to_string(), 3 << 30, 101)]);
+        assert_eq!(history.days(101), vec![101]);
+
+        history.record(100 + HISTORY_DAYS, &user(1));
+        assert_eq!(history.days(0), vec![101, 100 + HISTORY_DAYS]);
+
+        let path = std::env::temp_dir().join(format!("swap-history-{}", std::process::id()));
+        history.save(&path).unwrap();
+        assert_eq!(History::load(&path), history);
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn formats_days_as_dates() {
+        assert_eq!(format_day(0), "1970-01-01");
+        assert_eq!(format_day(19_723), "2024-01-01");
+        assert_eq!(format_day(19_782), "2024-02-29");
+    }
+}
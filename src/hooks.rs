@@ -0,0 +1,208 @@
+// Start/stop hook scripts for systemd-swap
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// exec_after_start runs once swap setup is complete (when readiness is sent
+// to systemd), exec_before_stop runs before teardown begins. Both get the
+// effective mode as their only argument and the active swap devices in the
+// environment, so one script can serve every mode.
+
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+use crate::defaults;
+use crate::{info, warn};
+
+/// How long teardown waits for exec_before_stop before killing it
+const STOP_HOOK_TIMEOUT: Duration = Duration::from_secs(30);
+
+static AFTER_START: OnceLock<Option<PathBuf>> = OnceLock::new();
+static AFTER_START_RAN: AtomicBool = AtomicBool::new(false);
+
+/// Points in the daemon lifecycle that run a hook
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HookPoint {
+    AfterStart,
+    BeforeStop,
+}
+
+impl HookPoint {
+    pub fn name(self) -> &'static str {
+        match self {
+            HookPoint::AfterStart => "after_start",
+            HookPoint::BeforeStop => "before_stop",
+        }
+    }
+
+    fn key(self) -> &'static str {
+        match self {
+            HookPoint::AfterStart => "exec_after_start",
+            HookPoint::BeforeStop => "exec_before_stop",
+        }
+    }
+
+    fn default(self) -> &'static str {
+        match self {
+            HookPoint::AfterStart => defaults::EXEC_AFTER_START,
+            HookPoint::BeforeStop => defaults::EXEC_BEFORE_STOP,
+        }
+    }
+}
+
+/// Hook executable configured for a point, None when unset
+pub fn hook_path(config: &Config, point: HookPoint) -> Option<PathBuf> {
+    let path = config.get(point.key()).unwrap_or(point.default()).trim();
+    (!path.is_empty()).then(|| PathBuf::from(path))
+}
+
+/// Active swap devices, split into zram devices and everything else
+#[derive(Debug, Default, PartialEq)]
+pub struct ActiveSwap {
+    pub zram: Vec<String>,
+    pub other: Vec<String>,
+}
+
+impl ActiveSwap {
+    pub fn read() -> Self {
+        Self::parse(&std::fs::read_to_string("/proc/swaps").unwrap_or_default())
+    }
+
+    fn parse(swaps: &str) -> Self {
+        let mut active = Self::default();
+        for dev in swaps.lines().skip(1).filter_map(|l| l.split_whitespace().next()) {
+            if dev.starts_with("/dev/zram") {
+                active.zram.push(dev.to_string());
+            } else {
+                active.other.push(dev.to_string());
+            }
+        }
+        active
+    }
+
+    /// Environment passed to hooks
+    fn env(&self) -> [(&'static str, String); 3] {
+        let all: Vec<&str> = self.zram.iter().chain(&self.other).map(String::as_str).collect();
+        [
+            ("SWAP_DEVICES", all.join(" ")),
+            ("SWAP_ZRAM_DEVICES", self.zram.join(" ")),
+            ("SWAP_OTHER_DEVICES", self.other.join(" ")),
+        ]
+    }
+}
+
+fn command(hook: &PathBuf, point: HookPoint, mode: &str) -> Command {
+    let mut cmd = Command::new(hook);
+    cmd.arg(mode)
+        .env("SWAP_HOOK", point.name())
+        .env("SWAP_MODE", mode)
+        .envs(ActiveSwap::read().env())
+        .stdin(Stdio::null());
+    cmd
+}
+
+/// Remember exec_after_start for the readiness notification
+pub fn init(config: &Config) {
+    let _ = AFTER_START.set(hook_path(config, HookPoint::AfterStart));
+}
+
+/// Run exec_after_start once; called when readiness is sent to systemd
+pub fn run_after_start(mode: &str) {
+    let Some(Some(hook)) = AFTER_START.get() else {
+        return;
+    };
+    if AFTER_START_RAN.swap(true, Ordering::AcqRel) {
+        return;
+    }
+    info!("Hooks: running {} ({})", hook.display(), HookPoint::AfterStart.name());
+    match command(hook, HookPoint::AfterStart, mode).spawn() {
+        // Reap in the background so a slow hook never delays monitoring
+        Ok(mut child) => {
+            let hook = hook.clone();
+            crate::context::spawn(move || match child.wait() {
+                Ok(status) if !status.success() => {
+                    warn!("Hooks: {} exited with {}", hook.display(), status)
+                }
+                Err(e) => warn!("Hooks: {} failed: {}", hook.display(), e),
+                _ => {}
+            });
+        }
+        Err(e) => warn!("Hooks: failed to run {}: {}", hook.display(), e),
+    }
+}
+
+/// Run exec_before_stop and wait for it (up to STOP_HOOK_TIMEOUT)
+pub fn run_before_stop(config: &Config, mode: &str) {
+    let Some(hook) = hook_path(config, HookPoint::BeforeStop) else {
+        return;
+    };
+    info!("Hooks: running {} ({})", hook.display(), HookPoint::BeforeStop.name());
+    let mut child = match command(&hook, HookPoint::BeforeStop, mode).spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            warn!("Hooks: failed to run {}: {}", hook.display(), e);
+            return;
+        }
+    };
+    let deadline = Instant::now() + STOP_HOOK_TIMEOUT;
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) => {
+                if !status.success() {
+                    warn!("Hooks: {} exited with {}", hook.display(), status);
+                }
+                return;
+            }
+            Ok(None) if Instant::now() < deadline => thread::sleep(Duration::from_millis(100)),
+            Ok(None) => {
+                warn!(
+                    "Hooks: {} still running after {}s, killing it",
+                    hook.display(),
+                    STOP_HOOK_TIMEOUT.as_secs()
+                );
+                let _ = child.kill();
+                let _ = child.wait();
+                return;
+            }
+            Err(e) => {
+                warn!("Hooks: {} failed: {}", hook.display(), e);
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn splits_zram_from_other_devices() {
+        let swaps = "Filename\tType\tSize\tUsed\tPriority\n\
+                     /dev/zram0  partition\t8388604\t0\t32767\n\
+                     /dev/loop3  partition\t524284\t0\t-2\n\
+                     /dev/zram1  partition\t8388604\t0\t32767\n";
+        let active = ActiveSwap::parse(swaps);
+        assert_eq!(active.zram, ["/dev/zram0", "/dev/zram1"]);
+        assert_eq!(active.other, ["/dev/loop3"]);
+        let env: HashMap<_, _> = active.env().into_iter().collect();
+        assert_eq!(env["SWAP_DEVICES"], "/dev/zram0 /dev/zram1 /dev/loop3");
+    }
+
+    #[test]
+    fn empty_hook_is_unset() {
+        let mut values = HashMap::new();
+        values.insert("exec_after_start".to_string(), "  ".to_string());
+        values.insert("exec_before_stop".to_string(), "/usr/local/bin/arc".to_string());
+        let config = Config::from_values(values);
+        assert_eq!(hook_path(&config, HookPoint::AfterStart), None);
+        assert_eq!(
+            hook_path(&config, HookPoint::BeforeStop),
+            Some(PathBuf::from("/usr/local/bin/arc"))
+        );
+    }
+}
@@ -0,0 +1,566 @@
+// Swap-in latency probe for systemd-swap
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// Measures how long a page takes to come back from each swap tier. A worker
+// process is started in a scope of its own (systemd-run --scope) and fills two
+// buffers of PROBE_PAGES pages: one compressible (a quarter of each page
+// random) and one incompressible (all random). Writing to the scope's
+// memory.reclaim pushes both out to swap; the worker then touches every page
+// in a shuffled order, so swap readahead brings little in early, and times
+// each fault. /proc/self/smaps tells how many pages of a buffer were swapped;
+// that many of its slowest touches are the swap-ins.
+//
+// Which backend takes the pages is the kernel's choice, made by priority. The
+// probe does not reorder priorities (an active area's priority cannot change
+// without swapoff); it lets the data pick the tier instead: with zswap the
+// compressible buffer lands in the pool (T1) and the incompressible one is
+// rejected by it and goes to the swap area below, with zram both land in
+// zram until it is full. The tier is read from where the pages went (zswap
+// counters, /proc/swaps use by tier); a probe whose pages spread over several
+// tiers is not counted.
+//
+// The worker is not told to madvise(MADV_PAGEOUT) its own buffers, although
+// that would do without the scope and its cgroup v2 requirement. It would
+// need a scoped #[allow(unsafe_code)] FFI call like systemd.rs and loopdev.rs
+// have, and it would not steer the tier either. The deciding point is that
+// MADV_PAGEOUT evicts the pages directly, while memory.reclaim takes the same
+// reclaim path as memory pressure, so the pages meet zswap and the swap areas
+// the way the daemon's real swap-outs do.
+//
+// A worker that stops answering for WORKER_TIMEOUT is killed, so a stuck
+// probe cannot hold up the daemon's sampler or the `latency` command.
+//
+// Results per tier are kept in state_dir/latency. With swap_latency_probe=1
+// the daemon probes every swap_latency_interval seconds;
+// `systemd-swap latency` probes once and prints them.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use thiserror::Error;
+
+use crate::absorb::read_swaps;
+use crate::config::Config;
+use crate::defaults;
+use crate::helpers::{now_secs, write_sysfs};
+use crate::meminfo::get_page_size;
+use crate::tiers::{self, Tier};
+use crate::zswap::get_status as zswap_status;
+use crate::{debug, info, is_shutdown, warn};
+
+/// Hidden subcommand used to re-exec the binary as the probe worker
+pub const WORKER_COMMAND: &str = "latency-worker";
+
+/// Line printed by the worker once both buffers are filled
+const WORKER_READY: &str = "READY";
+
+/// Pages in each probe buffer
+const PROBE_PAGES: usize = 256;
+
+/// Longest wait for a line from the worker before it is killed
+const WORKER_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Swapped pages a buffer needs for its latencies to count
+const MIN_SWAPPED: usize = 32;
+
+/// Share of the swapped pages one tier must hold for the probe to count
+const PURE_PERCENT: u64 = 75;
+
+#[derive(Error, Debug)]
+pub enum LatencyError {
+    #[error("IO error: {0}")]
+    Io(#[from] io::Error),
+    #[error("no active swap area to probe")]
+    NoSwap,
+    #[error("probe worker failed: {0}")]
+    Worker(String),
+    #[error("cannot reclaim the probe's memory: {0}")]
+    Reclaim(String),
+}
+
+pub type Result<T> = std::result::Result<T, LatencyError>;
+
+/// Swap-in latencies of one tier, in microseconds, from its latest probe
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TierLatency {
+    /// Probes that measured this tier so far
+    pub probes: u64,
+    pub p50: u64,
+    pub p99: u64,
+    pub max: u64,
+    /// Seconds since the epoch of the latest probe
+    pub updated: u64,
+}
+
+impl TierLatency {
+    /// Percentiles of one probe's swap-in times; None without any
+    fn from_samples(mut samples: Vec<u64>, probes: u64, updated: u64) -> Option<Self> {
+        samples.sort_unstable();
+        let max = *samples.last()?;
+        let at = |percent: usize| samples[(samples.len() * percent / 100).min(samples.len() - 1)];
+        Some(Self {
+            probes,
+            p50: at(50),
+            p99: at(99),
+            max,
+            updated,
+        })
+    }
+}
+
+/// Latest latencies by tier
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Latencies(pub BTreeMap<Tier, TierLatency>);
+
+impl Latencies {
+    pub fn load(path: &Path) -> Self {
+        let content = fs::read_to_string(path).unwrap_or_default();
+        let mut latencies = Self::default();
+        for line in content.lines() {
+            let mut words = line.split_whitespace();
+            let Some(tier) = words.next().and_then(|label| Tier::ALL.into_iter().find(|t| t.label() == label)) else {
+                continue;
+            };
+            let mut entry = TierLatency::default();
+            for (key, value) in words.filter_map(|w| w.split_once('=')) {
+                let value: u64 = value.parse().unwrap_or(0);
+                match key {
+                    "probes" => entry.probes = value,
+                    "p50_us" => entry.p50 = value,
+                    "p99_us" => entry.p99 = value,
+                    "max_us" => entry.max = value,
+                    "updated" => entry.updated = value,
+                    _ => {}
+                }
+            }
+            latencies.0.insert(tier, entry);
+        }
+        latencies
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let content: String = self
+            .0
+            .iter()
+            .map(|(tier, l)| {
+                format!(
+                    "{} probes={} p50_us={} p99_us={} max_us={} updated={}\n",
+                    tier.label(),
+                    l.probes,
+                    l.p50,
+                    l.p99,
+                    l.max,
+                    l.updated
+                )
+            })
+            .collect();
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, content)?;
+        fs::rename(&tmp, path)
+    }
+
+    /// Record one probe's swap-in times for `tier`; a probe without any
+    /// swap-ins leaves the tier's latest latencies as they were
+    pub fn record(&mut self, tier: Tier, samples: Vec<u64>, now: u64) -> Option<TierLatency> {
+        let probes = self.0.get(&tier).map_or(0, |l| l.probes) + 1;
+        let latency = TierLatency::from_samples(samples, probes, now)?;
+        self.0.insert(tier, latency);
+        Some(latency)
+    }
+}
+
+/// Results file below the persistent state directory
+pub fn latency_file(config: &Config) -> PathBuf {
+    crate::config::state_dir(config).join("latency")
+}
+
+/// Swapped pages and per-page touch times (µs) of one worker buffer
+#[derive(Debug, Clone, Default, PartialEq)]
+struct BufferTimes {
+    swapped: usize,
+    times: Vec<u64>,
+}
+
+impl BufferTimes {
+    /// "<swapped> <µs> <µs> ..." as printed by the worker
+    fn parse(line: &str) -> Option<Self> {
+        let mut words = line.split_whitespace();
+        let swapped = words.next()?.parse().ok()?;
+        let times = words.map(|w| w.parse().ok()).collect::<Option<Vec<u64>>>()?;
+        Some(Self { swapped, times })
+    }
+
+    /// The slowest touches, one per swapped page
+    fn swap_ins(mut self) -> Vec<u64> {
+        self.times.sort_unstable_by(|a, b| b.cmp(a));
+        self.times.truncate(self.swapped);
+        self.times
+    }
+}
+
+/// Where a probe's pages went: growth of swap use per tier in bytes, and the
+/// zswap pool's stored pages and incompressible stored pages
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Placement {
+    growth: BTreeMap<Tier, u64>,
+    zswap_stored: u64,
+    zswap_incompressible: u64,
+}
+
+impl Placement {
+    fn snapshot() -> (BTreeMap<Tier, u64>, u64, u64) {
+        let mut used = BTreeMap::new();
+        for (tier, area) in tiers::classify(read_swaps()) {
+            *used.entry(tier).or_insert(0) += area.used;
+        }
+        let zswap = zswap_status().filter(|z| z.enabled && z.debugfs);
+        (
+            used,
+            zswap.as_ref().map_or(0, |z| z.stored_pages),
+            zswap.as_ref().map_or(0, |z| z.stored_incompressible_pages),
+        )
+    }
+
+    fn between(before: &(BTreeMap<Tier, u64>, u64, u64), after: &(BTreeMap<Tier, u64>, u64, u64)) -> Self {
+        Self {
+            growth: after
+                .0
+                .iter()
+                .map(|(tier, used)| (*tier, used.saturating_sub(before.0.get(tier).copied().unwrap_or(0))))
+                .collect(),
+            zswap_stored: after.1.saturating_sub(before.1),
+            zswap_incompressible: after.2.saturating_sub(before.2),
+        }
+    }
+
+    /// Tier of a buffer with `swapped` pages out, the incompressible one if
+    /// `incompressible`; None when its pages spread over several tiers
+    fn tier_of(&self, swapped: usize, incompressible: bool, page: u64) -> Option<Tier> {
+        let swapped = swapped as u64;
+        let in_pool = if incompressible {
+            self.zswap_incompressible
+        } else {
+            self.zswap_stored.saturating_sub(self.zswap_incompressible)
+        };
+        if in_pool * 100 >= swapped * PURE_PERCENT {
+            return Some(Tier::Zswap);
+        }
+        if in_pool > 0 && !incompressible {
+            return None;
+        }
+        // Pages held by zswap still take a slot in the area below, so the
+        // areas' growth covers both buffers
+        let total: u64 = self.growth.values().sum();
+        let (tier, grown) = self.growth.iter().max_by_key(|(_, grown)| **grown)?;
+        (*grown >= swapped * page && *grown * 100 >= total * PURE_PERCENT).then_some(*tier)
+    }
+}
+
+/// Worker process in a scope of its own
+struct Worker {
+    child: Child,
+    /// Lines of the worker's stdout, read on a thread of their own
+    lines: Receiver<io::Result<String>>,
+}
+
+impl Worker {
+    fn spawn() -> Result<Self> {
+        let exe = std::env::current_exe()?;
+        let mut child = Command::new("systemd-run")
+            .args(["--scope", "--quiet", "--collect", "-p", "MemoryAccounting=yes"])
+            .arg(exe)
+            .args([WORKER_COMMAND, "--pages", &PROBE_PAGES.to_string()])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()?;
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| io::Error::other("worker stdout not captured"))?;
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(Self { child, lines })
+    }
+
+    fn read_line(&mut self) -> Result<String> {
+        match self.lines.recv_timeout(WORKER_TIMEOUT) {
+            Ok(line) => Ok(line?),
+            Err(RecvTimeoutError::Timeout) => {
+                let _ = self.child.kill();
+                Err(LatencyError::Worker(format!("no answer for {}s, killed", WORKER_TIMEOUT.as_secs())))
+            }
+            Err(RecvTimeoutError::Disconnected) => Err(LatencyError::Worker("exited early".to_string())),
+        }
+    }
+
+    /// memory.reclaim of the worker's scope (systemd-run execs the worker,
+    /// so its pid is the child's)
+    fn reclaim_file(&self) -> Result<PathBuf> {
+        let cgroup = fs::read_to_string(format!("/proc/{}/cgroup", self.child.id()))?;
+        let path = cgroup
+            .lines()
+            .find_map(|l| l.strip_prefix("0::"))
+            .ok_or_else(|| LatencyError::Reclaim("cgroup v2 is not mounted".to_string()))?;
+        Ok(Path::new("/sys/fs/cgroup").join(path.trim_start_matches('/')).join("memory.reclaim"))
+    }
+
+    fn stop(mut self) {
+        drop(self.child.stdin.take());
+        let _ = self.child.wait();
+    }
+}
+
+/// Run one probe and record its latencies in `path`. Returns the tiers
+/// measured.
+pub fn probe(path: &Path) -> Result<Vec<(Tier, TierLatency)>> {
+    if read_swaps().is_empty() {
+        return Err(LatencyError::NoSwap);
+    }
+    let page = get_page_size();
+    let mut worker = Worker::spawn()?;
+    let result = measure(&mut worker, page);
+    worker.stop();
+    let buffers = result?;
+
+    let now = now_secs();
+    let mut latencies = Latencies::load(path);
+    let mut measured = Vec::new();
+    for (tier, times) in buffers {
+        debug!("Latency: {} swap-ins from {}", times.len(), tier.name());
+        if let Some(latency) = latencies.record(tier, times, now) {
+            measured.push((tier, latency));
+        }
+    }
+    if !measured.is_empty() {
+        latencies.save(path)?;
+    }
+    Ok(measured)
+}
+
+/// Push the worker's buffers out and collect their swap-in times by tier
+fn measure(worker: &mut Worker, page: u64) -> Result<Vec<(Tier, Vec<u64>)>> {
+    if worker.read_line()?.trim() != WORKER_READY {
+        return Err(LatencyError::Worker("unexpected output".to_string()));
+    }
+    let reclaim = worker.reclaim_file()?;
+    let before = Placement::snapshot();
+    // EAGAIN when not all of it could be reclaimed; smaps tells what was
+    if let Err(e) = write_sysfs(&reclaim, (2 * PROBE_PAGES as u64 * page).to_string()) {
+        if e.raw_os_error() != Some(libc::EAGAIN) {
+            return Err(LatencyError::Reclaim(format!("{}: {}", reclaim.display(), e)));
+        }
+    }
+    let placement = Placement::between(&before, &Placement::snapshot());
+    if let Some(stdin) = worker.child.stdin.as_mut() {
+        writeln!(stdin, "touch")?;
+    }
+
+    let mut measured = Vec::new();
+    for incompressible in [false, true] {
+        let line = worker.read_line()?;
+        let times = BufferTimes::parse(&line).ok_or_else(|| LatencyError::Worker(format!("bad line {:?}", line)))?;
+        if times.swapped < MIN_SWAPPED {
+            continue;
+        }
+        match placement.tier_of(times.swapped, incompressible, page) {
+            Some(tier) => measured.push((tier, times.swap_ins())),
+            None => debug!("Latency: probe pages spread over several tiers, not counted"),
+        }
+    }
+    Ok(measured)
+}
+
+/// Probe every swap_latency_interval seconds until shutdown, if
+/// swap_latency_probe is on
+pub fn spawn_sampler(config: &Config) {
+    let enabled = match config.get_opt("swap_latency_probe") {
+        Some(_) => config.get_bool("swap_latency_probe"),
+        None => defaults::SWAP_LATENCY_PROBE,
+    };
+    if !enabled {
+        return;
+    }
+    let interval = config
+        .get_as::<u64>("swap_latency_interval")
+        .unwrap_or(defaults::SWAP_LATENCY_INTERVAL)
+        .clamp(300, 86400);
+    let path = latency_file(config);
+    info!("Latency: probing swap-in latency every {}s", interval);
+    crate::context::spawn(move || {
+        let mut warned = false;
+        loop {
+            let started = Instant::now();
+            while started.elapsed() < Duration::from_secs(interval) {
+                if is_shutdown() {
+                    return;
+                }
+                thread::sleep(Duration::from_secs(1));
+            }
+            match probe(&path) {
+                Ok(measured) => {
+                    for (tier, l) in measured {
+                        info!("Latency: {} swap-in p50 {}µs, p99 {}µs", tier.name(), l.p50, l.p99);
+                    }
+                }
+                Err(LatencyError::NoSwap) => {}
+                Err(e) if !warned => {
+                    warn!("Latency: probe failed: {}", e);
+                    warned = true;
+                }
+                Err(e) => debug!("Latency: probe failed: {}", e),
+            }
+        }
+    });
+}
+
+/// Swap kB of the mapping containing `addr` in smaps content
+fn mapping_swap(smaps: &str, addr: usize) -> Option<u64> {
+    let mut inside = false;
+    for line in smaps.lines() {
+        let range = line.split_whitespace().next().and_then(|r| r.split_once('-'));
+        if let Some((start, end)) = range {
+            if let (Ok(start), Ok(end)) = (usize::from_str_radix(start, 16), usize::from_str_radix(end, 16)) {
+                inside = (start..end).contains(&addr);
+                continue;
+            }
+        }
+        if inside {
+            if let Some(value) = line.strip_prefix("Swap:") {
+                return value.trim().trim_end_matches("kB").trim().parse().ok();
+            }
+        }
+    }
+    None
+}
+
+/// Entry point of the worker process.
+///
+/// Fills the two buffers, reports READY, waits for "touch" on stdin, then
+/// prints one line per buffer (compressible first): the pages smaps shows
+/// swapped out, then the touch time of every page in microseconds. Exits
+/// when stdin closes.
+pub fn run_worker(pages: usize) -> Result<()> {
+    let page = get_page_size() as usize;
+    let mut seed: u64 = 0x9E37_79B9_7F4A_7C15;
+    let mut next = || {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        seed
+    };
+    // Separate allocations of this size are separate mappings in smaps
+    let mut buffers = [vec![0u8; pages * page], vec![0u8; pages * page]];
+    for (index, buf) in buffers.iter_mut().enumerate() {
+        let random = if index == 0 { page / 4 } else { page };
+        for page_start in (0..buf.len()).step_by(page) {
+            for word in buf[page_start..page_start + random].chunks_mut(8) {
+                word.copy_from_slice(&next().to_le_bytes()[..word.len()]);
+            }
+        }
+    }
+
+    let mut stdout = io::stdout();
+    writeln!(stdout, "{}", WORKER_READY)?;
+    stdout.flush()?;
+    let mut stdin = BufReader::new(io::stdin());
+    let mut line = String::new();
+    stdin.read_line(&mut line)?;
+
+    let smaps = fs::read_to_string("/proc/self/smaps")?;
+    let swapped: Vec<usize> = buffers
+        .iter()
+        .map(|buf| mapping_swap(&smaps, buf.as_ptr() as usize).unwrap_or(0) as usize * 1024 / page)
+        .collect();
+    let mut order: Vec<usize> = (0..pages).collect();
+    for i in (1..order.len()).rev() {
+        order.swap(i, next() as usize % (i + 1));
+    }
+    let mut times = [vec![0u64; pages], vec![0u64; pages]];
+    for (buf, times) in buffers.iter().zip(times.iter_mut()) {
+        for &index in &order {
+            let started = Instant::now();
+            std::hint::black_box(buf[index * page]);
+            times[index] = started.elapsed().as_micros() as u64;
+        }
+    }
+    for (swapped, times) in swapped.iter().zip(&times) {
+        let times: Vec<String> = times.iter().map(u64::to_string).collect();
+        writeln!(stdout, "{} {}", swapped, times.join(" "))?;
+    }
+    stdout.flush()?;
+
+    let _ = stdin.read(&mut [0u8; 1]);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn swap_ins_are_the_slowest_touches() {
+        let times = BufferTimes::parse("2 1 40 0 35 1").unwrap();
+        assert_eq!(times.swap_ins(), vec![40, 35]);
+        assert!(BufferTimes::parse("2 1 x").is_none());
+
+        let mut latencies = Latencies::default();
+        latencies.record(Tier::Fast, (1..=100).collect(), 1_700_000_000);
+        latencies.record(Tier::Fast, (1..=100).collect(), 1_700_003_600);
+        let fast = latencies.0[&Tier::Fast];
+        assert_eq!((fast.probes, fast.p50, fast.p99, fast.max), (2, 51, 100, 100));
+
+        // A worker that reports no swap-ins
+        let none = BufferTimes::parse("0 40 35").unwrap().swap_ins();
+        assert!(none.is_empty());
+        assert_eq!(latencies.record(Tier::Fast, none, 1_700_007_200), None);
+        assert_eq!(latencies.0[&Tier::Fast], fast);
+
+        let path = std::env::temp_dir().join(format!("latency-{}", std::process::id()));
+        latencies.save(&path).unwrap();
+        assert_eq!(Latencies::load(&path), latencies);
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn attributes_buffers_to_the_tier_that_took_them() {
+        let page = 4096;
+        let placement = |growth: &[(Tier, u64)], stored, incompressible| Placement {
+            growth: growth.iter().copied().collect(),
+            zswap_stored: stored,
+            zswap_incompressible: incompressible,
+        };
+        // zswap in front of an SSD file: the pool takes the compressible
+        // buffer, the incompressible one is written to the file
+        let zswap = placement(&[(Tier::Fast, 512 * page)], 256, 0);
+        assert_eq!(zswap.tier_of(256, false, page), Some(Tier::Zswap));
+        assert_eq!(zswap.tier_of(256, true, page), Some(Tier::Fast));
+        // zram takes both
+        let zram = placement(&[(Tier::Zram, 512 * page), (Tier::Slow, 0)], 0, 0);
+        assert_eq!(zram.tier_of(256, true, page), Some(Tier::Zram));
+        // zram filled up halfway through: spread, not counted
+        let spread = placement(&[(Tier::Zram, 256 * page), (Tier::Slow, 256 * page)], 0, 0);
+        assert_eq!(spread.tier_of(256, true, page), None);
+    }
+
+    #[test]
+    fn finds_swap_of_a_mapping() {
+        let smaps = "55d0c0000000-55d0c0100000 rw-p 00000000 00:00 0\nRss:  64 kB\nSwap:  960 kB\n\
+                     7f0000000000-7f0000200000 rw-p 00000000 00:00 0\nSwap:  12 kB\n";
+        assert_eq!(mapping_swap(smaps, 0x55d0c0000800), Some(960));
+        assert_eq!(mapping_swap(smaps, 0x7f00001fffff), Some(12));
+        assert_eq!(mapping_swap(smaps, 0x1000), None);
+    }
+}
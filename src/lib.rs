@@ -1,30 +1,60 @@
 //! Core library for systemd-swap: dynamic swap management for Linux.
 //!
-//! Exposes the public modules for the daemon binary and any future consumers.
+//! Exposes the public modules for the daemon binary and for other processes
+//! that embed the swap manager through [`manager::SwapManagerBuilder`].
 // SPDX-License-Identifier: GPL-3.0-or-later
 
 #![deny(unsafe_code)]
+pub mod absorb;
+pub mod alignment;
+pub mod alarms;
 pub mod autoconfig;
+pub mod btrfs;
+pub mod budget;
+pub mod collect;
 pub mod config;
+pub mod context;
+pub mod control;
 pub mod defaults;
+pub mod disks;
+pub mod drill;
+pub mod events;
+pub mod exec;
+pub mod execwatch;
+pub mod fstrim;
+pub mod handoff;
+pub mod health;
+pub mod hibernate;
 pub mod helpers;
+pub mod history;
+pub mod hooks;
+pub mod latency;
+pub mod loopdev;
+pub mod manager;
 pub mod meminfo;
+pub mod modes;
+pub mod oomd;
+pub mod plan;
+pub mod profiler;
+pub mod psi;
+pub mod readonly;
+pub mod recovery;
+pub mod schema;
+pub mod selftest;
+pub mod sizing;
+pub mod state;
 pub mod swapfile;
+pub mod swapwatch;
+pub mod sysctl;
 pub mod systemd;
+pub mod tiers;
+pub mod wipe;
+pub mod workload;
 pub mod zram;
 pub mod zswap;
 
-use std::sync::atomic::{AtomicBool, Ordering};
-
-/// Global shutdown flag for signal handling
-pub static SHUTDOWN: AtomicBool = AtomicBool::new(false);
-
-/// Check if shutdown was requested
+/// Check if shutdown was requested for the current manager instance; the
+/// standalone daemon's signal handler requests it too (see context.rs)
 pub fn is_shutdown() -> bool {
-    SHUTDOWN.load(Ordering::Acquire)
-}
-
-/// Request shutdown
-pub fn request_shutdown() {
-    SHUTDOWN.store(true, Ordering::Release);
+    context::shutdown_requested()
 }
@@ -0,0 +1,388 @@
+// Stable loop device numbers for systemd-swap
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// Loop-backed swap file N keeps the same /dev/loopM across restarts and
+// reboots, so logs, unit names and external references stay valid. The
+// number a file got the first time is recorded in a registry in the
+// persistent state directory; later attaches create that device through
+// /dev/loop-control (LOOP_CTL_ADD) if needed and bind to it. When the
+// number is in use by something else or cannot be created, the next free
+// device is taken and recorded instead.
+//
+// Queue tunables of a loop device (scheduler, wbt, max_sectors, ...) outlive
+// the attachment: whoever binds the device next inherits them. Before the
+// first change the original values are journaled per device, and detaching
+// through `detach` writes them back. The journal lives in /run but outside
+// WORK_DIR, since a restart wipes WORK_DIR while adopted loops stay attached
+// with our values.
+//
+// File numbers alone do not identify a swap file reliably: a file may be
+// renamed, and loop info in WORK_DIR may be stale or lost. The device and
+// inode of each file are recorded next to the loop numbers when it is
+// created, and matched against the file or a loop device's backing inode
+// (which stays valid after a rename) before anything is removed or adopted.
+
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+use crate::config::RUN_SYSD;
+use crate::exec::TimedCommand;
+use crate::helpers::{makedirs, run_cmd_output, selected_option, write_file, write_sysfs, HelperError};
+use crate::{info, warn};
+
+/// ioctl on /dev/loop-control that creates /dev/loopN (linux/loop.h)
+const LOOP_CTL_ADD: libc::c_ulong = 0x4C80;
+
+/// Loop device number of "/dev/loop7"
+pub fn loop_number(device: &str) -> Option<u32> {
+    device.strip_prefix("/dev/loop")?.parse().ok()
+}
+
+/// Whether /dev/loopN exists with nothing attached
+fn is_unbound(num: u32) -> bool {
+    let sys = format!("/sys/block/loop{}", num);
+    Path::new(&sys).exists() && !Path::new(&format!("{}/loop/backing_file", sys)).exists()
+}
+
+/// Create /dev/loopN; true if it exists afterwards
+fn ensure_device(num: u32) -> bool {
+    if Path::new(&format!("/sys/block/loop{}", num)).exists() {
+        return true;
+    }
+    let Ok(control) = OpenOptions::new().read(true).write(true).open("/dev/loop-control") else {
+        return false;
+    };
+    // SAFETY: LOOP_CTL_ADD takes the device number by value and touches no memory of ours.
+    #[allow(unsafe_code)]
+    let ret = unsafe { libc::ioctl(control.as_raw_fd(), LOOP_CTL_ADD as _, num as libc::c_ulong) };
+    ret >= 0 || std::io::Error::last_os_error().raw_os_error() == Some(libc::EEXIST)
+}
+
+/// Queue tunables the daemon may change on its loop devices
+const QUEUE_TUNABLES: &[&str] = &[
+    "rotational",
+    "iostats",
+    "add_random",
+    "scheduler",
+    "nomerges",
+    "wbt_lat_usec",
+    "max_sectors_kb",
+    "rq_affinity",
+    "read_ahead_kb",
+];
+
+fn queue_journal(device: &str) -> Option<String> {
+    Some(format!("{}/swap-loop-queue/loop{}", RUN_SYSD, loop_number(device)?))
+}
+
+fn parse_journal(content: &str) -> Vec<(String, String)> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let (name, value) = line.split_once('=')?;
+            QUEUE_TUNABLES
+                .contains(&name)
+                .then(|| (name.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+/// Record the device's queue tunables before the daemon changes them.
+/// The first snapshot wins, so re-tuning an adopted device keeps the
+/// values from before the daemon ever touched it.
+pub fn snapshot_queue(device: &str) {
+    let Some(journal) = queue_journal(device) else {
+        return;
+    };
+    if Path::new(&journal).exists() {
+        return;
+    }
+    let queue = format!("/sys/block/{}/queue", device.trim_start_matches("/dev/"));
+    let content: String = QUEUE_TUNABLES
+        .iter()
+        .filter_map(|name| {
+            let raw = fs::read_to_string(format!("{}/{}", queue, name)).ok()?;
+            let value = if *name == "scheduler" {
+                selected_option(&raw)?
+            } else {
+                raw.trim().to_string()
+            };
+            Some(format!("{}={}\n", name, value))
+        })
+        .collect();
+    if content.is_empty() {
+        return;
+    }
+    let saved = Path::new(&journal)
+        .parent()
+        .map_or(Ok(()), |dir| makedirs(dir).map_err(std::io::Error::other))
+        .and_then(|_| write_file(&journal, &content).map_err(std::io::Error::other));
+    if let Err(e) = saved {
+        warn!("swapFC: cannot journal queue settings of {}: {}", device, e);
+    }
+}
+
+/// Write back the journaled queue tunables of a device and forget them
+pub fn restore_queue(device: &str) {
+    let Some(journal) = queue_journal(device) else {
+        return;
+    };
+    let Ok(content) = fs::read_to_string(&journal) else {
+        return;
+    };
+    let queue = format!("/sys/block/{}/queue", device.trim_start_matches("/dev/"));
+    for (name, value) in parse_journal(&content) {
+        if let Err(e) = write_sysfs(format!("{}/{}", queue, name), &value) {
+            warn!("swapFC: cannot restore {} {}={}: {}", device, name, value, e);
+        }
+    }
+    let _ = fs::remove_file(&journal);
+}
+
+/// Detach a loop device, restoring its queue tunables first
+pub fn detach(device: &str) {
+    restore_queue(device);
+    let _ = std::process::Command::new("losetup").args(["-d", device]).status_timed();
+}
+
+/// Swap file number → loop device number, kept across reboots
+#[derive(Debug, Clone, Default)]
+pub struct LoopRegistry {
+    path: PathBuf,
+    numbers: BTreeMap<u32, u32>,
+}
+
+impl LoopRegistry {
+    pub fn load(state_dir: &Path) -> Self {
+        let path = state_dir.join("loop_devices");
+        let numbers = fs::read_to_string(&path)
+            .map(|content| Self::parse(&content))
+            .unwrap_or_default();
+        Self { path, numbers }
+    }
+
+    fn parse(content: &str) -> BTreeMap<u32, u32> {
+        content
+            .lines()
+            .filter_map(|line| {
+                let (file, num) = line.split_once('=')?;
+                Some((file.trim().parse().ok()?, num.trim().parse().ok()?))
+            })
+            .collect()
+    }
+
+    fn to_file(&self) -> String {
+        self.numbers.iter().map(|(file, num)| format!("{}={}\n", file, num)).collect()
+    }
+
+    pub fn get(&self, file_num: u32) -> Option<u32> {
+        self.numbers.get(&file_num).copied()
+    }
+
+    /// Remember the device of a swap file; no other file keeps that number
+    pub fn record(&mut self, file_num: u32, device: &str) {
+        let Some(num) = loop_number(device) else {
+            return;
+        };
+        if self.get(file_num) == Some(num) {
+            return;
+        }
+        self.numbers.retain(|_, n| *n != num);
+        self.numbers.insert(file_num, num);
+        if let Err(e) = write_file(&self.path, &self.to_file()) {
+            warn!("swapFC: cannot save loop registry {}: {}", self.path.display(), e);
+        }
+    }
+
+    /// Attach `file` to its recorded loop device, or to the first free one
+    pub fn attach(&mut self, file_num: u32, file: &Path) -> Result<String, HelperError> {
+        let file = file.to_string_lossy();
+        if let Some(num) = self.get(file_num) {
+            let device = format!("/dev/loop{}", num);
+            if ensure_device(num) && is_unbound(num) {
+                match run_cmd_output(&["losetup", "--direct-io=on", &device, &file]) {
+                    Ok(_) => return Ok(device),
+                    Err(e) => warn!("swapFC: cannot reuse {} for file #{}: {}", device, file_num, e),
+                }
+            } else {
+                info!("swapFC: {} is busy, file #{} gets a new loop device", device, file_num);
+            }
+        }
+        let device = run_cmd_output(&["losetup", "-f", "--show", "--direct-io=on", &file])?;
+        let device = device.trim().to_string();
+        self.record(file_num, &device);
+        Ok(device)
+    }
+}
+
+/// Identity of a swap file: st_dev and st_ino
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FileId {
+    pub dev: u64,
+    pub ino: u64,
+}
+
+impl FileId {
+    pub fn of(path: &Path) -> Option<Self> {
+        let meta = fs::metadata(path).ok()?;
+        Some(Self {
+            dev: meta.dev(),
+            ino: meta.ino(),
+        })
+    }
+
+    /// From losetup's BACK-MAJ:MIN and BACK-INO columns
+    pub fn from_losetup(maj_min: &str, ino: &str) -> Option<Self> {
+        let (major, minor) = maj_min.split_once(':')?;
+        Some(Self {
+            dev: nix::sys::stat::makedev(major.parse().ok()?, minor.parse().ok()?),
+            ino: ino.parse().ok()?,
+        })
+    }
+
+    /// Backing file of an attached loop device, also after a rename
+    pub fn of_loop(device: &str) -> Option<Self> {
+        let out = run_cmd_output(&["losetup", "-l", "--noheadings", "-o", "BACK-MAJ:MIN,BACK-INO", device]).ok()?;
+        let mut fields = out.split_whitespace();
+        Self::from_losetup(fields.next()?, fields.next()?)
+    }
+}
+
+/// Swap file number → identity of the file created for it, kept across reboots
+#[derive(Debug, Clone, Default)]
+pub struct InodeRegistry {
+    path: PathBuf,
+    ids: BTreeMap<u32, FileId>,
+}
+
+impl InodeRegistry {
+    pub fn load(state_dir: &Path) -> Self {
+        let path = state_dir.join("swapfile_inodes");
+        let ids = fs::read_to_string(&path)
+            .map(|content| Self::parse(&content))
+            .unwrap_or_default();
+        Self { path, ids }
+    }
+
+    fn parse(content: &str) -> BTreeMap<u32, FileId> {
+        content
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.split_whitespace();
+                let file = fields.next()?.parse().ok()?;
+                let dev = fields.next()?.parse().ok()?;
+                let ino = fields.next()?.parse().ok()?;
+                Some((file, FileId { dev, ino }))
+            })
+            .collect()
+    }
+
+    fn to_file(&self) -> String {
+        self.ids
+            .iter()
+            .map(|(file, id)| format!("{} {} {}\n", file, id.dev, id.ino))
+            .collect()
+    }
+
+    fn save(&self) {
+        if let Err(e) = write_file(&self.path, &self.to_file()) {
+            warn!("swapFC: cannot save inode registry {}: {}", self.path.display(), e);
+        }
+    }
+
+    pub fn get(&self, file_num: u32) -> Option<FileId> {
+        self.ids.get(&file_num).copied()
+    }
+
+    /// Number of the swap file with this identity
+    pub fn find(&self, id: FileId) -> Option<u32> {
+        self.ids.iter().find(|(_, i)| **i == id).map(|(file, _)| *file)
+    }
+
+    /// Remember the identity of a new swap file; an inode number reused by
+    /// the filesystem no longer points to the file that had it before
+    pub fn record(&mut self, file_num: u32, id: FileId) {
+        if self.get(file_num) == Some(id) {
+            return;
+        }
+        self.ids.retain(|_, i| *i != id);
+        self.ids.insert(file_num, id);
+        self.save();
+    }
+
+    pub fn forget(&mut self, file_num: u32) {
+        if self.ids.remove(&file_num).is_some() {
+            self.save();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn registry_round_trip() {
+        let mut registry = LoopRegistry {
+            path: std::env::temp_dir().join(format!("systemd-swap-loops-{}", std::process::id())),
+            ..LoopRegistry::default()
+        };
+        registry.record(1, "/dev/loop7");
+        registry.record(2, "/dev/loop9");
+        // A number moves to the file that got it last
+        registry.record(3, "/dev/loop7");
+        registry.record(4, "/dev/sda");
+        assert_eq!(registry.get(1), None);
+        assert_eq!(registry.get(3), Some(7));
+        assert_eq!(LoopRegistry::parse(&registry.to_file()), registry.numbers);
+        let _ = fs::remove_file(&registry.path);
+    }
+
+    #[test]
+    fn inode_registry_round_trip() {
+        let mut registry = InodeRegistry {
+            path: std::env::temp_dir().join(format!("systemd-swap-inodes-{}", std::process::id())),
+            ..InodeRegistry::default()
+        };
+        let id = |ino| FileId { dev: 2049, ino };
+        registry.record(1, id(12));
+        registry.record(2, id(13));
+        // The filesystem reused inode 12 for file 3
+        registry.record(3, id(12));
+        assert_eq!(registry.find(id(12)), Some(3));
+        assert_eq!(registry.get(1), None);
+        registry.forget(2);
+        assert_eq!(registry.find(id(13)), None);
+        assert_eq!(InodeRegistry::parse(&registry.to_file()), registry.ids);
+        let _ = fs::remove_file(&registry.path);
+
+        assert_eq!(
+            FileId::from_losetup("8:1", "12"),
+            Some(FileId { dev: nix::sys::stat::makedev(8, 1), ino: 12 })
+        );
+        assert_eq!(FileId::from_losetup("-", ""), None);
+    }
+
+    #[test]
+    fn journal_keeps_known_tunables() {
+        let journal = parse_journal("scheduler=mq-deadline\nwbt_lat_usec=2000\nbogus=1\nread_ahead_kb=128\n");
+        assert_eq!(
+            journal,
+            vec![
+                ("scheduler".to_string(), "mq-deadline".to_string()),
+                ("wbt_lat_usec".to_string(), "2000".to_string()),
+                ("read_ahead_kb".to_string(), "128".to_string()),
+            ]
+        );
+        assert_eq!(queue_journal("/dev/loop3").as_deref(), Some("/run/systemd/swap-loop-queue/loop3"));
+    }
+
+    #[test]
+    fn loop_numbers() {
+        assert_eq!(loop_number("/dev/loop12"), Some(12));
+        assert_eq!(loop_number("/dev/zram0"), None);
+    }
+}
@@ -7,23 +7,51 @@ use std::process::{Command, Stdio};
 
 use clap::{Parser, Subcommand};
 
-use systemd_swap::autoconfig::{RecommendedConfig, SwapMode as AutoSwapMode, SystemCapabilities};
-use systemd_swap::config::{Config, WORK_DIR};
+use systemd_swap::alignment::Alignment;
+use systemd_swap::collect;
+use systemd_swap::autoconfig::{RecommendedConfig, SystemCapabilities};
+use systemd_swap::config::Config;
 use systemd_swap::defaults;
 use systemd_swap::helpers::{
-    am_i_root, find_swap_units, force_remove, get_what_from_swap_unit, makedirs, read_file,
+    am_i_root, format_duration, format_size, get_fstype, now_secs,
 };
 use systemd_swap::meminfo::get_mem_stats;
-use systemd_swap::swapfile::SwapFile;
-use systemd_swap::systemd::{notify_ready, notify_stopping, swapoff};
-use systemd_swap::zswap::ZswapBackup;
-use systemd_swap::{error, info, request_shutdown, warn};
+use systemd_swap::events::EventKind;
+use systemd_swap::exec::TimedCommand;
+use systemd_swap::handoff;
+use systemd_swap::health::{HealthInputs, HealthReport};
+use systemd_swap::hibernate::HibernationStatus;
+use systemd_swap::state::DaemonState;
+use systemd_swap::manager::SwapManager;
+use systemd_swap::modes::SwapMode;
+use systemd_swap::oomd::OomdStatus;
+use systemd_swap::plan::StopPlan;
+use systemd_swap::profiler::TickSummary;
+use systemd_swap::sizing::Sizing;
+use systemd_swap::swapfile::{StorageType, SwapFilePacing};
+use systemd_swap::sysctl;
+use systemd_swap::tiers::{self, TierUsage};
+use systemd_swap::workload::{observations_file, Observations, WorkloadClass};
+use systemd_swap::zram::{ZramPoolConfig, ZramStats};
+use systemd_swap::zswap::ZswapCounters;
+use systemd_swap::systemd::notify_stopping;
+use systemd_swap::{error, info};
 
 #[derive(Parser)]
 #[command(name = "systemd-swap")]
 #[command(about = "Dynamic swap management for zram, zswap, and swap files")]
 #[command(version)]
 struct Cli {
+    /// Alternate configuration file, or a directory laid out like / holding
+    /// the configuration tree (same as SYSTEMD_SWAP_CONF)
+    #[arg(long, global = true, value_name = "PATH")]
+    config: Option<std::path::PathBuf>,
+
+    /// Only observe: refuse every command and program that would change the
+    /// system (always on in builds with the read-only feature)
+    #[arg(long, global = true)]
+    read_only: bool,
+
     #[command(subcommand)]
     command: Option<Commands>,
 }
@@ -33,32 +61,160 @@ enum Commands {
     /// Start the swap management daemon
     Start,
     /// Stop the swap management daemon
-    Stop,
+    Stop {
+        /// List what would be swapped off, restored and removed, without changing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
     /// Show swap status information
-    Status,
+    Status {
+        /// Print a machine-readable JSON snapshot instead
+        #[arg(long)]
+        json: bool,
+    },
+    /// Hand the running daemon over to the installed binary without swapoff
+    Handoff,
+    /// Drain a static swap partition into managed swap and deactivate it
+    AbsorbPartition {
+        /// Swap partition, e.g. /dev/sda2
+        device: String,
+        /// Also remove the swap signature from the partition afterwards
+        #[arg(long)]
+        wipe: bool,
+    },
+    /// Check daemon health; exits 0 (healthy), 1 (degraded) or 2 (failed)
+    Health,
+    /// Show memory and swap usage corrected for zram/zswap compression
+    Free,
+    /// Show swap use now, or its daily peaks for capacity planning
+    Stats {
+        /// Show recorded daily peaks instead of current use
+        #[arg(long)]
+        history: bool,
+        /// Break swap use down by top-level cgroup slice
+        #[arg(long)]
+        by_cgroup: bool,
+        /// Days of history to show
+        #[arg(long, default_value_t = 7)]
+        days: u64,
+    },
     /// Show recommended configuration for this system
     Autoconfig,
+    /// Validate the configuration and show the effective derived values
+    CheckConfig,
+    /// Describe a configuration key (or list all keys)
+    Explain {
+        /// Configuration key, e.g. swapfile_safe_headroom
+        key: Option<String>,
+    },
+    /// Run a memory stress self-test against the running daemon
+    Selftest {
+        /// Amount of memory to allocate (e.g. 2G, 512M, 50%)
+        #[arg(long, default_value = "50%")]
+        size: String,
+        /// Seconds to hold the allocation before releasing it
+        #[arg(long, default_value_t = 30)]
+        hold: u64,
+        /// Seconds to wait for contraction after the load is released
+        #[arg(long, default_value_t = 300)]
+        settle: u64,
+    },
+    /// Show or change the log level of the running daemon, without a restart
+    LogLevel {
+        #[command(subcommand)]
+        action: Option<LogLevelAction>,
+    },
+    /// Walk the emergency swap file path with a simulated critical sample
+    /// (nothing is allocated or created)
+    SimulateOom,
+    /// Measure swap-in latency per tier now and show the recorded latencies
+    Latency,
+    /// Internal: allocation worker spawned by `selftest`
+    #[command(name = "selftest-worker", hide = true)]
+    SelftestWorker {
+        #[arg(long)]
+        size: u64,
+    },
+    /// Internal: probe worker spawned by `latency`
+    #[command(name = "latency-worker", hide = true)]
+    LatencyWorker {
+        #[arg(long)]
+        pages: usize,
+    },
+}
+
+#[derive(Subcommand)]
+enum LogLevelAction {
+    /// Switch the daemon to this level (debug, info, warn, error)
+    Set { level: String },
 }
 
-/// Swap strategy based on filesystem detection
-#[derive(Debug, Clone, Copy, PartialEq)]
-enum SwapMode {
-    Auto,
-    ZramSwapfc,    // zram + swap files for overflow
-    ZswapSwapfc,   // zswap + swapfc (preallocated or sparse loop)
-    ZramOnly,      // zram only
-    Manual,        // Use explicit config values (zram_enabled, zswap_enabled, swapfc_enabled)
-    Disabled,      // Swap management disabled (service exits cleanly)
+impl Commands {
+    /// Whether the command changes the system; refused in read-only mode.
+    /// `latency` runs, but only shows what is recorded.
+    fn mutates(&self) -> bool {
+        match self {
+            Commands::Start
+            | Commands::Stop { dry_run: false }
+            | Commands::Handoff
+            | Commands::AbsorbPartition { .. }
+            | Commands::Selftest { .. }
+            | Commands::LogLevel { action: Some(_) }
+            | Commands::SelftestWorker { .. }
+            | Commands::LatencyWorker { .. } => true,
+            Commands::Stop { dry_run: true }
+            | Commands::Status { .. }
+            | Commands::Health
+            | Commands::Free
+            | Commands::Stats { .. }
+            | Commands::Autoconfig
+            | Commands::CheckConfig
+            | Commands::Explain { .. }
+            | Commands::LogLevel { action: None }
+            | Commands::SimulateOom
+            | Commands::Latency => false,
+        }
+    }
 }
 
 fn main() {
     let cli = Cli::parse();
+    if let Some(path) = cli.config {
+        systemd_swap::config::set_config_override(path);
+    }
+    if cli.read_only {
+        systemd_swap::readonly::enable();
+    }
+    if systemd_swap::readonly::is_enabled() && cli.command.as_ref().is_some_and(Commands::mutates) {
+        error!("This command changes the system and is refused in read-only mode");
+        std::process::exit(1);
+    }
 
     let result = match cli.command {
         Some(Commands::Start) => start(),
-        Some(Commands::Stop) => stop(false),
-        Some(Commands::Status) => status(),
+        Some(Commands::Stop { dry_run: false }) => stop(false),
+        Some(Commands::Stop { dry_run: true }) => stop_dry_run(),
+        Some(Commands::Status { json: false }) => status(),
+        Some(Commands::Status { json: true }) => status_json(),
+        Some(Commands::Handoff) => handoff(),
+        Some(Commands::AbsorbPartition { device, wipe }) => absorb_partition(&device, wipe),
+        Some(Commands::Health) => health(),
+        Some(Commands::Free) => free(),
+        Some(Commands::Stats { history, by_cgroup, days }) => stats(history, by_cgroup, days),
         Some(Commands::Autoconfig) => autoconfig(),
+        Some(Commands::CheckConfig) => check_config(),
+        Some(Commands::Explain { key }) => explain(key.as_deref()),
+        Some(Commands::Selftest { size, hold, settle }) => selftest(&size, hold, settle),
+        Some(Commands::LogLevel { action: None }) => log_level(None),
+        Some(Commands::LogLevel { action: Some(LogLevelAction::Set { level }) }) => log_level(Some(&level)),
+        Some(Commands::SimulateOom) => simulate_oom(),
+        Some(Commands::Latency) => latency(),
+        Some(Commands::SelftestWorker { size }) => {
+            systemd_swap::selftest::run_worker(size).map_err(|e| e.into())
+        }
+        Some(Commands::LatencyWorker { pages }) => {
+            systemd_swap::latency::run_worker(pages).map_err(|e| e.into())
+        }
         None => {
             // No subcommand provided, show help
             use clap::CommandFactory;
@@ -74,103 +230,16 @@ fn main() {
     }
 }
 
-/// Parse swap_mode from config
-fn get_swap_mode(config: &Config) -> SwapMode {
-    match config
-        .get("swap_mode")
-        .unwrap_or("auto")
-        .to_lowercase()
-        .as_str()
-    {
-        "zram+swapfc" | "zram_swapfc" => SwapMode::ZramSwapfc,
-        "zswap+swapfc" | "zswap" | "zswap+swapfile" | "zswap+loopfile" | "zswap_loopfile" => SwapMode::ZswapSwapfc,
-        "zram" | "zram_only" => SwapMode::ZramOnly,
-        "zram+swapfile" => SwapMode::ZramSwapfc,
-        "disabled" => SwapMode::Disabled,
-        "manual" => SwapMode::Manual,
-        _ => SwapMode::Auto,
-    }
-}
-
-/// Start a background thread that periodically logs zswap statistics.
-/// Useful for observing pool growth and compression ratio.
-fn start_zswap_monitor() {
-    use std::thread;
-    use std::time::Duration;
-    use systemd_swap::zswap;
-
-    thread::spawn(move || {
-        // Initial delay to let zswap settle
-        thread::sleep(Duration::from_secs(10));
-
-        let mut last_wb_pages: u64 = 0;
-        let mut last_pool_limit: u64 = 0;
-
-        loop {
-            match zswap::get_status() {
-                Some(status) => {
-                    status.log_summary();
-
-                    // Warn if zswap shrinker is writing back pages rapidly
-                    if status.written_back_pages > last_wb_pages + 1000 {
-                        info!(
-                            "Zswap: shrinker wrote {} pages to disk swap",
-                            status.written_back_pages - last_wb_pages
-                        );
-                    }
-                    last_wb_pages = status.written_back_pages;
-
-                    // Warn if pool limit is being hit repeatedly
-                    if status.pool_limit_hit > last_pool_limit {
-                        warn!(
-                            "Zswap: pool limit hit {} more time(s) - consider increasing max_pool_percent",
-                            status.pool_limit_hit - last_pool_limit
-                        );
-                    }
-                    last_pool_limit = status.pool_limit_hit;
-                }
-                None => {
-                    warn!("Zswap monitor: failed to read status");
-                }
-            }
-
-            thread::sleep(Duration::from_secs(30));
-        }
-    });
-}
-
-/// Disable zswap when using zram
-/// According to kernel documentation, zswap and zram should not be used together
-/// as both perform compression in RAM and can cause:
-/// - Double compression (waste of CPU)
-/// - LRU inversion issues
-/// - Unpredictable memory pressure behavior
-fn disable_zswap_for_zram() {
-    use systemd_swap::zswap;
-
-    if zswap::is_available() && zswap::is_enabled() {
-        info!("Disabling zswap (recommended when using zram)");
-        let zswap_enabled = "/sys/module/zswap/parameters/enabled";
-        if let Err(e) = std::fs::write(zswap_enabled, "0") {
-            warn!("Failed to disable zswap: {}", e);
-        } else {
-            info!("Zswap disabled successfully");
-        }
-    }
-}
-
-
-
 /// Start the swap daemon
 fn start() -> Result<(), Box<dyn std::error::Error>> {
     am_i_root()?;
 
-    // Detect system capabilities for autoconfig
-    let caps = SystemCapabilities::detect();
-    let recommended = RecommendedConfig::from_capabilities(&caps);
+    // Detect system capabilities for autoconfig (reused if a recent run cached them)
+    let caps = SystemCapabilities::detect_cached();
 
     // Clean up any previous instance
     let _ = stop(true);
+    handoff::complete();
 
     // Clean up legacy swapfc/swapfile path
     let legacy_path = Path::new("/swapfc/swapfile");
@@ -183,350 +252,217 @@ fn start() -> Result<(), Box<dyn std::error::Error>> {
         }
     }
 
-    // Initialize directories
-    makedirs(WORK_DIR)?;
-    makedirs(format!(
-        "{}/system/local-fs.target.wants",
-        systemd_swap::config::RUN_SYSD
-    ))?;
-    makedirs(format!(
-        "{}/system/swap.target.wants",
-        systemd_swap::config::RUN_SYSD
-    ))?;
+    let config = Config::load()?;
 
-    let mut config = Config::load()?;
-    let swap_mode = get_swap_mode(&config);
+    // stop() wiped WORK_DIR; the manager stores the detection again
+    let manager = SwapManager::standalone(config).with_capabilities(caps);
 
     // Register signal handlers once, before entering any mode
+    let handle = manager.handle();
     ctrlc::set_handler(move || {
-        request_shutdown();
+        handle.request_shutdown();
     })?;
 
-    // Apply autoconfig only in auto mode — for explicit modes, each subsystem
-    // uses its own fallback defaults from unwrap_or() calls.
-    if matches!(swap_mode, SwapMode::Auto) {
-        config.apply_autoconfig(&recommended);
-    }
+    manager.run()?;
+    Ok(())
+}
 
-    // Determine effective mode
-    let effective_mode = match swap_mode {
-        SwapMode::Auto => match recommended.swap_mode {
-            AutoSwapMode::ZramSwapfc => {
-                info!("Auto-detected: using zram + swapfc");
-                SwapMode::ZramSwapfc
-            }
-            AutoSwapMode::ZramOnly => {
-                info!("Auto-detected: using zram only");
-                SwapMode::ZramOnly
-            }
-        },
-        mode => mode,
-    };
+/// Stop the swap daemon
+fn stop(on_init: bool) -> Result<(), Box<dyn std::error::Error>> {
+    am_i_root()?;
 
-    match effective_mode {
-        SwapMode::ZramSwapfc => run_zram_swapfc(&config),
-        SwapMode::ZswapSwapfc => run_zswap_swapfc(&config),
-        SwapMode::ZramOnly => run_zram_only(&config),
-        SwapMode::Manual => run_manual(&config),
-        SwapMode::Disabled => {
-            info!("Swap management disabled, service will exit");
-            notify_ready();
-            Ok(())
+    if !on_init {
+        notify_stopping();
+        // Upgrade handoff: the next instance adopts everything as-is
+        if handoff::pending_for_stop() {
+            return Ok(());
         }
-        SwapMode::Auto => unreachable!("Auto mode should be resolved before this point"),
     }
-}
 
-/// ZramSwapfc: zram pool as primary + swapfc as overflow backing
-fn run_zram_swapfc(config: &Config) -> Result<(), Box<dyn std::error::Error>> {
-    // Desktop-optimized mode: zram pool for speed + swapfc for overflow
-    // zram is faster than zswap because it's a dedicated block device
-
-    // Disable zswap when using zram (per kernel documentation)
-    disable_zswap_for_zram();
-
-    // Start zram pool (primary high-priority swap)
-    info!("Setting up ZramPool as primary swap...");
-    let zram_ok = match systemd_swap::zram::ZramPool::new(config) {
-        Ok(mut pool) => match pool.start_primary() {
-            Ok(()) => {
-                // Run pool monitor in background thread (handles expansion/contraction)
-                std::thread::spawn(move || {
-                    if let Err(e) = pool.run_monitor() {
-                        warn!("ZramPool monitor error: {}", e);
-                    }
-                });
-                true
-            }
-            Err(e) => {
-                error!("ZramPool: start_primary failed: {}", e);
-                false
-            }
-        },
-        Err(e) => {
-            error!("ZramPool: init failed: {}", e);
-            false
-        }
-    };
+    let config = Config::load()?;
+    systemd_swap::exec::init(&config);
 
-    // Create swapfc for overflow (lower priority) - non-critical
-    info!("Setting up swapfc as secondary swap for overflow...");
-    match SwapFile::new(config) {
-        Ok(mut swapfc) => {
-            // Create initial swap file to prevent OOM when zram fills.
-            info!("Creating initial swap file for zram overflow protection...");
-            if let Err(e) = swapfc.create_initial_swap() {
-                warn!(
-                    "Initial swapfile creation failed: {} (will retry on demand)",
-                    e
-                );
-            }
-            if let Err(e) = swapfc.run() {
-                warn!("Swapfile monitor exited: {}", e);
-            }
-        }
-        Err(e) => {
-            if zram_ok {
-                warn!("Swapfile setup failed, continuing with zram only: {}", e);
-                notify_ready();
-                loop {
-                    std::thread::sleep(std::time::Duration::from_secs(60));
-                    if systemd_swap::is_shutdown() {
-                        break;
-                    }
-                }
-            } else {
-                error!("Both zram and swapfile failed");
-                return Err(e.into());
-            }
-        }
+    if !on_init {
+        let mode = DaemonState::load().map(|d| d.mode).unwrap_or_default();
+        systemd_swap::hooks::run_before_stop(&config, &mode);
     }
-    Ok(())
-}
 
-/// ZswapSwapfc: create swapfile first (zswap needs a backing swap device), then enable zswap
-fn run_zswap_swapfc(config: &Config) -> Result<(), Box<dyn std::error::Error>> {
-    match SwapFile::new(config) {
-        Ok(mut swapfc) => {
-            swapfc.enable_zswap_mode();
-            info!("Creating initial swap file for zswap backing...");
-            swapfc.create_initial_swap()?;
-
-            // Now configure zswap (after swap is available) - non-critical
-            match systemd_swap::zswap::start(config) {
-                Ok(backup) => {
-                    let zswap_backup = Some(backup);
-                    save_zswap_backup(&zswap_backup)?;
-                }
-                Err(e) => {
-                    warn!("Zswap setup failed, continuing with swapfile only: {}", e);
-                }
-            }
+    // Units, devices, saved settings and files are collected first so that
+    // `stop --dry-run` shows exactly this. On init (on_init=true), swap stays
+    // active and swap files are kept: adopt_existing_swapfiles() will reuse
+    // them and ZramPool will adopt existing zram devices. Doing swapoff under
+    // memory pressure causes OOM on low-RAM systems.
+    StopPlan::build(&config, on_init).execute();
 
-            start_zswap_monitor();
-            swapfc.run()?;
-        }
-        Err(e) => {
-            error!("Swapfile setup failed (required for zswap backing): {}", e);
-            return Err(e.into());
-        }
-    }
     Ok(())
 }
 
-/// ZramOnly: zram pool only, no swap files
-fn run_zram_only(config: &Config) -> Result<(), Box<dyn std::error::Error>> {
-    disable_zswap_for_zram();
-
-    match systemd_swap::zram::ZramPool::new(config) {
-        Ok(mut pool) => {
-            if let Err(e) = pool.start_primary() {
-                error!("ZramPool: {}", e);
-            }
-            notify_ready();
-            info!("ZramPool setup complete");
-
-            if let Err(e) = pool.run_monitor() {
-                warn!("ZramPool monitor error: {}", e);
-            }
-        }
-        Err(e) => {
-            error!("ZramPool: {}", e);
-            notify_ready();
-            loop {
-                std::thread::sleep(std::time::Duration::from_secs(60));
-                if systemd_swap::is_shutdown() {
-                    break;
-                }
-            }
-        }
+/// Show what `stop` would do without doing it
+fn stop_dry_run() -> Result<(), Box<dyn std::error::Error>> {
+    let config = Config::load()?;
+    if handoff::is_pending() {
+        println!("Note: an upgrade handoff is pending; stop would keep all swap active\n");
     }
+    print!("{}", StopPlan::build(&config, false).describe());
     Ok(())
 }
 
-/// Manual mode: legacy mode driven by explicit config flags
-fn run_manual(config: &Config) -> Result<(), Box<dyn std::error::Error>> {
-    warn!("Manual mode: using explicit config flags (zram_enabled, zswap_enabled, swapfc_enabled)");
-
-    if config.get_bool("zswap_enabled") {
-        match systemd_swap::zswap::start(config) {
-            Ok(backup) => {
-                let zswap_backup = Some(backup);
-                save_zswap_backup(&zswap_backup)?;
-            }
-            Err(e) => error!("Zswap: {}", e),
-        }
-    }
-
-    if config.get_bool("zram_enabled") {
-        if !config.get_bool("zswap_enabled") {
-            disable_zswap_for_zram();
-        }
-        if let Err(e) = systemd_swap::zram::start(config) {
-            error!("Zram: {}", e);
-        }
-    }
-
-    if config.get_bool("swapfile_enabled") {
-        let mut swapfc = SwapFile::new(config)?;
-        swapfc.create_initial_swap()?;
-        swapfc.run()?;
-    } else {
-        notify_ready();
-        info!("Manual mode swap setup complete");
-        loop {
-            std::thread::sleep(std::time::Duration::from_secs(60));
-            if systemd_swap::is_shutdown() {
-                break;
-            }
-        }
-    }
-    Ok(())
+/// One-line health summary with a monitoring-friendly exit code
+fn health() -> Result<(), Box<dyn std::error::Error>> {
+    let report = HealthReport::evaluate(&HealthInputs::read());
+    println!("{}", report.line());
+    std::process::exit(report.health.exit_code());
 }
 
-/// Save zswap backup for later restoration
-fn save_zswap_backup(backup: &Option<ZswapBackup>) -> Result<(), Box<dyn std::error::Error>> {
-    if let Some(ref backup) = backup {
-        let backup_path = format!("{}/zswap_backup", WORK_DIR);
-        makedirs(&backup_path)?;
-        for (path, value) in &backup.parameters {
-            let filename = Path::new(path).file_name().unwrap_or_default();
-            let save_path = format!("{}/{}", backup_path, filename.to_string_lossy());
-            fs::write(&save_path, format!("{}={}", path, value))?;
-        }
-    }
+/// Restart the daemon on the installed binary, keeping all swap active
+fn handoff() -> Result<(), Box<dyn std::error::Error>> {
+    am_i_root()?;
+    let handoff = handoff::request()?;
+    println!(
+        "Handed off {} → {} ({} swap device(s) kept active)",
+        handoff.from_version,
+        handoff.to_version,
+        handoff.devices.len()
+    );
     Ok(())
 }
 
-/// Stop the swap daemon
-fn stop(on_init: bool) -> Result<(), Box<dyn std::error::Error>> {
+/// Move a swap partition's contents into managed swap and retire it
+fn absorb_partition(device: &str, wipe: bool) -> Result<(), Box<dyn std::error::Error>> {
     am_i_root()?;
-
-    if !on_init {
-        notify_stopping();
-    }
-
-    let config = Config::load()?;
-
-    // Stop all managed swap units (check both swapfile and legacy swapfc names).
-    // On init (on_init=true), skip swapfile units: adopt_existing_swapfiles() will
-    // take ownership of them without swapping them off under memory pressure.
-    for subsystem in ["swapfile", "swapfc", "zram"] {
-        if on_init {
-            // On init, skip ALL subsystems: adopt_existing_swapfiles() will
-            // reuse swapfiles, and ZramPool will adopt existing zram devices.
-            // Doing swapoff under memory pressure causes OOM on low-RAM systems.
-            continue;
-        }
-        for unit_path in find_swap_units() {
-            if let Ok(content) = read_file(&unit_path) {
-                if content.to_lowercase().contains(subsystem) {
-                    if let Some(dev) = get_what_from_swap_unit(&unit_path) {
-                        info!("{}: swapoff {}", subsystem, dev);
-                        let _ = swapoff(&dev);
-                        force_remove(&unit_path, true);
-
-                        if subsystem == "swapfile" && dev.starts_with("/dev/loop") {
-                            // Detach the loop device after swapoff to prevent it from
-                            // persisting with a "(deleted)" backing file reference.
-                            let _ = std::process::Command::new("losetup")
-                                .args(["-d", &dev])
-                                .status();
-                        } else if subsystem == "swapfile" && Path::new(&dev).is_file() {
-                            force_remove(&dev, true);
-                        } else if subsystem == "zram" {
-                            let _ = systemd_swap::zram::release(&dev);
-                        }
-                    }
-                }
-            }
+    let report = systemd_swap::absorb::absorb_partition(device, wipe)?;
+    println!(
+        "Absorbed {}: {} moved to managed swap{}",
+        report.device,
+        format_size(report.drained),
+        if report.wiped { ", swap signature removed" } else { "" }
+    );
+    if report.fstab_lines.is_empty() {
+        println!("\nNo /etc/fstab entry refers to it. If it was activated automatically");
+        println!("(GPT swap partition), keep it off at boot with:");
+        println!("  systemctl mask {}", report.unit);
+    } else {
+        println!("\nRemove or comment out these /etc/fstab lines so it is not activated at boot:");
+        for line in &report.fstab_lines {
+            println!("  {}", line);
         }
     }
-
-    // Restore zswap parameters
-    let backup_path = format!("{}/zswap_backup", WORK_DIR);
-    if Path::new(&backup_path).is_dir() {
-        info!("Zswap: restore configuration: start");
-        if let Ok(entries) = fs::read_dir(&backup_path) {
-            for entry in entries.flatten() {
-                if let Ok(content) = fs::read_to_string(entry.path()) {
-                    if let Some((path, value)) = content.split_once('=') {
-                        if let Err(e) = fs::write(path, value) {
-                            warn!("Failed to restore {}: {}", path, e);
-                        }
-                    }
-                }
-            }
-        }
-        info!("Zswap: restore configuration: complete");
+    if !report.wiped {
+        println!("\nThe partition still holds a swap signature; rerun with --wipe or reuse it");
+        println!("(e.g. mkfs) once you no longer need it as a fallback.");
     }
+    Ok(())
+}
 
-    // Remove work directory
-    info!("Removing working directory...");
-    let _ = fs::remove_dir_all(WORK_DIR);
+/// Active swap file from `swapon`, as listed in status
+struct SwapEntry {
+    name: String,
+    size: u64,
+    used: u64,
+    prio: i32,
+}
 
-    // Remove swap files (check both current and legacy paths).
-    // Skip during on_init: adopt_existing_swapfiles() will reuse them.
-    if !on_init {
-        let swapfile_path = config.get("swapfile_path").unwrap_or(defaults::SWAPFILE_PATH);
-        info!("Removing files in {}...", swapfile_path);
-        if let Ok(entries) = fs::read_dir(swapfile_path) {
-            for entry in entries.flatten() {
-                force_remove(entry.path(), true);
-            }
-        }
-        // Also clean legacy path
-        let legacy_swapfc_path = config.get("swapfc_path").unwrap_or("/swapfc/swapfile");
-        if legacy_swapfc_path != swapfile_path {
-            if let Ok(entries) = fs::read_dir(legacy_swapfc_path) {
-                for entry in entries.flatten() {
-                    force_remove(entry.path(), true);
+/// Swap files and loop devices from swapon
+fn swapon_files() -> Vec<SwapEntry> {
+    let mut files = Vec::new();
+    if let Ok(output) = Command::new("swapon")
+        .args(["--raw", "--noheadings", "--bytes"])
+        .stdout(Stdio::piped())
+        .output_timed()
+    {
+        for line in String::from_utf8_lossy(&output.stdout).lines() {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() >= 4 {
+                let name = fields[0];
+                if name.contains("loop") || name.contains("swapfile") || name.starts_with("/swapfile/") {
+                    files.push(SwapEntry {
+                        name: name.to_string(),
+                        size: fields[2].parse().unwrap_or(0),
+                        used: fields[3].parse().unwrap_or(0),
+                        prio: fields.get(4).and_then(|p| p.parse().ok()).unwrap_or(0),
+                    });
                 }
             }
         }
     }
+    files
+}
 
-    Ok(())
+/// Actual disk usage (sparse/NOCOW files: real blocks, not apparent size)
+fn disk_usage(path: &str) -> Option<u64> {
+    Command::new("du")
+        .args(["-s", "--block-size=1", path])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .output_timed()
+        .ok()
+        .and_then(|out| {
+            String::from_utf8_lossy(&out.stdout)
+                .split_whitespace()
+                .next()
+                .and_then(|s| s.parse::<u64>().ok())
+        })
 }
 
-/// Format bytes as human-readable size
-fn format_size(bytes: u64) -> String {
-    const KIB: u64 = 1024;
-    const MIB: u64 = 1024 * KIB;
-    const GIB: u64 = 1024 * MIB;
-    if bytes >= GIB {
-        format!("{:.1} GiB", bytes as f64 / GIB as f64)
-    } else if bytes >= MIB {
-        format!("{:.1} MiB", bytes as f64 / MIB as f64)
-    } else if bytes >= KIB {
-        format!("{:.0} KiB", bytes as f64 / KIB as f64)
-    } else {
-        format!("{} B", bytes)
+/// What the daemon last wrote to WORK_DIR, read as one snapshot
+struct DaemonFiles {
+    daemon: Option<DaemonState>,
+    zram: Option<ZramStats>,
+    diverged: Vec<(String, String)>,
+    algorithm: Option<String>,
+    pacing: Option<SwapFilePacing>,
+}
+
+impl DaemonFiles {
+    fn read() -> Self {
+        systemd_swap::state::read_consistent(|| Self {
+            daemon: DaemonState::load(),
+            zram: systemd_swap::zram::get_zram_stats(),
+            diverged: systemd_swap::zram::algorithm_divergence(),
+            algorithm: systemd_swap::zram::pool_algorithm(),
+            pacing: SwapFilePacing::load(),
+        })
     }
 }
 
 /// Show swap status
 fn status() -> Result<(), Box<dyn std::error::Error>> {
+    let config = Config::load().ok();
+    let swapfile_path = config
+        .as_ref()
+        .and_then(|c| c.get("swapfile_path").ok().map(|s| s.to_string()))
+        .unwrap_or_else(|| defaults::SWAPFILE_PATH.to_string());
+    let sparse_loop = config.as_ref().is_some_and(|c| c.get_bool("swapfile_sparse_loop"));
+
+    // Sources that run commands or may block on a struggling system are
+    // collected in parallel, each with a timeout
+    let zswap_pending = collect::spawn("Zswap", systemd_swap::zswap::get_status);
+    let files_pending = collect::spawn("Swap files (swapon)", swapon_files);
+    let du_pending = {
+        let path = swapfile_path.clone();
+        collect::spawn("Disk usage (du)", move || disk_usage(&path))
+    };
+    let storage_pending = {
+        let path = swapfile_path.clone();
+        collect::spawn("Storage type (findmnt)", move || StorageType::detect(&path))
+    };
+    let shared_disks_pending = {
+        let path = swapfile_path.clone();
+        collect::spawn("Shared disks (findmnt)", move || systemd_swap::disks::shared_areas(Path::new(&path)))
+    };
+    // Only loop-backed files are aligned
+    let alignment_pending = sparse_loop.then(|| {
+        let path = swapfile_path.clone();
+        collect::spawn("Alignment (findmnt)", move || {
+            let alignment = Alignment::detect(Path::new(&path));
+            let misaligned = alignment.misaligned_files(Path::new(&path));
+            (alignment, misaligned)
+        })
+    });
+    let oomd_pending = collect::spawn("systemd-oomd (systemctl)", OomdStatus::detect);
+    let hibernation_pending = collect::spawn("Hibernation", HibernationStatus::detect);
+    let tiers_pending = collect::spawn("Tiers (findmnt)", || tiers::classify(systemd_swap::absorb::read_swaps()));
+
     let swap_stats = get_mem_stats(&["SwapTotal", "SwapFree"])?;
     let swap_total = swap_stats["SwapTotal"];
     let swap_free = swap_stats["SwapFree"];
@@ -534,11 +470,46 @@ fn status() -> Result<(), Box<dyn std::error::Error>> {
 
     // Collect zswap usage once (used in both Zswap and Swap sections)
     let swap_usage = systemd_swap::meminfo::get_effective_swap_usage().ok();
+    let shared = DaemonFiles::read();
+
+    // --- Daemon ---
+    match &shared.daemon {
+        Some(daemon) => {
+            println!("systemd-swap {} ({} mode)", daemon.version, daemon.mode);
+            println!(
+                "  Running for:   {} (pid {})",
+                format_duration(daemon.uptime_secs()),
+                daemon.pid
+            );
+            if let Ok(level) = systemd_swap::control::request("log-level") {
+                println!("  Log level:     {}", level);
+            }
+            for ticks in TickSummary::load_all() {
+                println!(
+                    "  {:<14} p50 {}ms, p95 {}ms, max {}ms over {} ticks{}",
+                    format!("{} ticks:", ticks.loop_name),
+                    ticks.p50_ms,
+                    ticks.p95_ms,
+                    ticks.max_ms,
+                    ticks.ticks,
+                    if ticks.overruns > 0 {
+                        format!(", {} over the check interval", ticks.overruns)
+                    } else {
+                        String::new()
+                    }
+                );
+            }
+        }
+        None => println!("systemd-swap: not running"),
+    }
 
     // --- Zswap ---
-    if let Some(zswap) = systemd_swap::zswap::get_status() {
-        if zswap.enabled {
-            println!("Zswap ({}):", zswap.compressor);
+    let zswap_name = zswap_pending.name;
+    let zswap_status = zswap_pending.wait();
+    match &zswap_status {
+        None => println!("\n{}", collect::unavailable(zswap_name)),
+        Some(Some(zswap)) if zswap.enabled => {
+            println!("\nZswap ({}):", zswap.compressor);
             println!("  Pool limit:    {}% of RAM", zswap.max_pool_percent);
             if let Some(ref usage) = swap_usage {
                 if usage.zswap_active {
@@ -552,15 +523,42 @@ fn status() -> Result<(), Box<dyn std::error::Error>> {
                     println!("  Stored data:   {} → {} compressed ({:.1}x ratio)",
                         format_size(original), format_size(compressed), ratio);
                     println!("  Pool fill:     {}%", usage.zswap_pool_percent);
+                    println!(
+                        "  Swap slots:    {}% free, {}% of allocated held by zswap{}",
+                        usage.slot_free_percent(),
+                        usage.zswap_slot_share(),
+                        if usage.slots_exhausted() { " (exhausted - expanding swap)" } else { "" }
+                    );
                 } else {
                     println!("  Pool:          empty");
                 }
             }
+            if let Some(counters) = zswap.counters() {
+                // Deltas tell whether problems are ongoing or left over from before start
+                let since = ZswapCounters::load_baseline().map(|b| counters.since(&b));
+                let delta = |f: fn(&ZswapCounters) -> u64| {
+                    since
+                        .as_ref()
+                        .map(|d| format!(" ({} since start)", f(d)))
+                        .unwrap_or_default()
+                };
+                println!("  Written back:  {} pages{}", counters.written_back_pages, delta(|c| c.written_back_pages));
+                println!(
+                    "  Rejects:       {} (compress fail {}, poor {}, reclaim fail {}){}",
+                    counters.rejects(),
+                    counters.reject_compress_fail,
+                    counters.reject_compress_poor,
+                    counters.reject_reclaim_fail,
+                    delta(ZswapCounters::rejects)
+                );
+                println!("  Limit hits:    {}{}", counters.pool_limit_hit, delta(|c| c.pool_limit_hit));
+            }
         }
+        _ => {}
     }
 
     // --- Zram ---
-    if let Some(stats) = systemd_swap::zram::get_zram_stats() {
+    if let Some(stats) = &shared.zram {
         if stats.orig_data_size > 0 {
             println!("\nZram:");
             println!("  Capacity:      {}", format_size(stats.disksize));
@@ -568,59 +566,170 @@ fn status() -> Result<(), Box<dyn std::error::Error>> {
                 format_size(stats.orig_data_size), format_size(stats.mem_used_total),
                 stats.compression_ratio());
             println!("  Utilization:   {}%", stats.memory_utilization());
+            let page_size = systemd_swap::meminfo::get_page_size();
+            if stats.huge_pages > 0 {
+                println!("  Huge pages:    {} (incompressible)", format_size(stats.huge_pages * page_size));
+            }
+            if stats.bd_writes > 0 {
+                println!("  Written back:  {} now on backing device, {} total",
+                    format_size(stats.bd_count * page_size), format_size(stats.bd_writes * page_size));
+            }
+            if stats.io.failures() > 0 {
+                println!("  I/O failures:  {} failed reads, {} failed writes, {} invalid (memory corruption or driver bug)",
+                    stats.io.failed_reads, stats.io.failed_writes, stats.io.invalid_io);
+            }
+        }
+        if !shared.diverged.is_empty() {
+            let devices: Vec<String> = shared.diverged.iter().map(|(dev, alg)| format!("{} {}", dev, alg)).collect();
+            println!(
+                "  Algorithm:     {} configured, but {} (ratio mixes algorithms; zram_recreate_diverged=1 converges)",
+                shared.algorithm.as_deref().unwrap_or_default(),
+                devices.join(", ")
+            );
         }
     }
 
-    // Parse swapon for individual file details (needed early for du calculation)
-    struct SwapEntry {
-        name: String,
-        size: u64,
-        used: u64,
+    let files_name = files_pending.name;
+    let files = files_pending.wait();
+    let files_timed_out = files.is_none();
+    let mut files = files.unwrap_or_default();
+    // Kernel overflow order: highest priority first
+    files.sort_by_key(|f| std::cmp::Reverse(f.prio));
+
+    let du_name = du_pending.name;
+    let disk_used = du_pending.wait();
+    let du_timed_out = disk_used.is_none();
+    let disk_used = disk_used.flatten().filter(|_| !files.is_empty());
+
+    // --- OOM kills ---
+    if let Some(kills) = systemd_swap::events::oom_kill_count().filter(|&k| k > 0) {
+        let events = systemd_swap::events::load();
+        let last_oom = events.iter().rev().find(|e| e.kind == EventKind::OomKill);
+        println!("\nOOM kills:");
+        println!("  Since boot:    {}", kills);
+        if let Some(event) = last_oom {
+            let ago = now_secs().saturating_sub(event.timestamp);
+            let swap_used: u64 = event.get("swap_used").and_then(|v| v.parse().ok()).unwrap_or(0);
+            let swap_total: u64 = event.get("swap_total").and_then(|v| v.parse().ok()).unwrap_or(0);
+            let available: u64 = event.get("mem_available").and_then(|v| v.parse().ok()).unwrap_or(0);
+            println!(
+                "  Last:          {}m ago — swap {} of {} used, {} RAM available",
+                ago / 60,
+                format_size(swap_used),
+                format_size(swap_total),
+                format_size(available)
+            );
+        } else {
+            println!("  Last:          before systemd-swap started");
+        }
     }
 
-    let mut files: Vec<SwapEntry> = Vec::new();
+    // --- systemd-oomd ---
+    let oomd_name = oomd_pending.name;
+    let oomd = oomd_pending.wait();
+    if oomd.is_none() {
+        println!("\n{}", collect::unavailable(oomd_name));
+    }
+    if let Some(oomd) = oomd.filter(|o| o.running) {
+        println!("\nsystemd-oomd:");
+        if oomd.swap_kill_active() {
+            println!(
+                "  Swap kill:     at {}% swap used ({})",
+                oomd.swap_used_limit,
+                oomd.swap_kill_units.join(", ")
+            );
+        } else {
+            println!("  Swap kill:     off (no slice has ManagedOOMSwap=kill)");
+        }
+        println!(
+            "  Pressure kill: {} for {} (ManagedOOMMemoryPressure=kill units)",
+            oomd.pressure_limit, oomd.pressure_duration
+        );
+        if oomd.swap_kill_active() {
+            let config = Config::load().ok();
+            let aligned = config.as_ref().is_some_and(|c| c.get_bool("oomd_align"));
+            let free_swap_perc = config
+                .and_then(|c| c.get_as::<u8>("swapfile_free_swap_perc").ok())
+                .unwrap_or(defaults::SWAPFILE_FREE_SWAP_PERC);
+            let free_swap_perc = if aligned {
+                oomd.aligned_free_swap_perc(free_swap_perc)
+            } else {
+                free_swap_perc
+            };
+            let expand_at = 100u8.saturating_sub(free_swap_perc);
+            let gap = oomd.swap_used_limit.saturating_sub(expand_at);
+            println!(
+                "  Swap files:    expand at {}% used, {} points before oomd{}",
+                expand_at,
+                gap,
+                if aligned { " (oomd_align)" } else { "" }
+            );
+            if gap < systemd_swap::oomd::OOMD_HEADROOM {
+                println!("  Hint:          set oomd_align=1 so swap files expand before oomd kills");
+            }
+        }
+    }
 
-    if let Ok(output) = Command::new("swapon")
-        .args(["--raw", "--noheadings", "--bytes"])
-        .stdout(Stdio::piped())
-        .output()
-    {
-        for line in String::from_utf8_lossy(&output.stdout).lines() {
-            let fields: Vec<&str> = line.split_whitespace().collect();
-            if fields.len() >= 4 {
-                let name = fields[0];
-                if name.contains("loop") || name.contains("swapfile") || name.starts_with("/swapfile/") {
-                    files.push(SwapEntry {
-                        name: name.to_string(),
-                        size: fields[2].parse().unwrap_or(0),
-                        used: fields[3].parse().unwrap_or(0),
-                    });
+    // --- Kernel tuning ---
+    if let Some(before) = sysctl::backup_value(sysctl::MIN_FREE_KBYTES) {
+        let after = sysctl::read(sysctl::MIN_FREE_KBYTES).unwrap_or_default();
+        println!("\nKernel tuning:");
+        println!("  min_free_kbytes: {} → {} (auto)", before, after);
+    }
+
+    // --- Hibernation ---
+    let hibernation_name = hibernation_pending.name;
+    let hibernation = hibernation_pending.wait();
+    if hibernation.is_none() {
+        println!("\n{}", collect::unavailable(hibernation_name));
+    }
+    if let Some(hibernation) = hibernation.flatten() {
+        println!("\nHibernation:");
+        match &hibernation.area {
+            Some(area) => println!(
+                "  Resume area:   {} ({} free of {})",
+                area.path,
+                format_size(area.free()),
+                format_size(area.size)
+            ),
+            None => println!("  Resume area:   device {} not active", hibernation.resume_device),
+        }
+        println!(
+            "  Image size:    {}{}",
+            format_size(hibernation.image_size),
+            if systemd_swap::hibernate::backup_value().is_some() { " (set by systemd-swap)" } else { "" }
+        );
+        if let Some(warning) = hibernation.warning() {
+            println!("  Warning:       {}", warning);
+        }
+    }
+
+    // --- Tiers ---
+    let tiers_name = tiers_pending.name;
+    match tiers_pending.wait() {
+        None => println!("\n{}", collect::unavailable(tiers_name)),
+        Some(areas) => {
+            let zswap_tier = zswap_tier(zswap_status.flatten().as_ref(), swap_usage.as_ref());
+            let usage = tiers::summarize(&areas, zswap_tier);
+            if !usage.is_empty() {
+                println!("\nTiers (filled in this order):");
+                for tier in &usage {
+                    println!("  {}", format_tier(tier));
+                }
+                for note in tiers::misordered(&areas) {
+                    println!("  Order:         {}", note);
+                }
+                let external = systemd_swap::swapwatch::external(areas.iter().map(|(_, a)| a.clone()).collect());
+                for (tier, area) in areas.iter().filter(|(_, a)| external.iter().any(|e| e.path == a.path)) {
+                    println!("  External:      {}", systemd_swap::swapwatch::describe(*tier, area));
                 }
             }
         }
     }
-
-    // Actual disk usage (sparse/NOCOW files: real blocks, not apparent size)
-    let disk_used = if !files.is_empty() {
-        let swapfile_path = Config::load()
-            .ok()
-            .and_then(|c| c.get("swapfile_path").ok().map(|s| s.to_string()))
-            .unwrap_or_else(|| defaults::SWAPFILE_PATH.to_string());
-        Command::new("du")
-            .args(["-s", "--block-size=1", &swapfile_path])
-            .stdout(Stdio::piped())
-            .stderr(Stdio::null())
-            .output()
-            .ok()
-            .and_then(|out| {
-                String::from_utf8_lossy(&out.stdout)
-                    .split_whitespace()
-                    .next()
-                    .and_then(|s| s.parse::<u64>().ok())
-            })
-    } else {
-        None
-    };
+    if let Ok(config) = Config::load() {
+        let path = systemd_swap::latency::latency_file(&config);
+        print_latencies(&systemd_swap::latency::Latencies::load(&path));
+    }
 
     // --- Swap ---
     println!("\nSwap:");
@@ -652,19 +761,71 @@ fn status() -> Result<(), Box<dyn std::error::Error>> {
         }
         if du_bytes > 0 && swap_used > 0 {
             println!("  On disk:       {}", format_size(du_bytes));
+        } else if du_timed_out && !files.is_empty() {
+            println!("  On disk:       {}", collect::unavailable(du_name));
         }
 
+        // Rate limiting of the swap file monitor
+        if let Some(pacing) = &shared.pacing {
+            let wait = |secs: u64| {
+                if secs == 0 {
+                    "now".to_string()
+                } else {
+                    format!("in {}s", secs)
+                }
+            };
+            println!(
+                "  Pacing:        next allowed creation {} (cooldown {}s)",
+                wait(pacing.creation_in),
+                pacing.cooldown_secs
+            );
+            println!(
+                "                 emergency creation {}, removal {}",
+                wait(pacing.emergency_in),
+                wait(pacing.removal_in)
+            );
+            if pacing.fs_busy {
+                println!("                 expansion deferred: btrfs balance/resize running");
+            }
+        }
+        for area in shared_disks_pending.wait().unwrap_or_default() {
+            let note = if area.is_slow() { ", files only in emergencies" } else { "" };
+            println!("  Shared disk:   {}{}", area, note);
+        }
+
+        if files_timed_out {
+            println!("\n  {}", collect::unavailable(files_name));
+        }
         if !files.is_empty() {
             let file_total: u64 = files.iter().map(|f| f.size).sum();
             println!("\n  Swap files:    {} ({} capacity)", files.len(), format_size(file_total));
+            println!(
+                "  Storage:       {} (files listed in overflow order)",
+                storage_pending
+                    .wait()
+                    .map(StorageType::name)
+                    .unwrap_or("unknown (findmnt did not answer)")
+            );
+            if let Some(pending) = alignment_pending {
+                match pending.wait() {
+                    Some((alignment, misaligned)) => {
+                        println!("  Alignment:     {}", alignment);
+                        if !misaligned.is_empty() {
+                            let names: Vec<String> = misaligned.iter().map(|p| p.display().to_string()).collect();
+                            println!("                 not aligned: {}", names.join(", "));
+                        }
+                    }
+                    None => println!("  Alignment:     unknown (findmnt did not answer)"),
+                }
+            }
 
             // Individual file list
             println!();
-            println!("  {:<24} {:>12} {:>12}", "Device", "Size", "Used");
-            println!("  {}", "-".repeat(50));
+            println!("  {:<24} {:>12} {:>12} {:>7}", "Device", "Size", "Used", "Prio");
+            println!("  {}", "-".repeat(58));
             for f in &files {
-                println!("  {:<24} {:>12} {:>12}",
-                    f.name, format_size(f.size), format_size(f.used));
+                println!("  {:<24} {:>12} {:>12} {:>7}",
+                    f.name, format_size(f.size), format_size(f.used), f.prio);
             }
         }
     } else {
@@ -674,15 +835,321 @@ fn status() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+/// Pool limit and pool size of zswap as tier T1, while it is enabled
+fn zswap_tier(
+    zswap: Option<&systemd_swap::zswap::ZswapStatus>,
+    usage: Option<&systemd_swap::meminfo::EffectiveSwapUsage>,
+) -> Option<(u64, u64)> {
+    let zswap = zswap.filter(|z| z.enabled)?;
+    let ram = get_mem_stats(&["MemTotal"]).ok()?["MemTotal"];
+    let pool = usage.filter(|u| u.zswap_active).map_or(0, |u| u.zswap_pool_bytes);
+    Some((ram * zswap.max_pool_percent as u64 / 100, pool))
+}
+
+/// One line of the tier view: capacity, use and the priorities of its areas
+fn format_tier(tier: &TierUsage) -> String {
+    let areas = match tier.priorities {
+        Some((low, high)) => format!(
+            " ({} area{}, prio {})",
+            tier.areas,
+            if tier.areas == 1 { "" } else { "s" },
+            if low == high { low.to_string() } else { format!("{}..{}", low, high) }
+        ),
+        None => " (pool limit in RAM)".to_string(),
+    };
+    format!(
+        "{} {:<19} {:>10}, {} used{}",
+        tier.tier.label(),
+        tier.tier.name(),
+        format_size(tier.capacity),
+        format_size(tier.used),
+        areas
+    )
+}
+
+/// Print a JSON snapshot of the daemon and swap state
+fn status_json() -> Result<(), Box<dyn std::error::Error>> {
+    use systemd_swap::helpers::json_string;
+
+    let opt = |v: Option<String>| v.unwrap_or_else(|| "null".to_string());
+
+    // Same timeouts as status; sources that do not answer are listed in "unavailable"
+    let zswap_pending = collect::spawn("zswap", systemd_swap::zswap::get_status);
+    let oomd_pending = collect::spawn("oomd", OomdStatus::detect);
+    let hibernation_pending = collect::spawn("hibernation", HibernationStatus::detect);
+    let tiers_pending = collect::spawn("tiers", || tiers::classify(systemd_swap::absorb::read_swaps()));
+    let mut unavailable = Vec::new();
+    let shared = DaemonFiles::read();
+
+    let daemon = shared.daemon.as_ref().map(|d| {
+        format!(
+            "{{\"version\":{},\"pid\":{},\"mode\":{},\"started\":{},\"uptime_secs\":{},\"log_level\":{}}}",
+            json_string(&d.version),
+            d.pid,
+            json_string(&d.mode),
+            d.started,
+            d.uptime_secs(),
+            opt(systemd_swap::control::request("log-level").ok().map(|l| json_string(&l)))
+        )
+    });
+
+    let mem = get_mem_stats(&["SwapTotal", "SwapFree"])?;
+    let swap = format!(
+        "{{\"total\":{},\"used\":{}}}",
+        mem["SwapTotal"],
+        mem["SwapTotal"].saturating_sub(mem["SwapFree"])
+    );
+
+    let zram = shared.zram.as_ref().map(|z| {
+        let diverged: Vec<String> = shared
+            .diverged
+            .iter()
+            .map(|(dev, alg)| format!("{{\"device\":{},\"algorithm\":{}}}", json_string(dev), json_string(alg)))
+            .collect();
+        format!(
+            "{{\"disksize\":{},\"stored\":{},\"compressed\":{},\"mem_used\":{},\"huge_pages\":{},\"bd_count\":{},\"bd_writes\":{},\"failed_reads\":{},\"failed_writes\":{},\"invalid_io\":{},\"notify_free\":{},\"algorithm\":{},\"diverged\":[{}]}}",
+            z.disksize,
+            z.orig_data_size,
+            z.compr_data_size,
+            z.mem_used_total,
+            z.huge_pages,
+            z.bd_count,
+            z.bd_writes,
+            z.io.failed_reads,
+            z.io.failed_writes,
+            z.io.invalid_io,
+            z.io.notify_free,
+            opt(shared.algorithm.as_deref().map(json_string)),
+            diverged.join(",")
+        )
+    });
+
+    let counters_json = |c: &ZswapCounters| {
+        format!(
+            "{{\"written_back_pages\":{},\"rejects\":{},\"pool_limit_hit\":{}}}",
+            c.written_back_pages,
+            c.rejects(),
+            c.pool_limit_hit
+        )
+    };
+    let zswap_status = zswap_pending.wait_or_record(&mut unavailable).flatten();
+    let zswap_counters = zswap_status.as_ref().and_then(|z| z.counters());
+    let swap_usage = systemd_swap::meminfo::get_effective_swap_usage().ok();
+    let zswap = swap_usage
+        .as_ref()
+        .filter(|u| u.zswap_active)
+        .map(|u| {
+            let since_start = zswap_counters
+                .zip(ZswapCounters::load_baseline())
+                .map(|(c, b)| counters_json(&c.since(&b)));
+            format!(
+                "{{\"stored\":{},\"pool\":{},\"pool_percent\":{},\"counters\":{},\"since_start\":{}}}",
+                u.zswapped_original_bytes,
+                u.zswap_pool_bytes,
+                u.zswap_pool_percent,
+                opt(zswap_counters.as_ref().map(counters_json)),
+                opt(since_start)
+            )
+        });
+
+    let pacing = shared.pacing.as_ref().map(|p| {
+        format!(
+            "{{\"cooldown_secs\":{},\"creation_in\":{},\"emergency_in\":{},\"removal_in\":{}}}",
+            p.cooldown_secs, p.creation_in, p.emergency_in, p.removal_in
+        )
+    });
+
+    let oom_kills = systemd_swap::events::oom_kill_count().map(|k| k.to_string());
+
+    let oomd = oomd_pending.wait_or_record(&mut unavailable).filter(|o| o.running).map(|o| {
+        let units: Vec<String> = o.swap_kill_units.iter().map(|u| json_string(u)).collect();
+        format!(
+            "{{\"swap_used_limit\":{},\"swap_kill_units\":[{}],\"pressure_limit\":{},\"pressure_duration\":{}}}",
+            o.swap_used_limit,
+            units.join(","),
+            json_string(&o.pressure_limit),
+            json_string(&o.pressure_duration)
+        )
+    });
+
+    let hibernation = hibernation_pending.wait_or_record(&mut unavailable).flatten().map(|h| {
+        format!(
+            "{{\"resume_device\":{},\"resume_area\":{},\"free\":{},\"image_size\":{},\"warning\":{}}}",
+            json_string(&h.resume_device),
+            opt(h.area.as_ref().map(|a| json_string(&a.path))),
+            opt(h.area.as_ref().map(|a| a.free().to_string())),
+            h.image_size,
+            opt(h.warning().map(|w| json_string(&w)))
+        )
+    });
+
+    let tiers = tiers_pending.wait_or_record(&mut unavailable).map(|areas| {
+        let usage: Vec<String> = tiers::summarize(&areas, zswap_tier(zswap_status.as_ref(), swap_usage.as_ref()))
+            .iter()
+            .map(|t| {
+                format!(
+                    "{{\"tier\":{},\"name\":{},\"areas\":{},\"capacity\":{},\"used\":{},\"priority_min\":{},\"priority_max\":{}}}",
+                    json_string(t.tier.label()),
+                    json_string(t.tier.name()),
+                    t.areas,
+                    t.capacity,
+                    t.used,
+                    opt(t.priorities.map(|(low, _)| low.to_string())),
+                    opt(t.priorities.map(|(_, high)| high.to_string()))
+                )
+            })
+            .collect();
+        format!("[{}]", usage.join(","))
+    });
+
+    let unavailable: Vec<String> = unavailable.iter().map(|name| json_string(name)).collect();
+    println!(
+        "{{\"daemon\":{},\"swap\":{},\"tiers\":{},\"zram\":{},\"zswap\":{},\"pacing\":{},\"oom_kills\":{},\"oomd\":{},\"hibernation\":{},\"unavailable\":[{}]}}",
+        opt(daemon),
+        swap,
+        opt(tiers),
+        opt(zram),
+        opt(zswap),
+        opt(pacing),
+        opt(oom_kills),
+        opt(oomd),
+        opt(hibernation),
+        unavailable.join(",")
+    );
+    Ok(())
+}
+
+/// `free`-style memory summary that accounts for compressed swap.
+///
+/// The kernel counts zram and zswap pages as swap at their original size,
+/// while the RAM they actually occupy shows up as plain "used" memory.
+/// This splits the two apart so the figures add up.
+fn free() -> Result<(), Box<dyn std::error::Error>> {
+    let mem = get_mem_stats(&[
+        "MemTotal",
+        "MemFree",
+        "MemAvailable",
+        "Buffers",
+        "Cached",
+        "SReclaimable",
+        "Shmem",
+        "SwapTotal",
+        "SwapFree",
+    ])?;
+    let total = mem["MemTotal"];
+    let available = mem["MemAvailable"];
+    let buff_cache = mem["Buffers"] + mem["Cached"] + mem["SReclaimable"];
+    let used = total.saturating_sub(available);
+    let swap_total = mem["SwapTotal"];
+    let swap_used = swap_total.saturating_sub(mem["SwapFree"]);
+
+    let col = |bytes: u64| format!("{:>12}", format_size(bytes));
+
+    println!(
+        "{:<8}{:>12}{:>12}{:>12}{:>12}{:>12}{:>12}",
+        "", "total", "used", "free", "shared", "buff/cache", "available"
+    );
+    println!(
+        "{:<8}{}{}{}{}{}{}",
+        "Mem:",
+        col(total),
+        col(used),
+        col(mem["MemFree"]),
+        col(mem["Shmem"]),
+        col(buff_cache),
+        col(available)
+    );
+    println!(
+        "{:<8}{}{}{}",
+        "Swap:",
+        col(swap_total),
+        col(swap_used),
+        col(swap_total.saturating_sub(swap_used))
+    );
+
+    // Compressed stores: data they hold vs RAM they occupy
+    let (zram_stored, zram_phys) = systemd_swap::zram::get_zram_stats()
+        .map(|z| (z.orig_data_size, z.mem_used_total))
+        .unwrap_or((0, 0));
+    let (zswap_stored, zswap_phys) = systemd_swap::meminfo::get_effective_swap_usage()
+        .ok()
+        .filter(|u| u.zswap_active)
+        .map(|u| (u.zswapped_original_bytes, u.zswap_pool_bytes))
+        .unwrap_or((0, 0));
+
+    if zram_stored > 0 || zswap_stored > 0 {
+        let ratio = |stored: u64, phys: u64| {
+            if phys > 0 {
+                format!("{:>11.1}x", stored as f64 / phys as f64)
+            } else {
+                format!("{:>12}", "-")
+            }
+        };
+        println!();
+        println!("{:<8}{:>12}{:>12}{:>12}", "", "stored", "in RAM", "ratio");
+        if zram_stored > 0 {
+            println!(
+                "{:<8}{}{}{}",
+                "Zram:",
+                col(zram_stored),
+                col(zram_phys),
+                ratio(zram_stored, zram_phys)
+            );
+        }
+        if zswap_stored > 0 {
+            println!(
+                "{:<8}{}{}{}",
+                "Zswap:",
+                col(zswap_stored),
+                col(zswap_phys),
+                ratio(zswap_stored, zswap_phys)
+            );
+        }
+
+        // Swap "used" includes compressed pages that never reached disk
+        let on_disk = swap_used.saturating_sub(zram_stored + zswap_stored);
+        let compressed_phys = zram_phys + zswap_phys;
+        let held = used.saturating_sub(compressed_phys) + zram_stored + zswap_stored;
+        println!();
+        println!(
+            "Swap on disk:  {} of {} used",
+            format_size(on_disk),
+            format_size(swap_used)
+        );
+        println!(
+            "Data in RAM:   {} held in {} ({} of it compressed)",
+            format_size(held),
+            format_size(used),
+            format_size(compressed_phys)
+        );
+    }
+
+    Ok(())
+}
+
 /// Show recommended configuration based on system hardware
 fn autoconfig() -> Result<(), Box<dyn std::error::Error>> {
     println!("Detecting system capabilities...\n");
 
+    // Always detect afresh here, so this doubles as a way to refresh the cache
     let caps = SystemCapabilities::detect();
-    let recommended = RecommendedConfig::from_capabilities(&caps);
+    caps.refresh_cache();
+    let config = Config::load()?;
+    let workload = WorkloadClass::detect(caps.cpu_count, &config);
+    let recommended = RecommendedConfig::from_capabilities(&caps)
+        .with_workload(workload)
+        .with_shared_disk(systemd_swap::disks::slow_shared_area(Path::new(&caps.swap_path)));
 
     println!("=== System Information ===");
     println!("Swap path filesystem: {:?}", caps.swap_path_fstype);
+    println!("Live system:          {}", caps.is_live_system);
+    println!("Diskless:             {}", caps.diskless);
+    if let Some(shared) = &recommended.shared_disk {
+        println!("Shared disk:          {}, no swap files", shared);
+    }
+    println!("Workload class:       {} ({} CPUs, graphical session: {})",
+        workload, caps.cpu_count, systemd_swap::workload::is_graphical());
+    println!("Observed usage:       {}", Observations::load(&observations_file(&config)));
 
     println!("\n=== Recommended Mode ===");
     println!("  swap_mode:  {:?}", recommended.swap_mode);
@@ -692,5 +1159,392 @@ fn autoconfig() -> Result<(), Box<dyn std::error::Error>> {
         println!("  {:<34} {}", key, value);
     }
 
+    // Sized from the keys above, with the configured values winning as in auto mode
+    let mut config = config;
+    config.apply_autoconfig(&recommended);
+    let mut sizing = Sizing {
+        ram: caps.total_ram_bytes,
+        free_disk: caps.free_disk_space_bytes,
+        hibernation: HibernationStatus::detect(),
+        ..Sizing::default()
+    };
+    // Both recommended modes run the zram pool
+    sizing.zram_disksize = ZramPoolConfig::from_config(&config)?.initial_disksize(caps.total_ram_bytes);
+    sizing.zram_mem_limit = config.get_size_or("zram_mem_limit", "0")?;
+    if recommended.swap_mode == systemd_swap::autoconfig::SwapMode::ZramSwapfc {
+        let swapfile = systemd_swap::swapfile::SwapFileConfig::from_config(&config)?;
+        sizing.file_capacity = swapfile.max_capacity();
+        sizing.max_count = swapfile.max_count;
+    }
+    println!("\n=== Sizing ===");
+    print!("{}", sizing.table());
+
+    Ok(())
+}
+
+/// Validate the configuration and print the values the daemon would derive from it
+fn check_config() -> Result<(), Box<dyn std::error::Error>> {
+    use systemd_swap::swapfile::SwapFileConfig;
+
+    let caps = SystemCapabilities::detect_cached();
+    let mut config = Config::load()?;
+    let workload = WorkloadClass::detect(caps.cpu_count, &config);
+    let recommended = RecommendedConfig::from_capabilities(&caps)
+        .with_workload(workload)
+        .with_shared_disk(systemd_swap::disks::slow_shared_area(Path::new(&caps.swap_path)));
+    let swap_mode = SwapMode::from_config(&config);
+    if matches!(swap_mode, SwapMode::Auto) {
+        config.apply_autoconfig(&recommended);
+    }
+    let effective_mode = swap_mode.resolve(&recommended);
+
+    println!("=== Sources (lowest precedence first) ===");
+    for source in config.sources() {
+        println!("  {}", source);
+    }
+
+    println!("\n=== Mode ===");
+    println!("  swap_mode:  {:?} (effective: {:?})", swap_mode, effective_mode);
+    println!("  workload:   {}", workload);
+    if let Some(shared) = &recommended.shared_disk {
+        println!("  shared:     {}", shared);
+    }
+
+    if effective_mode == SwapMode::Manual {
+        if let Some(split) = systemd_swap::budget::for_config(&config, caps.total_ram_bytes)? {
+            println!("\n=== Compressed Memory Budget ===");
+            println!("  {:<34} {}", "budget", format_size(split.budget));
+            println!("  {:<34} {}%", "zswap_max_pool_percent", split.zswap_pool_percent);
+            println!("  {:<34} {}", "zram_mem_limit", format_size(split.zram_mem_limit));
+            for warning in &split.warnings {
+                println!("  warning: {}", warning);
+            }
+        }
+    }
+
+    if effective_mode.uses_zram(&config) {
+        println!("\n=== Zram ===");
+        // The pool modes size as ZramPool::new does; manual sets up one device
+        let pool = ZramPoolConfig::from_config(&config)?;
+        let disksize = match effective_mode {
+            SwapMode::Manual if !pool.sized_by_ratio => config.get_size_or("zram_size", defaults::ZRAM_SIZE)?,
+            SwapMode::Manual => pool.initial_size,
+            _ => pool.initial_disksize(caps.total_ram_bytes),
+        };
+        let source = if pool.sized_by_ratio { "ratio × mem_limit" } else { "zram_size" };
+        println!("  {:<34} {} ({})", "disksize", format_size(disksize), source);
+        let mem_limit = config.get_size_or("zram_mem_limit", "0")?;
+        if mem_limit > 0 {
+            println!("  {:<34} {}", "mem_limit", format_size(mem_limit));
+        }
+    }
+
+    let (uses_swapfile, zswap) = match effective_mode {
+        SwapMode::ZramSwapfc => (true, false),
+        SwapMode::ZswapSwapfc => (true, true),
+        SwapMode::Manual => (
+            config.get_bool("swapfile_enabled"),
+            config.get_bool("zswap_enabled"),
+        ),
+        _ => (false, false),
+    };
+
+    if uses_swapfile {
+        let mut swapfile = SwapFileConfig::from_config(&config)?;
+        swapfile.set_zswap_mode(zswap);
+
+        println!("\n=== Swap Files ===");
+        println!("  {:<34} {}", "path", swapfile.path.display());
+        if swapfile.layout.is_empty() {
+            println!("  {:<34} {}", "chunk_size", format_size(swapfile.chunk_size));
+        } else {
+            let sizes: Vec<String> = swapfile.layout.iter().map(|s| format_size(*s)).collect();
+            println!("  {:<34} {}", "layout", sizes.join(","));
+        }
+        println!("  {:<34} {}", "max_count", swapfile.max_count);
+        if let Some(mount) = systemd_swap::btrfs::BtrfsMount::of(&swapfile.path) {
+            let layout = systemd_swap::btrfs::Layout::detect(&mount);
+            println!(
+                "  {:<34} {} (subvolume {}), swap subvolume {}",
+                "btrfs_layout",
+                layout.name(),
+                mount.fsroot,
+                systemd_swap::btrfs::SwapParent::from_setting(&swapfile.btrfs_parent, layout).name()
+            );
+        }
+        if swapfile.can_grow_on(get_fstype(&swapfile.path).as_deref()) {
+            println!("  {:<34} up to {}", "grow_in_place", format_size(swapfile.grow_max_size));
+        } else {
+            println!("  {:<34} off", "grow_in_place");
+        }
+        let storage = StorageType::detect(&swapfile.path);
+        match swapfile.priority.for_file(storage, 1) {
+            Some(top) => println!(
+                "  {:<34} {} down ({} storage)",
+                "priority",
+                top,
+                storage.name()
+            ),
+            None => println!("  {:<34} kernel-assigned", "priority"),
+        }
+        let min_count_source = if swapfile.min_count_auto {
+            format!(
+                "auto: {} RAM, {}",
+                format_size(caps.total_ram_bytes),
+                if zswap { "zswap" } else { "zram" }
+            )
+        } else {
+            "configured".to_string()
+        };
+        println!("  {:<34} {} ({})", "min_count", swapfile.min_count, min_count_source);
+        if zswap {
+            println!("  {:<34} {}", "reserve_files", swapfile.reserve_files(true));
+        }
+        match swapfile.oomd_guard_free_swap {
+            Some(guard) => println!(
+                "  {:<34} {} (aligned with systemd-oomd, guard below {}%)",
+                "free_swap_perc", swapfile.free_swap_perc, guard
+            ),
+            None => println!("  {:<34} {}", "free_swap_perc", swapfile.free_swap_perc),
+        }
+        println!("  {:<34} {}", "max_capacity", format_size(swapfile.max_capacity()));
+
+        if let Some(fs @ ("tmpfs" | "ramfs")) = get_fstype(&swapfile.path).as_deref() {
+            return Err(format!(
+                "swapfile_path {} is on {}, which lives in RAM; point it at a disk or use swap_mode=zram",
+                swapfile.path.display(),
+                fs
+            )
+            .into());
+        }
+    }
+
+    println!("\nConfiguration OK");
+    Ok(())
+}
+
+/// Current swap use, or the daily peaks recorded by the daemon
+fn stats(history: bool, by_cgroup: bool, days: u64) -> Result<(), Box<dyn std::error::Error>> {
+    use systemd_swap::history::{self, History, TOTAL};
+
+    if !history {
+        let samples = history::sample();
+        for (cgroup, bytes) in &samples {
+            if cgroup == TOTAL {
+                println!("Swap in use: {}", format_size(*bytes));
+            } else if by_cgroup {
+                println!("  {:<24}{:>12}", cgroup, format_size(*bytes));
+            }
+        }
+        if by_cgroup && samples.iter().all(|(c, _)| c == TOTAL) {
+            println!("  (no per-cgroup swap accounting: needs cgroup v2 with memory.swap.current)");
+        }
+        return Ok(());
+    }
+
+    let config = Config::load()?;
+    let path = history::history_file(&config);
+    let data = History::load(&path);
+    let days = days.clamp(1, history::HISTORY_DAYS);
+    let since = history::today().saturating_sub(days - 1);
+    let recorded = data.days(since);
+    if recorded.is_empty() {
+        println!("No swap history in {} yet", path.display());
+        if !history::enabled(&config) {
+            println!("Recording is off (swap_history=0)");
+        }
+        return Ok(());
+    }
+
+    // The four slices with the highest peaks get a column
+    let cgroups = data.cgroups(since);
+    let columns: Vec<&str> = if by_cgroup {
+        cgroups.iter().take(4).map(|(c, _, _)| c.as_str()).collect()
+    } else {
+        Vec::new()
+    };
+    let cell = |bytes: Option<u64>| format!("{:>14}", bytes.map(format_size).unwrap_or_else(|| "-".into()));
+
+    println!("Daily swap peaks, last {} day(s) (UTC):", days);
+    print!("  {:<12}{:>14}", "Date", "total");
+    for cgroup in &columns {
+        print!("{:>14}", cgroup);
+    }
+    println!();
+    for day in &recorded {
+        print!("  {:<12}{}", history::format_day(*day), cell(data.peak(*day, TOTAL)));
+        for cgroup in &columns {
+            print!("{}", cell(data.peak(*day, cgroup)));
+        }
+        println!();
+    }
+
+    let top = recorded
+        .iter()
+        .filter_map(|d| data.peak(*d, TOTAL).map(|p| (p, *d)))
+        .max();
+    if let Some((peak, day)) = top {
+        let swap_total = get_mem_stats(&["SwapTotal"]).map(|m| m["SwapTotal"]).unwrap_or(0);
+        println!(
+            "\nPeak: {} on {} (swap configured now: {})",
+            format_size(peak),
+            history::format_day(day),
+            format_size(swap_total)
+        );
+    }
+    if by_cgroup {
+        for (cgroup, peak, day) in &cgroups {
+            println!("  {} peaked at {} on {}", cgroup, format_size(*peak), history::format_day(*day));
+        }
+    }
     Ok(())
 }
+
+/// Print the built-in documentation for a config key, or list every key
+fn explain(key: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    use systemd_swap::schema;
+
+    let Some(key) = key else {
+        let mut section = "";
+        for doc in schema::KEYS {
+            if doc.section != section {
+                section = doc.section;
+                println!("\n=== {} ===", section);
+            }
+            println!("  {:<34} {}", doc.key, doc.default);
+        }
+        println!("\nRun `systemd-swap explain <key>` for details.");
+        return Ok(());
+    };
+
+    let Some(doc) = schema::lookup(key) else {
+        let similar = schema::matching(key);
+        if !similar.is_empty() {
+            eprintln!("Did you mean:");
+            for doc in similar {
+                eprintln!("  {}", doc.key);
+            }
+        }
+        return Err(format!("unknown configuration key: {}", key).into());
+    };
+
+    println!("{}", doc.key);
+    println!("  Section:  {}", doc.section);
+    println!(
+        "  Default:  {}",
+        if doc.default.is_empty() { "(empty)" } else { doc.default }
+    );
+    if let Some(bounds) = doc.bounds {
+        println!("  Accepts:  {}", bounds);
+    }
+    if let Ok(config) = Config::load() {
+        match config.get_opt(doc.key) {
+            Some(value) => println!("  Current:  {}", value),
+            None => println!("  Current:  (not set)"),
+        }
+    }
+    println!("\n  {}", doc.description);
+    if !doc.notes.is_empty() {
+        println!("\n  {}", doc.notes);
+    }
+    Ok(())
+}
+
+/// Stress the running daemon with a throwaway allocation and report the outcome
+fn selftest(size: &str, hold: u64, settle: u64) -> Result<(), Box<dyn std::error::Error>> {
+    use std::time::Duration;
+    use systemd_swap::selftest::{self, SelftestOptions};
+
+    let size = systemd_swap::helpers::parse_size(size)?;
+    let opts = SelftestOptions {
+        size,
+        hold: Duration::from_secs(hold),
+        settle_timeout: Duration::from_secs(settle),
+    };
+
+    let report = selftest::run(&opts)?;
+    report.print();
+    if report.passed() {
+        Ok(())
+    } else {
+        Err("self-test failed".into())
+    }
+}
+
+/// Show or set the running daemon's log level through its control socket
+fn log_level(level: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    // WORK_DIR, and with it the socket, is root's
+    am_i_root()?;
+    let current = match level {
+        Some(level) => systemd_swap::control::request(&format!("log-level {}", level))?,
+        None => systemd_swap::control::request("log-level")?,
+    };
+    println!("{}", current);
+    Ok(())
+}
+
+/// Check the emergency path against the live system without memory pressure
+/// Probe swap-in latency once and print what is recorded per tier
+fn latency() -> Result<(), Box<dyn std::error::Error>> {
+    use systemd_swap::latency::{self, Latencies, LatencyError};
+
+    let config = Config::load()?;
+    let path = latency::latency_file(&config);
+    if systemd_swap::readonly::is_enabled() {
+        println!("Probe: skipped in read-only mode");
+        print_latencies(&Latencies::load(&path));
+        return Ok(());
+    }
+    // Reclaiming the probe's scope needs root
+    am_i_root()?;
+    match latency::probe(&path) {
+        Ok(measured) if measured.is_empty() => {
+            println!("Probe: pages were not swapped out or spread over several tiers, nothing recorded")
+        }
+        Ok(measured) => {
+            let tiers: Vec<&str> = measured.iter().map(|(tier, _)| tier.label()).collect();
+            println!("Probe: measured {}", tiers.join(", "));
+        }
+        Err(LatencyError::NoSwap) => println!("Probe: no active swap area"),
+        Err(e) => return Err(e.into()),
+    }
+    print_latencies(&Latencies::load(&path));
+    Ok(())
+}
+
+/// Recorded swap-in latencies, one line per tier
+fn print_latencies(latencies: &systemd_swap::latency::Latencies) {
+    if latencies.0.is_empty() {
+        return;
+    }
+    let now = now_secs();
+    println!("\nSwap-in latency (latest probe):");
+    for (tier, l) in &latencies.0 {
+        println!(
+            "  {} {:<19} p50 {}µs, p99 {}µs, max {}µs ({} probe{}, {} ago)",
+            tier.label(),
+            tier.name(),
+            l.p50,
+            l.p99,
+            l.max,
+            l.probes,
+            if l.probes == 1 { "" } else { "s" },
+            format_duration(now.saturating_sub(l.updated))
+        );
+    }
+}
+
+fn simulate_oom() -> Result<(), Box<dyn std::error::Error>> {
+    // The checks only mean something with the daemon's privileges
+    am_i_root()?;
+    let config = Config::load()?;
+    systemd_swap::exec::init(&config);
+
+    let report = systemd_swap::drill::run(&config)?;
+    report.print();
+    if report.ready() {
+        Ok(())
+    } else {
+        Err("emergency path not ready".into())
+    }
+}
@@ -0,0 +1,256 @@
+// Embeddable swap manager for systemd-swap
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// SwapManagerBuilder runs the same startup sequence as `systemd-swap start`
+// inside another process, with injected configuration and callbacks:
+//
+//     let manager = SwapManagerBuilder::new()
+//         .config(config)
+//         .work_dir("/run/my-agent/swap")
+//         .on_log(|level, line| my_logger(level, line))
+//         .on_ready(|| println!("swap ready"))
+//         .build()?;
+//     let handle = manager.handle();
+//     let task = manager.spawn();
+//     ...
+//     handle.request_shutdown();
+//     task.join();
+//     handle.teardown();
+//
+// The manager's threads carry its context (context.rs), so logging, the
+// working directory and shutdown stay scoped to this instance. Systemd
+// notifications are left to the host. Like the daemon, the manager needs
+// root and owns the system's swap: run at most one per host.
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+use thiserror::Error;
+
+use crate::autoconfig::{RecommendedConfig, SystemCapabilities};
+use crate::config::{Config, ConfigError, RUN_SYSD};
+use crate::context::{self, Context, LogLevel, LogSink};
+use crate::defaults;
+use crate::helpers::{makedirs, HelperError};
+use crate::modes::{ModeError, SwapMode, SystemSubsystems};
+use crate::plan::StopPlan;
+use crate::state::DaemonState;
+use crate::warn;
+use crate::workload::WorkloadClass;
+
+#[derive(Error, Debug)]
+pub enum ManagerError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Config error: {0}")]
+    Config(#[from] ConfigError),
+    #[error("Helper error: {0}")]
+    Helper(#[from] HelperError),
+    #[error("Mode error: {0}")]
+    Mode(#[from] ModeError),
+}
+
+pub type Result<T> = std::result::Result<T, ManagerError>;
+
+type ReadyCallback = Box<dyn FnMut() + Send>;
+
+/// Builder for an embedded swap manager
+#[derive(Default)]
+pub struct SwapManagerBuilder {
+    config: Option<Config>,
+    work_dir: Option<PathBuf>,
+    log: Option<LogSink>,
+    on_ready: Option<ReadyCallback>,
+}
+
+impl SwapManagerBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Configuration to run with; defaults to `Config::load()`
+    pub fn config(mut self, config: Config) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    /// Runtime state directory; defaults to WORK_DIR
+    pub fn work_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.work_dir = Some(dir.into());
+        self
+    }
+
+    /// Receive log lines instead of having them printed
+    pub fn on_log(mut self, sink: impl Fn(LogLevel, &str) + Send + Sync + 'static) -> Self {
+        self.log = Some(Arc::new(sink));
+        self
+    }
+
+    /// Called once the initial swap is set up
+    pub fn on_ready(mut self, f: impl FnMut() + Send + 'static) -> Self {
+        self.on_ready = Some(Box::new(f));
+        self
+    }
+
+    pub fn build(self) -> Result<SwapManager> {
+        let config = match self.config {
+            Some(config) => config,
+            None => Config::load()?,
+        };
+        let defaults = Context::default();
+        Ok(SwapManager {
+            context: Context {
+                work_dir: self.work_dir.unwrap_or(defaults.work_dir),
+                shutdown: defaults.shutdown,
+                log: self.log,
+                notify_systemd: false,
+            },
+            config,
+            caps: None,
+            on_ready: self.on_ready,
+        })
+    }
+}
+
+/// Run `f` with `ctx` bound to the calling thread, then restore its context
+fn with_context<T>(ctx: Context, f: impl FnOnce() -> T) -> T {
+    let previous = context::current();
+    context::enter(ctx);
+    let result = f();
+    match previous {
+        Some(ctx) => context::enter(ctx),
+        None => context::leave(),
+    }
+    result
+}
+
+/// Controls a manager from any thread, also after it was moved into `spawn`
+#[derive(Debug, Clone)]
+pub struct ManagerHandle {
+    context: Context,
+    config: Config,
+}
+
+impl ManagerHandle {
+    /// Ask the manager's monitors to stop; `run` returns once they have
+    pub fn request_shutdown(&self) {
+        self.context.shutdown.store(true, Ordering::Release);
+    }
+
+    pub fn is_shutdown_requested(&self) -> bool {
+        self.context.shutdown.load(Ordering::Acquire)
+    }
+
+    /// Undo everything the manager set up, as `systemd-swap stop` does:
+    /// swap units and devices, saved settings, swap files and the working
+    /// directory. Call once `run` has returned.
+    pub fn teardown(&self) {
+        with_context(self.context.clone(), || StopPlan::build(&self.config, false).execute());
+    }
+}
+
+/// A swap manager running inside the current process
+pub struct SwapManager {
+    context: Context,
+    config: Config,
+    /// Detection to reuse instead of probing the hardware again
+    caps: Option<SystemCapabilities>,
+    on_ready: Option<ReadyCallback>,
+}
+
+impl SwapManager {
+    /// Build a manager for the standalone daemon: process-wide defaults
+    /// (WORK_DIR, stdout/stderr logging, sd_notify); its signal handler
+    /// stops it through `handle().request_shutdown()`
+    pub fn standalone(config: Config) -> Self {
+        Self {
+            context: Context::default(),
+            config,
+            caps: None,
+            on_ready: None,
+        }
+    }
+
+    /// Reuse a detection made before the working directory was reset
+    pub fn with_capabilities(mut self, caps: SystemCapabilities) -> Self {
+        self.caps = Some(caps);
+        self
+    }
+
+    pub fn handle(&self) -> ManagerHandle {
+        ManagerHandle {
+            context: self.context.clone(),
+            config: self.config.clone(),
+        }
+    }
+
+    /// Set up swap and monitor it on the calling thread until shutdown
+    pub fn run(self) -> Result<()> {
+        with_context(self.context.clone(), || self.run_in_context())
+    }
+
+    /// `run` on a new thread
+    pub fn spawn(self) -> JoinHandle<Result<()>> {
+        std::thread::spawn(move || self.run())
+    }
+
+    fn run_in_context(mut self) -> Result<()> {
+        crate::readonly::check("run the swap manager")?;
+        let caps = self.caps.take().unwrap_or_else(SystemCapabilities::detect_cached);
+        let workload = WorkloadClass::detect(caps.cpu_count, &self.config);
+        let recommended = RecommendedConfig::from_capabilities(&caps)
+            .with_workload(workload)
+            .with_shared_disk(crate::disks::slow_shared_area(Path::new(&caps.swap_path)));
+
+        makedirs(context::work_dir())?;
+        crate::recovery::clear_stopping();
+        caps.refresh_cache();
+        makedirs(format!("{}/system/local-fs.target.wants", RUN_SYSD))?;
+        makedirs(format!("{}/system/swap.target.wants", RUN_SYSD))?;
+
+        let config = &mut self.config;
+        crate::exec::init(config);
+        let swap_mode = SwapMode::from_config(config);
+        let state_dir = crate::config::state_dir(config);
+        if let Err(e) = makedirs(&state_dir) {
+            warn!("Could not create state directory {}: {}", state_dir.display(), e);
+        }
+
+        // Apply autoconfig only in auto mode — for explicit modes, each subsystem
+        // uses its own fallback defaults from unwrap_or() calls.
+        if matches!(swap_mode, SwapMode::Auto) {
+            config.apply_autoconfig(&recommended);
+        }
+
+        let effective_mode = swap_mode.resolve(&recommended);
+        if effective_mode == SwapMode::Manual {
+            crate::budget::apply(config, caps.total_ram_bytes)?;
+        }
+        crate::sysctl::apply_min_free_kbytes(config, effective_mode);
+        crate::hibernate::apply(config);
+        crate::zswap::sync_persistence(config, effective_mode.uses_zswap(config));
+        crate::alarms::start(config);
+        crate::hooks::init(config);
+        crate::events::spawn_oom_watcher();
+        crate::swapwatch::spawn_watcher();
+        crate::history::spawn_recorder(config);
+        crate::workload::spawn_observer(config);
+        crate::latency::spawn_sampler(config);
+
+        let runner = effective_mode
+            .runner()
+            .expect("Auto mode should be resolved before this point");
+        let swapfile_path = config.get("swapfile_path").unwrap_or(defaults::SWAPFILE_PATH);
+        DaemonState::record(runner.name(), swapfile_path)?;
+        crate::recovery::spawn_watcher();
+        crate::control::spawn_listener();
+        let mut subsystems = SystemSubsystems::new();
+        if let Some(on_ready) = self.on_ready.take() {
+            subsystems = subsystems.with_ready_callback(on_ready);
+        }
+        runner.run(config, &mut subsystems)?;
+        Ok(())
+    }
+}
@@ -88,6 +88,72 @@ pub fn get_free_ram_percent() -> Result<u8> {
     Ok(percent.min(100) as u8)
 }
 
+/// RAM taken by all zram devices (mem_used_total from mm_stat), in bytes
+pub fn zram_mem_used() -> u64 {
+    let Ok(entries) = std::fs::read_dir("/sys/block") else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with("zram"))
+        .filter_map(|entry| {
+            let mm_stat = std::fs::read_to_string(entry.path().join("mm_stat")).ok()?;
+            mm_stat.split_whitespace().nth(2)?.parse::<u64>().ok()
+        })
+        .sum()
+}
+
+/// Free RAM as MemAvailable reports it, and adjusted for zram growth it has
+/// not caught up with yet
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FreeRam {
+    pub raw: u8,
+    pub adjusted: u8,
+}
+
+/// Adjusted free RAM for a monitor loop.
+///
+/// The pages zram allocates for compressed data show up in MemAvailable with
+/// a delay, so right after a burst of swap-out free RAM looks higher than it
+/// is. The tracker keeps a running deficit: zram's mem_used_total growth adds
+/// to it, every fall of MemAvailable and every shrink of zram pays it down,
+/// and what is left is subtracted. Once MemAvailable has caught up, however
+/// many samples that takes, the two agree again.
+#[derive(Debug, Default)]
+pub struct FreeRamTracker {
+    /// MemAvailable and zram mem_used_total at the last sample, in bytes
+    last: Option<(u64, u64)>,
+    /// zram growth MemAvailable has not reflected yet, in bytes
+    deficit: u64,
+}
+
+impl FreeRamTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sample /proc/meminfo and zram now
+    pub fn sample(&mut self) -> Result<FreeRam> {
+        let stats = get_mem_stats(&["MemTotal", "MemAvailable"])?;
+        Ok(self.update(stats["MemTotal"], stats["MemAvailable"], zram_mem_used()))
+    }
+
+    fn update(&mut self, total: u64, available: u64, zram_used: u64) -> FreeRam {
+        if let Some((last_available, last_zram)) = self.last {
+            let growth = zram_used.saturating_sub(last_zram);
+            let shrink = last_zram.saturating_sub(zram_used);
+            let counted = last_available.saturating_sub(available);
+            self.deficit = (self.deficit + growth).saturating_sub(counted + shrink);
+        }
+        self.last = Some((available, zram_used));
+        let percent = |bytes: u64| ((bytes * 100) / total.max(1)).min(100) as u8;
+        FreeRam {
+            raw: percent(available),
+            adjusted: percent(available.saturating_sub(self.deficit)),
+        }
+    }
+}
+
 /// Get free swap percentage (0-100)
 pub fn get_free_swap_percent() -> Result<u8> {
     let stats = get_mem_stats(&["SwapTotal", "SwapFree"])?;
@@ -145,6 +211,9 @@ pub fn get_cpu_count() -> usize {
         .unwrap_or(1)
 }
 
+/// Below this share of unallocated swap slots, swap-outs are about to fail
+pub const SLOT_SCARCE_PERCENT: u8 = 10;
+
 /// Effective swap usage information accounting for zswap
 #[derive(Debug, Default)]
 pub struct EffectiveSwapUsage {
@@ -168,6 +237,33 @@ pub struct EffectiveSwapUsage {
     pub zswap_active: bool,
 }
 
+impl EffectiveSwapUsage {
+    /// Unallocated swap slots as a percentage of SwapTotal.
+    ///
+    /// Every page in the zswap pool holds a swap slot, so this can run out
+    /// while the effective free swap (which counts those pages as free) looks healthy.
+    pub fn slot_free_percent(&self) -> u8 {
+        if self.swap_total == 0 {
+            return 0;
+        }
+        (self.swap_free * 100 / self.swap_total).min(100) as u8
+    }
+
+    /// Percentage of allocated slots held by pages that are still in zswap RAM
+    pub fn zswap_slot_share(&self) -> u8 {
+        if self.swap_used_kernel == 0 {
+            return 0;
+        }
+        (self.zswapped_original_bytes * 100 / self.swap_used_kernel).min(100) as u8
+    }
+
+    /// Swap slots, not disk or pool space, are what is running out: only a
+    /// new swap area helps, growing the zswap pool does not.
+    pub fn slots_exhausted(&self) -> bool {
+        self.swap_total > 0 && self.zswap_active && self.slot_free_percent() < SLOT_SCARCE_PERCENT
+    }
+}
+
 /// Get effective swap usage accounting for zswap compression
 ///
 /// When zswap is active, the kernel reports swap usage based on allocated slots,
@@ -214,8 +310,8 @@ pub fn get_effective_swap_usage() -> Result<EffectiveSwapUsage> {
                 .unwrap_or(20);
 
         let max_pool_size = mem_total * max_pool_percent / 100;
-        if max_pool_size > 0 {
-            result.zswap_pool_percent = ((zswap_compressed * 100) / max_pool_size).min(100) as u8;
+        if let Some(pct) = (zswap_compressed * 100).checked_div(max_pool_size) {
+            result.zswap_pool_percent = pct.min(100) as u8;
         }
     }
 
@@ -271,6 +367,34 @@ fn get_mem_stats_optional(fields: &[&str]) -> Result<HashMap<String, u64>> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn zram_growth_not_yet_in_mem_available_is_subtracted() {
+        const GB: u64 = 1 << 30;
+        let mut tracker = FreeRamTracker::new();
+        assert_eq!(tracker.update(10 * GB, 5 * GB, 0), FreeRam { raw: 50, adjusted: 50 });
+        // zram grew by 2GB, MemAvailable fell by only 1GB of it
+        assert_eq!(tracker.update(10 * GB, 4 * GB, 2 * GB), FreeRam { raw: 40, adjusted: 30 });
+        // MemAvailable caught up, zram steady
+        assert_eq!(tracker.update(10 * GB, 3 * GB, 2 * GB), FreeRam { raw: 30, adjusted: 30 });
+        // Shrinking zram never adds free RAM
+        assert_eq!(tracker.update(10 * GB, 3 * GB, GB), FreeRam { raw: 30, adjusted: 30 });
+    }
+
+    #[test]
+    fn deficit_lasts_until_mem_available_catches_up() {
+        const GB: u64 = 1 << 30;
+        let mut tracker = FreeRamTracker::new();
+        tracker.update(10 * GB, 5 * GB, 0);
+        assert_eq!(tracker.update(10 * GB, 4 * GB, 2 * GB), FreeRam { raw: 40, adjusted: 30 });
+        // Next sample: MemAvailable has not moved yet, the deficit stays
+        assert_eq!(tracker.update(10 * GB, 4 * GB, 2 * GB), FreeRam { raw: 40, adjusted: 30 });
+        // Half of it shows up, then the rest
+        assert_eq!(tracker.update(10 * GB, 7 * GB / 2, 2 * GB), FreeRam { raw: 35, adjusted: 30 });
+        assert_eq!(tracker.update(10 * GB, 3 * GB, 2 * GB), FreeRam { raw: 30, adjusted: 30 });
+        // Paid off: a later fall is not credited to old growth
+        assert_eq!(tracker.update(10 * GB, 2 * GB, 2 * GB), FreeRam { raw: 20, adjusted: 20 });
+    }
+
     #[test]
     fn test_get_ram_size() {
         let size = get_ram_size().unwrap();
@@ -283,6 +407,24 @@ mod tests {
         assert!(percent <= 100);
     }
 
+    #[test]
+    fn slots_run_out_while_effective_free_looks_healthy() {
+        const GB: u64 = 1024 * 1024 * 1024;
+        // 10GB swap, 9.5GB of slots allocated, half of that still in the zswap pool
+        let usage = EffectiveSwapUsage {
+            swap_total: 10 * GB,
+            swap_free: GB / 2,
+            swap_used_kernel: 10 * GB - GB / 2,
+            zswapped_original_bytes: (10 * GB - GB / 2) / 2,
+            zswap_active: true,
+            ..EffectiveSwapUsage::default()
+        };
+        assert_eq!(usage.slot_free_percent(), 5);
+        assert_eq!(usage.zswap_slot_share(), 50);
+        assert!(usage.slots_exhausted());
+        assert!(!EffectiveSwapUsage::default().slots_exhausted());
+    }
+
     #[test]
     fn test_get_effective_swap_usage() {
         // This test may not work without swap, but should not panic
@@ -0,0 +1,632 @@
+// Swap mode orchestration for systemd-swap
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::time::Duration;
+
+use thiserror::Error;
+
+use crate::autoconfig::{RecommendedConfig, SwapMode as AutoSwapMode};
+use crate::config::Config;
+use crate::swapfile::{SwapFile, SwapFileError};
+use crate::zram::{ZramError, ZramPool};
+use crate::zswap::{ZswapBackup, ZswapError};
+use crate::{error, info, warn};
+
+#[derive(Error, Debug)]
+pub enum ModeError {
+    #[error("Zram error: {0}")]
+    Zram(#[from] ZramError),
+    #[error("Zswap error: {0}")]
+    Zswap(#[from] ZswapError),
+    #[error("Swapfile error: {0}")]
+    SwapFile(#[from] SwapFileError),
+    #[error("Subsystem not initialized: {0}")]
+    NotInitialized(&'static str),
+}
+
+pub type Result<T> = std::result::Result<T, ModeError>;
+
+/// Swap strategy based on filesystem detection
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SwapMode {
+    Auto,
+    ZramSwapfc,    // zram + swap files for overflow
+    ZswapSwapfc,   // zswap + swapfc (preallocated or sparse loop)
+    ZramOnly,      // zram only
+    Manual,        // Use explicit config values (zram_enabled, zswap_enabled, swapfc_enabled)
+    Disabled,      // Swap management disabled (service exits cleanly)
+}
+
+impl SwapMode {
+    /// Parse swap_mode from config
+    pub fn from_config(config: &Config) -> Self {
+        match config
+            .get("swap_mode")
+            .unwrap_or("auto")
+            .to_lowercase()
+            .as_str()
+        {
+            "zram+swapfc" | "zram_swapfc" => SwapMode::ZramSwapfc,
+            "zswap+swapfc" | "zswap" | "zswap+swapfile" | "zswap+loopfile" | "zswap_loopfile" => SwapMode::ZswapSwapfc,
+            "zram" | "zram_only" => SwapMode::ZramOnly,
+            "zram+swapfile" => SwapMode::ZramSwapfc,
+            "disabled" => SwapMode::Disabled,
+            "manual" => SwapMode::Manual,
+            _ => SwapMode::Auto,
+        }
+    }
+
+    /// Resolve Auto into the concrete mode recommended for this system
+    pub fn resolve(self, recommended: &RecommendedConfig) -> Self {
+        match self {
+            SwapMode::Auto => match recommended.swap_mode {
+                AutoSwapMode::ZramSwapfc => {
+                    info!("Auto-detected: using zram + swapfc");
+                    SwapMode::ZramSwapfc
+                }
+                AutoSwapMode::ZramOnly => {
+                    info!("Auto-detected: using zram only");
+                    SwapMode::ZramOnly
+                }
+            },
+            mode => mode,
+        }
+    }
+
+    /// Whether a concrete mode sets up zram
+    pub fn uses_zram(self, config: &Config) -> bool {
+        match self {
+            SwapMode::ZramSwapfc | SwapMode::ZramOnly => true,
+            SwapMode::Manual => config.get_bool("zram_enabled"),
+            _ => false,
+        }
+    }
+
+    /// Whether a concrete mode sets up zswap
+    pub fn uses_zswap(self, config: &Config) -> bool {
+        match self {
+            SwapMode::ZswapSwapfc => true,
+            SwapMode::Manual => config.get_bool("zswap_enabled"),
+            _ => false,
+        }
+    }
+
+    /// Runner for a concrete mode (None for unresolved Auto)
+    pub fn runner(self) -> Option<Box<dyn SwapModeRunner>> {
+        match self {
+            SwapMode::ZramSwapfc => Some(Box::new(ZramSwapfcRunner)),
+            SwapMode::ZswapSwapfc => Some(Box::new(ZswapSwapfcRunner)),
+            SwapMode::ZramOnly => Some(Box::new(ZramOnlyRunner)),
+            SwapMode::Manual => Some(Box::new(ManualRunner)),
+            SwapMode::Disabled => Some(Box::new(DisabledRunner)),
+            SwapMode::Auto => None,
+        }
+    }
+}
+
+/// Operations the mode runners orchestrate.
+///
+/// `SystemSubsystems` drives the real kernel/systemd interfaces; tests provide
+/// their own implementation to check orchestration without touching the system.
+pub trait Subsystems {
+    /// Turn zswap off so it does not compress in front of zram
+    fn disable_zswap_for_zram(&mut self);
+    /// Create the zram pool
+    fn zram_pool_init(&mut self, config: &Config) -> Result<()>;
+    /// Bring up the initial zram pool devices
+    fn zram_pool_start(&mut self) -> Result<()>;
+    /// Run the zram pool monitor, in the background or blocking until shutdown
+    fn zram_pool_monitor(&mut self, background: bool) -> Result<()>;
+    /// Set up a single zram device (manual mode)
+    fn zram_start_single(&mut self, config: &Config) -> Result<()>;
+    /// Configure zswap
+    fn zswap_start(&mut self, config: &Config) -> Result<()>;
+    /// Save the parameters `zswap_start` replaced, for `stop` to restore
+    fn zswap_save_backup(&mut self) -> Result<()>;
+    /// Start periodic zswap statistics logging
+    fn zswap_spawn_monitor(&mut self);
+    /// Prepare swap file management
+    fn swapfile_init(&mut self, config: &Config, zswap_mode: bool) -> Result<()>;
+    /// Create the first swap file
+    fn swapfile_create_initial(&mut self) -> Result<()>;
+    /// Run the swap file monitor until shutdown
+    fn swapfile_run(&mut self) -> Result<()>;
+    /// Tell systemd the service is ready
+    fn notify_ready(&mut self);
+    /// Block until shutdown is requested
+    fn wait_for_shutdown(&mut self);
+}
+
+/// Per-mode startup and monitoring policy
+pub trait SwapModeRunner {
+    /// Mode name for logs
+    fn name(&self) -> &'static str;
+    /// Set up the mode's subsystems and block until shutdown
+    fn run(&self, config: &Config, subsystems: &mut dyn Subsystems) -> Result<()>;
+}
+
+/// ZramSwapfc: zram pool as primary + swapfc as overflow backing
+pub struct ZramSwapfcRunner;
+
+impl SwapModeRunner for ZramSwapfcRunner {
+    fn name(&self) -> &'static str {
+        "zram+swapfc"
+    }
+
+    fn run(&self, config: &Config, subsystems: &mut dyn Subsystems) -> Result<()> {
+        // Desktop-optimized mode: zram pool for speed + swapfc for overflow
+        // zram is faster than zswap because it's a dedicated block device
+
+        // Disable zswap when using zram (per kernel documentation)
+        subsystems.disable_zswap_for_zram();
+
+        // Start zram pool (primary high-priority swap)
+        info!("Setting up ZramPool as primary swap...");
+        let zram_ok = match subsystems.zram_pool_init(config) {
+            Ok(()) => match subsystems.zram_pool_start() {
+                Ok(()) => {
+                    // Run pool monitor in background thread (handles expansion/contraction)
+                    subsystems.zram_pool_monitor(true)?;
+                    true
+                }
+                Err(e) => {
+                    error!("ZramPool: start_primary failed: {}", e);
+                    false
+                }
+            },
+            Err(e) => {
+                error!("ZramPool: init failed: {}", e);
+                false
+            }
+        };
+
+        // Create swapfc for overflow (lower priority) - non-critical
+        info!("Setting up swapfc as secondary swap for overflow...");
+        match subsystems.swapfile_init(config, false) {
+            Ok(()) => {
+                // Create initial swap file to prevent OOM when zram fills.
+                info!("Creating initial swap file for zram overflow protection...");
+                if let Err(e) = subsystems.swapfile_create_initial() {
+                    warn!(
+                        "Initial swapfile creation failed: {} (will retry on demand)",
+                        e
+                    );
+                }
+                if let Err(e) = subsystems.swapfile_run() {
+                    warn!("Swapfile monitor exited: {}", e);
+                }
+            }
+            Err(e) => {
+                if zram_ok {
+                    warn!("Swapfile setup failed, continuing with zram only: {}", e);
+                    subsystems.notify_ready();
+                    subsystems.wait_for_shutdown();
+                } else {
+                    error!("Both zram and swapfile failed");
+                    return Err(e);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// ZswapSwapfc: create swapfile first (zswap needs a backing swap device), then enable zswap
+pub struct ZswapSwapfcRunner;
+
+impl SwapModeRunner for ZswapSwapfcRunner {
+    fn name(&self) -> &'static str {
+        "zswap+swapfc"
+    }
+
+    fn run(&self, config: &Config, subsystems: &mut dyn Subsystems) -> Result<()> {
+        if let Err(e) = subsystems.swapfile_init(config, true) {
+            error!("Swapfile setup failed (required for zswap backing): {}", e);
+            return Err(e);
+        }
+
+        info!("Creating initial swap file for zswap backing...");
+        subsystems.swapfile_create_initial()?;
+
+        // Now configure zswap (after swap is available) - non-critical, but
+        // once it is set up `stop` needs its backup
+        match subsystems.zswap_start(config) {
+            Ok(()) => subsystems.zswap_save_backup()?,
+            Err(e) => warn!("Zswap setup failed, continuing with swapfile only: {}", e),
+        }
+
+        subsystems.zswap_spawn_monitor();
+        subsystems.swapfile_run()
+    }
+}
+
+/// ZramOnly: zram pool only, no swap files
+pub struct ZramOnlyRunner;
+
+impl SwapModeRunner for ZramOnlyRunner {
+    fn name(&self) -> &'static str {
+        "zram"
+    }
+
+    fn run(&self, config: &Config, subsystems: &mut dyn Subsystems) -> Result<()> {
+        subsystems.disable_zswap_for_zram();
+
+        match subsystems.zram_pool_init(config) {
+            Ok(()) => {
+                if let Err(e) = subsystems.zram_pool_start() {
+                    error!("ZramPool: {}", e);
+                }
+                subsystems.notify_ready();
+                info!("ZramPool setup complete");
+
+                if let Err(e) = subsystems.zram_pool_monitor(false) {
+                    warn!("ZramPool monitor error: {}", e);
+                }
+            }
+            Err(e) => {
+                error!("ZramPool: {}", e);
+                subsystems.notify_ready();
+                subsystems.wait_for_shutdown();
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Manual mode: legacy mode driven by explicit config flags
+pub struct ManualRunner;
+
+impl SwapModeRunner for ManualRunner {
+    fn name(&self) -> &'static str {
+        "manual"
+    }
+
+    fn run(&self, config: &Config, subsystems: &mut dyn Subsystems) -> Result<()> {
+        warn!("Manual mode: using explicit config flags (zram_enabled, zswap_enabled, swapfc_enabled)");
+
+        if config.get_bool("zswap_enabled") {
+            match subsystems.zswap_start(config) {
+                Ok(()) => subsystems.zswap_save_backup()?,
+                Err(e) => error!("Zswap: {}", e),
+            }
+        }
+
+        if config.get_bool("zram_enabled") {
+            if !config.get_bool("zswap_enabled") {
+                subsystems.disable_zswap_for_zram();
+            }
+            if let Err(e) = subsystems.zram_start_single(config) {
+                error!("Zram: {}", e);
+            }
+        }
+
+        if config.get_bool("swapfile_enabled") {
+            subsystems.swapfile_init(config, false)?;
+            subsystems.swapfile_create_initial()?;
+            subsystems.swapfile_run()?;
+        } else {
+            subsystems.notify_ready();
+            info!("Manual mode swap setup complete");
+            subsystems.wait_for_shutdown();
+        }
+        Ok(())
+    }
+}
+
+/// Disabled: nothing to manage, report ready and exit
+pub struct DisabledRunner;
+
+impl SwapModeRunner for DisabledRunner {
+    fn name(&self) -> &'static str {
+        "disabled"
+    }
+
+    fn run(&self, _config: &Config, subsystems: &mut dyn Subsystems) -> Result<()> {
+        info!("Swap management disabled, service will exit");
+        subsystems.notify_ready();
+        Ok(())
+    }
+}
+
+/// Subsystems backed by the real zram, zswap and swap file managers
+#[derive(Default)]
+pub struct SystemSubsystems {
+    pool: Option<ZramPool>,
+    swapfile: Option<SwapFile>,
+    zswap_backup: Option<ZswapBackup>,
+    on_ready: Option<Box<dyn FnMut() + Send>>,
+}
+
+impl SystemSubsystems {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Also call `f` when the initial swap is ready (embedded managers)
+    pub fn with_ready_callback(mut self, f: Box<dyn FnMut() + Send>) -> Self {
+        self.on_ready = Some(f);
+        self
+    }
+}
+
+impl Subsystems for SystemSubsystems {
+    fn disable_zswap_for_zram(&mut self) {
+        crate::zswap::disable_for_zram();
+    }
+
+    fn zram_pool_init(&mut self, config: &Config) -> Result<()> {
+        self.pool = Some(ZramPool::new(config)?);
+        Ok(())
+    }
+
+    fn zram_pool_start(&mut self) -> Result<()> {
+        let pool = self.pool.as_mut().ok_or(ModeError::NotInitialized("zram pool"))?;
+        pool.start_primary()?;
+        Ok(())
+    }
+
+    fn zram_pool_monitor(&mut self, background: bool) -> Result<()> {
+        if background {
+            let mut pool = self.pool.take().ok_or(ModeError::NotInitialized("zram pool"))?;
+            crate::context::spawn(move || {
+                if let Err(e) = pool.run_monitor() {
+                    warn!("ZramPool monitor error: {}", e);
+                }
+            });
+            return Ok(());
+        }
+        let pool = self.pool.as_mut().ok_or(ModeError::NotInitialized("zram pool"))?;
+        pool.run_monitor()?;
+        Ok(())
+    }
+
+    fn zram_start_single(&mut self, config: &Config) -> Result<()> {
+        crate::zram::start(config)?;
+        Ok(())
+    }
+
+    fn zswap_start(&mut self, config: &Config) -> Result<()> {
+        self.zswap_backup = Some(crate::zswap::start(config)?);
+        Ok(())
+    }
+
+    fn zswap_save_backup(&mut self) -> Result<()> {
+        let backup = self.zswap_backup.as_ref().ok_or(ModeError::NotInitialized("zswap"))?;
+        backup.save()?;
+        Ok(())
+    }
+
+    fn zswap_spawn_monitor(&mut self) {
+        crate::zswap::spawn_monitor();
+    }
+
+    fn swapfile_init(&mut self, config: &Config, zswap_mode: bool) -> Result<()> {
+        let mut swapfile = SwapFile::new(config)?;
+        if zswap_mode {
+            swapfile.enable_zswap_mode();
+        }
+        self.swapfile = Some(swapfile);
+        Ok(())
+    }
+
+    fn swapfile_create_initial(&mut self) -> Result<()> {
+        let swapfile = self.swapfile.as_mut().ok_or(ModeError::NotInitialized("swapfile"))?;
+        swapfile.create_initial_swap()?;
+        Ok(())
+    }
+
+    fn swapfile_run(&mut self) -> Result<()> {
+        let swapfile = self.swapfile.as_mut().ok_or(ModeError::NotInitialized("swapfile"))?;
+        swapfile.run()?;
+        Ok(())
+    }
+
+    fn notify_ready(&mut self) {
+        crate::systemd::notify_ready();
+        if let Some(on_ready) = self.on_ready.as_mut() {
+            on_ready();
+        }
+    }
+
+    fn wait_for_shutdown(&mut self) {
+        while !crate::is_shutdown() {
+            std::thread::sleep(Duration::from_secs(60));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    /// Records calls and fails the steps listed in `fail`
+    #[derive(Default)]
+    struct MockSubsystems {
+        calls: Vec<&'static str>,
+        fail: Vec<&'static str>,
+    }
+
+    impl MockSubsystems {
+        fn failing(fail: &[&'static str]) -> Self {
+            Self {
+                calls: Vec::new(),
+                fail: fail.to_vec(),
+            }
+        }
+
+        fn step(&mut self, name: &'static str) -> Result<()> {
+            self.calls.push(name);
+            if self.fail.contains(&name) {
+                Err(ModeError::NotInitialized(name))
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    impl Subsystems for MockSubsystems {
+        fn disable_zswap_for_zram(&mut self) {
+            self.calls.push("disable_zswap");
+        }
+        fn zram_pool_init(&mut self, _config: &Config) -> Result<()> {
+            self.step("zram_init")
+        }
+        fn zram_pool_start(&mut self) -> Result<()> {
+            self.step("zram_start")
+        }
+        fn zram_pool_monitor(&mut self, background: bool) -> Result<()> {
+            self.step(if background { "zram_monitor_bg" } else { "zram_monitor" })
+        }
+        fn zram_start_single(&mut self, _config: &Config) -> Result<()> {
+            self.step("zram_single")
+        }
+        fn zswap_start(&mut self, _config: &Config) -> Result<()> {
+            self.step("zswap_start")
+        }
+        fn zswap_save_backup(&mut self) -> Result<()> {
+            self.step("zswap_backup")
+        }
+        fn zswap_spawn_monitor(&mut self) {
+            self.calls.push("zswap_monitor");
+        }
+        fn swapfile_init(&mut self, _config: &Config, zswap_mode: bool) -> Result<()> {
+            self.step(if zswap_mode { "swapfile_init_zswap" } else { "swapfile_init" })
+        }
+        fn swapfile_create_initial(&mut self) -> Result<()> {
+            self.step("swapfile_create")
+        }
+        fn swapfile_run(&mut self) -> Result<()> {
+            self.step("swapfile_run")
+        }
+        fn notify_ready(&mut self) {
+            self.calls.push("ready");
+        }
+        fn wait_for_shutdown(&mut self) {
+            self.calls.push("wait");
+        }
+    }
+
+    fn config(pairs: &[(&str, &str)]) -> Config {
+        let values: HashMap<String, String> = pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        Config::from_values(values)
+    }
+
+    fn run(mode: SwapMode, config: &Config, mock: &mut MockSubsystems) -> Result<()> {
+        mode.runner().expect("concrete mode").run(config, mock)
+    }
+
+    // ── SwapMode parsing ─────────────────────────────────────────────────────
+
+    #[test]
+    fn mode_aliases() {
+        let cases = [
+            ("zram+swapfc", SwapMode::ZramSwapfc),
+            ("zram+swapfile", SwapMode::ZramSwapfc),
+            ("ZSWAP", SwapMode::ZswapSwapfc),
+            ("zswap_loopfile", SwapMode::ZswapSwapfc),
+            ("zram_only", SwapMode::ZramOnly),
+            ("manual", SwapMode::Manual),
+            ("disabled", SwapMode::Disabled),
+            ("bogus", SwapMode::Auto),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(SwapMode::from_config(&config(&[("swap_mode", value)])), expected);
+        }
+        assert_eq!(SwapMode::from_config(&config(&[])), SwapMode::Auto);
+    }
+
+    #[test]
+    fn auto_has_no_runner() {
+        assert!(SwapMode::Auto.runner().is_none());
+    }
+
+    // ── Runners ──────────────────────────────────────────────────────────────
+
+    #[test]
+    fn zram_swapfc_happy_path() {
+        let mut mock = MockSubsystems::default();
+        run(SwapMode::ZramSwapfc, &config(&[]), &mut mock).unwrap();
+        assert_eq!(
+            mock.calls,
+            [
+                "disable_zswap",
+                "zram_init",
+                "zram_start",
+                "zram_monitor_bg",
+                "swapfile_init",
+                "swapfile_create",
+                "swapfile_run"
+            ]
+        );
+    }
+
+    #[test]
+    fn zram_swapfc_falls_back_to_zram_only() {
+        let mut mock = MockSubsystems::failing(&["swapfile_init"]);
+        run(SwapMode::ZramSwapfc, &config(&[]), &mut mock).unwrap();
+        assert!(mock.calls.ends_with(&["swapfile_init", "ready", "wait"]));
+    }
+
+    #[test]
+    fn zram_swapfc_fails_when_both_fail() {
+        let mut mock = MockSubsystems::failing(&["zram_init", "swapfile_init"]);
+        assert!(run(SwapMode::ZramSwapfc, &config(&[]), &mut mock).is_err());
+        assert!(!mock.calls.contains(&"zram_start"));
+    }
+
+    #[test]
+    fn zswap_swapfc_creates_backing_before_zswap() {
+        let mut mock = MockSubsystems::failing(&["zswap_start"]);
+        run(SwapMode::ZswapSwapfc, &config(&[]), &mut mock).unwrap();
+        assert_eq!(
+            mock.calls,
+            [
+                "swapfile_init_zswap",
+                "swapfile_create",
+                "zswap_start",
+                "zswap_monitor",
+                "swapfile_run"
+            ]
+        );
+    }
+
+    #[test]
+    fn zswap_swapfc_fails_without_a_zswap_backup() {
+        let mut mock = MockSubsystems::failing(&["zswap_backup"]);
+        assert!(run(SwapMode::ZswapSwapfc, &config(&[]), &mut mock).is_err());
+        assert!(mock.calls.ends_with(&["zswap_start", "zswap_backup"]));
+    }
+
+    #[test]
+    fn zswap_swapfc_requires_initial_swapfile() {
+        let mut mock = MockSubsystems::failing(&["swapfile_create"]);
+        assert!(run(SwapMode::ZswapSwapfc, &config(&[]), &mut mock).is_err());
+        assert!(!mock.calls.contains(&"zswap_start"));
+    }
+
+    #[test]
+    fn zram_only_keeps_monitoring_after_start_failure() {
+        let mut mock = MockSubsystems::failing(&["zram_start"]);
+        run(SwapMode::ZramOnly, &config(&[]), &mut mock).unwrap();
+        assert!(mock.calls.ends_with(&["zram_start", "ready", "zram_monitor"]));
+    }
+
+    #[test]
+    fn manual_follows_flags() {
+        let cfg = config(&[("zram_enabled", "1"), ("swapfile_enabled", "0")]);
+        let mut mock = MockSubsystems::default();
+        run(SwapMode::Manual, &cfg, &mut mock).unwrap();
+        assert_eq!(mock.calls, ["disable_zswap", "zram_single", "ready", "wait"]);
+    }
+
+    #[test]
+    fn disabled_only_notifies() {
+        let mut mock = MockSubsystems::default();
+        run(SwapMode::Disabled, &config(&[]), &mut mock).unwrap();
+        assert_eq!(mock.calls, ["ready"]);
+    }
+}
@@ -0,0 +1,223 @@
+// systemd-oomd coordination for systemd-swap
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// systemd-oomd kills cgroups once swap usage passes SwapUsedLimit on units
+// with ManagedOOMSwap=kill. If swap files only expand close to that limit
+// (or are still in cooldown), oomd kills apps that one more swap file would
+// have saved. With oomd_align=1 the expansion trigger is moved to leave
+// OOMD_HEADROOM points below the limit, and a guard trigger expands on the
+// short emergency cooldown as usage approaches it.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use crate::exec::TimedCommand;
+
+/// Percentage points of swap usage kept between our trigger and oomd's limit
+pub const OOMD_HEADROOM: u8 = 15;
+/// Within this many points of the limit, expansion ignores the normal cooldown
+pub const OOMD_GUARD_MARGIN: u8 = 5;
+
+const OOMD_CONF: &str = "/etc/systemd/oomd.conf";
+/// Drop-in directories, highest priority first
+const OOMD_CONF_DIRS: &[&str] = &[
+    "/etc/systemd/oomd.conf.d",
+    "/run/systemd/oomd.conf.d",
+    "/usr/lib/systemd/oomd.conf.d",
+];
+/// Slices where distributions enable ManagedOOMSwap=kill
+const SWAP_KILL_SLICES: &[&str] = &["-.slice", "system.slice", "user.slice"];
+
+/// What systemd-oomd is doing on this system
+#[derive(Debug, Clone, PartialEq)]
+pub struct OomdStatus {
+    pub running: bool,
+    /// Swap usage (percent) above which oomd kills under ManagedOOMSwap=kill
+    pub swap_used_limit: u8,
+    /// DefaultMemoryPressureLimit as configured (e.g. "60%")
+    pub pressure_limit: String,
+    /// DefaultMemoryPressureDurationSec as configured (e.g. "30s")
+    pub pressure_duration: String,
+    /// Slices with ManagedOOMSwap=kill
+    pub swap_kill_units: Vec<String>,
+}
+
+impl Default for OomdStatus {
+    fn default() -> Self {
+        Self {
+            running: false,
+            swap_used_limit: 90,
+            pressure_limit: "60%".to_string(),
+            pressure_duration: "30s".to_string(),
+            swap_kill_units: Vec::new(),
+        }
+    }
+}
+
+impl OomdStatus {
+    /// Detect systemd-oomd and read its configuration
+    pub fn detect() -> Self {
+        let mut status = Self {
+            running: oomd_running(),
+            ..Self::default()
+        };
+        if !status.running {
+            return status;
+        }
+        for path in conf_files() {
+            if let Ok(content) = fs::read_to_string(&path) {
+                status.apply_conf(&content);
+            }
+        }
+        status.swap_kill_units = SWAP_KILL_SLICES
+            .iter()
+            .filter(|unit| managed_oom_swap(unit).as_deref() == Some("kill"))
+            .map(|unit| unit.to_string())
+            .collect();
+        status
+    }
+
+    /// Apply the [OOM] section of an oomd.conf file
+    fn apply_conf(&mut self, content: &str) {
+        let mut in_oom = false;
+        for line in content.lines().map(str::trim) {
+            if line.starts_with('[') {
+                in_oom = line == "[OOM]";
+                continue;
+            }
+            if !in_oom || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let value = value.trim();
+            match key.trim() {
+                "SwapUsedLimit" => {
+                    if let Some(limit) = parse_percent(value) {
+                        self.swap_used_limit = limit;
+                    }
+                }
+                "DefaultMemoryPressureLimit" if !value.is_empty() => {
+                    self.pressure_limit = value.to_string()
+                }
+                "DefaultMemoryPressureDurationSec" if !value.is_empty() => {
+                    self.pressure_duration = value.to_string()
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Whether oomd will kill based on swap usage
+    pub fn swap_kill_active(&self) -> bool {
+        self.running && !self.swap_kill_units.is_empty()
+    }
+
+    /// Lowest swap free percentage that still leaves OOMD_HEADROOM before the kill limit
+    pub fn aligned_free_swap_perc(&self, configured: u8) -> u8 {
+        let floor = 100u8.saturating_sub(self.swap_used_limit).saturating_add(OOMD_HEADROOM);
+        configured.max(floor.min(100))
+    }
+
+    /// Free swap percentage below which expansion ignores the normal cooldown
+    pub fn guard_free_swap_perc(&self) -> u8 {
+        100u8
+            .saturating_sub(self.swap_used_limit)
+            .saturating_add(OOMD_GUARD_MARGIN)
+            .min(100)
+    }
+}
+
+/// Parse "90%" or "90" (oomd also accepts permille/permyriad, rounded here)
+fn parse_percent(value: &str) -> Option<u8> {
+    let parsed = if let Some(v) = value.strip_suffix('%') {
+        v.trim().parse::<f64>().ok()
+    } else if let Some(v) = value.strip_suffix('‰') {
+        v.trim().parse::<f64>().ok().map(|v| v / 10.0)
+    } else if let Some(v) = value.strip_suffix('‱') {
+        v.trim().parse::<f64>().ok().map(|v| v / 100.0)
+    } else {
+        value.parse::<f64>().ok()
+    }?;
+    (0.0..=100.0).contains(&parsed).then_some(parsed.round() as u8)
+}
+
+fn oomd_running() -> bool {
+    let Ok(entries) = fs::read_dir("/proc") else {
+        return false;
+    };
+    entries.flatten().any(|entry| {
+        fs::read_to_string(entry.path().join("comm"))
+            .map(|comm| comm.trim() == "systemd-oomd")
+            .unwrap_or(false)
+    })
+}
+
+/// oomd.conf followed by drop-ins in filename order; /etc shadows /run shadows /usr/lib
+fn conf_files() -> Vec<std::path::PathBuf> {
+    let mut files = vec![Path::new(OOMD_CONF).to_path_buf()];
+    let mut dropins = BTreeMap::new();
+    for dir in OOMD_CONF_DIRS.iter().rev() {
+        let Ok(entries) = fs::read_dir(dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().is_some_and(|ext| ext == "conf") {
+                dropins.insert(entry.file_name(), path);
+            }
+        }
+    }
+    files.extend(dropins.into_values());
+    files
+}
+
+fn managed_oom_swap(unit: &str) -> Option<String> {
+    let output = Command::new("systemctl")
+        .args(["show", "--value", "-p", "ManagedOOMSwap", "--", unit])
+        .stderr(Stdio::null())
+        .output_timed()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_oom_section_only() {
+        let mut status = OomdStatus::default();
+        status.apply_conf(
+            "[OOM]\n\
+             SwapUsedLimit=80%\n\
+             #SwapUsedLimit=10%\n\
+             DefaultMemoryPressureDurationSec=20s\n\
+             [Other]\n\
+             SwapUsedLimit=5%\n",
+        );
+        assert_eq!(status.swap_used_limit, 80);
+        assert_eq!(status.pressure_duration, "20s");
+        assert_eq!(status.pressure_limit, "60%");
+        assert_eq!(parse_percent("955‰"), Some(96));
+        assert_eq!(parse_percent("150%"), None);
+    }
+
+    #[test]
+    fn alignment_keeps_headroom_below_kill_limit() {
+        let status = OomdStatus {
+            swap_used_limit: 70,
+            ..OomdStatus::default()
+        };
+        // 30% free at the limit, expansion must start at 45% free
+        assert_eq!(status.aligned_free_swap_perc(40), 45);
+        assert_eq!(status.aligned_free_swap_perc(50), 50);
+        assert_eq!(status.guard_free_swap_perc(), 35);
+    }
+}
@@ -0,0 +1,384 @@
+// Teardown plan for systemd-swap
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// `stop` first collects everything it is about to touch into a StopPlan and
+// then executes it, so `stop --dry-run` can print the very same plan without
+// changing anything: units that are swapped off, loop devices detached, zram
+// devices released, zswap parameters and sysctls restored, and files removed.
+
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+use crate::config::Config;
+use crate::context::work_dir;
+use crate::defaults;
+use crate::helpers::{
+    find_swap_units, force_remove, format_size, get_tag_from_swap_unit, get_what_from_swap_unit, tag_subsystem,
+    write_sysfs,
+};
+use crate::hooks::{hook_path, HookPoint};
+use crate::state::DaemonState;
+use crate::wipe::WIPE_PREFIX;
+use crate::{info, warn};
+
+/// Subsystems whose swap units `stop` tears down, in teardown order.
+/// `swapfc` is the legacy name of the swap file subsystem.
+const SUBSYSTEMS: &[&str] = &["burst", "swapfile", "swapfc", "zram"];
+
+/// What happens to a device after it has been swapped off
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cleanup {
+    None,
+    /// Detach the loop device so it does not keep a "(deleted)" backing file
+    DetachLoop { backing_file: Option<String> },
+    /// Swap file outside the swap file directory
+    RemoveFile,
+    /// Reset and hot-remove the zram device
+    ReleaseZram,
+}
+
+/// One managed swap unit and its device
+#[derive(Debug, Clone)]
+pub struct SwapTeardown {
+    pub subsystem: &'static str,
+    pub unit: String,
+    pub device: String,
+    /// Size and usage from /proc/swaps, None if the device is not active
+    pub size: Option<u64>,
+    pub used: Option<u64>,
+    pub cleanup: Cleanup,
+}
+
+/// A kernel setting restored to the value saved before the daemon changed it
+#[derive(Debug, Clone)]
+pub struct Restore {
+    /// sysfs path (zswap) or sysctl name
+    pub target: String,
+    pub current: Option<String>,
+    pub original: String,
+}
+
+/// Everything `stop` will do, in order
+#[derive(Debug, Clone, Default)]
+pub struct StopPlan {
+    pub hook: Option<PathBuf>,
+    pub swaps: Vec<SwapTeardown>,
+    pub zswap: Vec<Restore>,
+    pub sysctls: Vec<Restore>,
+    /// /sys/power settings (hibernation image size)
+    pub power: Vec<Restore>,
+    pub work_dir: bool,
+    /// Files in the swap file directories, with the disk space they use
+    pub files: Vec<(PathBuf, u64)>,
+}
+
+/// (size, used) in bytes of an active swap device
+fn swap_usage(swaps: &str, device: &str) -> Option<(u64, u64)> {
+    swaps.lines().skip(1).find_map(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 5 || fields[0] != device {
+            return None;
+        }
+        Some((fields[2].parse::<u64>().ok()? * 1024, fields[3].parse::<u64>().ok()? * 1024))
+    })
+}
+
+fn loop_backing_file(device: &str) -> Option<String> {
+    let name = Path::new(device).file_name()?.to_str()?;
+    fs::read_to_string(format!("/sys/block/{}/loop/backing_file", name))
+        .ok()
+        .map(|s| s.trim().to_string())
+}
+
+fn cleanup_for(subsystem: &str, device: &str) -> Cleanup {
+    match subsystem {
+        "swapfile" if device.starts_with("/dev/loop") => Cleanup::DetachLoop {
+            backing_file: loop_backing_file(device),
+        },
+        "swapfile" | "burst" if Path::new(device).is_file() => Cleanup::RemoveFile,
+        "zram" => Cleanup::ReleaseZram,
+        _ => Cleanup::None,
+    }
+}
+
+/// Files in a swap file directory. Files still waiting to be wiped are kept
+/// when wiping is on, so the next start finishes the wipe.
+fn dir_files(dir: &str, keep_wipes: bool) -> Vec<(PathBuf, u64)> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut files: Vec<(PathBuf, u64)> = entries
+        .flatten()
+        .filter(|entry| !entry.file_type().is_ok_and(|t| t.is_dir()))
+        .filter(|entry| !(keep_wipes && entry.file_name().to_string_lossy().starts_with(WIPE_PREFIX)))
+        .map(|entry| {
+            let disk = entry.metadata().map(|m| m.blocks() * 512).unwrap_or(0);
+            (entry.path(), disk)
+        })
+        .collect();
+    files.sort();
+    files
+}
+
+impl StopPlan {
+    /// Collect what `stop` would do. On init (before `start`) swap is left
+    /// active and swap files are kept, so they can be adopted.
+    pub fn build(config: &Config, on_init: bool) -> Self {
+        let mut plan = Self {
+            work_dir: Path::new(&work_dir()).exists(),
+            ..Self::default()
+        };
+
+        if !on_init {
+            plan.hook = hook_path(config, HookPoint::BeforeStop);
+            let swaps = fs::read_to_string("/proc/swaps").unwrap_or_default();
+            for unit in find_swap_units() {
+                let Some(tag) = get_tag_from_swap_unit(&unit) else {
+                    continue;
+                };
+                let Some(subsystem) = SUBSYSTEMS.iter().find(|s| **s == tag_subsystem(&tag)) else {
+                    continue;
+                };
+                let Some(device) = get_what_from_swap_unit(&unit) else {
+                    continue;
+                };
+                let usage = swap_usage(&swaps, &device);
+                plan.swaps.push(SwapTeardown {
+                    subsystem,
+                    cleanup: cleanup_for(subsystem, &device),
+                    unit,
+                    device,
+                    size: usage.map(|u| u.0),
+                    used: usage.map(|u| u.1),
+                });
+            }
+            plan.swaps
+                .sort_by_key(|s| SUBSYSTEMS.iter().position(|n| *n == s.subsystem));
+        }
+
+        if let Ok(entries) = fs::read_dir(format!("{}/zswap_backup", work_dir())) {
+            for entry in entries.flatten() {
+                if let Ok(content) = fs::read_to_string(entry.path()) {
+                    if let Some((path, value)) = content.split_once('=') {
+                        plan.zswap.push(Restore {
+                            current: fs::read_to_string(path).ok().map(|v| v.trim().to_string()),
+                            target: path.to_string(),
+                            original: value.to_string(),
+                        });
+                    }
+                }
+            }
+        }
+
+        plan.sysctls = crate::sysctl::backups()
+            .into_iter()
+            .map(|(name, original)| Restore {
+                current: crate::sysctl::read(&name).ok(),
+                target: name,
+                original,
+            })
+            .collect();
+
+        if let Some(original) = crate::hibernate::backup_value() {
+            plan.power.push(Restore {
+                target: crate::hibernate::IMAGE_SIZE.to_string(),
+                current: fs::read_to_string(crate::hibernate::IMAGE_SIZE).ok().map(|v| v.trim().to_string()),
+                original,
+            });
+        }
+
+        if !on_init {
+            // The daemon's directory, which autoconfig may have moved
+            let swapfile_path = DaemonState::recorded_swapfile_path()
+                .unwrap_or_else(|| config.get("swapfile_path").unwrap_or(defaults::SWAPFILE_PATH).to_string());
+            let swapfile_path = swapfile_path.as_str();
+            let keep_wipes = config.get_bool("swapfile_wipe_on_remove");
+            plan.files = dir_files(swapfile_path, keep_wipes);
+            plan.files.extend(dir_files(&format!("{}/burst", swapfile_path), keep_wipes));
+            let legacy_swapfc_path = config.get("swapfc_path").unwrap_or("/swapfc/swapfile");
+            if legacy_swapfc_path != swapfile_path {
+                plan.files.extend(dir_files(legacy_swapfc_path, keep_wipes));
+            }
+        }
+        plan
+    }
+
+    /// Whether stop would leave the system untouched
+    pub fn is_empty(&self) -> bool {
+        self.hook.is_none()
+            && self.swaps.is_empty()
+            && self.zswap.is_empty()
+            && self.sysctls.is_empty()
+            && self.power.is_empty()
+            && !self.work_dir
+            && self.files.is_empty()
+    }
+
+    /// Human-readable listing for `stop --dry-run`
+    pub fn describe(&self) -> String {
+        if self.is_empty() {
+            return "Nothing to stop: no managed swap, saved settings or swap files found\n".to_string();
+        }
+        let mut out = String::new();
+        if let Some(hook) = &self.hook {
+            out += &format!("Run exec_before_stop hook:\n  {}\n", hook.display());
+        }
+        if !self.swaps.is_empty() {
+            out += "Swapoff:\n";
+            let total_used: u64 = self.swaps.iter().filter_map(|s| s.used).sum();
+            for swap in &self.swaps {
+                let usage = match (swap.size, swap.used) {
+                    (Some(size), Some(used)) => format!("{} of {} used", format_size(used), format_size(size)),
+                    _ => "not active".to_string(),
+                };
+                out += &format!("  {:<9} {:<28} {}\n", swap.subsystem, swap.device, usage);
+                out += &format!("            unit {}\n", swap.unit);
+                match &swap.cleanup {
+                    Cleanup::DetachLoop { backing_file } => {
+                        out += &format!(
+                            "            detach loop device, restore queue settings (backing file {})\n",
+                            backing_file.as_deref().unwrap_or("unknown")
+                        )
+                    }
+                    Cleanup::RemoveFile => out += "            remove swap file\n",
+                    Cleanup::ReleaseZram => out += "            release zram device\n",
+                    Cleanup::None => {}
+                }
+            }
+            out += &format!(
+                "  {} in use must be paged back into RAM or other swap\n",
+                format_size(total_used)
+            );
+        }
+        for (title, restores) in [
+            ("Restore zswap:", &self.zswap),
+            ("Restore sysctl:", &self.sysctls),
+            ("Restore power:", &self.power),
+        ] {
+            if restores.is_empty() {
+                continue;
+            }
+            out += title;
+            out += "\n";
+            for restore in restores {
+                out += &format!(
+                    "  {} {} -> {}\n",
+                    restore.target,
+                    restore.current.as_deref().unwrap_or("?"),
+                    restore.original
+                );
+            }
+        }
+        if self.work_dir {
+            out += &format!("Remove working directory:\n  {}\n", work_dir());
+        }
+        if !self.files.is_empty() {
+            out += "Remove files:\n";
+            for (path, disk) in &self.files {
+                out += &format!("  {:<40} {}\n", path.display(), format_size(*disk));
+            }
+            let total: u64 = self.files.iter().map(|f| f.1).sum();
+            out += &format!("  {} of disk space freed\n", format_size(total));
+        }
+        out
+    }
+
+    /// Carry out the plan. The before-stop hook is run by the caller.
+    pub fn execute(&self) {
+        for swap in &self.swaps {
+            info!("{}: swapoff {}", swap.subsystem, swap.device);
+            let _ = crate::systemd::swapoff(&swap.device);
+            force_remove(&swap.unit, true);
+            match swap.cleanup {
+                Cleanup::DetachLoop { .. } => {
+                    // Detach the loop device after swapoff to prevent it from
+                    // persisting with a "(deleted)" backing file reference.
+                    crate::loopdev::detach(&swap.device);
+                }
+                Cleanup::RemoveFile => force_remove(&swap.device, true),
+                Cleanup::ReleaseZram => {
+                    let _ = crate::zram::release(&swap.device);
+                }
+                Cleanup::None => {}
+            }
+        }
+
+        if !self.zswap.is_empty() {
+            info!("Zswap: restore configuration: start");
+            for restore in &self.zswap {
+                if let Err(e) = write_sysfs(&restore.target, &restore.original) {
+                    warn!("Failed to restore {}: {}", restore.target, e);
+                }
+            }
+            info!("Zswap: restore configuration: complete");
+        }
+
+        // Restore kernel tunables changed by the daemon
+        crate::sysctl::restore_all();
+        for restore in &self.power {
+            if let Err(e) = write_sysfs(&restore.target, &restore.original) {
+                warn!("Failed to restore {}: {}", restore.target, e);
+            }
+        }
+
+        info!("Removing working directory...");
+        crate::recovery::mark_stopping();
+        let _ = fs::remove_dir_all(work_dir());
+
+        for (path, _) in &self.files {
+            force_remove(path, true);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_usage_of_active_device() {
+        let swaps = "Filename\tType\tSize\tUsed\tPriority\n\
+                     /dev/zram0  partition\t8388604\t2048\t100\n\
+                     /dev/loop1  partition\t1048572\t0\t-2\n";
+        assert_eq!(swap_usage(swaps, "/dev/zram0"), Some((8388604 * 1024, 2048 * 1024)));
+        assert_eq!(swap_usage(swaps, "/dev/loop1"), Some((1048572 * 1024, 0)));
+        assert_eq!(swap_usage(swaps, "/dev/zram1"), None);
+    }
+
+    #[test]
+    fn empty_plan_says_so() {
+        let plan = StopPlan::default();
+        assert!(plan.is_empty());
+        assert!(plan.describe().starts_with("Nothing to stop"));
+
+        let plan = StopPlan {
+            files: vec![(PathBuf::from("/swapfile/1"), 512 * 1024 * 1024)],
+            ..StopPlan::default()
+        };
+        let text = plan.describe();
+        assert!(text.contains("/swapfile/1"));
+        assert!(text.contains("disk space freed"));
+    }
+
+    #[test]
+    fn removes_files_where_the_daemon_put_them() {
+        // Immutable root: autoconfig moved swap files to /var/swap, which
+        // swap.conf does not mention
+        let dir = std::env::temp_dir().join(format!("plan-immutable-{}", std::process::id()));
+        let var_swap = dir.join("var/swap");
+        fs::create_dir_all(&var_swap).unwrap();
+        fs::write(var_swap.join("1"), "swap").unwrap();
+        crate::context::enter(crate::context::Context {
+            work_dir: dir.clone(),
+            ..Default::default()
+        });
+        DaemonState::record("zram+swapfile", &var_swap.to_string_lossy()).unwrap();
+
+        let plan = StopPlan::build(&Config::from_values(Default::default()), false);
+        assert!(plan.files.iter().any(|(path, _)| *path == var_swap.join("1")));
+
+        crate::context::leave();
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,254 @@
+// Monitor loop tick timings for systemd-swap
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// Each monitor loop keeps a TickProfiler and opens a Tick guard per
+// iteration. The guard splits the iteration into reading stats, deciding and
+// acting, and records the total when it is dropped (so `continue` is fine).
+// A tick longer than the check interval is logged with its breakdown; when
+// reading stats alone took that long, the loop skips acting on them, since
+// they are already stale. Percentiles are logged and written to WORK_DIR
+// every REPORT_INTERVAL so `status` can show them.
+
+use std::collections::VecDeque;
+use std::fs;
+use std::time::{Duration, Instant};
+
+use crate::context::work_dir;
+use crate::helpers::read_file;
+use crate::{info, warn};
+
+/// Ticks kept for percentiles
+const WINDOW: usize = 256;
+/// How often percentiles are logged and exported
+const REPORT_INTERVAL: Duration = Duration::from_secs(600);
+
+fn ticks_dir() -> String {
+    format!("{}/ticks", work_dir())
+}
+
+/// Part of a tick that time is attributed to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Phase {
+    Stats,
+    Decision,
+    Action,
+}
+
+/// Timing summary of recent ticks
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TickSummary {
+    pub loop_name: String,
+    pub ticks: u64,
+    pub p50_ms: u64,
+    pub p95_ms: u64,
+    pub max_ms: u64,
+    /// Ticks that took longer than the check interval
+    pub overruns: u64,
+}
+
+impl TickSummary {
+    fn to_file(&self) -> String {
+        format!(
+            "ticks={}\np50_ms={}\np95_ms={}\nmax_ms={}\noverruns={}\n",
+            self.ticks, self.p50_ms, self.p95_ms, self.max_ms, self.overruns
+        )
+    }
+
+    fn from_file(loop_name: &str, content: &str) -> Self {
+        let mut summary = Self {
+            loop_name: loop_name.to_string(),
+            ..Self::default()
+        };
+        for (key, value) in content.lines().filter_map(|l| l.split_once('=')) {
+            let value = value.trim().parse().unwrap_or(0);
+            match key {
+                "ticks" => summary.ticks = value,
+                "p50_ms" => summary.p50_ms = value,
+                "p95_ms" => summary.p95_ms = value,
+                "max_ms" => summary.max_ms = value,
+                "overruns" => summary.overruns = value,
+                _ => {}
+            }
+        }
+        summary
+    }
+
+    /// Summaries exported by the running daemon, one per monitor loop
+    pub fn load_all() -> Vec<Self> {
+        let Ok(entries) = fs::read_dir(ticks_dir()) else {
+            return Vec::new();
+        };
+        let mut summaries: Vec<Self> = entries
+            .flatten()
+            .filter_map(|entry| {
+                let name = entry.file_name().to_string_lossy().to_string();
+                Some(Self::from_file(&name, &read_file(entry.path()).ok()?))
+            })
+            .collect();
+        summaries.sort_by(|a, b| a.loop_name.cmp(&b.loop_name));
+        summaries
+    }
+}
+
+/// Nearest-rank percentile of sorted durations, in milliseconds
+fn percentile(sorted: &[Duration], p: usize) -> u64 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let rank = (sorted.len() * p).div_ceil(100).max(1);
+    sorted[rank - 1].as_millis() as u64
+}
+
+/// Tick timings of one monitor loop
+#[derive(Debug)]
+pub struct TickProfiler {
+    loop_name: &'static str,
+    /// Log prefix of the owning loop (e.g. "swapFC")
+    log_prefix: &'static str,
+    window: VecDeque<Duration>,
+    ticks: u64,
+    overruns: u64,
+    last_report: Instant,
+}
+
+impl TickProfiler {
+    pub fn new(loop_name: &'static str, log_prefix: &'static str) -> Self {
+        Self {
+            loop_name,
+            log_prefix,
+            window: VecDeque::with_capacity(WINDOW),
+            ticks: 0,
+            overruns: 0,
+            last_report: Instant::now(),
+        }
+    }
+
+    /// Start timing an iteration expected to finish within `interval`
+    pub fn begin(&mut self, interval: Duration) -> Tick<'_> {
+        let now = Instant::now();
+        Tick {
+            profiler: self,
+            interval,
+            started: now,
+            last_mark: now,
+            phase: Phase::Stats,
+            spent: [Duration::ZERO; 3],
+        }
+    }
+
+    pub fn summary(&self) -> TickSummary {
+        let mut sorted: Vec<Duration> = self.window.iter().copied().collect();
+        sorted.sort();
+        TickSummary {
+            loop_name: self.loop_name.to_string(),
+            ticks: self.ticks,
+            p50_ms: percentile(&sorted, 50),
+            p95_ms: percentile(&sorted, 95),
+            max_ms: sorted.last().map(|d| d.as_millis() as u64).unwrap_or(0),
+            overruns: self.overruns,
+        }
+    }
+
+    fn record(&mut self, total: Duration, overrun: bool) {
+        if self.window.len() == WINDOW {
+            self.window.pop_front();
+        }
+        self.window.push_back(total);
+        self.ticks += 1;
+        if overrun {
+            self.overruns += 1;
+        }
+        if self.last_report.elapsed() >= REPORT_INTERVAL {
+            self.last_report = Instant::now();
+            let summary = self.summary();
+            info!(
+                "{}: tick timings p50={}ms p95={}ms max={}ms over {} ticks, {} overrun(s)",
+                self.log_prefix, summary.p50_ms, summary.p95_ms, summary.max_ms, summary.ticks, summary.overruns
+            );
+            let _ = fs::create_dir_all(ticks_dir());
+            let _ = fs::write(format!("{}/{}", ticks_dir(), self.loop_name), summary.to_file());
+        }
+    }
+}
+
+/// One monitor loop iteration; records its timing when dropped
+pub struct Tick<'a> {
+    profiler: &'a mut TickProfiler,
+    interval: Duration,
+    started: Instant,
+    last_mark: Instant,
+    /// Phase the time since `last_mark` belongs to
+    phase: Phase,
+    spent: [Duration; 3],
+}
+
+impl Tick<'_> {
+    /// Close the current phase; the time since the previous mark belongs to it
+    pub fn mark(&mut self, next: Phase) {
+        let now = Instant::now();
+        self.spent[self.phase as usize] += now - self.last_mark;
+        self.last_mark = now;
+        self.phase = next;
+    }
+
+    /// Whether the tick has already used up its interval (e.g. reading stats
+    /// blocked); acting on the stale stats would only add to the backlog
+    pub fn overrun(&self) -> bool {
+        self.started.elapsed() > self.interval
+    }
+}
+
+impl Drop for Tick<'_> {
+    fn drop(&mut self) {
+        self.mark(self.phase);
+        let total = self.started.elapsed();
+        let overrun = total > self.interval;
+        if overrun {
+            let ms = |d: Duration| d.as_millis();
+            warn!(
+                "{}: monitor tick took {}ms, longer than the {}s check interval (stats {}ms, decision {}ms, actions {}ms)",
+                self.profiler.log_prefix,
+                ms(total),
+                self.interval.as_secs(),
+                ms(self.spent[Phase::Stats as usize]),
+                ms(self.spent[Phase::Decision as usize]),
+                ms(self.spent[Phase::Action as usize])
+            );
+        }
+        self.profiler.record(total, overrun);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percentiles_over_window() {
+        let sorted: Vec<Duration> = (1..=100).map(Duration::from_millis).collect();
+        assert_eq!(percentile(&sorted, 50), 50);
+        assert_eq!(percentile(&sorted, 95), 95);
+        assert_eq!(percentile(&[], 95), 0);
+        assert_eq!(percentile(&sorted[..1], 95), 1);
+    }
+
+    #[test]
+    fn ticks_are_recorded_on_drop() {
+        let mut profiler = TickProfiler::new("test", "Test");
+        {
+            let mut tick = profiler.begin(Duration::from_secs(60));
+            tick.mark(Phase::Decision);
+            assert!(!tick.overrun());
+        }
+        {
+            let tick = profiler.begin(Duration::ZERO);
+            std::thread::sleep(Duration::from_millis(2));
+            assert!(tick.overrun());
+        }
+        let summary = profiler.summary();
+        assert_eq!(summary.ticks, 2);
+        assert_eq!(summary.overruns, 1);
+        let restored = TickSummary::from_file("test", &summary.to_file());
+        assert_eq!(restored, summary);
+    }
+}
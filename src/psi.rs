@@ -0,0 +1,202 @@
+// PSI-triggered monitor wakeups for systemd-swap
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// With the "psi" cargo feature and psi_trigger=1, monitor loops block on a
+// /proc/pressure/memory trigger instead of waking every few seconds: they
+// sleep up to psi_idle_interval while the system is calm and return to their
+// normal cadence for a while after pressure crosses the trigger threshold.
+// Without the feature, or on kernels without PSI, the loops keep polling.
+
+use std::thread;
+use std::time::Duration;
+#[cfg(feature = "psi")]
+use std::time::Instant;
+
+use crate::config::Config;
+use crate::defaults;
+use crate::info;
+
+/// How long loops keep their normal cadence after a pressure event
+#[cfg(feature = "psi")]
+const ACTIVE_PERIOD: Duration = Duration::from_secs(30);
+
+/// Longest single poll, so shutdown requests are noticed promptly
+#[cfg(feature = "psi")]
+const SHUTDOWN_CHECK: Duration = Duration::from_secs(5);
+
+#[cfg(feature = "psi")]
+const PSI_MEMORY: &str = "/proc/pressure/memory";
+
+/// Memory pressure trigger on /proc/pressure/memory
+#[cfg(feature = "psi")]
+struct PsiTrigger {
+    file: std::fs::File,
+}
+
+#[cfg(feature = "psi")]
+impl PsiTrigger {
+    /// Register a "some" trigger: wake when tasks stall `stall_us` within `window_us`
+    fn new(stall_us: u64, window_us: u64) -> std::io::Result<Self> {
+        use std::io::Write;
+        use std::os::unix::fs::OpenOptionsExt;
+
+        let mut file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(PSI_MEMORY)?;
+        file.write_all(format!("some {} {}\0", stall_us, window_us).as_bytes())?;
+        Ok(Self { file })
+    }
+
+    /// Wait up to `timeout`; Ok(true) when the trigger fired
+    fn poll(&self, timeout: Duration) -> nix::Result<bool> {
+        use nix::poll::{poll, PollFd, PollFlags, PollTimeout};
+        use std::os::fd::AsFd;
+
+        let mut fds = [PollFd::new(self.file.as_fd(), PollFlags::POLLPRI)];
+        let timeout = PollTimeout::try_from(timeout).unwrap_or(PollTimeout::MAX);
+        match poll(&mut fds, timeout) {
+            Ok(0) | Err(nix::errno::Errno::EINTR) => Ok(false),
+            Ok(_) => {
+                let revents = fds[0].revents().unwrap_or(PollFlags::empty());
+                if revents.contains(PollFlags::POLLERR) {
+                    return Err(nix::errno::Errno::EIO);
+                }
+                Ok(revents.contains(PollFlags::POLLPRI))
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Sleeps between monitor ticks, waking early on memory pressure when PSI is available
+pub struct PressureWaiter {
+    #[cfg(feature = "psi")]
+    trigger: Option<PsiTrigger>,
+    #[cfg(feature = "psi")]
+    idle_interval: Duration,
+    #[cfg(feature = "psi")]
+    active_until: Option<Instant>,
+}
+
+impl PressureWaiter {
+    /// Set up the waiter for a monitor loop (`owner` is used in log messages)
+    pub fn new(config: &Config, owner: &str) -> Self {
+        let requested = match config.get_opt("psi_trigger") {
+            Some(_) => config.get_bool("psi_trigger"),
+            None => defaults::PSI_TRIGGER,
+        };
+
+        #[cfg(feature = "psi")]
+        {
+            let idle_interval = Duration::from_secs(
+                config
+                    .get_as("psi_idle_interval")
+                    .unwrap_or(defaults::PSI_IDLE_INTERVAL)
+                    .clamp(1, 300),
+            );
+            let trigger = if requested {
+                // Kernel limits: window 500ms..10s, stall no longer than the window
+                let window_us: u64 = config
+                    .get_as("psi_window_us")
+                    .unwrap_or(defaults::PSI_WINDOW_US)
+                    .clamp(500_000, 10_000_000);
+                let stall_us: u64 = config
+                    .get_as("psi_stall_us")
+                    .unwrap_or(defaults::PSI_STALL_US)
+                    .clamp(1, window_us);
+                // Without CAP_SYS_RESOURCE (the unit only keeps CAP_SYS_ADMIN) the
+                // kernel only accepts windows that are multiples of 2s
+                let armed = PsiTrigger::new(stall_us, window_us).or_else(|e| {
+                    let rounded = window_us.div_ceil(2_000_000) * 2_000_000;
+                    if e.raw_os_error() == Some(libc::EINVAL) && rounded != window_us {
+                        PsiTrigger::new(stall_us, rounded)
+                    } else {
+                        Err(e)
+                    }
+                });
+                match armed {
+                    Ok(t) => {
+                        info!(
+                            "{}: PSI trigger armed (some {}us / {}us), idle interval {}s",
+                            owner,
+                            stall_us,
+                            window_us,
+                            idle_interval.as_secs()
+                        );
+                        Some(t)
+                    }
+                    Err(e) => {
+                        info!("{}: PSI trigger unavailable ({}), polling instead", owner, e);
+                        None
+                    }
+                }
+            } else {
+                None
+            };
+            Self {
+                trigger,
+                idle_interval,
+                active_until: None,
+            }
+        }
+
+        #[cfg(not(feature = "psi"))]
+        {
+            if requested {
+                info!("{}: built without PSI support, polling instead", owner);
+            }
+            Self {}
+        }
+    }
+
+    /// Whether pressure fired recently enough to keep the normal cadence
+    #[cfg(feature = "psi")]
+    fn recently_active(&self) -> bool {
+        self.active_until
+            .map(|t| Instant::now() < t)
+            .unwrap_or(false)
+    }
+
+    /// Sleep until the next monitor tick.
+    /// Returns true when woken early by memory pressure.
+    pub fn wait(&mut self, interval: Duration) -> bool {
+        #[cfg(feature = "psi")]
+        if self.trigger.is_some() {
+            let timeout = if self.recently_active() {
+                interval
+            } else {
+                self.idle_interval.max(interval)
+            };
+            let deadline = Instant::now() + timeout;
+            loop {
+                let now = Instant::now();
+                if now >= deadline || crate::is_shutdown() {
+                    return false;
+                }
+                let slice = (deadline - now).min(SHUTDOWN_CHECK);
+                let polled = match self.trigger.as_ref() {
+                    Some(t) => t.poll(slice),
+                    None => Ok(false),
+                };
+                match polled {
+                    Ok(true) => {
+                        self.active_until = Some(Instant::now() + ACTIVE_PERIOD);
+                        return true;
+                    }
+                    Ok(false) => {}
+                    Err(e) => {
+                        crate::warn!("PSI trigger failed ({}), falling back to polling", e);
+                        self.trigger = None;
+                        thread::sleep(deadline.saturating_duration_since(Instant::now()).min(interval));
+                        return false;
+                    }
+                }
+            }
+        }
+
+        thread::sleep(interval);
+        false
+    }
+}
@@ -0,0 +1,119 @@
+// Read-only diagnostics mode for systemd-swap
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// With --read-only, or in a build with the `read-only` feature, the binary
+// only observes: status, health, free, stats, autoconfig, check-config and the
+// other reports work, and nothing on the system is changed. Three choke
+// points enforce it:
+//   commands  main.rs refuses subcommands that change the system before they
+//             run, and the swap manager refuses to start
+//   programs  exec.rs refuses every external program not known to be
+//             read-only (`permits`)
+//   files     helpers.rs refuses writes to sysfs and procfs (write_sysfs),
+//             file writes, removals and links; the zram, sysctl, zswap, loop
+//             and swap file code writes through them
+// A mutating path reached from an observation command thus fails instead of
+// acting. Only the daemon's own runtime files (events, history, pacing) are
+// written directly, and the daemon never starts in read-only mode.
+// Files the CLI would keep for itself (the capability cache) are skipped.
+// The feature cannot be switched off at runtime, which makes a build with it
+// safe to ship in recovery images.
+
+use std::ffi::OsStr;
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static READ_ONLY: AtomicBool = AtomicBool::new(false);
+
+/// Switch this process to read-only mode; there is no way back
+pub fn enable() {
+    READ_ONLY.store(true, Ordering::Release);
+}
+
+/// Whether the process may not change the system
+pub fn is_enabled() -> bool {
+    cfg!(feature = "read-only") || READ_ONLY.load(Ordering::Acquire)
+}
+
+/// Error refusing `action` in read-only mode
+pub fn refused(action: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::PermissionDenied, format!("read-only mode: refusing to {}", action))
+}
+
+/// Fail with `refused(action)` in read-only mode
+pub fn check(action: &str) -> io::Result<()> {
+    if is_enabled() {
+        Err(refused(action))
+    } else {
+        Ok(())
+    }
+}
+
+/// Whether running `program` with `args` only observes the system. Anything
+/// not listed here is taken to change it.
+pub fn permits(program: &OsStr, args: &[&OsStr]) -> bool {
+    let name = Path::new(program).file_name().unwrap_or(program).to_str().unwrap_or("");
+    let args: Vec<&str> = args.iter().map(|a| a.to_str().unwrap_or("")).collect();
+    let first = args.first().copied().unwrap_or("");
+    let second = args.get(1).copied().unwrap_or("");
+    match name {
+        "findmnt" | "lsblk" | "blkid" | "du" | "stat" | "systemd-escape" => true,
+        // Without a device or --all, swapon only lists
+        "swapon" => args.iter().all(|a| {
+            matches!(*a, "--show" | "-s" | "--summary" | "--raw" | "--noheadings" | "--bytes") || a.starts_with("--show=")
+        }),
+        "losetup" => matches!(first, "-l" | "--list" | "-a" | "--all" | "-j" | "--associated"),
+        "systemctl" => args.iter().find(|a| !a.starts_with('-')).is_some_and(|verb| {
+            matches!(
+                *verb,
+                "is-active" | "is-enabled" | "is-failed" | "show" | "status" | "cat" | "list-units" | "list-unit-files"
+                    | "list-timers"
+            )
+        }),
+        "btrfs" => matches!(
+            (first, second),
+            ("filesystem", "show" | "usage" | "df" | "du")
+                | ("subvolume", "show" | "list")
+                | ("inspect-internal", "map-swapfile")
+                | ("property", "get")
+        ),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn permits_line(line: &str) -> bool {
+        let mut words = line.split_whitespace().map(OsStr::new);
+        let program = words.next().unwrap();
+        permits(program, &words.collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn observing_programs_only() {
+        for line in [
+            "findmnt -n -o FSTYPE /swapfile",
+            "swapon --show=NAME,SIZE --bytes --noheadings",
+            "swapon --raw --noheadings --bytes",
+            "losetup -l --noheadings -o BACK-INO /dev/loop0",
+            "/usr/bin/systemctl --quiet is-active systemd-oomd.service",
+            "btrfs inspect-internal map-swapfile -r /swapfile/1",
+        ] {
+            assert!(permits_line(line), "{}", line);
+        }
+        for line in [
+            "mkswap /swapfile/1",
+            "swapon /dev/zram0",
+            "swapon --all",
+            "losetup -f --show --direct-io=on /swapfile/1",
+            "systemctl --no-block start swap-zram0.swap",
+            "btrfs subvolume create /swapfile",
+            "wipefs -a /dev/sda2",
+        ] {
+            assert!(!permits_line(line), "{}", line);
+        }
+    }
+}
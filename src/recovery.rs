@@ -0,0 +1,198 @@
+// Working directory recovery for systemd-swap
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// WORK_DIR lives on /run, which systemd-tmpfiles or an admin may clear while
+// swap stays active. Without it `status` finds no daemon and `stop` no longer
+// knows which loop devices and zram devices are ours. A watcher notices the
+// loss, re-creates the directory and writes back the daemon state and the
+// settings backups taken at startup, which only the daemon still knows.
+// Subsystems holding a WorkDirWatch then re-populate their own registries
+// from kernel state, the way they do when adopting devices on restart.
+// `stop` removes the directory on purpose while the daemon still runs, and
+// leaves a marker next to it so the watcher does not bring it back.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use crate::context::work_dir;
+use crate::helpers::makedirs;
+use crate::state::DaemonState;
+use crate::{info, is_shutdown, warn};
+
+/// Seconds between checks of the watcher thread
+const CHECK_INTERVAL: u64 = 10;
+
+/// Files and directories of WORK_DIR written once at startup that cannot be
+/// derived from the kernel afterwards (original values of changed settings)
+const SETTINGS_BACKUPS: &[&str] = &[
+    "sysctl_backup",
+    "image_size_backup",
+    "zswap_backup",
+    "zswap_baseline",
+    "capabilities",
+];
+
+/// Bumped each time the working directory is re-created
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Last seen copy of SETTINGS_BACKUPS, as (path relative to WORK_DIR, content).
+/// Also serializes recoveries.
+static BACKUPS: Mutex<Vec<(PathBuf, Vec<u8>)>> = Mutex::new(Vec::new());
+
+/// Left by `stop` next to the working directory it removes, so the daemon,
+/// which systemd only signals after ExecStop, does not re-create it
+fn stopping_marker() -> String {
+    format!("{}.stopping", work_dir())
+}
+
+/// Announce that the working directory is removed on purpose
+pub fn mark_stopping() {
+    let _ = fs::write(stopping_marker(), std::process::id().to_string());
+}
+
+/// Clear a marker left by the previous `stop`, once this instance starts
+pub fn clear_stopping() {
+    let _ = fs::remove_file(stopping_marker());
+}
+
+/// Read SETTINGS_BACKUPS below `dir`
+fn read_backups(dir: &Path) -> Vec<(PathBuf, Vec<u8>)> {
+    let mut files = Vec::new();
+    for name in SETTINGS_BACKUPS {
+        let path = dir.join(name);
+        if path.is_dir() {
+            for entry in fs::read_dir(&path).into_iter().flatten().flatten() {
+                if let Ok(content) = fs::read(entry.path()) {
+                    files.push((Path::new(name).join(entry.file_name()), content));
+                }
+            }
+        } else if let Ok(content) = fs::read(&path) {
+            files.push((PathBuf::from(name), content));
+        }
+    }
+    files
+}
+
+/// Write `files` back below `dir`
+fn write_backups(dir: &Path, files: &[(PathBuf, Vec<u8>)]) -> std::io::Result<()> {
+    for (rel, content) in files {
+        let path = dir.join(rel);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, content)?;
+    }
+    Ok(())
+}
+
+/// Re-create the working directory if it is gone; otherwise refresh the copy
+/// of the settings backups. Returns true if it had to be re-created.
+pub fn check() -> bool {
+    let dir = PathBuf::from(work_dir());
+    let mut backups = BACKUPS.lock().unwrap_or_else(|e| e.into_inner());
+    if dir.is_dir() {
+        *backups = read_backups(&dir);
+        return false;
+    }
+    if Path::new(&stopping_marker()).exists() {
+        return false;
+    }
+
+    warn!(
+        "{} disappeared while swap is active (/run cleared?), re-creating it from kernel state",
+        dir.display()
+    );
+    if let Err(e) = makedirs(&dir) {
+        warn!("Cannot re-create {}: {}", dir.display(), e);
+        return false;
+    }
+    match DaemonState::rewrite() {
+        Ok(true) => info!("Recovery: daemon state restored"),
+        Ok(false) => {}
+        Err(e) => warn!("Recovery: cannot restore daemon state: {}", e),
+    }
+    match write_backups(&dir, &backups) {
+        Ok(()) if !backups.is_empty() => info!("Recovery: {} settings backup(s) restored", backups.len()),
+        Ok(()) => {}
+        Err(e) => warn!("Recovery: cannot restore settings backups: {}", e),
+    }
+    GENERATION.fetch_add(1, Ordering::AcqRel);
+    true
+}
+
+/// Check the working directory every CHECK_INTERVAL seconds until shutdown,
+/// so it is restored in every mode, also without a monitor that writes to it
+pub fn spawn_watcher() {
+    check();
+    crate::context::spawn(|| {
+        while !is_shutdown() {
+            thread::sleep(Duration::from_secs(CHECK_INTERVAL));
+            check();
+        }
+    });
+}
+
+/// Tells a subsystem when to re-populate its part of the working directory
+#[derive(Debug)]
+pub struct WorkDirWatch {
+    generation: u64,
+}
+
+impl Default for WorkDirWatch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WorkDirWatch {
+    pub fn new() -> Self {
+        Self {
+            generation: GENERATION.load(Ordering::Acquire),
+        }
+    }
+
+    /// True once after each re-creation of the working directory, whichever
+    /// thread noticed the loss
+    pub fn recreated(&mut self) -> bool {
+        check();
+        let generation = GENERATION.load(Ordering::Acquire);
+        if generation == self.generation {
+            return false;
+        }
+        self.generation = generation;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings_backups_round_trip() {
+        let src = std::env::temp_dir().join(format!("swap-recovery-src-{}", std::process::id()));
+        let dst = std::env::temp_dir().join(format!("swap-recovery-dst-{}", std::process::id()));
+        fs::create_dir_all(src.join("zswap_backup")).unwrap();
+        fs::write(src.join("sysctl_backup"), "vm.swappiness=60\n").unwrap();
+        fs::write(src.join("zswap_backup/enabled"), "/sys/module/zswap/parameters/enabled=N").unwrap();
+        fs::write(src.join("state"), "pid=1\n").unwrap();
+
+        let mut files = read_backups(&src);
+        files.sort();
+        assert_eq!(
+            files.iter().map(|(p, _)| p.to_string_lossy().into_owned()).collect::<Vec<_>>(),
+            vec!["sysctl_backup", "zswap_backup/enabled"],
+            "state is rewritten by DaemonState, not copied"
+        );
+
+        write_backups(&dst, &files).unwrap();
+        assert_eq!(fs::read_to_string(dst.join("sysctl_backup")).unwrap(), "vm.swappiness=60\n");
+        assert!(dst.join("zswap_backup/enabled").is_file());
+        let _ = fs::remove_dir_all(&src);
+        let _ = fs::remove_dir_all(&dst);
+    }
+}
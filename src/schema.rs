@@ -0,0 +1,841 @@
+// Configuration key schema for systemd-swap
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// One entry per configuration key: what it does, its default, the range the
+// daemon accepts and how it interacts with other keys. `systemd-swap explain`
+// prints these entries; the tests keep them in step with swap-default.conf
+// and defaults.rs.
+
+use crate::defaults;
+
+/// Documentation for one configuration key
+#[derive(Debug, Clone, Copy)]
+pub struct KeyDoc {
+    pub key: &'static str,
+    /// Area the key belongs to (matches the swap-default.conf sections)
+    pub section: &'static str,
+    /// Default when the key is not set ("" = empty/unset)
+    pub default: &'static str,
+    /// Accepted values or range; out-of-range numbers are clamped
+    pub bounds: Option<&'static str>,
+    pub description: &'static str,
+    /// Interactions with other keys or modes ("" = none)
+    pub notes: &'static str,
+}
+
+/// Every key systemd-swap reads, in swap-default.conf order
+pub const KEYS: &[KeyDoc] = &[
+    // ── Mode ─────────────────────────────────────────────────────────────────
+    KeyDoc {
+        key: "swap_mode",
+        section: "Mode",
+        default: "auto",
+        bounds: Some("auto, zram+swapfile, zswap+swapfile, zram, manual, disabled"),
+        description: "Which swap backends the daemon manages.",
+        notes: "auto picks zram+swapfile on btrfs/ext4/xfs with free disk >= RAM and zram otherwise, \
+                and overrides the zram_* and swapfile_* sizing keys with its recommendations. \
+                manual uses zram_enabled, zswap_enabled and swapfile_enabled.",
+    },
+    KeyDoc {
+        key: "compressed_budget_perc",
+        section: "Mode",
+        default: "50",
+        bounds: Some("0..100, 0 = off"),
+        description: "In manual mode with zswap and zram both enabled, cap their combined RAM use at this % of RAM.",
+        notes: "zram_mem_limit defaults to what zswap_max_pool_percent leaves (at least 10% of the budget); \
+                limits adding up to more are scaled down together with a warning. See check-config.",
+    },
+    // ── Kernel tuning ────────────────────────────────────────────────────────
+    KeyDoc {
+        key: "min_free_kbytes_auto",
+        section: "Kernel tuning",
+        default: if defaults::MIN_FREE_KBYTES_AUTO { "1" } else { "0" },
+        bounds: Some("0 or 1"),
+        description: "Raise vm.min_free_kbytes while the daemon runs: 4% of RAM with zram, 3% with zswap, \
+                      between 64MB and 1GB. Manual mode goes by zram_enabled and zswap_enabled.",
+        notes: "A higher existing value is never lowered. The previous value is restored on stop.",
+    },
+    KeyDoc {
+        key: "hibernate_image_size",
+        section: "Kernel tuning",
+        default: defaults::HIBERNATE_IMAGE_SIZE,
+        bounds: Some("empty, auto, or a size"),
+        description: "Set /sys/power/image_size while the daemon runs; auto fits it to the resume= swap area.",
+        notes: "auto allows twice the resume area (typical image compression), at most 2/5 of RAM. \
+                Only applies when a resume device is configured; the previous value is restored on stop.",
+    },
+    // ── Alarms ───────────────────────────────────────────────────────────────
+    KeyDoc {
+        key: "alarm_hook",
+        section: "Alarms",
+        default: defaults::ALARM_HOOK,
+        bounds: Some("absolute path to an executable, empty = off"),
+        description: "Program run when an alarm is raised or cleared, as <alarm> <raised|cleared> <value>.",
+        notes: "The same values are exported as SWAP_ALARM, SWAP_ALARM_STATE and SWAP_ALARM_VALUE. \
+                Alarms are only evaluated when alarm_hook or alarm_dbus is set.",
+    },
+    KeyDoc {
+        key: "alarm_dbus",
+        section: "Alarms",
+        default: "0",
+        bounds: Some("0 or 1"),
+        description: "Emit org.biglinux.SystemdSwap.Alarm on /org/biglinux/SystemdSwap for every alarm.",
+        notes: "",
+    },
+    KeyDoc {
+        key: "alarm_swap_used_perc",
+        section: "Alarms",
+        default: "85",
+        bounds: Some("0..100, 0 = off"),
+        description: "Raise the swap_usage alarm when swap usage stays above this percentage.",
+        notes: "Must hold for alarm_swap_used_duration seconds; a dip below resets the timer.",
+    },
+    KeyDoc {
+        key: "alarm_swap_used_duration",
+        section: "Alarms",
+        default: "300",
+        bounds: Some("seconds"),
+        description: "How long swap usage must stay above alarm_swap_used_perc before the alarm is raised.",
+        notes: "",
+    },
+    KeyDoc {
+        key: "alarm_zswap_reject_rate",
+        section: "Alarms",
+        default: "1000",
+        bounds: Some("rejected pages per minute, 0 = off"),
+        description: "Raise the zswap_rejects alarm when zswap rejects pages faster than this.",
+        notes: "Needs debugfs zswap statistics; silently inactive without them.",
+    },
+    // ── Hooks ────────────────────────────────────────────────────────────────
+    KeyDoc {
+        key: "exec_after_start",
+        section: "Hooks",
+        default: defaults::EXEC_AFTER_START,
+        bounds: Some("absolute path to an executable, empty = off"),
+        description: "Program run once swap setup is complete, with the effective mode as its argument.",
+        notes: "Exports SWAP_HOOK, SWAP_MODE, SWAP_DEVICES, SWAP_ZRAM_DEVICES and SWAP_OTHER_DEVICES. \
+                Runs in the background; the daemon does not wait for it.",
+    },
+    KeyDoc {
+        key: "exec_before_stop",
+        section: "Hooks",
+        default: defaults::EXEC_BEFORE_STOP,
+        bounds: Some("absolute path to an executable, empty = off"),
+        description: "Program run before teardown, with the effective mode as its argument.",
+        notes: "Same environment as exec_after_start. Teardown waits up to 30s, then kills it. \
+                Skipped during an upgrade handoff.",
+    },
+    // ── External commands ────────────────────────────────────────────────────
+    KeyDoc {
+        key: "cmd_timeout",
+        section: "Commands",
+        default: "60",
+        bounds: Some("0 = none, else >= 5"),
+        description: "Seconds an external command may run before it is killed, unless it has its own timeout.",
+        notes: "A killed command fails that attempt; monitors retry on a later tick.",
+    },
+    KeyDoc {
+        key: "cmd_timeout_systemctl",
+        section: "Commands",
+        default: "120",
+        bounds: Some("0 = none, else >= 5"),
+        description: "Timeout for systemctl calls (swap unit start/stop, daemon-reload).",
+        notes: "Guards against a hung D-Bus. The unit's job may still finish in systemd after the kill.",
+    },
+    KeyDoc {
+        key: "cmd_timeout_mkswap",
+        section: "Commands",
+        default: "120",
+        bounds: Some("0 = none, else >= 5"),
+        description: "Timeout for mkswap.",
+        notes: "A swap file whose mkswap times out is removed and created again later.",
+    },
+    KeyDoc {
+        key: "cmd_timeout_swapoff",
+        section: "Commands",
+        default: "600",
+        bounds: Some("0 = none, else >= 5"),
+        description: "Timeout for swapoff of a zram device being removed from the pool.",
+        notes: "The interrupted swapoff leaves the device active; it counts as one drain attempt.",
+    },
+    KeyDoc {
+        key: "cmd_timeout_btrfs",
+        section: "Commands",
+        default: "300",
+        bounds: Some("0 = none, else >= 5"),
+        description: "Timeout for btrfs commands.",
+        notes: "",
+    },
+    // ── systemd-oomd ─────────────────────────────────────────────────────────
+    KeyDoc {
+        key: "oomd_align",
+        section: "systemd-oomd",
+        default: defaults::OOMD_ALIGN,
+        bounds: Some("0 or 1"),
+        description: "Keep swap file expansion at least 15 points of swap usage below systemd-oomd's SwapUsedLimit.",
+        notes: "Only applies while systemd-oomd runs with ManagedOOMSwap=kill. Raises swapfile_free_swap_perc \
+                (and swapfile_remove_free_swap_perc to stay above it), and expands on the 5s emergency \
+                cooldown within 5 points of the limit.",
+    },
+    // ── PSI ──────────────────────────────────────────────────────────────────
+    KeyDoc {
+        key: "psi_trigger",
+        section: "PSI",
+        default: "0",
+        bounds: Some("0 or 1"),
+        description: "Wake the zram and swap file monitors on /proc/pressure/memory triggers instead of polling.",
+        notes: "Only in builds with the psi feature. Falls back to polling on kernels without PSI.",
+    },
+    KeyDoc {
+        key: "psi_stall_us",
+        section: "PSI",
+        default: "150000",
+        bounds: Some("1..psi_window_us"),
+        description: "Memory stall time, in microseconds within psi_window_us, that fires the trigger.",
+        notes: "",
+    },
+    KeyDoc {
+        key: "psi_window_us",
+        section: "PSI",
+        default: "2000000",
+        bounds: Some("500000..10000000"),
+        description: "PSI trigger window in microseconds.",
+        notes: "Rounded up to a multiple of 2s when the kernel rejects it (no CAP_SYS_RESOURCE).",
+    },
+    KeyDoc {
+        key: "psi_idle_interval",
+        section: "PSI",
+        default: "30",
+        bounds: Some("1..300"),
+        description: "Longest time in seconds between monitor checks while no pressure is reported.",
+        notes: "",
+    },
+    // ── Zram ─────────────────────────────────────────────────────────────────
+    KeyDoc {
+        key: "zram_enabled",
+        section: "Zram",
+        default: "0",
+        bounds: Some("0 or 1"),
+        description: "Enable zram in manual mode.",
+        notes: "Ignored in every other swap_mode.",
+    },
+    KeyDoc {
+        key: "zram_size",
+        section: "Zram",
+        default: defaults::ZRAM_SIZE,
+        bounds: Some("size: 150%, 8G, 100%+2G"),
+        description: "Total virtual (uncompressed) zram disksize across the pool.",
+        notes: "auto mode sets 150%. Unset, the dynamic pool starts at 50% and grows on demand. \
+                Real RAM use depends on the compression ratio.",
+    },
+    KeyDoc {
+        key: "zram_alg",
+        section: "Zram",
+        default: defaults::ZRAM_ALG,
+        bounds: Some("zstd, lz4, lzo, lzo-rle, ..."),
+        description: "Compression algorithm for zram devices.",
+        notes: "Must be listed in /sys/block/zram0/comp_algorithm.",
+    },
+    KeyDoc {
+        key: "zram_mem_limit",
+        section: "Zram",
+        default: "0%",
+        bounds: Some("size: 25%, 2G; 0 = no limit"),
+        description: "Cap on the RAM zram may use for compressed data, split evenly across pool devices.",
+        notes: "Usually left at 0%: zram_size already bounds capacity.",
+    },
+    KeyDoc {
+        key: "zram_size_mode",
+        section: "Zram",
+        default: defaults::ZRAM_SIZE_MODE,
+        bounds: Some("ram or ratio"),
+        description: "How the zram disksize is chosen: ram uses zram_size; ratio uses \
+                      zram_expected_ratio × zram_mem_limit.",
+        notes: "ratio needs zram_mem_limit (falls back to zram_size without it) and skips the 50%-of-RAM \
+                minimum pool size, so /proc/swaps shows capacity zram can actually reach.",
+    },
+    KeyDoc {
+        key: "zram_expected_ratio",
+        section: "Zram",
+        default: "3.0",
+        bounds: Some("1.0..10.0"),
+        description: "Compression ratio assumed by zram_size_mode=ratio.",
+        notes: "zstd typically reaches 3-4x on desktop workloads; check status for the real ratio.",
+    },
+    KeyDoc {
+        key: "zram_prio",
+        section: "Zram",
+        default: "32767",
+        bounds: Some("-1..32767"),
+        description: "Swap priority of zram devices (higher is used first).",
+        notes: "Keep it above the swap file priorities so disk swap is only overflow.",
+    },
+    KeyDoc {
+        key: "zram_max_devices",
+        section: "Zram",
+        default: "8",
+        bounds: Some("1..8"),
+        description: "Maximum number of zram devices in the dynamic pool.",
+        notes: "",
+    },
+    KeyDoc {
+        key: "zram_expand_threshold",
+        section: "Zram",
+        default: "85",
+        bounds: Some("50..95"),
+        description: "Add a zram device when pool utilization exceeds this percentage.",
+        notes: "Expansion also needs zram_min_free_ram free RAM and a ratio of at least zram_expand_min_ratio.",
+    },
+    KeyDoc {
+        key: "zram_contract_threshold",
+        section: "Zram",
+        default: "20",
+        bounds: Some("5..50"),
+        description: "Remove a zram device when pool utilization stays below this percentage.",
+        notes: "Must stay low for zram_contract_stability seconds.",
+    },
+    KeyDoc {
+        key: "zram_expand_cooldown",
+        section: "Zram",
+        default: "10",
+        bounds: Some("5..120"),
+        description: "Minimum seconds between two pool expansions.",
+        notes: "",
+    },
+    KeyDoc {
+        key: "zram_contract_stability",
+        section: "Zram",
+        default: "120",
+        bounds: Some("30..600"),
+        description: "Seconds utilization must stay below zram_contract_threshold before contracting.",
+        notes: "",
+    },
+    KeyDoc {
+        key: "zram_min_free_ram",
+        section: "Zram",
+        default: "15",
+        bounds: Some("5..40"),
+        description: "Do not expand the pool when free RAM is below this percentage.",
+        notes: "",
+    },
+    KeyDoc {
+        key: "zram_check_interval",
+        section: "Zram",
+        default: "5",
+        bounds: Some("3..300"),
+        description: "Seconds between zram pool checks.",
+        notes: "With psi_trigger the monitor sleeps up to psi_idle_interval while calm.",
+    },
+    KeyDoc {
+        key: "zram_expand_min_ratio",
+        section: "Zram",
+        default: "2.0",
+        bounds: Some("1.5..5.0"),
+        description: "Minimum compression ratio the pool must achieve before it may expand.",
+        notes: "Prevents growing zram when data compresses poorly.",
+    },
+    KeyDoc {
+        key: "zram_backing_dev",
+        section: "Zram",
+        default: "",
+        bounds: None,
+        description: "Block device the first zram pool device can write pages back to.",
+        notes: "Set before disksize; needs CONFIG_ZRAM_WRITEBACK.",
+    },
+    KeyDoc {
+        key: "zram_exec_watch",
+        section: "Zram",
+        default: "0",
+        bounds: Some("0 or 1"),
+        description: "Add a pool device as soon as an application from zram_exec_watch_apps starts, \
+                      ahead of its memory demand.",
+        notes: "Launches are found by scanning /proc each zram_check_interval. The device is removed \
+                again if the pool's data does not reach zram_expand_threshold of the previous size \
+                within zram_exec_watch_grace.",
+    },
+    KeyDoc {
+        key: "zram_exec_watch_apps",
+        section: "Zram",
+        default: defaults::ZRAM_EXEC_WATCH_APPS,
+        bounds: Some("comma-separated process names"),
+        description: "Applications whose launch zram_exec_watch expands the pool for.",
+        notes: "Matched against the process name (comm, at most 15 characters). Only the first \
+                process of an application counts as a launch.",
+    },
+    KeyDoc {
+        key: "zram_exec_watch_grace",
+        section: "Zram",
+        default: "120",
+        bounds: Some("30..1800"),
+        description: "Seconds a device added on a launch waits for the demand before it is removed.",
+        notes: "",
+    },
+    KeyDoc {
+        key: "zram_huge_idle_writeback",
+        section: "Zram",
+        default: "0",
+        bounds: Some("0 or 1"),
+        description: "Periodically write huge and idle zram pages to zram_backing_dev.",
+        notes: "Ignored without zram_backing_dev. Written-back counts are shown by status.",
+    },
+    KeyDoc {
+        key: "zram_recreate_diverged",
+        section: "Zram",
+        default: "0",
+        bounds: Some("0 or 1"),
+        description: "Recreate adopted zram devices whose comp_algorithm differs from zram_alg.",
+        notes: "One device at a time, at most once a minute, and only while its stored data fits in \
+                available RAM. Divergence is reported by status either way.",
+    },
+    KeyDoc {
+        key: "zram_warmup",
+        section: "Zram",
+        default: "0",
+        bounds: Some("0 or 1"),
+        description: "Pre-fault each new zram device before mkswap: write one page per MiB and discard \
+                      them again, so the first real swap-out does not pay allocation latency.",
+        notes: "Costs a few milliseconds per GB of disksize at startup and on pool expansion; \
+                the time is logged. Meant for latency-sensitive benchmarks.",
+    },
+    KeyDoc {
+        key: "zram_writeback_interval",
+        section: "Zram",
+        default: "600",
+        bounds: Some("60..86400"),
+        description: "Seconds between huge/idle writeback passes.",
+        notes: "Only used with zram_huge_idle_writeback=1.",
+    },
+    KeyDoc {
+        key: "zram_writeback_idle_age",
+        section: "Zram",
+        default: "3600",
+        bounds: None,
+        description: "Seconds a zram page must stay untouched before it is written back as idle.",
+        notes: "0 writes back huge pages only. Kernels without CONFIG_ZRAM_TRACK_ENTRY_TIME use one \
+                writeback interval instead.",
+    },
+    KeyDoc {
+        key: "zram_writeback_min_huge",
+        section: "Zram",
+        default: "16M",
+        bounds: None,
+        description: "Huge page data a zram device must hold before its huge pages are written back.",
+        notes: "",
+    },
+    // ── Zswap ────────────────────────────────────────────────────────────────
+    KeyDoc {
+        key: "zswap_enabled",
+        section: "Zswap",
+        default: "1",
+        bounds: Some("0 or 1"),
+        description: "Enable zswap in manual mode.",
+        notes: "Ignored in every other swap_mode.",
+    },
+    KeyDoc {
+        key: "zswap_compressor",
+        section: "Zswap",
+        default: defaults::ZSWAP_COMPRESSOR,
+        bounds: Some("zstd, lz4, lzo, ..."),
+        description: "Compression algorithm for the zswap pool.",
+        notes: "",
+    },
+    KeyDoc {
+        key: "zswap_zpool",
+        section: "Zswap",
+        default: defaults::ZSWAP_ZPOOL,
+        bounds: Some("zsmalloc, z3fold, zbud"),
+        description: "Allocator for the compressed pool.",
+        notes: "Newer kernels only support zsmalloc.",
+    },
+    KeyDoc {
+        key: "zswap_max_pool_percent",
+        section: "Zswap",
+        default: "45",
+        bounds: Some("percent of RAM"),
+        description: "Largest share of RAM the compressed pool may use.",
+        notes: "",
+    },
+    KeyDoc {
+        key: "zswap_shrinker_enabled",
+        section: "Zswap",
+        default: defaults::ZSWAP_SHRINKER_ENABLED,
+        bounds: Some("0 or 1"),
+        description: "Let the kernel write cold pages from the pool to the swap files.",
+        notes: "Kernel 6.8+.",
+    },
+    KeyDoc {
+        key: "zswap_accept_threshold",
+        section: "Zswap",
+        default: defaults::ZSWAP_ACCEPT_THRESHOLD,
+        bounds: Some("percent of zswap_max_pool_percent"),
+        description: "After the pool fills, accept new pages again once it drops to this percentage.",
+        notes: "",
+    },
+    KeyDoc {
+        key: "zswap_persist",
+        section: "Zswap",
+        default: "0",
+        bounds: Some("0 or 1"),
+        description: "Keep the zswap parameters in /etc/modprobe.d/systemd-swap-zswap.conf across reboots.",
+        notes: "Rewritten when they change, removed when the mode no longer uses zswap. With zswap built \
+                into the kernel the file is not read; the log and the file show the kernel command line \
+                to use instead.",
+    },
+    // ── Swap files ───────────────────────────────────────────────────────────
+    KeyDoc {
+        key: "swapfile_enabled",
+        section: "Swap files",
+        default: "0",
+        bounds: Some("0 or 1"),
+        description: "Enable dynamic swap files in manual mode.",
+        notes: "Ignored in every other swap_mode.",
+    },
+    KeyDoc {
+        key: "swapfile_path",
+        section: "Swap files",
+        default: defaults::SWAPFILE_PATH,
+        bounds: Some("absolute path outside /etc, /usr, /boot, /run, ..."),
+        description: "Directory where swap files are created.",
+        notes: "On read-only roots auto mode uses /var/swap. Paths on tmpfs or ramfs are refused.",
+    },
+    KeyDoc {
+        key: "swapfile_chunk_size",
+        section: "Swap files",
+        default: defaults::SWAPFILE_CHUNK_SIZE,
+        bounds: Some("at least 512M (128M with swapfile_sparse_loop)"),
+        description: "Size of each swap file.",
+        notes: "Replaced by swapfile_layout when that is set.",
+    },
+    KeyDoc {
+        key: "swapfile_max_count",
+        section: "Swap files",
+        default: "28",
+        bounds: Some("1..28"),
+        description: "Maximum number of swap files.",
+        notes: "Equals the number of entries when swapfile_layout is set.",
+    },
+    KeyDoc {
+        key: "swapfile_min_count",
+        section: "Swap files",
+        default: defaults::SWAPFILE_MIN_COUNT,
+        bounds: Some("auto or 0..swapfile_max_count"),
+        description: "Swap files created at startup and never removed while contracting.",
+        notes: "auto is 1 with zram; with zswap 1 below 8GB RAM, 2 up to 32GB, 3 above. \
+                In zswap mode it also raises the number of unused reserve files (at least 2). \
+                See check-config for the effective value.",
+    },
+    KeyDoc {
+        key: "swapfile_free_ram_perc",
+        section: "Swap files",
+        default: "20",
+        bounds: Some("percent"),
+        description: "Before the first swap file exists, poll at the full swapfile_frequency rate once \
+                      free RAM drops below this percentage.",
+        notes: "Expansion itself follows free swap (swapfile_free_swap_perc) plus an emergency trigger \
+                below 10% free RAM.",
+    },
+    KeyDoc {
+        key: "swapfile_free_swap_perc",
+        section: "Swap files",
+        default: "40",
+        bounds: Some("percent"),
+        description: "Create a swap file when free swap drops below this percentage.",
+        notes: "Not used for expansion in zswap mode, where the reserve files drive growth.",
+    },
+    KeyDoc {
+        key: "swapfile_remove_free_swap_perc",
+        section: "Swap files",
+        default: "70",
+        bounds: Some("percent"),
+        description: "Remove a swap file when free swap rises above this percentage.",
+        notes: "zswap mode uses 85% and keeps its reserve files. Removal also waits for the removal cooldown.",
+    },
+    KeyDoc {
+        key: "swapfile_frequency",
+        section: "Swap files",
+        default: "1",
+        bounds: Some("1..86400"),
+        description: "Seconds between swap file checks; before the first file exists the loop polls \
+                      slower while RAM is plentiful.",
+        notes: "",
+    },
+    KeyDoc {
+        key: "swapfile_shrink_threshold",
+        section: "Swap files",
+        default: "30",
+        bounds: Some("10..50"),
+        description: "A swap file whose usage is at or below this percentage may be removed.",
+        notes: "Its pages must fit into the other files while leaving swapfile_safe_headroom free.",
+    },
+    KeyDoc {
+        key: "swapfile_safe_headroom",
+        section: "Swap files",
+        default: "40",
+        bounds: Some("20..60"),
+        description: "Free space (percent) the remaining swap files must keep after absorbing the pages \
+                      of a file being removed.",
+        notes: "Higher values make contraction more conservative.",
+    },
+    KeyDoc {
+        key: "swapfile_layout",
+        section: "Swap files",
+        default: defaults::SWAPFILE_LAYOUT,
+        bounds: Some("comma-separated sizes, at most 28 entries"),
+        description: "Explicit size of each swap file, e.g. 2G,2G,4G,8G.",
+        notes: "Files are created in this order and removed in reverse. \
+                Overrides swapfile_chunk_size and swapfile_max_count.",
+    },
+    KeyDoc {
+        key: "swapfile_priority",
+        section: "Swap files",
+        default: defaults::SWAPFILE_PRIORITY,
+        bounds: Some("auto, 0..32767, or -1"),
+        description: "Swap priority of swap files; file N gets the starting value minus N-1.",
+        notes: "auto starts at 3999 on NVMe, 2999 on SSD and 1999 on HDD, so faster storage is \
+                used first and files stay below zram_prio. -1 leaves priorities to the kernel.",
+    },
+    KeyDoc {
+        key: "swapfile_grow_max_size",
+        section: "Swap files",
+        default: defaults::SWAPFILE_GROW_MAX_SIZE,
+        bounds: Some("size, 0 = off"),
+        description: "Largest size a swap file may be grown to in place, folding the last file into it.",
+        notes: "ext4/xfs with preallocated files only, and never with swapfile_layout. Runs while \
+                RAM and swap are plentiful, at most every 10 minutes, and keeps total capacity the same.",
+    },
+    KeyDoc {
+        key: "swapfile_wipe_on_remove",
+        section: "Swap files",
+        default: defaults::SWAPFILE_WIPE_ON_REMOVE,
+        bounds: Some("0 or 1"),
+        description: "Clear a removed swap file's blocks in the background before unlinking it.",
+        notes: "Zero overwrite on HDD, hole punching (discard) on SSD/NVMe and copy-on-write files, \
+                at 32 MiB/s. Skipped when the swap directory is on dm-crypt.",
+    },
+    KeyDoc {
+        key: "swapfile_trim_window",
+        section: "Swap files",
+        default: "1800",
+        bounds: Some("seconds, 0 = off"),
+        description: "Hold discard wipes back from this long before a fstrim.timer run until fstrim.service has finished.",
+        notes: "They resume after a random delay of up to 5 minutes. Needs systemd 248 or later; \
+                without an active fstrim.timer wipes are never held back.",
+    },
+    KeyDoc {
+        key: "swapfile_burst_max_count",
+        section: "Swap files",
+        default: "0",
+        bounds: Some("0 = off; pool + burst ≤ 28"),
+        description: "Ephemeral burst swap files added once the persistent pool is at swapfile_max_count.",
+        notes: "Kept in <swapfile_path>/burst with kernel-assigned priority below the pool, added at \
+                most every 5s and removed after swapfile_burst_quiet. Not with swapfile_sparse_loop.",
+    },
+    KeyDoc {
+        key: "swapfile_burst_free_swap_perc",
+        section: "Swap files",
+        default: "50",
+        bounds: Some("0-100"),
+        description: "Free swap percentage below which a burst file is added.",
+        notes: "",
+    },
+    KeyDoc {
+        key: "swapfile_burst_quiet",
+        section: "Swap files",
+        default: "120",
+        bounds: Some("seconds"),
+        description: "How long free swap must stay above the burst threshold before burst files are removed.",
+        notes: "Burst files go one at a time, lowest priority first, and only when their data fits elsewhere.",
+    },
+    KeyDoc {
+        key: "swapfile_btrfs_parent",
+        section: "Swap files",
+        default: defaults::SWAPFILE_BTRFS_PARENT,
+        bounds: Some("auto, nested or toplevel"),
+        description: "Where a new btrfs swap subvolume is created.",
+        notes: "auto nests it at swapfile_path on flat layouts, and creates a top-level @swap mounted \
+                at swapfile_path on @-prefixed (Ubuntu, Arch, Timeshift) and snapper snapshot roots, \
+                where a nested subvolume would be left behind by snapshot restores. An existing \
+                subvolume at swapfile_path is kept.",
+    },
+    KeyDoc {
+        key: "swapfile_nocow",
+        section: "Swap files",
+        default: defaults::SWAPFILE_NOCOW,
+        bounds: Some("0 or 1"),
+        description: "Mark swap files NOCOW (chattr +C) on btrfs.",
+        notes: "Disabling it is only safe with swapfile_sparse_loop, otherwise btrfs can deadlock.",
+    },
+    KeyDoc {
+        key: "swapfile_sparse_loop",
+        section: "Swap files",
+        default: "0",
+        bounds: Some("0 or 1"),
+        description: "Back swap files with sparse files attached to direct-io loop devices.",
+        notes: "Allows smaller chunks (128M) and growth files of swapfile_growth_chunk_size. \
+                File sizes are rounded down to the filesystem block and the disk's discard granularity.",
+    },
+    KeyDoc {
+        key: "swapfile_growth_chunk_size",
+        section: "Swap files",
+        default: "",
+        bounds: Some("size, empty or 0 = 2x swapfile_chunk_size"),
+        description: "Size of the growth files created when loop-backed swap is 80% full.",
+        notes: "Only used with swapfile_sparse_loop.",
+    },
+    KeyDoc {
+        key: "swapfile_discard",
+        section: "Swap files",
+        default: "none",
+        bounds: Some("none, auto, once"),
+        description: "Discard policy for swap files.",
+        notes: "Not read by this version: swap files are always activated without discard.",
+    },
+    KeyDoc {
+        key: "swapfile_btrfs_compress",
+        section: "Swap files",
+        default: "",
+        bounds: Some("zstd, lzo, zlib, none"),
+        description: "Btrfs compression for loop-backed swap files with swapfile_nocow=0.",
+        notes: "Not read by this version: compression follows the mount options.",
+    },
+    // ── Loop devices ─────────────────────────────────────────────────────────
+    KeyDoc {
+        key: "swapfile_loop_scheduler",
+        section: "Loop devices",
+        default: "auto",
+        bounds: Some("auto, kyber, bfq, mq-deadline, none"),
+        description: "I/O scheduler for swap loop devices.",
+        notes: "Not read by this version: loop devices always use none, since a second scheduler \
+                above the real disk can deadlock under pressure.",
+    },
+    KeyDoc {
+        key: "swapfile_loop_backing_fs",
+        section: "Loop devices",
+        default: "auto",
+        bounds: Some("auto, f2fs"),
+        description: "Filesystem holding the loop backing files.",
+        notes: "Not read by this version: the host filesystem is always used.",
+    },
+    // ── Paths ────────────────────────────────────────────────────────────────
+    KeyDoc {
+        key: "state_dir",
+        section: "Paths",
+        default: crate::config::STATE_DIR,
+        bounds: Some("writable absolute path"),
+        description: "Directory for state that must survive reboots.",
+        notes: "Holds the loop device registry that keeps loop-backed swap files on the same \
+                /dev/loopN. SYSTEMD_SWAP_STATE_DIR overrides it.",
+    },
+    KeyDoc {
+        key: "swap_history",
+        section: "Paths",
+        default: "1",
+        bounds: Some("0 or 1"),
+        description: "Record daily swap peaks, total and per top-level cgroup slice, in state_dir.",
+        notes: "Shown by `stats --history [--by-cgroup]`; 90 days are kept. Per-slice values need \
+                cgroup v2.",
+    },
+    KeyDoc {
+        key: "swap_latency_probe",
+        section: "Paths",
+        default: "0",
+        bounds: Some("0 or 1"),
+        description: "Measure swap-in latency per tier every swap_latency_interval seconds.",
+        notes: "Results are kept in state_dir/latency and shown by `status` and `latency`. Each \
+                probe pushes 2 MiB of a worker process out to swap. Needs cgroup v2.",
+    },
+    KeyDoc {
+        key: "swap_latency_interval",
+        section: "Paths",
+        default: "3600",
+        bounds: Some("300-86400 seconds"),
+        description: "Seconds between swap-in latency probes.",
+        notes: "Only used with swap_latency_probe=1.",
+    },
+];
+
+/// Documentation for a key
+pub fn lookup(key: &str) -> Option<&'static KeyDoc> {
+    KEYS.iter().find(|doc| doc.key == key)
+}
+
+/// Keys containing `fragment`, for "did you mean" hints and prefix listings
+pub fn matching(fragment: &str) -> Vec<&'static KeyDoc> {
+    KEYS.iter().filter(|doc| doc.key.contains(fragment)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn default_conf_keys() -> Vec<String> {
+        include_str!("../include/swap-default.conf")
+            .lines()
+            .map(|l| l.trim_start_matches('#').trim())
+            .filter_map(|l| l.split_once('='))
+            .map(|(k, _)| k.trim())
+            .filter(|k| !k.is_empty() && k.chars().all(|c| c.is_ascii_lowercase() || c == '_'))
+            .map(str::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn every_default_conf_key_is_documented() {
+        for key in default_conf_keys() {
+            assert!(lookup(&key).is_some(), "{} missing from schema", key);
+        }
+    }
+
+    #[test]
+    fn keys_are_unique() {
+        for (i, doc) in KEYS.iter().enumerate() {
+            assert!(
+                KEYS[i + 1..].iter().all(|other| other.key != doc.key),
+                "{} listed twice",
+                doc.key
+            );
+        }
+    }
+
+    #[test]
+    fn numeric_defaults_match_defaults_rs() {
+        let expected = [
+            ("alarm_swap_used_perc", defaults::ALARM_SWAP_USED_PERC.to_string()),
+            ("alarm_swap_used_duration", defaults::ALARM_SWAP_USED_DURATION.to_string()),
+            ("alarm_zswap_reject_rate", defaults::ALARM_ZSWAP_REJECT_RATE.to_string()),
+            ("psi_stall_us", defaults::PSI_STALL_US.to_string()),
+            ("psi_window_us", defaults::PSI_WINDOW_US.to_string()),
+            ("psi_idle_interval", defaults::PSI_IDLE_INTERVAL.to_string()),
+            ("zram_prio", defaults::ZRAM_PRIO.to_string()),
+            ("zram_max_devices", defaults::ZRAM_MAX_DEVICES.to_string()),
+            ("zram_expand_threshold", defaults::ZRAM_EXPAND_THRESHOLD.to_string()),
+            ("zram_contract_threshold", defaults::ZRAM_CONTRACT_THRESHOLD.to_string()),
+            ("zram_expand_cooldown", defaults::ZRAM_EXPAND_COOLDOWN.to_string()),
+            ("zram_contract_stability", defaults::ZRAM_CONTRACT_STABILITY.to_string()),
+            ("zram_min_free_ram", defaults::ZRAM_MIN_FREE_RAM.to_string()),
+            ("zram_check_interval", defaults::ZRAM_CHECK_INTERVAL.to_string()),
+            ("zram_expand_min_ratio", format!("{:.1}", defaults::ZRAM_EXPAND_MIN_RATIO)),
+            ("zram_writeback_interval", defaults::ZRAM_WRITEBACK_INTERVAL.to_string()),
+            ("zram_writeback_idle_age", defaults::ZRAM_WRITEBACK_IDLE_AGE.to_string()),
+            ("zram_exec_watch_grace", defaults::ZRAM_EXEC_WATCH_GRACE.to_string()),
+            ("cmd_timeout", defaults::CMD_TIMEOUT.to_string()),
+            ("cmd_timeout_systemctl", defaults::CMD_TIMEOUT_SYSTEMCTL.to_string()),
+            ("cmd_timeout_mkswap", defaults::CMD_TIMEOUT_MKSWAP.to_string()),
+            ("cmd_timeout_swapoff", defaults::CMD_TIMEOUT_SWAPOFF.to_string()),
+            ("cmd_timeout_btrfs", defaults::CMD_TIMEOUT_BTRFS.to_string()),
+            ("zswap_max_pool_percent", defaults::ZSWAP_MAX_POOL_PERCENT.to_string()),
+            ("swapfile_max_count", defaults::SWAPFILE_MAX_COUNT.to_string()),
+            ("swapfile_free_ram_perc", defaults::SWAPFILE_FREE_RAM_PERC.to_string()),
+            ("swapfile_free_swap_perc", defaults::SWAPFILE_FREE_SWAP_PERC.to_string()),
+            ("swapfile_remove_free_swap_perc", defaults::SWAPFILE_REMOVE_FREE_SWAP_PERC.to_string()),
+            ("swapfile_frequency", defaults::SWAPFILE_FREQUENCY.to_string()),
+            ("swapfile_shrink_threshold", defaults::SWAPFILE_SHRINK_THRESHOLD.to_string()),
+            ("swapfile_trim_window", defaults::SWAPFILE_TRIM_WINDOW.to_string()),
+            ("swapfile_safe_headroom", defaults::SWAPFILE_SAFE_HEADROOM.to_string()),
+            ("swap_latency_interval", defaults::SWAP_LATENCY_INTERVAL.to_string()),
+        ];
+        for (key, value) in expected {
+            assert_eq!(lookup(key).map(|d| d.default), Some(value.as_str()), "{}", key);
+        }
+    }
+}
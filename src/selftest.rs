@@ -0,0 +1,475 @@
+//! Memory stress self-test for systemd-swap.
+//!
+//! Spawns a disposable worker process that allocates and touches a fixed
+//! amount of anonymous memory, then watches whether the running daemon
+//! expands swap to absorb the load and contracts again once it is released.
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::os::unix::process::ExitStatusExt;
+use std::path::Path;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use thiserror::Error;
+
+use crate::context::work_dir;
+use crate::helpers::{format_size, MB};
+use crate::meminfo::{get_mem_stats, get_page_size};
+use crate::{info, warn};
+
+#[derive(Error, Debug)]
+pub enum SelftestError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("MemInfo error: {0}")]
+    MemInfo(#[from] crate::meminfo::MemInfoError),
+    #[error("systemd-swap daemon is not running ({0} not found)")]
+    DaemonNotRunning(String),
+}
+
+pub type Result<T> = std::result::Result<T, SelftestError>;
+
+/// Hidden subcommand used to re-exec the binary as the allocation worker
+pub const WORKER_COMMAND: &str = "selftest-worker";
+
+/// Line printed by the worker once the whole allocation has been touched
+const WORKER_READY: &str = "READY";
+
+/// Upper bound for the allocation phase (slow disks can take a while to absorb it)
+const ALLOC_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Interval between swap samples
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Swap usage (% of total) above which the daemon is expected to have expanded
+const EXPANSION_EXPECTED_PERCENT: u64 = 85;
+
+/// Self-test parameters
+#[derive(Debug, Clone)]
+pub struct SelftestOptions {
+    /// Bytes of anonymous memory the worker allocates
+    pub size: u64,
+    /// How long the worker keeps the allocation after touching it
+    pub hold: Duration,
+    /// How long to wait for contraction after the worker exits
+    pub settle_timeout: Duration,
+}
+
+/// Point-in-time view of swap capacity
+#[derive(Debug, Clone, Copy, Default)]
+struct SwapSnapshot {
+    swap_total: u64,
+    swap_used: u64,
+    zram_devices: usize,
+    swap_files: usize,
+}
+
+impl SwapSnapshot {
+    fn take() -> Result<Self> {
+        let stats = get_mem_stats(&["SwapTotal", "SwapFree"])?;
+        let mut snap = Self {
+            swap_total: stats["SwapTotal"],
+            swap_used: stats["SwapTotal"].saturating_sub(stats["SwapFree"]),
+            ..Default::default()
+        };
+
+        // Skip header: Filename Type Size Used Priority
+        let swaps = std::fs::read_to_string("/proc/swaps")?;
+        for line in swaps.lines().skip(1) {
+            let mut fields = line.split_whitespace();
+            let (Some(name), Some(kind)) = (fields.next(), fields.next()) else {
+                continue;
+            };
+            if name.starts_with("/dev/zram") {
+                snap.zram_devices += 1;
+            } else if kind == "file" || name.starts_with("/dev/loop") {
+                snap.swap_files += 1;
+            }
+        }
+        Ok(snap)
+    }
+
+    fn usage_percent(&self) -> u64 {
+        (self.swap_used * 100).checked_div(self.swap_total).unwrap_or(0)
+    }
+
+    /// Keep the highest value seen for every field
+    fn merge_peak(&mut self, other: &SwapSnapshot) {
+        self.swap_total = self.swap_total.max(other.swap_total);
+        self.swap_used = self.swap_used.max(other.swap_used);
+        self.zram_devices = self.zram_devices.max(other.zram_devices);
+        self.swap_files = self.swap_files.max(other.swap_files);
+    }
+}
+
+/// Result of a single self-test check
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Outcome {
+    Pass,
+    Fail,
+    Skip,
+}
+
+impl std::fmt::Display for Outcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Outcome::Pass => write!(f, "PASS"),
+            Outcome::Fail => write!(f, "FAIL"),
+            Outcome::Skip => write!(f, "SKIP"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Check {
+    pub name: &'static str,
+    pub outcome: Outcome,
+    pub detail: String,
+}
+
+/// Pass/fail report produced by [`run`]
+#[derive(Debug, Clone, Default)]
+pub struct SelftestReport {
+    pub size: u64,
+    pub checks: Vec<Check>,
+}
+
+impl SelftestReport {
+    /// True when no check failed (skipped checks do not count as failures)
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(|c| c.outcome != Outcome::Fail)
+    }
+
+    fn push(&mut self, name: &'static str, outcome: Outcome, detail: String) {
+        self.checks.push(Check {
+            name,
+            outcome,
+            detail,
+        });
+    }
+
+    pub fn print(&self) {
+        println!("Self-test report ({} load):", format_size(self.size));
+        for check in &self.checks {
+            println!("  [{}] {:<12} {}", check.outcome, check.name, check.detail);
+        }
+        println!(
+            "Result: {}",
+            if self.passed() { "PASS" } else { "FAIL" }
+        );
+    }
+}
+
+/// Worker process handle with a background reader for its stdout
+struct Worker {
+    child: Child,
+    lines: Receiver<String>,
+}
+
+impl Worker {
+    fn spawn(size: u64) -> Result<Self> {
+        let exe = std::env::current_exe()?;
+        let mut child = Command::new(exe)
+            .args([WORKER_COMMAND, "--size", &size.to_string()])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()?;
+
+        let stdout = child.stdout.take().ok_or_else(|| {
+            std::io::Error::other("worker stdout not captured")
+        })?;
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().map_while(|l| l.ok()) {
+                if tx.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(Self { child, lines: rx })
+    }
+
+    /// Non-blocking check whether the worker reported READY
+    fn is_ready(&self) -> bool {
+        loop {
+            match self.lines.try_recv() {
+                Ok(line) if line.trim() == WORKER_READY => return true,
+                Ok(_) => continue,
+                Err(TryRecvError::Empty) | Err(TryRecvError::Disconnected) => return false,
+            }
+        }
+    }
+
+    fn exited(&mut self) -> Option<ExitStatus> {
+        self.child.try_wait().ok().flatten()
+    }
+
+    fn stop(mut self) {
+        // Closing stdin lets the worker exit on its own; kill as a fallback
+        drop(self.child.stdin.take());
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while Instant::now() < deadline {
+            if self.exited().is_some() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn describe_exit(status: ExitStatus) -> String {
+    match status.signal() {
+        Some(9) => "worker was killed by SIGKILL (likely the OOM killer)".to_string(),
+        Some(sig) => format!("worker was killed by signal {}", sig),
+        None => format!("worker exited early with {}", status),
+    }
+}
+
+/// Run the self-test against the running daemon and return a report.
+pub fn run(opts: &SelftestOptions) -> Result<SelftestReport> {
+    if !Path::new(&work_dir()).is_dir() {
+        return Err(SelftestError::DaemonNotRunning(work_dir()));
+    }
+
+    let mut report = SelftestReport {
+        size: opts.size,
+        checks: Vec::new(),
+    };
+
+    let available = get_mem_stats(&["MemAvailable"])?["MemAvailable"];
+    let baseline = SwapSnapshot::take()?;
+    info!(
+        "Selftest: baseline swap {}/{} used, {} zram device(s), {} swap file(s)",
+        format_size(baseline.swap_used),
+        format_size(baseline.swap_total),
+        baseline.zram_devices,
+        baseline.swap_files
+    );
+
+    // Phase 1: allocate and touch
+    info!("Selftest: allocating {}...", format_size(opts.size));
+    let mut worker = Worker::spawn(opts.size)?;
+    let mut peak = baseline;
+    let started = Instant::now();
+    let mut early_exit = None;
+    let mut ready = false;
+    loop {
+        peak.merge_peak(&SwapSnapshot::take()?);
+        if worker.is_ready() {
+            ready = true;
+            break;
+        }
+        if let Some(status) = worker.exited() {
+            early_exit = Some(status);
+            break;
+        }
+        if started.elapsed() > ALLOC_TIMEOUT {
+            warn!("Selftest: allocation did not finish within {}s", ALLOC_TIMEOUT.as_secs());
+            break;
+        }
+        thread::sleep(SAMPLE_INTERVAL);
+    }
+    let alloc_secs = started.elapsed().as_secs();
+
+    match early_exit {
+        Some(status) => {
+            report.push("allocation", Outcome::Fail, describe_exit(status));
+            worker.stop();
+            return Ok(report);
+        }
+        None if !ready => {
+            report.push(
+                "allocation",
+                Outcome::Fail,
+                format!("not finished after {}s", ALLOC_TIMEOUT.as_secs()),
+            );
+            worker.stop();
+            return Ok(report);
+        }
+        None => report.push(
+            "allocation",
+            Outcome::Pass,
+            format!("{} touched in {}s", format_size(opts.size), alloc_secs),
+        ),
+    }
+
+    // Phase 2: hold the allocation while the daemon reacts
+    info!("Selftest: holding allocation for {}s", opts.hold.as_secs());
+    let hold_start = Instant::now();
+    let mut died_during_hold = None;
+    while hold_start.elapsed() < opts.hold {
+        peak.merge_peak(&SwapSnapshot::take()?);
+        if let Some(status) = worker.exited() {
+            died_during_hold = Some(status);
+            break;
+        }
+        thread::sleep(SAMPLE_INTERVAL);
+    }
+    worker.stop();
+    if let Some(status) = died_during_hold {
+        report.push("hold", Outcome::Fail, describe_exit(status));
+    }
+
+    let absorbed = peak.swap_used.saturating_sub(baseline.swap_used);
+    if absorbed > 0 {
+        report.push(
+            "swap",
+            Outcome::Pass,
+            format!(
+                "absorbed {} (peak {}% of {})",
+                format_size(absorbed),
+                peak.usage_percent(),
+                format_size(peak.swap_total)
+            ),
+        );
+    } else if opts.size < available {
+        report.push(
+            "swap",
+            Outcome::Skip,
+            format!(
+                "load fit in available RAM ({}); use a larger --size",
+                format_size(available)
+            ),
+        );
+    } else {
+        report.push(
+            "swap",
+            Outcome::Fail,
+            "load exceeded available RAM but swap usage did not grow".to_string(),
+        );
+    }
+
+    let expanded =
+        peak.zram_devices > baseline.zram_devices || peak.swap_files > baseline.swap_files;
+    if expanded {
+        report.push(
+            "expansion",
+            Outcome::Pass,
+            format!(
+                "zram {} → {} device(s), swap files {} → {}",
+                baseline.zram_devices, peak.zram_devices, baseline.swap_files, peak.swap_files
+            ),
+        );
+    } else if peak.usage_percent() >= EXPANSION_EXPECTED_PERCENT {
+        report.push(
+            "expansion",
+            Outcome::Fail,
+            format!(
+                "swap reached {}% but no device or file was added",
+                peak.usage_percent()
+            ),
+        );
+    } else {
+        report.push(
+            "expansion",
+            Outcome::Skip,
+            format!(
+                "not needed (peak usage {}% < {}%)",
+                peak.usage_percent(),
+                EXPANSION_EXPECTED_PERCENT
+            ),
+        );
+    }
+
+    // Phase 3: wait for the daemon to give the capacity back
+    if !expanded {
+        report.push(
+            "contraction",
+            Outcome::Skip,
+            "nothing to reclaim".to_string(),
+        );
+        return Ok(report);
+    }
+
+    info!(
+        "Selftest: waiting up to {}s for contraction",
+        opts.settle_timeout.as_secs()
+    );
+    let settle_start = Instant::now();
+    let mut last = SwapSnapshot::take()?;
+    loop {
+        if last.zram_devices <= baseline.zram_devices && last.swap_files <= baseline.swap_files {
+            report.push(
+                "contraction",
+                Outcome::Pass,
+                format!(
+                    "back to {} zram device(s), {} swap file(s) after {}s",
+                    last.zram_devices,
+                    last.swap_files,
+                    settle_start.elapsed().as_secs()
+                ),
+            );
+            break;
+        }
+        if settle_start.elapsed() >= opts.settle_timeout {
+            report.push(
+                "contraction",
+                Outcome::Fail,
+                format!(
+                    "still {} zram device(s), {} swap file(s) after {}s (baseline {}/{})",
+                    last.zram_devices,
+                    last.swap_files,
+                    opts.settle_timeout.as_secs(),
+                    baseline.zram_devices,
+                    baseline.swap_files
+                ),
+            );
+            break;
+        }
+        thread::sleep(Duration::from_secs(5));
+        last = SwapSnapshot::take()?;
+    }
+
+    Ok(report)
+}
+
+/// Entry point of the worker process.
+///
+/// Raises its own oom_score_adj so the OOM killer picks it first, touches every
+/// page of the allocation with moderately compressible data (zero pages would be
+/// deduplicated by zram and never reach swap), reports READY and then blocks
+/// until the parent closes stdin.
+pub fn run_worker(size: u64) -> Result<()> {
+    if let Err(e) = std::fs::write("/proc/self/oom_score_adj", "1000") {
+        warn!("Selftest worker: cannot raise oom_score_adj: {}", e);
+    }
+
+    let page = get_page_size() as usize;
+    let chunk = (64 * MB) as usize;
+    let mut chunks: Vec<Vec<u8>> = Vec::new();
+    let mut remaining = size as usize;
+    let mut seed: u64 = 0x9E37_79B9_7F4A_7C15;
+
+    while remaining > 0 {
+        let len = remaining.min(chunk);
+        let mut buf = vec![0u8; len];
+        // Random first half, zero second half: roughly 2x compressible
+        for page_start in (0..len).step_by(page) {
+            let end = (page_start + page / 2).min(len);
+            for word in buf[page_start..end].chunks_mut(8) {
+                seed ^= seed << 13;
+                seed ^= seed >> 7;
+                seed ^= seed << 17;
+                word.copy_from_slice(&seed.to_le_bytes()[..word.len()]);
+            }
+        }
+        chunks.push(buf);
+        remaining -= len;
+    }
+
+    let mut stdout = std::io::stdout();
+    writeln!(stdout, "{}", WORKER_READY)?;
+    stdout.flush()?;
+
+    // Block until the parent closes our stdin (or kills us)
+    let _ = std::io::stdin().read(&mut [0u8; 1]);
+    drop(chunks);
+    Ok(())
+}
@@ -0,0 +1,195 @@
+// Swap sizing rationale for systemd-swap
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// `autoconfig` prints why the recommended sizes fit the machine: what the zram
+// pool holds and costs in RAM at typical compression ratios, what the swap
+// files would take on disk at swapfile_max_count, the total the kernel can
+// swap, and whether the machine can hibernate. The hibernation image is
+// written to a fixed disk swap area named by resume=; zram never holds it
+// and the daemon's on-demand files come and go, so they do not count.
+
+use crate::helpers::format_size;
+use crate::hibernate::{HibernationStatus, Verdict};
+
+/// Compression ratios the zram rows are worked out for: poor (media, already
+/// compressed data), typical desktop, good (text, code)
+const ASSUMED_RATIOS: [f64; 3] = [2.0, 3.0, 4.0];
+
+/// Sizes the configuration leads to on this machine
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Sizing {
+    pub ram: u64,
+    /// 0 without zram
+    pub zram_disksize: u64,
+    /// 0 when the pool's RAM use is not capped
+    pub zram_mem_limit: u64,
+    /// At swapfile_max_count; 0 without swap files
+    pub file_capacity: u64,
+    pub max_count: u32,
+    pub free_disk: u64,
+    /// None when no resume device is configured
+    pub hibernation: Option<HibernationStatus>,
+}
+
+/// One line of the rationale: what, how much, and why
+#[derive(Debug, Clone, PartialEq)]
+pub struct SizingRow {
+    pub item: String,
+    pub size: String,
+    pub rationale: String,
+}
+
+impl SizingRow {
+    fn new(item: impl Into<String>, size: impl Into<String>, rationale: impl Into<String>) -> Self {
+        Self {
+            item: item.into(),
+            size: size.into(),
+            rationale: rationale.into(),
+        }
+    }
+}
+
+impl Sizing {
+    pub fn rows(&self) -> Vec<SizingRow> {
+        let percent_of = |part: u64, whole: u64| (part * 100).checked_div(whole).unwrap_or(0);
+        let mut rows = vec![SizingRow::new("RAM", format_size(self.ram), "installed")];
+
+        if self.zram_disksize > 0 {
+            rows.push(SizingRow::new(
+                "zram disksize",
+                format_size(self.zram_disksize),
+                format!("{}% of RAM, uncompressed data the pool accepts", percent_of(self.zram_disksize, self.ram)),
+            ));
+            for ratio in ASSUMED_RATIOS {
+                let mut stored = self.zram_disksize;
+                if self.zram_mem_limit > 0 {
+                    stored = stored.min((self.zram_mem_limit as f64 * ratio) as u64);
+                }
+                let cost = (stored as f64 / ratio) as u64;
+                rows.push(SizingRow::new(
+                    format!("  at {:.0}x ratio", ratio),
+                    format_size(stored),
+                    format!(
+                        "stored in {} of RAM ({}%){}",
+                        format_size(cost),
+                        percent_of(cost, self.ram),
+                        if stored < self.zram_disksize { ", capped by zram_mem_limit" } else { "" }
+                    ),
+                ));
+            }
+        }
+
+        if self.file_capacity > 0 {
+            let rationale = if self.file_capacity > self.free_disk {
+                format!(
+                    "{} files at most, more than the {} free on disk: expansion stops when the disk fills",
+                    self.max_count,
+                    format_size(self.free_disk)
+                )
+            } else {
+                format!(
+                    "{} files at most, created on demand; {}% of the {} free on disk",
+                    self.max_count,
+                    percent_of(self.file_capacity, self.free_disk),
+                    format_size(self.free_disk)
+                )
+            };
+            rows.push(SizingRow::new("swap files", format_size(self.file_capacity), rationale));
+        }
+
+        let files_on_disk = self.file_capacity.min(self.free_disk);
+        rows.push(SizingRow::new(
+            "total swap",
+            format_size(self.zram_disksize + files_on_disk),
+            format!(
+                "{} with RAM; anonymous memory beyond it is killed by the OOM killer",
+                format_size(self.ram + self.zram_disksize + files_on_disk)
+            ),
+        ));
+
+        // The kernel's default image_size, 2/5 of RAM
+        let image = self.ram / 5 * 2;
+        let (size, rationale) = match &self.hibernation {
+            None => (
+                format_size(image),
+                "not set up (no resume=): needs a swap partition or fixed swap file this large; \
+                 zram and on-demand swap files cannot hold the image"
+                    .to_string(),
+            ),
+            Some(status) => {
+                let area = status.area.as_ref().map_or(0, |a| a.size);
+                let verdict = match status.verdict() {
+                    Verdict::Fits => "possible".to_string(),
+                    Verdict::FitsIfCompressible => "possible if the image compresses as usual".to_string(),
+                    Verdict::TooSmall => "likely to fail, the resume area is too small".to_string(),
+                    Verdict::NoArea => format!("fails: resume device {} is not an active swap area", status.resume_device),
+                };
+                (
+                    format_size(image),
+                    format!(
+                        "{}; image up to {} into {} resume area",
+                        verdict,
+                        format_size(status.image_size),
+                        format_size(area)
+                    ),
+                )
+            }
+        };
+        rows.push(SizingRow::new("hibernation", size, rationale));
+        rows
+    }
+
+    /// The rows as an aligned table
+    pub fn table(&self) -> String {
+        let rows = self.rows();
+        let item_width = rows.iter().map(|r| r.item.len()).max().unwrap_or(0);
+        let size_width = rows.iter().map(|r| r.size.len()).max().unwrap_or(0);
+        rows.iter()
+            .map(|r| format!("  {:<iw$}  {:>sw$}  {}\n", r.item, r.size, r.rationale, iw = item_width, sw = size_width))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::GB;
+
+    fn sizing() -> Sizing {
+        Sizing {
+            ram: 16 * GB,
+            zram_disksize: 24 * GB,
+            zram_mem_limit: 0,
+            file_capacity: 14 * GB,
+            max_count: 28,
+            free_disk: 100 * GB,
+            hibernation: None,
+        }
+    }
+
+    fn row<'a>(rows: &'a [SizingRow], item: &str) -> &'a SizingRow {
+        rows.iter().find(|r| r.item == item).unwrap()
+    }
+
+    #[test]
+    fn zram_capacity_at_assumed_ratios() {
+        let rows = sizing().rows();
+        assert_eq!(row(&rows, "  at 3x ratio").rationale, "stored in 8.0 GiB of RAM (50%)");
+        assert_eq!(row(&rows, "total swap").size, "38.0 GiB");
+        assert!(row(&rows, "swap files").rationale.contains("14% of the 100.0 GiB free"));
+        assert!(row(&rows, "hibernation").rationale.starts_with("not set up"));
+
+        let capped = Sizing { zram_mem_limit: 4 * GB, ..sizing() }.rows();
+        assert_eq!(row(&capped, "  at 2x ratio").size, "8.0 GiB");
+        assert!(row(&capped, "  at 2x ratio").rationale.ends_with("capped by zram_mem_limit"));
+    }
+
+    #[test]
+    fn disk_bill_beyond_free_space_is_flagged() {
+        let short = Sizing { free_disk: 10 * GB, ..sizing() };
+        let rows = short.rows();
+        assert!(row(&rows, "swap files").rationale.contains("expansion stops when the disk fills"));
+        assert_eq!(row(&rows, "total swap").size, "34.0 GiB");
+        assert!(short.table().lines().all(|l| l.starts_with("  ")));
+    }
+}
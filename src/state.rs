@@ -0,0 +1,246 @@
+// Running daemon state for systemd-swap
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// The daemon records who it is and what it manages in WORK_DIR/state once the
+// effective swap mode is known. CLI commands (status, handoff) read it back;
+// a missing file or a dead pid means no daemon is running. The swap file
+// directory is recorded too: autoconfig may have moved it (/var/swap on an
+// immutable root), and stop must clean the directory the daemon used.
+//
+// Files the daemon rewrites while CLI commands read them (this state, pacing,
+// zram device and loop info) go through write_shared: the content goes to a
+// temporary file that is renamed over the old one, so a reader never sees a
+// torn file. Around each write the counter in WORK_DIR/generation turns odd
+// and back to even. read_consistent repeats a read until the counter was even
+// and unchanged across it, so a view built from several files belongs to one
+// moment. A write interrupted by a crash leaves the counter odd; readers then
+// give up waiting after READ_ATTEMPTS and take what they read.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use crate::context::work_dir;
+use crate::handoff::HANDOFF_PROTOCOL;
+use crate::helpers::{now_secs, read_file};
+
+/// State this process recorded, to write back if WORK_DIR is cleared
+static RECORDED: Mutex<Option<DaemonState>> = Mutex::new(None);
+
+/// Serializes shared writes of the daemon's threads
+static WRITE_LOCK: Mutex<()> = Mutex::new(());
+
+/// Reads tried before taking one that raced a write
+const READ_ATTEMPTS: u32 = 20;
+
+/// Pause before retrying a read that raced a write
+const READ_RETRY: Duration = Duration::from_millis(10);
+
+fn state_file() -> String {
+    format!("{}/state", work_dir())
+}
+
+fn generation_file() -> String {
+    format!("{}/generation", work_dir())
+}
+
+/// Current write generation; odd while a write is in progress
+pub fn generation() -> u64 {
+    fs::read_to_string(generation_file())
+        .ok()
+        .and_then(|s| s.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+/// Replace `path` by a whole new file. The temporary file is hidden, so
+/// scans for loop_* and the like never pick it up.
+fn replace(path: &Path, content: &str) -> io::Result<()> {
+    let name = path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+    let tmp = path.with_file_name(format!(".{}.tmp", name));
+    fs::write(&tmp, content)?;
+    fs::rename(&tmp, path)
+}
+
+/// Write a file CLI commands read while the daemon runs
+pub fn write_shared<P: AsRef<Path>>(path: P, content: &str) -> io::Result<()> {
+    write_shared_all(&[(path, content)])
+}
+
+/// Write several files CLI commands read while the daemon runs as one
+/// change: a consistent read sees all of them old or all of them new
+pub fn write_shared_all<P: AsRef<Path>>(files: &[(P, &str)]) -> io::Result<()> {
+    let _guard = WRITE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    // Odd while writing; an odd count left by a crash is skipped past
+    let start = (generation() + 1) | 1;
+    let counter = generation_file();
+    replace(Path::new(&counter), &start.to_string())?;
+    let written = files.iter().try_for_each(|(path, content)| replace(path.as_ref(), content));
+    replace(Path::new(&counter), &(start + 1).to_string())?;
+    written
+}
+
+/// Run `read` until no shared write overlapped it
+pub fn read_consistent<T>(mut read: impl FnMut() -> T) -> T {
+    for _ in 1..READ_ATTEMPTS {
+        let before = generation();
+        if before.is_multiple_of(2) {
+            let value = read();
+            if generation() == before {
+                return value;
+            }
+        }
+        thread::sleep(READ_RETRY);
+    }
+    read()
+}
+
+/// Identity and mode of the running daemon
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DaemonState {
+    pub version: String,
+    pub pid: u32,
+    /// Handoff protocol spoken by this daemon
+    pub protocol: u32,
+    /// Effective swap mode, as a swap_mode value (e.g. "zram+swapfile")
+    pub mode: String,
+    /// Unix time management started
+    pub started: u64,
+    /// Effective swap file directory, after autoconfig
+    pub swapfile_path: String,
+}
+
+impl DaemonState {
+    /// State for this process
+    pub fn current(mode: &str, swapfile_path: &str) -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            pid: std::process::id(),
+            protocol: HANDOFF_PROTOCOL,
+            mode: mode.to_string(),
+            started: now_secs(),
+            swapfile_path: swapfile_path.to_string(),
+        }
+    }
+
+    /// Record this process as the running daemon
+    pub fn record(mode: &str, swapfile_path: &str) -> std::io::Result<()> {
+        let state = Self::current(mode, swapfile_path);
+        write_shared(state_file(), &state.to_file())?;
+        *RECORDED.lock().unwrap_or_else(|e| e.into_inner()) = Some(state);
+        Ok(())
+    }
+
+    /// Write the recorded state again, keeping its start time. Returns false
+    /// if this process has not recorded any.
+    pub fn rewrite() -> std::io::Result<bool> {
+        let recorded = RECORDED.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        match recorded {
+            Some(state) => write_shared(state_file(), &state.to_file()).map(|_| true),
+            None => Ok(false),
+        }
+    }
+
+    /// Read the running daemon's state; None if it is missing or the pid is gone
+    pub fn load() -> Option<Self> {
+        let state = Self::from_file(&read_file(state_file()).ok()?);
+        if state.pid == 0 || !Path::new(&format!("/proc/{}", state.pid)).exists() {
+            return None;
+        }
+        Some(state)
+    }
+
+    /// Swap file directory the last daemon used, whether or not it still runs
+    pub fn recorded_swapfile_path() -> Option<String> {
+        let state = Self::from_file(&read_file(state_file()).ok()?);
+        Some(state.swapfile_path).filter(|path| !path.is_empty())
+    }
+
+    fn to_file(&self) -> String {
+        format!(
+            "version={}\npid={}\nprotocol={}\nmode={}\nstarted={}\nswapfile_path={}\n",
+            self.version, self.pid, self.protocol, self.mode, self.started, self.swapfile_path
+        )
+    }
+
+    fn from_file(content: &str) -> Self {
+        let mut state = Self::default();
+        for (key, value) in content
+            .lines()
+            .filter_map(|l| l.split_once('='))
+            .map(|(k, v)| (k.trim(), v.trim()))
+        {
+            match key {
+                "version" => state.version = value.to_string(),
+                "pid" => state.pid = value.parse().unwrap_or(0),
+                "protocol" => state.protocol = value.parse().unwrap_or(0),
+                "mode" => state.mode = value.to_string(),
+                "started" => state.started = value.parse().unwrap_or(0),
+                "swapfile_path" => state.swapfile_path = value.to_string(),
+                _ => {}
+            }
+        }
+        state
+    }
+
+    /// Seconds since management started
+    pub fn uptime_secs(&self) -> u64 {
+        now_secs().saturating_sub(self.started)
+    }
+
+    /// One-line summary for status and sd_notify
+    pub fn summary(&self) -> String {
+        format!("{} mode, systemd-swap {}", self.mode, self.version)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn state_file_roundtrip() {
+        let state = DaemonState {
+            version: "0.2.0".into(),
+            pid: 1234,
+            protocol: 1,
+            mode: "zram+swapfile".into(),
+            started: 1_700_000_000,
+            swapfile_path: "/var/swap".into(),
+        };
+        assert_eq!(DaemonState::from_file(&state.to_file()), state);
+    }
+
+    #[test]
+    fn shared_writes_bump_an_even_generation() {
+        let dir = std::env::temp_dir().join(format!("state-generation-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        crate::context::enter(crate::context::Context {
+            work_dir: dir.clone(),
+            ..Default::default()
+        });
+
+        let file = dir.join("pacing");
+        write_shared(&file, "cooldown=30\n").unwrap();
+        assert_eq!(generation(), 2);
+        assert_eq!(read_consistent(|| fs::read_to_string(&file).unwrap()), "cooldown=30\n");
+        assert!(!dir.join(".pacing.tmp").exists());
+
+        // A write cut short by a crash: readers do not wait forever
+        fs::write(generation_file(), "3").unwrap();
+        assert_eq!(read_consistent(generation), 3);
+        write_shared(&file, "cooldown=60\n").unwrap();
+        assert_eq!(generation(), 6);
+
+        // Several files, one generation step
+        let meta = dir.join("pool_meta");
+        write_shared_all(&[(&file, "cooldown=90\n"), (&meta, "devices=2\n")]).unwrap();
+        assert_eq!(generation(), 8);
+        assert_eq!(fs::read_to_string(&meta).unwrap(), "devices=2\n");
+
+        crate::context::leave();
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -4,18 +4,31 @@
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
-use std::thread;
 use std::time::{Duration, Instant};
 
 use thiserror::Error;
 
-use crate::config::{Config, WORK_DIR};
+use crate::alignment::Alignment;
+use crate::btrfs::{mount_toplevel_subvolume, BtrfsMount, Layout, SwapParent, TOPLEVEL_SUBVOLUME};
+use crate::config::Config;
+use crate::context::work_dir;
 use crate::defaults;
-use crate::helpers::{force_remove, get_fstype, makedirs, parse_size as parse_size_shared, run_cmd_output};
-use crate::meminfo::{get_free_ram_percent, get_free_swap_percent_effective};
+use crate::events::{Event, EventKind};
+use crate::exec::TimedCommand;
+use crate::helpers::{force_remove, format_size, get_fstype, makedirs, now_secs, parse_size, write_sysfs, GB};
+use crate::loopdev::{FileId, InodeRegistry, LoopRegistry};
+use crate::meminfo::{
+    get_effective_swap_usage, get_free_ram_percent, get_free_swap_percent_effective, get_ram_size, FreeRamTracker,
+};
+use crate::profiler::{Phase, TickProfiler};
+use crate::psi::PressureWaiter;
+use crate::recovery::WorkDirWatch;
+use crate::state::write_shared;
 use crate::systemd::{
     gen_swap_unit, notify_ready, notify_status, swapoff, systemctl, SystemctlAction,
 };
+use crate::tiers::Tier;
+use crate::wipe::WipeMethod;
 use crate::{debug, info, is_shutdown, warn};
 
 #[derive(Error, Debug)]
@@ -30,8 +43,14 @@ pub enum SwapFileError {
     InvalidPath,
     #[error("Unsupported filesystem (requires btrfs, ext4, or xfs)")]
     UnsupportedFs,
+    #[error("swapfile_path {0} is on {1}, which lives in RAM; swap files there free no memory")]
+    RamBackedPath(String, String),
     #[error("Not enough space")]
     NoSpace,
+    #[error("Invalid swapfile_layout: {0}")]
+    InvalidLayout(String),
+    #[error("Config error: {0}")]
+    Config(#[from] crate::config::ConfigError),
 }
 
 pub type Result<T> = std::result::Result<T, SwapFileError>;
@@ -68,11 +87,12 @@ pub struct SwapFileConfig {
     pub chunk_size: u64,
     pub max_count: u32,
     pub min_count: u32,
+    /// min_count was not set explicitly and follows RAM size and mode
+    pub min_count_auto: bool,
     pub free_ram_perc: u8,
     pub free_swap_perc: u8,
     pub remove_free_swap_perc: u8,
     pub frequency: u64,
-    /// Priority for swap files (-1 = auto-calculate based on storage type)
     /// Individual file usage threshold for removal consideration (default: 30%)
     pub shrink_threshold: u8,
     /// Safe headroom percentage to maintain in other files after migration (default: 40%)
@@ -96,6 +116,258 @@ pub struct SwapFileConfig {
     /// NOCOW (chattr +C) on btrfs swap files.
     /// Default: true (prevents btrfs deadlock under memory pressure).
     pub nocow: bool,
+    /// Explicit per-file sizes from `swapfile_layout` (file N gets layout[N-1]).
+    /// Empty = uniform chunk_size. When set, files are created in this order and
+    /// removed in reverse, and max_count equals the number of entries.
+    pub layout: Vec<u64>,
+    /// Free swap percentage below which expansion skips the normal cooldown,
+    /// set when aligned with a systemd-oomd swap kill limit (oomd_align)
+    pub oomd_guard_free_swap: Option<u8>,
+    /// How each file's swap priority is chosen
+    pub priority: PriorityPolicy,
+    /// Largest size a swap file may be grown to in place when folding the
+    /// last file into it (ext4/xfs preallocated files only). 0 = never grow.
+    pub grow_max_size: u64,
+    /// Clear a removed file's blocks before unlinking it (swapfile_wipe_on_remove)
+    pub wipe_on_remove: bool,
+    /// Seconds before a fstrim.timer run from which discard wipes wait for
+    /// it to finish; 0 = no coordination
+    pub trim_window: u64,
+    /// Parent of a new btrfs swap subvolume: auto, nested or toplevel
+    pub btrfs_parent: String,
+    /// Ephemeral burst files on top of a full pool; None = off
+    pub burst: Option<BurstConfig>,
+    /// The directory shares a spinning disk with swap set up by others (see
+    /// disks.rs): only the emergency triggers add files there
+    pub shared_slow_disk: bool,
+}
+
+/// Thresholds of the burst class: temporary files in `<swapfile_path>/burst`
+/// that are added quickly once the persistent pool is at max_count and
+/// removed again after a quiet period.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BurstConfig {
+    pub max_count: u32,
+    /// Effective free swap (percent) below which a burst file is added
+    pub free_swap_perc: u8,
+    /// Seconds free swap must stay above free_swap_perc before burst files go
+    pub quiet_secs: u64,
+}
+
+impl BurstConfig {
+    /// None when swapfile_burst_max_count is 0 or no swap area is left for it
+    fn from_config(config: &Config, pool_max: u32, sparse: bool) -> Option<Self> {
+        let requested = config
+            .get_as::<u32>("swapfile_burst_max_count")
+            .unwrap_or(defaults::SWAPFILE_BURST_MAX_COUNT);
+        if requested == 0 {
+            return None;
+        }
+        if sparse {
+            warn!("swapFC: burst files need preallocated swap files, ignored with swapfile_sparse_loop");
+            return None;
+        }
+        let max_count = burst_limit(requested, pool_max);
+        if max_count < requested {
+            warn!(
+                "swapFC: swapfile_max_count={} leaves {} of {} swap areas for burst files, asked for {}",
+                pool_max, max_count, MAX_SWAPFILES, requested
+            );
+        }
+        (max_count > 0).then(|| Self {
+            max_count,
+            free_swap_perc: config
+                .get_as::<u32>("swapfile_burst_free_swap_perc")
+                .unwrap_or(defaults::SWAPFILE_BURST_FREE_SWAP_PERC as u32)
+                .min(100) as u8,
+            quiet_secs: config
+                .get_as::<u64>("swapfile_burst_quiet")
+                .unwrap_or(defaults::SWAPFILE_BURST_QUIET),
+        })
+    }
+}
+
+/// Burst files allowed next to a pool of `pool_max` files: pool and burst
+/// files share the kernel's limit of swap areas
+fn burst_limit(requested: u32, pool_max: u32) -> u32 {
+    requested.min((MAX_SWAPFILES as u32).saturating_sub(pool_max))
+}
+
+/// Kernel name (e.g. "sda2", "dm-0") of the block device a path's filesystem is mounted from
+pub fn source_block_device(path: &Path) -> Option<String> {
+    let target = path
+        .ancestors()
+        .find(|p| p.exists())
+        .unwrap_or(Path::new("/"));
+    let output = Command::new("findmnt")
+        .args(["-n", "-o", "SOURCE", "--target"])
+        .arg(target)
+        .output_timed()
+        .ok()?;
+    // btrfs reports the subvolume as /dev/sda2[/@swap]
+    let source = String::from_utf8_lossy(&output.stdout);
+    let source = source.trim().split('[').next().unwrap_or("");
+    fs::canonicalize(source)
+        .ok()
+        .and_then(|p| p.file_name().map(|n| n.to_string_lossy().into_owned()))
+}
+
+/// Kind of device under the swap file directory, fastest first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum StorageType {
+    Nvme,
+    Ssd,
+    Hdd,
+    Unknown,
+}
+
+/// Priorities available to each storage type's files
+const PRIORITY_WINDOW: i32 = 1000;
+
+impl StorageType {
+    pub fn name(self) -> &'static str {
+        match self {
+            StorageType::Nvme => "nvme",
+            StorageType::Ssd => "ssd",
+            StorageType::Hdd => "hdd",
+            StorageType::Unknown => "unknown",
+        }
+    }
+
+    /// Classify a whole-disk block device by name and queue/rotational
+    fn classify(disk: &str, rotational: Option<bool>) -> Self {
+        match rotational {
+            _ if disk.starts_with("nvme") => StorageType::Nvme,
+            Some(false) => StorageType::Ssd,
+            Some(true) => StorageType::Hdd,
+            None => StorageType::Unknown,
+        }
+    }
+
+    /// Detect the storage type of the device a path lives on
+    pub fn detect<P: AsRef<Path>>(path: P) -> Self {
+        match source_block_device(path.as_ref()) {
+            Some(name) => Self::of_device(name),
+            None => StorageType::Unknown,
+        }
+    }
+
+    /// Storage type of a block device given by name, e.g. "sda2"
+    pub fn of_device(name: String) -> Self {
+        // Partitions have no queue of their own; use the parent disk
+        let sys = PathBuf::from("/sys/class/block").join(&name);
+        let disk = if sys.join("partition").exists() {
+            fs::canonicalize(&sys)
+                .ok()
+                .and_then(|p| p.parent().and_then(|d| d.file_name()).map(|n| n.to_string_lossy().into_owned()))
+                .unwrap_or(name)
+        } else {
+            name
+        };
+        let rotational = fs::read_to_string(format!("/sys/class/block/{}/queue/rotational", disk))
+            .ok()
+            .map(|r| r.trim() == "1");
+        Self::classify(&disk, rotational)
+    }
+
+    /// Highest priority in this type's window; faster storage gets a higher window
+    pub fn priority_top(self) -> i32 {
+        let windows = match self {
+            StorageType::Nvme => 3,
+            StorageType::Ssd => 2,
+            StorageType::Hdd | StorageType::Unknown => 1,
+        };
+        windows * PRIORITY_WINDOW + PRIORITY_WINDOW - 1
+    }
+}
+
+/// How swap file priorities are assigned (`swapfile_priority`)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PriorityPolicy {
+    /// Window derived from the directory's StorageType
+    Auto,
+    /// Counting down from a fixed value
+    Fixed(i32),
+    /// Let the kernel assign decreasing negative priorities
+    Kernel,
+}
+
+impl PriorityPolicy {
+    fn parse(value: &str) -> Self {
+        let value = value.trim();
+        if value.is_empty() || value.eq_ignore_ascii_case("auto") {
+            return PriorityPolicy::Auto;
+        }
+        match value.parse::<i32>() {
+            Ok(-1) => PriorityPolicy::Kernel,
+            Ok(n) if (0..=32767).contains(&n) => PriorityPolicy::Fixed(n),
+            _ => {
+                warn!("swapFC: invalid swapfile_priority '{}', using auto", value);
+                PriorityPolicy::Auto
+            }
+        }
+    }
+
+    /// Priority for the Nth file (1-based); earlier files sort first
+    pub fn for_file(self, storage: StorageType, file_num: u32) -> Option<i32> {
+        let top = match self {
+            PriorityPolicy::Auto => storage.priority_top(),
+            PriorityPolicy::Fixed(n) => n,
+            PriorityPolicy::Kernel => return None,
+        };
+        let offset = (file_num.saturating_sub(1) as i32).min(PRIORITY_WINDOW - 1);
+        Some((top - offset).max(0))
+    }
+}
+
+/// Hard limit on swap files (most kernels support 32 swap areas, zram uses some)
+const MAX_SWAPFILES: usize = 28;
+
+/// Unused files zswap mode always keeps as writeback reserve
+const ZSWAP_MIN_RESERVE: usize = 2;
+
+/// Initial swap file count for `swapfile_min_count=auto`.
+///
+/// Zswap writes back into these files, so larger hosts start with a few of
+/// them ready; zram overflow and small systems need only one.
+pub fn recommended_min_count(ram_bytes: u64, zswap: bool) -> u32 {
+    if !zswap {
+        return 1;
+    }
+    match ram_bytes {
+        r if r >= 32 * GB => 3,
+        r if r >= 8 * GB => 2,
+        _ => 1,
+    }
+}
+
+/// Parse `swapfile_layout` ("2G,2G,4G,8G") into per-file sizes.
+/// Every entry must be at least `min_size`; an empty string means no layout.
+fn parse_layout(value: &str, min_size: u64) -> Result<Vec<u64>> {
+    if value.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut sizes = Vec::new();
+    for entry in value.split(',') {
+        let entry = entry.trim();
+        let size = parse_size(entry).map_err(SwapFileError::InvalidLayout)?;
+        if size < min_size {
+            return Err(SwapFileError::InvalidLayout(format!(
+                "{} is below the {}MB minimum",
+                entry,
+                min_size / (1024 * 1024)
+            )));
+        }
+        sizes.push(size);
+    }
+    if sizes.len() > MAX_SWAPFILES {
+        return Err(SwapFileError::InvalidLayout(format!(
+            "{} entries, at most {} swap files are supported",
+            sizes.len(),
+            MAX_SWAPFILES
+        )));
+    }
+    Ok(sizes)
 }
 
 
@@ -144,19 +416,43 @@ impl SwapFileConfig {
             return Err(SwapFileError::InvalidPath);
         }
 
-        let chunk_size_str = config.get("swapfile_chunk_size").unwrap_or(defaults::SWAPFILE_CHUNK_SIZE).to_string();
-        let chunk_size = parse_size_shared(&chunk_size_str).map_err(|_| SwapFileError::InvalidPath)?;
+        let chunk_size = config.get_size_or("swapfile_chunk_size", defaults::SWAPFILE_CHUNK_SIZE)?;
         let sparse = config.get_bool("swapfile_sparse_loop");
-        let chunk_size = chunk_size.max(if sparse {
+        let min_chunk: u64 = if sparse {
             128 * 1024 * 1024
         } else {
             512 * 1024 * 1024
-        });
+        };
+        let chunk_size = chunk_size.max(min_chunk);
+
+        let layout = parse_layout(config.get("swapfile_layout").unwrap_or(defaults::SWAPFILE_LAYOUT), min_chunk)?;
 
         let max_count: u32 = config.get_as("swapfile_max_count").unwrap_or(defaults::SWAPFILE_MAX_COUNT);
-        let max_count = max_count.clamp(1, 28);
+        let max_count = if layout.is_empty() {
+            max_count.clamp(1, MAX_SWAPFILES as u32)
+        } else {
+            layout.len() as u32
+        };
 
-        let min_count: u32 = config.get_as("swapfile_min_count").unwrap_or(defaults::SWAPFILE_MIN_COUNT);
+        let min_count_value = config
+            .get("swapfile_min_count")
+            .unwrap_or(defaults::SWAPFILE_MIN_COUNT)
+            .trim();
+        let explicit_min_count = if min_count_value.is_empty() || min_count_value.eq_ignore_ascii_case("auto") {
+            None
+        } else {
+            match min_count_value.parse::<u32>() {
+                Ok(n) => Some(n),
+                Err(_) => {
+                    warn!("swapFC: invalid swapfile_min_count '{}', using auto", min_count_value);
+                    None
+                }
+            }
+        };
+        let min_count_auto = explicit_min_count.is_none();
+        let min_count = explicit_min_count
+            .unwrap_or_else(|| recommended_min_count(get_ram_size().unwrap_or(0), crate::zswap::is_enabled()))
+            .min(max_count);
         let frequency: u64 = config.get_as::<u32>("swapfile_frequency").unwrap_or(defaults::SWAPFILE_FREQUENCY) as u64;
         let frequency = frequency.clamp(1, 86400);
 
@@ -168,32 +464,212 @@ impl SwapFileConfig {
             config.get_as::<u32>("swapfile_safe_headroom").unwrap_or(defaults::SWAPFILE_SAFE_HEADROOM as u32) as u8;
         let safe_headroom = safe_headroom.clamp(20, 60);
 
+        let mut free_swap_perc = config
+            .get_as::<u32>("swapfile_free_swap_perc")
+            .unwrap_or(defaults::SWAPFILE_FREE_SWAP_PERC as u32)
+            .min(100) as u8;
+        let mut remove_free_swap_perc = config
+            .get_as::<u32>("swapfile_remove_free_swap_perc")
+            .unwrap_or(defaults::SWAPFILE_REMOVE_FREE_SWAP_PERC as u32)
+            .min(100) as u8;
+        let mut oomd_guard_free_swap = None;
+        if config.get_bool("oomd_align") {
+            let oomd = crate::oomd::OomdStatus::detect();
+            if oomd.swap_kill_active() {
+                let aligned = oomd.aligned_free_swap_perc(free_swap_perc);
+                if aligned != free_swap_perc {
+                    info!(
+                        "swapFC: systemd-oomd kills at {}% swap used, expanding below {}% free (was {}%)",
+                        oomd.swap_used_limit, aligned, free_swap_perc
+                    );
+                    free_swap_perc = aligned;
+                }
+                // Keep the removal threshold above the expansion threshold
+                remove_free_swap_perc = remove_free_swap_perc.max(free_swap_perc.saturating_add(10).min(100));
+                oomd_guard_free_swap = Some(oomd.guard_free_swap_perc());
+            }
+        }
+
         Ok(Self {
             path,
             chunk_size,
             max_count,
             min_count,
+            min_count_auto,
             free_ram_perc: config.get_as::<u32>("swapfile_free_ram_perc").unwrap_or(defaults::SWAPFILE_FREE_RAM_PERC as u32) as u8,
-            free_swap_perc: config.get_as::<u32>("swapfile_free_swap_perc").unwrap_or(defaults::SWAPFILE_FREE_SWAP_PERC as u32) as u8,
-            remove_free_swap_perc: config.get_as::<u32>("swapfile_remove_free_swap_perc").unwrap_or(defaults::SWAPFILE_REMOVE_FREE_SWAP_PERC as u32) as u8,
+            free_swap_perc,
+            remove_free_swap_perc,
             frequency,
             shrink_threshold,
             safe_headroom,
             sparse_loop_backing: sparse,
-            growth_chunk_size: {
-                let s = config.get("swapfile_growth_chunk_size").unwrap_or("").to_string();
-                if s.is_empty() {
-                    0
-                } else {
-                    parse_size_shared(&s).unwrap_or(0)
-                }
-            },
+            growth_chunk_size: config.get_size_or("swapfile_growth_chunk_size", "0")?,
             nocow: {
                 let s = config.get("swapfile_nocow").unwrap_or(defaults::SWAPFILE_NOCOW).to_string();
                 !matches!(s.as_str(), "0" | "false" | "no" | "off")
             },
+            layout,
+            oomd_guard_free_swap,
+            priority: PriorityPolicy::parse(
+                config.get("swapfile_priority").unwrap_or(defaults::SWAPFILE_PRIORITY),
+            ),
+            grow_max_size: config.get_size_or("swapfile_grow_max_size", defaults::SWAPFILE_GROW_MAX_SIZE)?,
+            wipe_on_remove: config.get_bool("swapfile_wipe_on_remove"),
+            trim_window: config
+                .get_as::<u64>("swapfile_trim_window")
+                .unwrap_or(defaults::SWAPFILE_TRIM_WINDOW),
+            btrfs_parent: config
+                .get("swapfile_btrfs_parent")
+                .unwrap_or(defaults::SWAPFILE_BTRFS_PARENT)
+                .to_string(),
+            burst: BurstConfig::from_config(config, max_count, sparse),
+            shared_slow_disk: false,
         })
     }
+
+    /// Whether files on this filesystem may be grown in place.
+    ///
+    /// fallocate extends ext4/xfs files with real extents that swapon accepts;
+    /// on btrfs it would create PREALLOC extents, and loop/layout files have fixed roles.
+    pub fn can_grow_on(&self, fstype: Option<&str>) -> bool {
+        matches!(fstype, Some("ext4" | "xfs"))
+            && !self.sparse_loop_backing
+            && self.layout.is_empty()
+            && self.grow_max_size > self.chunk_size
+    }
+
+    /// Re-derive an automatic min_count once the mode is known
+    pub fn set_zswap_mode(&mut self, zswap: bool) {
+        if self.min_count_auto {
+            self.min_count =
+                recommended_min_count(get_ram_size().unwrap_or(0), zswap).min(self.max_count);
+        }
+    }
+
+    /// Size of a sparse loop growth file
+    pub fn growth_size(&self) -> u64 {
+        if self.growth_chunk_size > 0 {
+            self.growth_chunk_size
+        } else {
+            self.chunk_size * 2
+        }
+    }
+
+    /// The trigger that adds a file for `s`, if any. With zswap the kernel
+    /// writes back into the reserve files, so only growth, absorb and slot
+    /// triggers apply; the RAM and swap pressure triggers are for zram and
+    /// plain swap file setups.
+    pub fn expansion_trigger(&self, s: &ExpansionSample) -> Option<Expansion> {
+        if s.disk_full || s.fs_busy || s.at_max_count {
+            return None;
+        }
+        if self.sparse_loop_backing && s.disk_free_swap < 20 && s.cooldown_ok {
+            return Some(Expansion::Growth);
+        }
+        if s.absorb_requested && s.emergency_cooldown_ok {
+            return Some(Expansion::Absorb);
+        }
+        if s.zswap {
+            return (!self.sparse_loop_backing && s.slots_exhausted && s.emergency_cooldown_ok)
+                .then_some(Expansion::Slots);
+        }
+        if s.unused_files >= 2 {
+            return None;
+        }
+        if s.free_ram < EMERGENCY_FREE_RAM && s.free_swap < 80 && s.emergency_cooldown_ok {
+            return Some(Expansion::Emergency);
+        }
+        if self.oomd_guard_free_swap.is_some_and(|guard| s.free_swap < guard) && s.emergency_cooldown_ok {
+            return Some(Expansion::OomdGuard);
+        }
+        // More files on a disk another swap area already thrashes make it
+        // worse; zram grows first and files wait for an emergency
+        if self.shared_slow_disk {
+            return None;
+        }
+        if s.files_stressed && s.free_swap < self.free_swap_perc && s.emergency_cooldown_ok {
+            return Some(Expansion::Stress);
+        }
+        (s.cooldown_ok && s.free_swap < self.free_swap_perc).then_some(Expansion::Normal)
+    }
+
+    /// The file to remove from `files` (active swap files), if any may go:
+    /// never at or below min_count, only nearly empty files `removable`
+    /// accepts, lowest priority first (created last, usually larger, so the
+    /// pool scales down instead of leaving a giant tail file), and only one
+    /// whose data the others can take (see `can_safely_remove`)
+    pub fn removal_candidate<'a>(
+        &self,
+        files: &'a [SwapFileInfo],
+        removable: impl Fn(&SwapFileInfo) -> bool,
+    ) -> Option<&'a SwapFileInfo> {
+        if files.len() <= self.min_count as usize {
+            return None; // Don't remove below minimum
+        }
+
+        let mut candidates: Vec<&SwapFileInfo> = files
+            .iter()
+            .filter(|f| f.is_nearly_empty(self.shrink_threshold) && removable(f))
+            .collect();
+        candidates.sort_by_key(|f| f.priority);
+        candidates.into_iter().find(|candidate| self.can_safely_remove(candidate, files))
+    }
+
+    /// Whether `target` can be swapped off: the other files in `all_files`
+    /// take its data and still keep safe_headroom% of their size free
+    pub fn can_safely_remove(&self, target: &SwapFileInfo, all_files: &[SwapFileInfo]) -> bool {
+        let others = all_files.iter().filter(|f| f.path != target.path);
+        let other_total_size: u64 = others.clone().map(|f| f.size_bytes).sum();
+        let other_total_used: u64 = others.map(|f| f.used_bytes).sum();
+
+        // If no other files, not safe to remove
+        if other_total_size == 0 {
+            return false;
+        }
+
+        let other_free_space = other_total_size.saturating_sub(other_total_used);
+        let required_headroom = (other_total_size * self.safe_headroom as u64) / 100;
+        let required_free = target.used_bytes + required_headroom;
+
+        if other_free_space < required_free {
+            debug!(
+                "swapFC: removing {} not safe - needs {}MB free, has {}MB",
+                target.path.display(),
+                required_free / (1024 * 1024),
+                other_free_space / (1024 * 1024)
+            );
+            return false;
+        }
+
+        true
+    }
+
+    /// Unused files to keep when contracting: the zswap reserve, never below min_count
+    pub fn reserve_files(&self, zswap: bool) -> usize {
+        if zswap {
+            (self.min_count as usize).max(ZSWAP_MIN_RESERVE)
+        } else {
+            0
+        }
+    }
+
+    /// Size of the Nth swap file (1-based): from the layout if set, else chunk_size
+    pub fn size_for_file(&self, file_num: u32) -> u64 {
+        if self.layout.is_empty() {
+            return self.chunk_size;
+        }
+        let idx = (file_num.max(1) - 1) as usize;
+        self.layout[idx.min(self.layout.len() - 1)]
+    }
+
+    /// Total capacity when every file is allocated
+    pub fn max_capacity(&self) -> u64 {
+        if self.layout.is_empty() {
+            self.chunk_size * self.max_count as u64
+        } else {
+            self.layout.iter().sum()
+        }
+    }
 }
 
 /// Optimize a loop block device's I/O queue parameters for swap.
@@ -209,27 +685,28 @@ fn tune_loop_device(loop_dev: &str) {
         warn!("swapFC: cannot tune {} - sysfs queue not found", dev_name);
         return;
     }
+    crate::loopdev::snapshot_queue(loop_dev);
 
-    let _ = fs::write(format!("{}/rotational", queue_path), "0");
-    let _ = fs::write(format!("{}/iostats", queue_path), "0");
-    let _ = fs::write(format!("{}/add_random", queue_path), "0");
+    let _ = write_sysfs(format!("{}/rotational", queue_path), "0");
+    let _ = write_sysfs(format!("{}/iostats", queue_path), "0");
+    let _ = write_sysfs(format!("{}/add_random", queue_path), "0");
 
     // Set scheduler to "none" (passthrough)
     let scheduler_path = format!("{}/scheduler", queue_path);
-    if fs::write(&scheduler_path, "none").is_ok() {
+    if write_sysfs(&scheduler_path, "none").is_ok() {
         info!("swapFC: {} scheduler set to [none]", dev_name);
     } else {
         warn!("swapFC: failed to set scheduler none on {}", dev_name);
     }
 
     // Queue parameters
-    let _ = fs::write(format!("{}/nomerges", queue_path), "0");
+    let _ = write_sysfs(format!("{}/nomerges", queue_path), "0");
     let wbt_path = format!("{}/wbt_lat_usec", queue_path);
     if Path::new(&wbt_path).exists() {
-        let _ = fs::write(&wbt_path, "75000");
+        let _ = write_sysfs(&wbt_path, "75000");
     }
-    let _ = fs::write(format!("{}/max_sectors_kb", queue_path), "512");
-    let _ = fs::write(format!("{}/rq_affinity", queue_path), "1");
+    let _ = write_sysfs(format!("{}/max_sectors_kb", queue_path), "512");
+    let _ = write_sysfs(format!("{}/rq_affinity", queue_path), "1");
 }
 
 /// Re-apply volatile queue parameters that swapon may reset.
@@ -242,13 +719,173 @@ fn retune_loop_queue(loop_dev: &str) {
         info!("swapFC: retune {} - queue path not found", dev_name);
         return;
     }
-    let _ = fs::write(format!("{}/nomerges", queue_path), "0");
+    let _ = write_sysfs(format!("{}/nomerges", queue_path), "0");
     let wbt_path = format!("{}/wbt_lat_usec", queue_path);
     if Path::new(&wbt_path).exists() {
-        let _ = fs::write(&wbt_path, "75000");
+        let _ = write_sysfs(&wbt_path, "75000");
+    }
+    let _ = write_sysfs(format!("{}/max_sectors_kb", queue_path), "512");
+    let _ = write_sysfs(format!("{}/rq_affinity", queue_path), "1");
+}
+
+/// Short cooldown for emergency/stress creation triggers
+pub const EMERGENCY_COOLDOWN_SECS: u64 = 5;
+
+/// Free RAM (percent) below which the emergency trigger fires
+pub const EMERGENCY_FREE_RAM: u8 = 10;
+
+/// Why the monitor adds a swap file, in the order the triggers are checked
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Expansion {
+    /// Sparse loop files: disk swap 80%+ full, add a larger file
+    Growth,
+    /// `absorb-partition` waits for room to drain a partition
+    Absorb,
+    /// Zswap: swap slots exhausted while the pool still has room
+    Slots,
+    /// Critical RAM pressure
+    Emergency,
+    /// Swap use close to systemd-oomd's kill limit
+    OomdGuard,
+    /// Every file 85%+ full
+    Stress,
+    /// Free swap below swapfile_free_swap_perc
+    Normal,
+}
+
+impl Expansion {
+    pub fn name(self) -> &'static str {
+        match self {
+            Expansion::Growth => "growth",
+            Expansion::Absorb => "absorb",
+            Expansion::Slots => "slot",
+            Expansion::Emergency => "emergency",
+            Expansion::OomdGuard => "oomd guard",
+            Expansion::Stress => "stress",
+            Expansion::Normal => "normal",
+        }
+    }
+
+    /// Creation cooldown after this trigger added a file
+    pub fn cooldown_after(self, current: u64) -> u64 {
+        match self {
+            Expansion::Absorb => current,
+            Expansion::Normal => (current * 2).min(120),
+            _ => 30,
+        }
+    }
+}
+
+/// One monitor sample, with the pacing state the expansion triggers check
+#[derive(Debug, Clone, Default)]
+pub struct ExpansionSample {
+    pub free_ram: u8,
+    /// Effective free swap (zswap pool counted as free)
+    pub free_swap: u8,
+    /// Free swap of the files themselves, from /proc/swaps
+    pub disk_free_swap: u8,
+    /// Files with no data yet
+    pub unused_files: usize,
+    /// Every file is at least 85% full
+    pub files_stressed: bool,
+    pub slots_exhausted: bool,
+    pub absorb_requested: bool,
+    pub cooldown_ok: bool,
+    pub emergency_cooldown_ok: bool,
+    pub zswap: bool,
+    pub disk_full: bool,
+    /// A btrfs balance, resize or device change runs on the filesystem
+    pub fs_busy: bool,
+    pub at_max_count: bool,
+}
+
+/// Minimum time between two in-place consolidations
+const CONSOLIDATION_INTERVAL_SECS: u64 = 600;
+/// Free RAM (percent) that makes a low-pressure window for consolidation
+const CONSOLIDATION_MIN_FREE_RAM: u8 = 50;
+
+/// Lowest-numbered file that can grow by `extra` bytes without passing `max_size`
+fn consolidation_target(sizes: &[(u32, u64)], extra: u64, max_size: u64) -> Option<u32> {
+    sizes
+        .iter()
+        .filter(|(_, size)| size + extra <= max_size)
+        .map(|(idx, _)| *idx)
+        .min()
+}
+
+/// Snapshot of the creation/removal rate limits, written each monitor tick
+/// so `status` can tell intentional pacing apart from a stuck daemon.
+#[derive(Debug, Clone, Default)]
+pub struct SwapFilePacing {
+    /// Current escalating creation cooldown
+    pub cooldown_secs: u64,
+    /// Seconds until a normal-pressure creation is allowed
+    pub creation_in: u64,
+    /// Seconds until an emergency/stress creation is allowed
+    pub emergency_in: u64,
+    /// Seconds until a swap file may be removed
+    pub removal_in: u64,
+    /// Expansion paused because the filesystem is full
+    pub disk_full: bool,
+    /// Expansion deferred while a btrfs balance, resize or device change runs
+    pub fs_busy: bool,
+    /// Seconds between monitor ticks when the snapshot was written
+    pub interval: u64,
+    /// Seconds since the snapshot was written (set by `load`)
+    pub age: u64,
+}
+
+impl SwapFilePacing {
+    fn state_path() -> String {
+        format!("{}/swapfile/pacing", work_dir())
+    }
+
+    fn save(&self) {
+        let now = now_secs();
+        let content = format!(
+            "updated={}\ncooldown={}\ncreation={}\nemergency={}\nremoval={}\ndisk_full={}\nfs_busy={}\ninterval={}\n",
+            now,
+            self.cooldown_secs,
+            self.creation_in,
+            self.emergency_in,
+            self.removal_in,
+            self.disk_full as u8,
+            self.fs_busy as u8,
+            self.interval
+        );
+        let _ = write_shared(Self::state_path(), &content);
+    }
+
+    /// Load the last snapshot, with timers advanced by the time since it was written
+    pub fn load() -> Option<Self> {
+        let content = fs::read_to_string(Self::state_path()).ok()?;
+        let mut pacing = Self::default();
+        let mut updated = 0;
+        for line in content.lines() {
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let value: u64 = value.trim().parse().unwrap_or(0);
+            match key.trim() {
+                "updated" => updated = value,
+                "cooldown" => pacing.cooldown_secs = value,
+                "creation" => pacing.creation_in = value,
+                "emergency" => pacing.emergency_in = value,
+                "removal" => pacing.removal_in = value,
+                "disk_full" => pacing.disk_full = value == 1,
+                "fs_busy" => pacing.fs_busy = value == 1,
+                "interval" => pacing.interval = value,
+                _ => {}
+            }
+        }
+        let now = now_secs();
+        let age = now.saturating_sub(updated);
+        pacing.age = age;
+        pacing.creation_in = pacing.creation_in.saturating_sub(age);
+        pacing.emergency_in = pacing.emergency_in.saturating_sub(age);
+        pacing.removal_in = pacing.removal_in.saturating_sub(age);
+        Some(pacing)
     }
-    let _ = fs::write(format!("{}/max_sectors_kb", queue_path), "512");
-    let _ = fs::write(format!("{}/rq_affinity", queue_path), "1");
 }
 
 /// SwapFC manager - supports btrfs, ext4, and xfs
@@ -257,6 +894,8 @@ pub struct SwapFile {
     allocated: u32,
     /// True if path is on btrfs (for subvolume/nodatacow handling)
     is_btrfs: bool,
+    /// Device class under the swap directory (picks the priority window)
+    storage: StorageType,
     /// Track the size of each allocated file (for proper cleanup and stats)
     file_sizes: Vec<u64>,
     /// Cooldown: last time a swap file was created (prevents runaway creation)
@@ -269,6 +908,32 @@ pub struct SwapFile {
     is_zswap_active: bool,
     /// Disk full flag: stops expansion attempts until space is freed
     disk_full: bool,
+    /// Btrfs mount of the swap directory, asked for exclusive operations
+    btrfs_mount: Option<BtrfsMount>,
+    /// Exclusive btrfs operation deferring expansion, seen at the last tick
+    fs_operation: Option<String>,
+    /// Files can be grown in place (ext4/xfs, preallocated, no layout)
+    can_grow: bool,
+    /// Last time the last file was folded into a grown one
+    last_consolidation: Option<Instant>,
+    /// How removed files are wiped; None = plain unlink
+    wipe: Option<WipeMethod>,
+    /// Loop device number of each loop-backed file, kept across reboots
+    loop_registry: LoopRegistry,
+    /// Device and inode of each swap file, matched before removal or adoption
+    inode_registry: InodeRegistry,
+    /// Last time free swap was below the burst threshold
+    burst_last_pressure: Instant,
+    /// Sleeps between ticks (PSI-triggered when available)
+    waiter: PressureWaiter,
+    /// Notices when WORK_DIR was cleared under us
+    work_dir_watch: WorkDirWatch,
+    /// Free RAM corrected for zram growth MemAvailable lags behind
+    free_ram: FreeRamTracker,
+    /// Sizes loop-backed files are rounded to; None for preallocated files
+    alignment: Option<Alignment>,
+    /// Swap area changes seen by swapwatch when shared_slow_disk was checked
+    swap_generation: u64,
 }
 
 impl SwapFile {
@@ -281,6 +946,18 @@ impl SwapFile {
             swapfile_config.chunk_size / (1024 * 1024),
             swapfile_config.sparse_loop_backing,
         );
+        if !swapfile_config.layout.is_empty() {
+            let sizes: Vec<String> = swapfile_config
+                .layout
+                .iter()
+                .map(|s| format!("{}MB", s / (1024 * 1024)))
+                .collect();
+            info!(
+                "swapFC: layout {} ({}MB total)",
+                sizes.join(","),
+                swapfile_config.max_capacity() / (1024 * 1024)
+            );
+        }
 
         notify_status("Monitoring memory status...");
 
@@ -294,6 +971,12 @@ impl SwapFile {
         // Verify supported filesystem
         match fstype.as_deref() {
             Some("btrfs") | Some("ext4") | Some("xfs") => {}
+            Some(fs @ ("tmpfs" | "ramfs")) => {
+                return Err(SwapFileError::RamBackedPath(
+                    swapfile_config.path.display().to_string(),
+                    fs.to_string(),
+                ));
+            }
             Some(fs) => {
                 warn!(
                     "swapFC: unsupported filesystem '{}', swap files may not work correctly",
@@ -320,48 +1003,76 @@ impl SwapFile {
                     }
                 }
 
-                // Try to create btrfs subvolume
-                let output = Command::new("btrfs")
-                    .args(["subvolume", "create"])
-                    .arg(&swapfile_config.path)
-                    .stdout(Stdio::piped())
-                    .stderr(Stdio::piped())
-                    .output()?;
-
-                if !output.status.success() {
-                    let stderr = String::from_utf8_lossy(&output.stderr);
-                    warn!("swapFC: btrfs subvolume create failed: {}", stderr.trim());
-
-                    // Fallback: try creating as regular directory
-                    info!("swapFC: falling back to regular directory");
-                    fs::create_dir_all(&swapfile_config.path)?;
-
-                    // Set nodatacow attribute if configured
-                    if swapfile_config.nocow {
-                        let _ = Command::new("chattr")
-                            .args(["+C"])
-                            .arg(&swapfile_config.path)
-                            .status();
+                // Known-good parent for the layout: a subvolume nested in @
+                // would be left behind by snapshot restores
+                let parent = BtrfsMount::of(&swapfile_config.path).filter(|m| {
+                    SwapParent::from_setting(&swapfile_config.btrfs_parent, Layout::detect(m)) == SwapParent::TopLevel
+                });
+                let mounted = match parent {
+                    Some(mount) => {
+                        match mount_toplevel_subvolume(&mount, &swapfile_config.path, swapfile_config.nocow) {
+                            Ok(()) => {
+                                info!(
+                                    "swapFC: {} layout, mounted top-level {} at {:?}",
+                                    Layout::detect(&mount).name(),
+                                    TOPLEVEL_SUBVOLUME,
+                                    swapfile_config.path
+                                );
+                                true
+                            }
+                            Err(e) => {
+                                warn!("swapFC: cannot use top-level {}: {}, nesting instead", TOPLEVEL_SUBVOLUME, e);
+                                false
+                            }
+                        }
                     }
+                    None => false,
+                };
 
-                    info!(
-                        "swapFC: created directory (non-subvolume) at {:?}",
-                        swapfile_config.path
-                    );
-                } else {
-                    // Set nodatacow on subvolume for safe swap I/O under memory pressure.
-                    // Without NOCOW, btrfs block allocation during swap writes can deadlock.
-                    if swapfile_config.nocow {
-                        let _ = Command::new("chattr")
-                            .args(["+C"])
-                            .arg(&swapfile_config.path)
-                            .status();
-                    }
+                if !mounted {
+                    // Try to create btrfs subvolume
+                    let output = Command::new("btrfs")
+                        .args(["subvolume", "create"])
+                        .arg(&swapfile_config.path)
+                        .stdout(Stdio::piped())
+                        .stderr(Stdio::piped())
+                        .output_timed()?;
+
+                    if !output.status.success() {
+                        let stderr = String::from_utf8_lossy(&output.stderr);
+                        warn!("swapFC: btrfs subvolume create failed: {}", stderr.trim());
+
+                        // Fallback: try creating as regular directory
+                        info!("swapFC: falling back to regular directory");
+                        fs::create_dir_all(&swapfile_config.path)?;
+
+                        // Set nodatacow attribute if configured
+                        if swapfile_config.nocow {
+                            let _ = Command::new("chattr")
+                                .args(["+C"])
+                                .arg(&swapfile_config.path)
+                                .status_timed();
+                        }
 
-                    info!(
-                        "swapFC: created btrfs subvolume at {:?}",
-                        swapfile_config.path
-                    );
+                        info!(
+                            "swapFC: created directory (non-subvolume) at {:?}",
+                            swapfile_config.path
+                        );
+                    } else {
+                        // Set nodatacow on subvolume for safe swap I/O under memory pressure.
+                        // Without NOCOW, btrfs block allocation during swap writes can deadlock.
+                        if swapfile_config.nocow {
+                            let _ = Command::new("chattr")
+                                .args(["+C"])
+                                .arg(&swapfile_config.path)
+                                .status_timed();
+                        }
+
+                        info!(
+                            "swapFC: created btrfs subvolume at {:?}",
+                            swapfile_config.path
+                        );
+                    }
                 }
             } else {
                 // Subvolume already exists — ensure nocow attribute matches config.
@@ -370,12 +1081,12 @@ impl SwapFile {
                     let _ = Command::new("chattr")
                         .args(["+C"])
                         .arg(&swapfile_config.path)
-                        .status();
+                        .status_timed();
                 } else {
                     let _ = Command::new("chattr")
                         .args(["-C"])
                         .arg(&swapfile_config.path)
-                        .status();
+                        .status_timed();
                 }
             }
         } else {
@@ -400,7 +1111,7 @@ impl SwapFile {
                 .arg(&swapfile_config.path)
                 .stdout(Stdio::piped())
                 .stderr(Stdio::null())
-                .output()
+                .output_timed()
             {
                 let opts = String::from_utf8_lossy(&output.stdout);
                 let needs_no_autodefrag = opts.contains("autodefrag");
@@ -442,7 +1153,7 @@ impl SwapFile {
                         .arg(&swapfile_config.path)
                         .stdout(Stdio::null())
                         .stderr(Stdio::null())
-                        .status();
+                        .status_timed();
                     if status.map(|s| !s.success()).unwrap_or(true) {
                         warn!(
                             "swapFC: failed to remount {:?} with {}. \
@@ -454,7 +1165,7 @@ impl SwapFile {
             }
         }
 
-        makedirs(format!("{}/swapfile", WORK_DIR))?;
+        makedirs(format!("{}/swapfile", work_dir()))?;
 
         // Check if ZSWAP is active
         let is_zswap_active = crate::zswap::is_enabled();
@@ -462,17 +1173,105 @@ impl SwapFile {
             info!("swapFC: ZSWAP detected active - swapfiles serve as writeback backing");
         }
 
-        Ok(Self {
+        let can_grow = swapfile_config.can_grow_on(fstype.as_deref());
+        if can_grow {
+            info!(
+                "swapFC: files may grow in place up to {}MB to consolidate swap units",
+                swapfile_config.grow_max_size / (1024 * 1024)
+            );
+        }
+
+        let storage = StorageType::detect(&swapfile_config.path);
+        if let Some(top) = swapfile_config.priority.for_file(storage, 1) {
+            info!(
+                "swapFC: {} storage (tier {}), file priorities from {} down",
+                storage.name(),
+                Tier::for_storage(storage).label(),
+                top
+            );
+            crate::tiers::check_priority(Tier::for_storage(storage), top, "swapfile_priority");
+        }
+
+        let wipe = if !swapfile_config.wipe_on_remove {
+            None
+        } else if source_block_device(&swapfile_config.path).is_some_and(|d| crate::wipe::is_encrypted(&d)) {
+            info!("swapFC: swap directory is on dm-crypt, removed files need no wipe");
+            None
+        } else {
+            let cow = (is_btrfs && !swapfile_config.nocow) || swapfile_config.sparse_loop_backing;
+            let method = WipeMethod::for_storage(storage, cow);
+            if cow && storage == StorageType::Hdd {
+                warn!("swapFC: swap files are copy-on-write, removed files can only be discarded, not overwritten");
+            }
+            info!("swapFC: removed files are wiped by {}", method.name());
+            Some(method)
+        };
+        crate::wipe::resume(&swapfile_config.path, wipe, swapfile_config.trim_window);
+
+        let alignment = swapfile_config.sparse_loop_backing.then(|| {
+            let alignment = Alignment::detect(&swapfile_config.path);
+            info!("swapFC: loop-backed file sizes aligned to {}", alignment);
+            if alignment.discard_offset > 0 {
+                warn!(
+                    "swapFC: the partition under {} is misaligned for discard, repartition to fix",
+                    swapfile_config.path.display()
+                );
+            }
+            alignment
+        });
+
+        // Looked up after the setup above, which may have mounted @swap there
+        let btrfs_mount = if is_btrfs { BtrfsMount::of(&swapfile_config.path) } else { None };
+
+        let mut swapfile = Self {
             config: swapfile_config,
             allocated: 0,
             is_btrfs,
+            storage,
             file_sizes: Vec::new(),
             last_creation: None,
             cooldown_secs: if is_zswap_active { 5 } else { 15 },
             prev_free_swap: 100,
             is_zswap_active,
             disk_full: false,
-        })
+            btrfs_mount,
+            fs_operation: None,
+            can_grow,
+            last_consolidation: None,
+            wipe,
+            loop_registry: LoopRegistry::load(&crate::config::state_dir(config)),
+            inode_registry: InodeRegistry::load(&crate::config::state_dir(config)),
+            burst_last_pressure: Instant::now(),
+            waiter: PressureWaiter::new(config, "swapFC"),
+            work_dir_watch: WorkDirWatch::new(),
+            free_ram: FreeRamTracker::new(),
+            alignment,
+            swap_generation: crate::swapwatch::generation(),
+        };
+        swapfile.check_shared_disk();
+        Ok(swapfile)
+    }
+
+    /// Only add files in emergencies while the directory shares a spinning
+    /// disk with swap set up by others (see disks.rs)
+    fn check_shared_disk(&mut self) {
+        let shared = crate::disks::slow_shared_area(&self.config.path);
+        if shared.is_some() == self.config.shared_slow_disk {
+            return;
+        }
+        match &shared {
+            Some(shared) => warn!(
+                "swapFC: {} is on spinning disk {} with swap area {}; files are only added in emergencies",
+                self.config.path.display(),
+                shared.disk,
+                shared.area
+            ),
+            None => info!(
+                "swapFC: no other swap on the disk under {} any more, files are added on pressure again",
+                self.config.path.display()
+            ),
+        }
+        self.config.shared_slow_disk = shared.is_some();
     }
 
     /// Enable zswap mode: set is_zswap_active and adjust cooldown.
@@ -481,6 +1280,7 @@ impl SwapFile {
         if !self.is_zswap_active {
             self.is_zswap_active = true;
             self.cooldown_secs = 5;
+            self.config.set_zswap_mode(true);
             info!(
                 "swapFC: ZSWAP mode enabled - initial_count={} chunk={}MB growth={}MB",
                 self.config.min_count,
@@ -496,6 +1296,27 @@ impl SwapFile {
 
     /// Read information about all swap files from /proc/swaps
     fn get_swapfiles_info(&self) -> Vec<SwapFileInfo> {
+        let burst_dir = self.burst_dir();
+        self.read_swaps(|path| {
+            // Note: use string comparison for /dev/loop* — Path::starts_with does component
+            // matching, so "/dev/loop10".starts_with("/dev/loop") is false ("loop10" ≠ "loop").
+            (path.starts_with(&self.config.path) && !path.starts_with(&burst_dir))
+                || (path.to_string_lossy().starts_with("/dev/loop") && self.is_our_loop_device(path))
+        })
+    }
+
+    /// Active burst files (see BurstConfig)
+    fn get_burst_files_info(&self) -> Vec<SwapFileInfo> {
+        let burst_dir = self.burst_dir();
+        self.read_swaps(|path| path.starts_with(&burst_dir))
+    }
+
+    fn burst_dir(&self) -> PathBuf {
+        self.config.path.join("burst")
+    }
+
+    /// Swap areas from /proc/swaps accepted by `is_ours`, highest priority first
+    fn read_swaps(&self, is_ours: impl Fn(&Path) -> bool) -> Vec<SwapFileInfo> {
         let mut files = Vec::new();
 
         let content = match std::fs::read_to_string("/proc/swaps") {
@@ -511,15 +1332,7 @@ impl SwapFile {
             }
 
             let path = PathBuf::from(fields[0]);
-
-            // Filter only our swap files (in the configured directory or loop devices)
-            // Note: use string comparison for /dev/loop* — Path::starts_with does component
-            // matching, so "/dev/loop10".starts_with("/dev/loop") is false ("loop10" ≠ "loop").
-            let path_str = path.to_string_lossy();
-            let is_our_file = path.starts_with(&self.config.path)
-                || (path_str.starts_with("/dev/loop") && self.is_our_loop_device(&path));
-
-            if !is_our_file {
+            if !is_ours(&path) {
                 continue;
             }
 
@@ -536,7 +1349,7 @@ impl SwapFile {
         }
 
         // Sort by priority (higher priority first - used first by kernel)
-        files.sort_by(|a, b| b.priority.cmp(&a.priority));
+        files.sort_by_key(|f| std::cmp::Reverse(f.priority));
         files
     }
 
@@ -545,7 +1358,7 @@ impl SwapFile {
         // Scan all loop_info files in WORK_DIR, not just up to self.allocated.
         // During adoption (adopt_existing_swapfiles), self.allocated is still 0,
         // so a 1..=self.allocated range would never iterate.
-        let loop_dir = format!("{}/swapfile", WORK_DIR);
+        let loop_dir = format!("{}/swapfile", work_dir());
         let Ok(entries) = std::fs::read_dir(&loop_dir) else {
             return false;
         };
@@ -570,124 +1383,62 @@ impl SwapFile {
         &self,
         files: &'a [SwapFileInfo],
     ) -> Option<&'a SwapFileInfo> {
-        if files.len() <= self.config.min_count as usize {
-            return None; // Don't remove below minimum
-        }
+        // With a layout only the last file of the ladder may go, so files are
+        // removed in reverse creation order and the next creation reuses its slot.
+        self.config.removal_candidate(files, |f| {
+            self.config.layout.is_empty() || self.find_file_index(&f.path) == Some(self.allocated)
+        })
+    }
 
-        // Find files with low usage (< shrink_threshold%)
-        let mut candidates: Vec<&SwapFileInfo> = files
-            .iter()
-            .filter(|f| f.is_nearly_empty(self.config.shrink_threshold))
-            .collect();
+    /// Remove a specific swap file by path
+    fn destroy_swapfile_by_path(&mut self, path: &Path) -> Result<()> {
+        // Find which index this file corresponds to; the identity must be
+        // read while a loop device is still attached
+        let identity = self.file_identity(path);
+        let file_index = self.find_file_index(path);
 
-        if candidates.is_empty() {
-            return None; // No file is empty enough
-        }
+        notify_status(&format!("Deallocating swap file {}...", path.display()));
 
-        // Sort candidates by priority ASCENDING (Lowest first)
-        // We want to remove low-priority files (created last, usually larger) first
-        // to scale down properly instead of leaving a giant tail file alone.
-        candidates.sort_by(|a, b| a.priority.cmp(&b.priority));
-
-        // For each candidate, verify if it's SAFE to remove
-        candidates
-            .into_iter()
-            .find(|&candidate| self.can_safely_remove(candidate, files))
-            .map(|v| v as _)
-    }
-
-    /// Verify if it's safe to remove a specific file
-    /// Safe if: data from the file can be absorbed by others with headroom
-    fn can_safely_remove(&self, target: &SwapFileInfo, all_files: &[SwapFileInfo]) -> bool {
-        // Calculate free space in OTHER files
-        let mut other_total_size: u64 = 0;
-        let mut other_total_used: u64 = 0;
-
-        for file in all_files {
-            if file.path != target.path {
-                other_total_size += file.size_bytes;
-                other_total_used += file.used_bytes;
-            }
-        }
-
-        // If no other files, not safe to remove
-        if other_total_size == 0 {
-            return false;
-        }
-
-        // Free space in other files
-        let other_free_space = other_total_size.saturating_sub(other_total_used);
-
-        // Data that needs to be migrated from target file
-        let data_to_migrate = target.used_bytes;
-
-        // Verify if there's enough space WITH safety margin
-        // Want to maintain at least safe_headroom% free after migration
-        let required_headroom = (other_total_size * self.config.safe_headroom as u64) / 100;
-        let required_free = data_to_migrate + required_headroom;
-
-        if other_free_space < required_free {
-            debug!(
-                "swapFC: removing {} not safe - needs {}MB free, has {}MB",
-                target.path.display(),
-                required_free / (1024 * 1024),
-                other_free_space / (1024 * 1024)
-            );
-            return false;
-        }
-
-        true
-    }
-
-    /// Remove a specific swap file by path
-    fn destroy_swapfile_by_path(&mut self, path: &Path) -> Result<()> {
-        // Find which index this file corresponds to
-        let file_index = self.find_file_index(path);
-
-        notify_status(&format!("Deallocating swap file {}...", path.display()));
-
-        // First: swapoff (kernel will migrate data to other files)
-        if let Err(e) = swapoff(&path.to_string_lossy()) {
-            warn!("swapFC: swapoff failed for {}: {}", path.display(), e);
-            return Err(SwapFileError::Io(std::io::Error::other("swapoff failed")));
-        }
+        // First: swapoff (kernel will migrate data to other files)
+        if let Err(e) = swapoff(&path.to_string_lossy()) {
+            warn!("swapFC: swapoff failed for {}: {}", path.display(), e);
+            return Err(SwapFileError::Io(std::io::Error::other("swapoff failed")));
+        }
 
         // If it's a loop device, get the backing file
         // Use string comparison: Path::starts_with does component matching.
         let is_loop = path.to_string_lossy().starts_with("/dev/loop");
         let backing_file = if is_loop {
             self.get_backing_file_for_loop(path)
+                .and_then(|backing| self.verify_backing(backing, identity, path))
         } else {
             Some(path.to_path_buf())
         };
 
         if is_loop {
             // Detach loop device
-            let _ = Command::new("losetup")
-                .args(["-d", &path.to_string_lossy()])
-                .status();
+            crate::loopdev::detach(&path.to_string_lossy());
         }
 
         // Remove backing file
         if let Some(ref backing) = backing_file {
-            force_remove(backing, false);
+            self.remove_disk_file(backing);
         }
 
         // Clean up systemd unit
         if let Some(idx) = file_index {
             let tag = format!("swapfile_{}", idx);
             for unit_path in crate::helpers::find_swap_units() {
-                if let Ok(content) = crate::helpers::read_file(&unit_path) {
-                    if content.contains(&tag) {
-                        force_remove(&unit_path, true);
-                        break;
-                    }
+                if crate::helpers::get_tag_from_swap_unit(&unit_path).as_deref() == Some(tag.as_str()) {
+                    force_remove(&unit_path, true);
+                    break;
                 }
             }
 
             // Clean up loop info file
-            let loop_info_path = format!("{}/swapfile/loop_{}", WORK_DIR, idx);
+            let loop_info_path = format!("{}/swapfile/loop_{}", work_dir(), idx);
             force_remove(&loop_info_path, false);
+            self.inode_registry.forget(idx);
 
             // Update file_sizes if we tracked this file
             if idx <= self.file_sizes.len() as u32 {
@@ -702,8 +1453,77 @@ impl SwapFile {
         Ok(())
     }
 
-    /// Find the index of a file/loop device in our managed files
+    /// Unlink a swap file that is no longer in use, wiping it first if configured
+    fn remove_disk_file(&self, path: &Path) {
+        if let Some(method) = self.wipe {
+            match crate::wipe::schedule(path, method, self.config.trim_window) {
+                Ok(()) => return,
+                Err(e) => warn!("swapFC: cannot wipe {}: {}", path.display(), e),
+            }
+        }
+        force_remove(path, false);
+    }
+
+    /// Device and inode of a swap file, or of a loop device's backing file
+    fn file_identity(&self, path: &Path) -> Option<FileId> {
+        let name = path.to_string_lossy();
+        if name.starts_with("/dev/loop") {
+            FileId::of_loop(&name)
+        } else {
+            FileId::of(path)
+        }
+    }
+
+    /// Index recorded for the identity of a swap file or loop device
+    fn index_by_identity(&self, path: &Path) -> Option<u32> {
+        self.inode_registry.find(self.file_identity(path)?)
+    }
+
+    /// Make sure `backing`, taken from loop info, is the file that backed
+    /// `loop_dev` (with identity `id`). Loop info can be stale; then the file
+    /// is looked up by inode in the swap directory, and if it is not there
+    /// nothing is removed.
+    fn verify_backing(&self, backing: PathBuf, id: Option<FileId>, loop_dev: &Path) -> Option<PathBuf> {
+        let Some(id) = id else {
+            return Some(backing);
+        };
+        if FileId::of(&backing) == Some(id) {
+            return Some(backing);
+        }
+        let found = fs::read_dir(&self.config.path)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|entry| entry.path())
+            .find(|p| FileId::of(p) == Some(id));
+        match found {
+            Some(actual) => {
+                warn!(
+                    "swapFC: {} is backed by {}, not {} as recorded",
+                    loop_dev.display(),
+                    actual.display(),
+                    backing.display()
+                );
+                Some(actual)
+            }
+            None => {
+                warn!(
+                    "swapFC: {} is not the file behind {}, leaving it in place",
+                    backing.display(),
+                    loop_dev.display()
+                );
+                None
+            }
+        }
+    }
+
+    /// Find the index of a file/loop device in our managed files: by inode
+    /// first, then by file name or loop info
     fn find_file_index(&self, path: &Path) -> Option<u32> {
+        if let Some(idx) = self.index_by_identity(path) {
+            return Some(idx);
+        }
+
         // Check if it's a direct file in our directory
         if path.starts_with(&self.config.path) {
             if let Some(name) = path.file_name() {
@@ -713,7 +1533,7 @@ impl SwapFile {
 
         // Check loop device info files
         for i in 1..=self.allocated {
-            let loop_info_path = format!("{}/swapfile/loop_{}", WORK_DIR, i);
+            let loop_info_path = format!("{}/swapfile/loop_{}", work_dir(), i);
             if let Ok(content) = fs::read_to_string(&loop_info_path) {
                 let lines: Vec<&str> = content.lines().collect();
                 if !lines.is_empty() && lines[0] == path.to_string_lossy() {
@@ -729,7 +1549,7 @@ impl SwapFile {
     fn get_backing_file_for_loop(&self, loop_path: &Path) -> Option<PathBuf> {
         // Scan all loop_info files (not bounded by self.allocated; may be called
         // during adoption before allocated is set).
-        let loop_dir = format!("{}/swapfile", WORK_DIR);
+        let loop_dir = format!("{}/swapfile", work_dir());
         let Ok(entries) = std::fs::read_dir(&loop_dir) else {
             return None;
         };
@@ -772,23 +1592,28 @@ impl SwapFile {
         let mut max_num: u32 = 0;
 
         for info in &existing {
-            if let Some(name) = info.path.file_name() {
-                if let Ok(n) = name.to_string_lossy().parse::<u32>() {
-                    max_num = max_num.max(n);
-                }
+            let identity = self.file_identity(&info.path);
+            let mut index = identity.and_then(|id| self.inode_registry.find(id));
+            if index.is_none() {
+                index = info.path.file_name().and_then(|name| name.to_string_lossy().parse::<u32>().ok());
             }
             // For loop devices, derive the backing file number from the loop info file.
-            if info.path.to_string_lossy().starts_with("/dev/loop") {
+            if index.is_none() && info.path.to_string_lossy().starts_with("/dev/loop") {
                 let loop_name = info.path.to_string_lossy();
                 // Find the matching loop info file we just wrote
-                for i in 1..=28u32 {
-                    let loop_info = format!("{}/swapfile/loop_{}", WORK_DIR, i);
-                    if let Ok(content) = fs::read_to_string(&loop_info) {
-                        if content.lines().next() == Some(&loop_name) {
-                            max_num = max_num.max(i);
-                            break;
-                        }
-                    }
+                index = (1..=28u32).find(|i| {
+                    let loop_info = format!("{}/swapfile/loop_{}", work_dir(), i);
+                    fs::read_to_string(&loop_info).is_ok_and(|content| content.lines().next() == Some(&loop_name))
+                });
+            }
+            let Some(index) = index else {
+                continue;
+            };
+            max_num = max_num.max(index);
+            // Files from before inode tracking are recorded on adoption
+            if let Some(id) = identity {
+                if self.inode_registry.get(index).is_none() {
+                    self.inode_registry.record(index, id);
                 }
             }
         }
@@ -814,6 +1639,19 @@ impl SwapFile {
         }
     }
 
+    /// Re-populate WORK_DIR/swapfile after it was cleared: loop info comes
+    /// back from losetup as on restart, pacing with the next tick
+    fn restore_work_dir(&mut self) {
+        if let Err(e) = makedirs(format!("{}/swapfile", work_dir())) {
+            warn!("swapFC: cannot re-create working directory: {}", e);
+            return;
+        }
+        if self.config.sparse_loop_backing {
+            self.reconstruct_loop_info_from_losetup();
+        }
+        info!("swapFC: working directory restored");
+    }
+
     /// Rebuild per-index loop info files from `losetup -l` output.
     ///
     /// Called during adoption at startup when WORK_DIR was cleared (e.g. after
@@ -821,67 +1659,86 @@ impl SwapFile {
     /// `self.config.path` back to its numeric index (the file's own name),
     /// then writes `{WORK_DIR}/swapfile/loop_N` so that `is_our_loop_device()`
     /// and `get_swapfiles_info()` can recognise them normally.
-    fn reconstruct_loop_info_from_losetup(&self) {
-        // losetup -l --noheadings -o NAME,BACK-FILE
+    fn reconstruct_loop_info_from_losetup(&mut self) {
+        // losetup -l --noheadings -o NAME,BACK-MAJ:MIN,BACK-INO,BACK-FILE
         let output = match Command::new("losetup")
-            .args(["-l", "--noheadings", "-o", "NAME,BACK-FILE"])
-            .output()
+            .args(["-l", "--noheadings", "-o", "NAME,BACK-MAJ:MIN,BACK-INO,BACK-FILE"])
+            .output_timed()
         {
             Ok(o) => o,
             Err(_) => return,
         };
 
+        let btrfs_mount = if self.is_btrfs {
+            BtrfsMount::of(&self.config.path)
+        } else {
+            None
+        };
         let stdout = String::from_utf8_lossy(&output.stdout);
         for line in stdout.lines() {
             let parts: Vec<&str> = line.split_whitespace().collect();
-            if parts.len() < 2 {
+            if parts.len() < 4 {
                 continue;
             }
             let loop_dev = parts[0];
-            let backing = parts[1];
+            let identity = FileId::from_losetup(parts[1], parts[2]);
+            let backing = parts[3];
 
             // Skip loop devices whose backing file has been deleted.
             // losetup appends "(deleted)" when the inode is unlinked but
             // the loop device keeps its file descriptor open — these are
             // from previous sessions whose files were already removed.
             // Detach them to prevent loop device accumulation.
-            if parts.get(2).copied() == Some("(deleted)") {
+            if parts.get(4).copied() == Some("(deleted)") {
                 info!(
                     "swapFC: detaching loop {} with deleted backing file",
                     loop_dev
                 );
-                let _ = Command::new("losetup").args(["-d", loop_dev]).status();
+                crate::loopdev::detach(loop_dev);
                 continue;
             }
 
             let backing_path = PathBuf::from(backing);
 
-            // Extract the numeric index from the backing file name.
-            // NOTE: btrfs subvolumes cause losetup to report the backing file path
-            // relative to the subvolume root (e.g. "/1" instead of "/swapfile/1").
-            // We cannot rely on the reported path prefix; match by numeric name only.
-            let idx: u32 = match backing_path
-                .file_name()
-                .and_then(|n| n.to_str())
-                .and_then(|n| n.parse().ok())
-            {
-                Some(n) => n,
-                None => continue,
-            };
+            // The inode recorded at creation identifies the file even if it
+            // was renamed; the file name is the fallback for files from
+            // before inode tracking.
+            let by_inode = identity.and_then(|id| {
+                let idx = self.inode_registry.find(id)?;
+                (FileId::of(&self.config.path.join(idx.to_string())) == Some(id)).then_some(idx)
+            });
+            let idx = match by_inode {
+                Some(idx) => idx,
+                None => {
+                    // Extract the numeric index from the backing file name.
+                    let Some(idx) = backing_path
+                        .file_name()
+                        .and_then(|n| n.to_str())
+                        .and_then(|n| n.parse::<u32>().ok())
+                    else {
+                        continue;
+                    };
 
-            // Verify that this numeric file exists in our managed directory.
-            let canonical_backing = self.config.path.join(idx.to_string());
-            let actual_backing = if canonical_backing.exists() {
-                canonical_backing
-            } else {
-                continue;
+                    // Verify that this numeric file exists in our managed directory.
+                    // On btrfs the reported path may be relative to the top level or
+                    // to the mounted subvolume (e.g. "/1" instead of "/swapfile/1").
+                    let Ok(canonical) = fs::canonicalize(self.config.path.join(idx.to_string())) else {
+                        continue;
+                    };
+                    if !crate::btrfs::backing_matches(&backing_path, &canonical, btrfs_mount.as_ref()) {
+                        continue;
+                    }
+                    idx
+                }
             };
+            let actual_backing = self.config.path.join(idx.to_string());
 
-            let loop_info_path = format!("{}/swapfile/loop_{}", WORK_DIR, idx);
-            let _ = fs::write(
+            let loop_info_path = format!("{}/swapfile/loop_{}", work_dir(), idx);
+            let _ = write_shared(
                 &loop_info_path,
-                format!("{}\n{}", loop_dev, actual_backing.display()),
+                &format!("{}\n{}", loop_dev, actual_backing.display()),
             );
+            self.loop_registry.record(idx, loop_dev);
             info!(
                 "swapFC: reconstructed loop info: {} → {} (index {})",
                 loop_dev,
@@ -907,6 +1764,7 @@ impl SwapFile {
         // Remove physical files in our directory that are NOT in /proc/swaps.
         // These are stale from crashes or force-reboots and waste disk space.
         self.cleanup_stale_disk_files();
+        self.cleanup_stale_burst_files();
 
         while self.allocated < self.config.min_count {
             if let Err(e) = self.create_swapfile() {
@@ -927,7 +1785,7 @@ impl SwapFile {
     /// Re-apply volatile queue parameters on all active loop devices.
     /// Called after initial creation and after udevadm settle.
     fn retune_all_loops(&self) {
-        let loop_dir = format!("{}/swapfile", WORK_DIR);
+        let loop_dir = format!("{}/swapfile", work_dir());
         let entries = match fs::read_dir(&loop_dir) {
             Ok(e) => e,
             Err(_) => return,
@@ -952,7 +1810,7 @@ impl SwapFile {
     /// so we use blockdev --setra (ioctl-based) and re-apply periodically.
     fn enforce_loop_readahead(&self) {
         let ra_sectors = 16; // 8KB = 16 sectors
-        let loop_dir = format!("{}/swapfile", WORK_DIR);
+        let loop_dir = format!("{}/swapfile", work_dir());
         let Ok(entries) = fs::read_dir(&loop_dir) else {
             return;
         };
@@ -969,7 +1827,7 @@ impl SwapFile {
                     .args(["--setra", &ra_sectors.to_string(), &loop_dev])
                     .stdout(Stdio::null())
                     .stderr(Stdio::null())
-                    .status();
+                    .status_timed();
             }
         }
     }
@@ -1027,6 +1885,10 @@ impl SwapFile {
             self.detach_orphaned_loops(&active);
         }
 
+        // Files in use by identity, in case loop info names the wrong path
+        let active_ids: std::collections::HashSet<FileId> =
+            active_swaps.iter().filter_map(|f| self.file_identity(&f.path)).collect();
+
         let Ok(entries) = std::fs::read_dir(&self.config.path) else {
             return;
         };
@@ -1039,9 +1901,15 @@ impl SwapFile {
                 .and_then(|n| n.to_str())
                 .map(|n| n.parse::<u32>().is_ok())
                 .unwrap_or(false);
+            if is_ours && FileId::of(&path).is_some_and(|id| active_ids.contains(&id)) {
+                if !active.contains(&path) {
+                    warn!("swapFC: {} is in use under another name, keeping it", path.display());
+                }
+                continue;
+            }
             if is_ours && !active.contains(&path) {
                 info!("swapFC: removing stale disk file {}", path.display());
-                force_remove(&path, false);
+                self.remove_disk_file(&path);
             }
         }
     }
@@ -1050,7 +1918,7 @@ impl SwapFile {
     /// These are loops left attached without active swap — e.g. after a stop
     /// timeout where only some loops were swapped off before the process was killed.
     fn detach_orphaned_loops(&self, active_backings: &std::collections::HashSet<PathBuf>) {
-        let loop_dir = format!("{}/swapfile", WORK_DIR);
+        let loop_dir = format!("{}/swapfile", work_dir());
         let Ok(entries) = std::fs::read_dir(&loop_dir) else {
             return;
         };
@@ -1076,9 +1944,7 @@ impl SwapFile {
                     loop_dev.trim(),
                     backing.display()
                 );
-                let _ = std::process::Command::new("losetup")
-                    .args(["-d", loop_dev.trim()])
-                    .status();
+                crate::loopdev::detach(loop_dev.trim());
                 let _ = fs::remove_file(entry.path());
             }
         }
@@ -1106,15 +1972,23 @@ impl SwapFile {
         }
 
         let mut retune_tick: u32 = 0;
+        let mut profiler = TickProfiler::new("swapfile", "swapFC");
 
         // Ensure minimum files are created at startup
         loop {
+            // After the last tick's create/remove decision, whichever way it went
+            self.save_pacing();
             let poll_interval = self.get_adaptive_poll_interval();
-            thread::sleep(Duration::from_secs(poll_interval));
+            self.waiter.wait(Duration::from_secs(poll_interval));
 
             if is_shutdown() {
                 break;
             }
+            let mut tick = profiler.begin(Duration::from_secs(poll_interval));
+
+            if self.work_dir_watch.recreated() {
+                self.restore_work_dir();
+            }
 
             // Periodically enforce readahead on loop devices (~every 5 ticks)
             // and re-apply all volatile queue params (~every 30 ticks)
@@ -1134,10 +2008,16 @@ impl SwapFile {
             // Use zswap-aware swap calculation: pages in zswap RAM pool
             // are NOT consuming disk swap, so don't count them as "used"
             let free_swap = get_free_swap_percent_effective().unwrap_or(100);
-            let free_ram = get_free_ram_percent().unwrap_or(100);
+            let free_ram_sample = self.free_ram.sample().ok();
+            let free_ram = free_ram_sample.map_or(100, |f| f.adjusted);
 
             // Get individual file statistics from /proc/swaps
             let swap_files = self.get_swapfiles_info();
+            tick.mark(Phase::Decision);
+            if tick.overrun() {
+                warn!("swapFC: reading swap stats outlasted the check interval - skipping decisions on stale stats");
+                continue;
+            }
 
             // Cooldown: prevent creating swapfiles too fast
             // ZSWAP: shorter cooldown since writeback consumes swapfiles quickly
@@ -1149,7 +2029,7 @@ impl SwapFile {
             // Emergency cooldown: short 5s for critical RAM/zswap situations
             let emergency_cooldown_ok = self
                 .last_creation
-                .map(|t| t.elapsed() >= Duration::from_secs(5))
+                .map(|t| t.elapsed() >= Duration::from_secs(EMERGENCY_COOLDOWN_SECS))
                 .unwrap_or(true);
 
             // Detect if swap is being actively consumed (free_swap dropped)
@@ -1159,6 +2039,11 @@ impl SwapFile {
                 self.cooldown_secs = 30;
             }
             self.prev_free_swap = free_swap;
+            self.check_fs_operation();
+            if crate::swapwatch::generation() != self.swap_generation {
+                self.swap_generation = crate::swapwatch::generation();
+                self.check_shared_disk();
+            }
 
             // ZSWAP SPARSE LOOP GROWTH STRATEGY:
             // Create a larger backing file when total disk swap is 80%+ full.
@@ -1171,118 +2056,120 @@ impl SwapFile {
             // swap slots are allocated, and the sparse blocks will be needed when the
             // shrinker evicts them.  Using effective free makes 99%-full files look
             // ~64% free and the growth trigger never fires.
-            if self.config.sparse_loop_backing
-                && !self.disk_full
-                && self.allocated < self.config.max_count
-            {
-                // Compute free percentage from actual /proc/swaps usage of our files.
-                let disk_free_swap: u8 = {
-                    let total: u64 = swap_files.iter().map(|f| f.size_bytes).sum();
-                    let used: u64 = swap_files.iter().map(|f| f.used_bytes).sum();
-                    if total == 0 {
-                        100
-                    } else {
-                        let free = total.saturating_sub(used);
-                        ((free * 100) / total).min(100) as u8
-                    }
-                };
+            // Compute free percentage from actual /proc/swaps usage of our files.
+            let disk_free_swap: u8 = {
+                let total: u64 = swap_files.iter().map(|f| f.size_bytes).sum();
+                let used: u64 = swap_files.iter().map(|f| f.used_bytes).sum();
+                if total == 0 {
+                    100
+                } else {
+                    let free = total.saturating_sub(used);
+                    ((free * 100) / total).min(100) as u8
+                }
+            };
 
-                if disk_free_swap < 20 && cooldown_ok {
-                    let growth = if self.config.growth_chunk_size > 0 {
-                        self.config.growth_chunk_size
-                    } else {
-                        self.config.chunk_size * 2
-                    };
-                    info!(
+            // Count files with no data yet to avoid pre-allocating more than needed
+            let unused_count = swap_files.iter().filter(|f| f.used_bytes == 0).count();
+
+            // SLOT TRIGGER input: with zswap, pages in the pool keep their swap
+            // slots, so effective free swap can look healthy while nearly every
+            // slot is allocated (sparse loop growth works from slot usage already)
+            let slot_usage = if self.is_zswap_active && !self.config.sparse_loop_backing {
+                get_effective_swap_usage().ok().filter(|u| u.slots_exhausted())
+            } else {
+                None
+            };
+
+            let sample = ExpansionSample {
+                free_ram,
+                free_swap,
+                disk_free_swap,
+                unused_files: unused_count,
+                files_stressed: !swap_files.is_empty() && swap_files.iter().all(|f| f.usage_percent() >= 85),
+                slots_exhausted: slot_usage.is_some(),
+                absorb_requested: crate::absorb::capacity_requested(),
+                cooldown_ok,
+                emergency_cooldown_ok,
+                zswap: self.is_zswap_active,
+                disk_full: self.disk_full,
+                fs_busy: self.fs_operation.is_some(),
+                at_max_count: self.allocated >= self.config.max_count,
+            };
+            if let Some(trigger) = self.config.expansion_trigger(&sample) {
+                match trigger {
+                    Expansion::Growth => info!(
                         "swapFC: ZswapLoopfile disk swap 80%+ full (disk_free={}%, effective_free={}%) - creating growth file ({}MB)",
                         disk_free_swap,
                         free_swap,
-                        growth / (1024 * 1024),
-                    );
+                        self.config.growth_size() / (1024 * 1024),
+                    ),
+                    Expansion::Absorb => info!("swapFC: adding capacity to absorb a swap partition"),
+                    Expansion::Slots => {
+                        if let Some(usage) = &slot_usage {
+                            info!(
+                                "swapFC: swap slots exhausted (slot_free={}%, {}% held by zswap pool, effective_free={}%) - adding slots, not pool room",
+                                usage.slot_free_percent(),
+                                usage.zswap_slot_share(),
+                                free_swap
+                            );
+                        }
+                    }
+                    Expansion::Emergency => info!(
+                        "swapFC: EMERGENCY! free_ram={}% (MemAvailable {}%) free_swap={}% unused={} - creating swap urgently",
+                        free_ram,
+                        free_ram_sample.map_or(100, |f| f.raw),
+                        free_swap,
+                        unused_count
+                    ),
+                    Expansion::OomdGuard => info!(
+                        "swapFC: free_swap={}% approaching systemd-oomd swap kill limit - expanding",
+                        free_swap
+                    ),
+                    Expansion::Stress => info!(
+                        "swapFC: all {} file(s) >= 85% full, free_swap={}% - expanding (stress trigger)",
+                        swap_files.len(), free_swap
+                    ),
+                    Expansion::Normal => info!(
+                        "swapFC: swap pressure! effective_free_swap={}% < {}% (thresh) - expanding (cooldown={}s)",
+                        free_swap, self.config.free_swap_perc, self.cooldown_secs
+                    ),
+                }
+                tick.mark(Phase::Action);
+                let created = if trigger == Expansion::Growth {
                     // Temporarily override chunk size for the next create call
                     let prev_chunk = self.config.chunk_size;
-                    self.config.chunk_size = growth;
-                    if self.create_swapfile().is_ok() {
-                        self.last_creation = Some(Instant::now());
-                        self.cooldown_secs = 30;
-                    }
+                    self.config.chunk_size = self.config.growth_size();
+                    let created = self.create_swapfile().is_ok();
                     self.config.chunk_size = prev_chunk;
-                    continue;
+                    created
+                } else {
+                    self.create_swapfile().is_ok()
+                };
+                if created {
+                    self.last_creation = Some(Instant::now());
+                    self.cooldown_secs = trigger.cooldown_after(self.cooldown_secs);
                 }
+                continue;
             }
 
-            // EXPANSION TRIGGERS (non-zswap only)
-            // With zswap active, the reserve file strategy above handles ALL expansion.
-            // The EMERGENCY and NORMAL triggers only apply to zram/plain swapfile modes.
-            if !self.is_zswap_active
-                && !self.disk_full
-                && self.allocated < self.config.max_count
-            {
-                // Count files with no data yet to avoid pre-allocating more than needed
-                let unused_count = swap_files.iter().filter(|f| f.used_bytes == 0).count();
-
-                // EMERGENCY TRIGGER: critical RAM pressure.
-                let emergency_ram_threshold: u8 = 10;
-
-                if free_ram < emergency_ram_threshold
-                    && free_swap < 80
-                    && unused_count < 2
-                    && emergency_cooldown_ok
-                {
-                    info!(
-                        "swapFC: EMERGENCY! free_ram={}% free_swap={}% unused={} - creating swap urgently",
-                        free_ram, free_swap, unused_count
-                    );
-                    if self.create_swapfile().is_ok() {
-                        self.last_creation = Some(Instant::now());
-                        self.cooldown_secs = 30;
-                    }
-                    continue;
-                }
-
-                let swap_threshold = self.config.free_swap_perc;
-
-                // STRESS TRIGGER: existing files filling up (bypasses long cooldown).
-                let files_stressed =
-                    !swap_files.is_empty() && swap_files.iter().all(|f| f.usage_percent() >= 85);
-
-                if files_stressed
-                    && free_swap < swap_threshold
-                    && unused_count < 2
-                    && emergency_cooldown_ok
-                {
-                    info!(
-                        "swapFC: all {} file(s) >= 85% full, free_swap={}% - expanding (stress trigger)",
-                        swap_files.len(), free_swap
-                    );
-                    if self.create_swapfile().is_ok() {
-                        self.last_creation = Some(Instant::now());
-                        self.cooldown_secs = 30;
-                    }
-                    continue;
-                }
-
-                // NORMAL TRIGGER: swap space running low.
-                if cooldown_ok && free_swap < swap_threshold && unused_count < 2 {
-                    info!(
-                        "swapFC: swap pressure! effective_free_swap={}% < {}% (thresh) - expanding (cooldown={}s)",
-                        free_swap, swap_threshold, self.cooldown_secs
-                    );
-                    if self.create_swapfile().is_ok() {
-                        self.last_creation = Some(Instant::now());
-                        self.cooldown_secs = (self.cooldown_secs * 2).min(120);
-                    }
+            // BURST: ephemeral files on top of a full pool, with their own
+            // thresholds and short cooldown; removed after a quiet period.
+            if let Some(burst) = self.config.burst {
+                let may_create = !self.disk_full && self.fs_operation.is_none() && emergency_cooldown_ok;
+                if self.burst_tick(burst, free_swap, &swap_files, may_create) {
+                    tick.mark(Phase::Action);
                     continue;
                 }
             }
 
             // CONTRACTION DECISION: check if swap is abundant enough to remove files
             if self.allocated > self.config.min_count {
-                // ZSWAP: must always keep at least 2 unused reserve files.
+                // ZSWAP: must always keep the unused reserve files (at least 2,
+                // more when min_count is higher).
                 // Never remove if it would drop below the reserve threshold.
                 if self.is_zswap_active {
                     let unused_count = swap_files.iter().filter(|f| f.used_bytes == 0).count();
-                    if unused_count <= 2 {
+                    if unused_count <= self.config.reserve_files(true) {
                         // At or below minimum reserve — skip contraction
                         continue;
                     }
@@ -1295,11 +2182,9 @@ impl SwapFile {
                     self.config.remove_free_swap_perc
                 };
 
-                // ZSWAP: 5 minutes minimum cooldown to prevent create-remove cycles
-                let removal_cooldown_secs = if self.is_zswap_active { 300 } else { 60 };
                 let removal_cooldown_ok = self
                     .last_creation
-                    .map(|t| t.elapsed() >= Duration::from_secs(removal_cooldown_secs))
+                    .map(|t| t.elapsed() >= Duration::from_secs(self.removal_cooldown_secs()))
                     .unwrap_or(true);
 
                 if free_swap > remove_threshold && removal_cooldown_ok {
@@ -1312,17 +2197,179 @@ impl SwapFile {
                             candidate.usage_percent()
                         );
                         let path = candidate.path.clone();
+                        tick.mark(Phase::Action);
                         if self.destroy_swapfile_by_path(&path).is_ok() {
                             self.disk_full = false; // Space freed, allow expansion again
                         }
+                        continue;
                     }
                 }
             }
+
+            // CONSOLIDATION: in a low-pressure window, grow an early file in place
+            // by the size of the last one and remove the last, so capacity stays
+            // the same with one device and unit fewer.
+            let consolidation_due = self
+                .last_consolidation
+                .map(|t| t.elapsed() >= Duration::from_secs(CONSOLIDATION_INTERVAL_SECS))
+                .unwrap_or(true);
+            if self.can_grow
+                && consolidation_due
+                && free_ram >= CONSOLIDATION_MIN_FREE_RAM
+                && free_swap >= self.config.free_swap_perc.saturating_add(20).min(100)
+            {
+                tick.mark(Phase::Action);
+                self.consolidate(&swap_files);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Fold the last file into the lowest-numbered file that can grow by its size
+    fn consolidate(&mut self, swap_files: &[SwapFileInfo]) {
+        let numbered: Vec<(u32, &SwapFileInfo)> = swap_files
+            .iter()
+            .filter_map(|f| Some((self.find_file_index(&f.path)?, f)))
+            .collect();
+        let Some(&(_, last)) = numbered.iter().find(|(idx, _)| *idx == self.allocated) else {
+            return;
+        };
+        let sizes: Vec<(u32, u64)> = numbered
+            .iter()
+            .filter(|(idx, _)| *idx != self.allocated)
+            .map(|(idx, f)| (*idx, f.size_bytes))
+            .collect();
+        let Some(target_idx) = consolidation_target(&sizes, last.size_bytes, self.config.grow_max_size) else {
+            return;
+        };
+        let Some(&(_, target)) = numbered.iter().find(|(idx, _)| *idx == target_idx) else {
+            return;
+        };
+        // Both files are swapped off in turn; the rest must hold their pages
+        if !self.config.can_safely_remove(target, swap_files) || !self.config.can_safely_remove(last, swap_files) {
+            return;
+        }
+        self.last_consolidation = Some(Instant::now());
+
+        let new_size = target.size_bytes + last.size_bytes;
+        let last_path = last.path.clone();
+        match self.grow_in_place(target_idx, new_size) {
+            Ok(()) => {
+                info!(
+                    "swapFC: grew {} in place to {}MB, folding in {}",
+                    target.path.display(),
+                    new_size / (1024 * 1024),
+                    last_path.display()
+                );
+                let _ = self.destroy_swapfile_by_path(&last_path);
+            }
+            Err(e) => warn!("swapFC: growing {} in place failed: {}", target.path.display(), e),
         }
+    }
+
+    /// swapoff, extend with fallocate, mkswap and swapon a preallocated file.
+    /// If extending fails the file is re-activated at its old size.
+    fn grow_in_place(&mut self, idx: u32, new_size: u64) -> Result<()> {
+        let swapfile_path = self.config.path.join(idx.to_string());
+        let extra = new_size.saturating_sub(fs::metadata(&swapfile_path)?.len());
+        if !self.has_enough_space(extra) {
+            return Err(SwapFileError::NoSpace);
+        }
+        notify_status(&format!("Growing swap file #{}...", idx));
+        swapoff(&swapfile_path.to_string_lossy())
+            .map_err(|e| SwapFileError::Io(std::io::Error::other(e.to_string())))?;
+
+        let grown = Command::new("fallocate")
+            .args(["-l", &new_size.to_string()])
+            .arg(&swapfile_path)
+            .status_timed()
+            .is_ok_and(|s| s.success());
+        let mkswap_ok = grown
+            && Command::new("mkswap")
+                .args(["-L", &format!("SWAP_btrfs_{}", idx)])
+                .arg(&swapfile_path)
+                .stdout(Stdio::null())
+                .status_timed()
+                .is_ok_and(|s| s.success());
+
+        let unit_name = gen_swap_unit(
+            &swapfile_path,
+            self.config.priority.for_file(self.storage, idx),
+            None,
+            &format!("swapfile_{}", idx),
+        )?;
+        systemctl(SystemctlAction::DaemonReload, "")?;
+        systemctl(SystemctlAction::Start, &unit_name)?;
+        notify_status("Monitoring memory status...");
 
+        if !(grown && mkswap_ok) {
+            return Err(SwapFileError::Io(std::io::Error::other("fallocate or mkswap failed")));
+        }
+        if let Some(size) = self.file_sizes.get_mut(idx as usize - 1) {
+            *size = new_size;
+        }
         Ok(())
     }
 
+    /// Minimum time between the last creation and a removal
+    fn removal_cooldown_secs(&self) -> u64 {
+        // ZSWAP: 5 minutes minimum cooldown to prevent create-remove cycles
+        if self.is_zswap_active { 300 } else { 60 }
+    }
+
+    /// Seconds left until `secs` have passed since the last creation
+    fn cooldown_remaining(&self, secs: u64) -> u64 {
+        self.last_creation
+            .map(|t| secs.saturating_sub(t.elapsed().as_secs()))
+            .unwrap_or(0)
+    }
+
+    /// Track exclusive btrfs operations: the kernel refuses swapon while one
+    /// runs, so expansion waits, with a warning and an event at either end
+    fn check_fs_operation(&mut self) {
+        let operation = self.btrfs_mount.as_ref().and_then(BtrfsMount::exclusive_operation);
+        if operation == self.fs_operation {
+            return;
+        }
+        let path = self.config.path.display().to_string();
+        let event = match (&operation, &self.fs_operation) {
+            (Some(op), _) => {
+                warn!("swapFC: btrfs {} running on {} - deferring expansion until it finishes", op, path);
+                Event::new(
+                    EventKind::ExpansionDeferred,
+                    vec![("operation".into(), op.clone()), ("path".into(), path)],
+                )
+            }
+            (None, Some(op)) => {
+                info!("swapFC: btrfs {} on {} finished - expansion resumed", op, path);
+                Event::new(
+                    EventKind::ExpansionResumed,
+                    vec![("operation".into(), op.clone()), ("path".into(), path)],
+                )
+            }
+            (None, None) => return,
+        };
+        if let Err(e) = crate::events::record(&event) {
+            warn!("swapFC: cannot record {} event: {}", event.kind.as_str(), e);
+        }
+        self.fs_operation = operation;
+    }
+
+    fn save_pacing(&self) {
+        SwapFilePacing {
+            cooldown_secs: self.cooldown_secs,
+            creation_in: self.cooldown_remaining(self.cooldown_secs),
+            emergency_in: self.cooldown_remaining(EMERGENCY_COOLDOWN_SECS),
+            removal_in: self.cooldown_remaining(self.removal_cooldown_secs()),
+            disk_full: self.disk_full,
+            fs_busy: self.fs_operation.is_some(),
+            interval: self.get_adaptive_poll_interval(),
+            age: 0,
+        }
+        .save();
+    }
+
     fn get_adaptive_poll_interval(&self) -> u64 {
         if self.allocated > 0 {
             return self.config.frequency;
@@ -1352,9 +2399,34 @@ impl SwapFile {
         }
     }
 
+    /// Undo the allocation of a file `create_swapfile` could not finish
+    fn abandon_new_file(&mut self, path: &Path) {
+        force_remove(path, false);
+        self.inode_registry.forget(self.allocated);
+        self.allocated -= 1;
+        self.file_sizes.pop();
+    }
+
     fn create_swapfile(&mut self) -> Result<()> {
         let next_file_num = self.allocated + 1;
-        let chunk_size = self.config.chunk_size;
+        // A layout fixes each file's size; otherwise chunk_size (possibly
+        // temporarily raised by the growth path) applies.
+        let mut chunk_size = if self.config.layout.is_empty() {
+            self.config.chunk_size
+        } else {
+            self.config.size_for_file(next_file_num)
+        };
+        if let Some(alignment) = self.alignment.filter(|a| !a.is_aligned(chunk_size)) {
+            let aligned = alignment.align(chunk_size);
+            info!(
+                "swapFC: file #{} sized {} bytes instead of {} for {} alignment",
+                next_file_num,
+                aligned,
+                chunk_size,
+                format_size(alignment.unit())
+            );
+            chunk_size = aligned;
+        }
 
         if !self.has_enough_space(chunk_size) {
             if !self.disk_full {
@@ -1396,7 +2468,7 @@ impl SwapFile {
             let _ = Command::new("chattr")
                 .args(["+C"])
                 .arg(&swapfile_path)
-                .status();
+                .status_timed();
         }
 
         // File allocation + optional loop device
@@ -1410,22 +2482,27 @@ impl SwapFile {
             let status = Command::new("truncate")
                 .args(["-s", &chunk_size.to_string()])
                 .arg(&swapfile_path)
-                .status()?;
-            if !status.success() {
-                force_remove(&swapfile_path, false);
-                self.allocated -= 1;
-                self.file_sizes.pop();
-                return Err(SwapFileError::NoSpace);
-            }
-            // direct-io=on: bypasses page cache, prevents deadlock
-            let loop_dev = run_cmd_output(&[
-                "losetup",
-                "-f",
-                "--show",
-                "--direct-io=on",
-                &swapfile_path.to_string_lossy(),
-            ])?;
-            let loop_dev = loop_dev.trim().to_string();
+                .status_timed();
+            match status {
+                Ok(status) if status.success() => {}
+                Ok(_) => {
+                    self.abandon_new_file(&swapfile_path);
+                    return Err(SwapFileError::NoSpace);
+                }
+                Err(e) => {
+                    self.abandon_new_file(&swapfile_path);
+                    return Err(e.into());
+                }
+            }
+            // direct-io=on: bypasses page cache, prevents deadlock.
+            // The file keeps the loop number it had before, if still free.
+            let loop_dev = match self.loop_registry.attach(self.allocated, &swapfile_path) {
+                Ok(dev) => dev,
+                Err(e) => {
+                    self.abandon_new_file(&swapfile_path);
+                    return Err(e.into());
+                }
+            };
 
             tune_loop_device(&loop_dev);
 
@@ -1439,25 +2516,12 @@ impl SwapFile {
                 self.allocated,
                 chunk_size / (1024 * 1024)
             );
-            {
-                use std::io::Write;
-                let f = std::fs::OpenOptions::new()
-                    .write(true)
-                    .open(&swapfile_path)?;
-                let mut writer = std::io::BufWriter::with_capacity(1024 * 1024, f);
-                let zeros = vec![0u8; 1024 * 1024];
-                let chunks = chunk_size / (1024 * 1024);
-                for _ in 0..chunks {
-                    writer.write_all(&zeros)?;
-                }
-                let remainder = (chunk_size % (1024 * 1024)) as usize;
-                if remainder > 0 {
-                    writer.write_all(&vec![0u8; remainder])?;
-                }
-                writer.flush()?;
-            }
+            preallocate(&swapfile_path, chunk_size)?;
             (swapfile_path.to_string_lossy().to_string(), None)
         };
+        if let Some(id) = FileId::of(&swapfile_path) {
+            self.inode_registry.record(self.allocated, id);
+        }
 
         // mkswap
         let fs_label = if self.config.sparse_loop_backing {
@@ -1465,33 +2529,40 @@ impl SwapFile {
         } else {
             format!("SWAP_btrfs_{}", self.allocated)
         };
+        // A failed or timed-out mkswap gives the slot back; the next tick retries
         let status = Command::new("mkswap")
             .args(["-L", &fs_label])
             .arg(&swapfile)
             .stdout(Stdio::null())
-            .status()?;
-        if !status.success() {
-            force_remove(&swapfile_path, false);
-            self.allocated -= 1;
-            self.file_sizes.pop();
-            return Err(SwapFileError::Io(std::io::Error::other("mkswap failed")));
+            .status_timed();
+        if !status.as_ref().is_ok_and(|s| s.success()) {
+            if let Some(ref loop_dev) = loop_device {
+                crate::loopdev::detach(loop_dev);
+            }
+            self.abandon_new_file(&swapfile_path);
+            return Err(match status {
+                Err(e) => e.into(),
+                Ok(_) => SwapFileError::Io(std::io::Error::other("mkswap failed")),
+            });
         }
 
         // No discard for loop-backed swap on btrfs (PUNCH_HOLE destroys extents)
         let discard_options: Option<&str> = None;
+        // Priority follows the file number, so a re-created file takes the
+        // slot of the one it replaces and the kernel's order stays by speed.
         let unit_name = gen_swap_unit(
             Path::new(&swapfile),
-            None,
+            self.config.priority.for_file(self.storage, self.allocated),
             discard_options,
             &format!("swapfile_{}", self.allocated),
         )?;
 
         // Store loop device info for cleanup
         if let Some(ref loop_dev) = loop_device {
-            let loop_info_path = format!("{}/swapfile/loop_{}", WORK_DIR, self.allocated);
-            let _ = fs::write(
+            let loop_info_path = format!("{}/swapfile/loop_{}", work_dir(), self.allocated);
+            let _ = write_shared(
                 &loop_info_path,
-                format!("{}\n{}", loop_dev, swapfile_path.display()),
+                &format!("{}\n{}", loop_dev, swapfile_path.display()),
             );
         }
 
@@ -1507,6 +2578,175 @@ impl SwapFile {
         notify_status("Monitoring memory status...");
         Ok(())
     }
+
+    /// Add a burst file. Burst files get no explicit priority, so the kernel
+    /// places them below the persistent pool and fills them last.
+    fn create_burst_file(&mut self, max_count: u32) -> Result<()> {
+        let active = self.get_burst_files_info();
+        let Some(num) = next_burst_number(&active, max_count) else {
+            return Ok(());
+        };
+        let chunk_size = self.config.chunk_size;
+        if !self.has_enough_space(chunk_size) {
+            return Err(SwapFileError::NoSpace);
+        }
+
+        let dir = self.burst_dir();
+        if !dir.exists() {
+            makedirs(&dir)?;
+            // New files inherit NOCOW from the directory
+            if self.is_btrfs && self.config.nocow {
+                let _ = Command::new("chattr").args(["+C"]).arg(&dir).status_timed();
+            }
+        }
+        let path = dir.join(num.to_string());
+        force_remove(&path, false);
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            std::fs::OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .mode(0o600)
+                .open(&path)?;
+        }
+        if self.is_btrfs && self.config.nocow {
+            let _ = Command::new("chattr").args(["+C"]).arg(&path).status_timed();
+        }
+
+        notify_status(&format!("Allocating burst swap file #{} ({}MB)...", num, chunk_size / (1024 * 1024)));
+        info!("swapFC: creating burst file #{} ({}MB)", num, chunk_size / (1024 * 1024));
+        if let Err(e) = preallocate(&path, chunk_size) {
+            force_remove(&path, false);
+            return Err(e.into());
+        }
+        let status = Command::new("mkswap")
+            .args(["-L", &format!("SWAP_burst_{}", num)])
+            .arg(&path)
+            .stdout(Stdio::null())
+            .status_timed();
+        if !status.as_ref().is_ok_and(|s| s.success()) {
+            force_remove(&path, false);
+            return Err(match status {
+                Err(e) => e.into(),
+                Ok(_) => SwapFileError::Io(std::io::Error::other("mkswap failed")),
+            });
+        }
+
+        let unit_name = gen_swap_unit(&path, None, None, &format!("burst_{}", num))?;
+        systemctl(SystemctlAction::DaemonReload, "")?;
+        systemctl(SystemctlAction::Start, &unit_name)?;
+        notify_status("Monitoring memory status...");
+        Ok(())
+    }
+
+    /// Remove a burst file and its unit
+    fn destroy_burst_file(&mut self, path: &Path) -> Result<()> {
+        if let Err(e) = swapoff(&path.to_string_lossy()) {
+            warn!("swapFC: swapoff failed for {}: {}", path.display(), e);
+            return Err(SwapFileError::Io(std::io::Error::other("swapoff failed")));
+        }
+        let num = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+        let tag = format!("burst_{}", num);
+        for unit_path in crate::helpers::find_swap_units() {
+            if crate::helpers::get_tag_from_swap_unit(&unit_path).as_deref() == Some(tag.as_str()) {
+                force_remove(&unit_path, true);
+                break;
+            }
+        }
+        self.remove_disk_file(path);
+        info!("swapFC: burst file {} removed", path.display());
+        Ok(())
+    }
+
+    /// Burst files left on disk by a crash or forced reboot
+    fn cleanup_stale_burst_files(&self) {
+        let active = self.get_burst_files_info();
+        let Ok(entries) = std::fs::read_dir(self.burst_dir()) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let numeric = path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.parse::<u32>().is_ok());
+            if numeric && !active.iter().any(|f| f.path == path) {
+                info!("swapFC: removing stale burst file {}", path.display());
+                self.remove_disk_file(&path);
+            }
+        }
+    }
+
+    /// Burst class tick: add a file on pressure once the pool is full,
+    /// remove one after the quiet period. True if it acted.
+    fn burst_tick(&mut self, burst: BurstConfig, free_swap: u8, swap_files: &[SwapFileInfo], may_create: bool) -> bool {
+        if free_swap < burst.free_swap_perc {
+            self.burst_last_pressure = Instant::now();
+        }
+        let burst_files = self.get_burst_files_info();
+
+        if may_create
+            && self.allocated >= self.config.max_count
+            && free_swap < burst.free_swap_perc
+            && burst_files.len() < burst.max_count as usize
+        {
+            info!(
+                "swapFC: pool full ({} files), free_swap={}% < {}% - adding burst file",
+                self.allocated, free_swap, burst.free_swap_perc
+            );
+            if self.create_burst_file(burst.max_count).is_ok() {
+                self.last_creation = Some(Instant::now());
+            }
+            return true;
+        }
+
+        if self.burst_last_pressure.elapsed() < Duration::from_secs(burst.quiet_secs) {
+            return false;
+        }
+        // Lowest priority first: the file the kernel fills last
+        let Some(last) = burst_files.last() else {
+            return false;
+        };
+        let all: Vec<SwapFileInfo> = swap_files.iter().chain(&burst_files).cloned().collect();
+        if !self.config.can_safely_remove(last, &all) {
+            return false;
+        }
+        info!(
+            "swapFC: no pressure for {}s, removing burst file {} (usage: {}%)",
+            burst.quiet_secs,
+            last.path.display(),
+            last.usage_percent()
+        );
+        let path = last.path.clone();
+        let _ = self.destroy_burst_file(&path);
+        true
+    }
+}
+
+/// Zero-fill an existing file to `size` bytes
+fn preallocate(path: &Path, size: u64) -> std::io::Result<()> {
+    use std::io::Write;
+    let f = std::fs::OpenOptions::new().write(true).open(path)?;
+    let mut writer = std::io::BufWriter::with_capacity(1024 * 1024, f);
+    let zeros = vec![0u8; 1024 * 1024];
+    for _ in 0..size / (1024 * 1024) {
+        writer.write_all(&zeros)?;
+    }
+    let remainder = (size % (1024 * 1024)) as usize;
+    if remainder > 0 {
+        writer.write_all(&vec![0u8; remainder])?;
+    }
+    writer.flush()
+}
+
+/// Lowest burst file number in 1..=max not taken by an active burst file
+fn next_burst_number(active: &[SwapFileInfo], max: u32) -> Option<u32> {
+    (1..=max).find(|n| {
+        !active
+            .iter()
+            .any(|f| f.path.file_name().and_then(|s| s.to_str()) == Some(n.to_string().as_str()))
+    })
 }
 
 /// Check if path is a btrfs subvolume
@@ -1520,7 +2760,299 @@ fn is_btrfs_subvolume(path: &Path) -> bool {
         .arg(path)
         .stdout(Stdio::null())
         .stderr(Stdio::null())
-        .status()
+        .status_timed()
         .map(|s| s.success())
         .unwrap_or(false)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MB: u64 = 1024 * 1024;
+    const GB: u64 = 1024 * MB;
+
+    fn config_with_layout(layout: &[u64]) -> SwapFileConfig {
+        SwapFileConfig {
+            path: PathBuf::from("/swapfile"),
+            chunk_size: 512 * MB,
+            max_count: layout.len() as u32,
+            min_count: 1,
+            min_count_auto: false,
+            free_ram_perc: 20,
+            free_swap_perc: 40,
+            remove_free_swap_perc: 70,
+            frequency: 1,
+            shrink_threshold: 30,
+            safe_headroom: 40,
+            sparse_loop_backing: false,
+            growth_chunk_size: 0,
+            nocow: true,
+            layout: layout.to_vec(),
+            oomd_guard_free_swap: None,
+            priority: PriorityPolicy::Auto,
+            grow_max_size: 0,
+            wipe_on_remove: false,
+            trim_window: 0,
+            btrfs_parent: "auto".to_string(),
+            burst: None,
+            shared_slow_disk: false,
+        }
+    }
+
+    #[test]
+    fn layout_empty_means_uniform() {
+        assert!(parse_layout("", 512 * MB).unwrap().is_empty());
+        assert!(parse_layout("  ", 512 * MB).unwrap().is_empty());
+    }
+
+    #[test]
+    fn layout_parses_ladder() {
+        assert_eq!(
+            parse_layout("2G, 2G,4G,8G", 512 * MB).unwrap(),
+            vec![2 * GB, 2 * GB, 4 * GB, 8 * GB]
+        );
+    }
+
+    #[test]
+    fn layout_rejects_small_and_invalid_entries() {
+        assert!(parse_layout("2G,256M", 512 * MB).is_err());
+        assert!(parse_layout("2G,,4G", 512 * MB).is_err());
+        assert!(parse_layout("2G,lots", 512 * MB).is_err());
+    }
+
+    #[test]
+    fn layout_rejects_too_many_files() {
+        let layout = vec!["1G"; MAX_SWAPFILES + 1].join(",");
+        assert!(parse_layout(&layout, 512 * MB).is_err());
+    }
+
+    #[test]
+    fn size_for_file_follows_layout() {
+        let cfg = config_with_layout(&[2 * GB, 4 * GB, 8 * GB]);
+        assert_eq!(cfg.size_for_file(1), 2 * GB);
+        assert_eq!(cfg.size_for_file(3), 8 * GB);
+        assert_eq!(cfg.max_capacity(), 14 * GB);
+
+        let uniform = config_with_layout(&[]);
+        assert_eq!(uniform.size_for_file(5), 512 * MB);
+    }
+
+    #[test]
+    fn min_count_follows_ram_and_mode() {
+        assert_eq!(recommended_min_count(64 * GB, false), 1);
+        assert_eq!(recommended_min_count(2 * GB, true), 1);
+        assert_eq!(recommended_min_count(16 * GB, true), 2);
+        assert_eq!(recommended_min_count(64 * GB, true), 3);
+    }
+
+    #[test]
+    fn reserve_includes_min_count() {
+        let mut cfg = config_with_layout(&[]);
+        cfg.max_count = 28;
+        assert_eq!(cfg.reserve_files(false), 0);
+        assert_eq!(cfg.reserve_files(true), ZSWAP_MIN_RESERVE);
+        cfg.min_count = 3;
+        assert_eq!(cfg.reserve_files(true), 3);
+    }
+
+    #[test]
+    fn storage_classification() {
+        assert_eq!(StorageType::classify("nvme0n1", Some(false)), StorageType::Nvme);
+        assert_eq!(StorageType::classify("sda", Some(false)), StorageType::Ssd);
+        assert_eq!(StorageType::classify("sdb", Some(true)), StorageType::Hdd);
+        assert_eq!(StorageType::classify("vda", None), StorageType::Unknown);
+    }
+
+    #[test]
+    fn priority_windows_order_by_speed() {
+        let auto = PriorityPolicy::Auto;
+        let nvme_last = auto.for_file(StorageType::Nvme, 28).unwrap();
+        let ssd_first = auto.for_file(StorageType::Ssd, 1).unwrap();
+        let hdd_first = auto.for_file(StorageType::Hdd, 1).unwrap();
+        assert!(nvme_last > ssd_first && ssd_first > hdd_first);
+        assert!(auto.for_file(StorageType::Nvme, 1).unwrap() < defaults::ZRAM_PRIO);
+        assert_eq!(PriorityPolicy::parse("100").for_file(StorageType::Hdd, 3), Some(98));
+        assert_eq!(PriorityPolicy::parse("-1").for_file(StorageType::Nvme, 1), None);
+        assert_eq!(PriorityPolicy::parse("auto"), PriorityPolicy::Auto);
+    }
+
+    #[test]
+    fn consolidation_grows_earliest_file_within_limit() {
+        let sizes = [(1, 4 * GB), (2, 512 * MB), (3, 512 * MB)];
+        assert_eq!(consolidation_target(&sizes, 512 * MB, 4 * GB), Some(2));
+        assert_eq!(consolidation_target(&sizes, 512 * MB, 8 * GB), Some(1));
+        assert_eq!(consolidation_target(&sizes, 4 * GB, 4 * GB), None);
+        assert_eq!(consolidation_target(&[], 512 * MB, 8 * GB), None);
+    }
+
+    #[test]
+    fn burst_files_share_the_swap_area_limit() {
+        assert_eq!(burst_limit(4, 20), 4);
+        assert_eq!(burst_limit(4, 26), 2);
+        assert_eq!(burst_limit(4, 28), 0);
+        let active: Vec<SwapFileInfo> = [1, 3]
+            .iter()
+            .map(|n| SwapFileInfo {
+                path: PathBuf::from(format!("/swapfile/burst/{}", n)),
+                size_bytes: 512 * MB,
+                used_bytes: 0,
+                priority: -2,
+            })
+            .collect();
+        assert_eq!(next_burst_number(&active, 4), Some(2));
+        assert_eq!(next_burst_number(&active[..1], 1), None);
+    }
+
+    #[test]
+    fn expansion_triggers_in_order() {
+        let config = config_with_layout(&[]);
+        let critical = ExpansionSample {
+            free_ram: 5,
+            free_swap: 30,
+            disk_free_swap: 30,
+            cooldown_ok: true,
+            emergency_cooldown_ok: true,
+            ..ExpansionSample::default()
+        };
+        assert_eq!(config.expansion_trigger(&critical), Some(Expansion::Emergency));
+        let cooling = ExpansionSample { cooldown_ok: false, emergency_cooldown_ok: false, ..critical.clone() };
+        assert_eq!(config.expansion_trigger(&cooling), None);
+        let spare = ExpansionSample { unused_files: 2, ..critical.clone() };
+        assert_eq!(config.expansion_trigger(&spare), None);
+        let calm = ExpansionSample { free_ram: 50, ..critical.clone() };
+        assert_eq!(config.expansion_trigger(&calm), Some(Expansion::Normal));
+
+        // Zswap writes back into reserve files: only slots add a file
+        let zswap = ExpansionSample { zswap: true, ..critical.clone() };
+        assert_eq!(config.expansion_trigger(&zswap), None);
+        let zswap = ExpansionSample { slots_exhausted: true, ..zswap };
+        assert_eq!(config.expansion_trigger(&zswap), Some(Expansion::Slots));
+        let full = ExpansionSample { disk_full: true, ..zswap.clone() };
+        assert_eq!(config.expansion_trigger(&full), None);
+        let balancing = ExpansionSample { fs_busy: true, ..zswap };
+        assert_eq!(config.expansion_trigger(&balancing), None);
+
+        // Sharing a spinning disk with a swap partition: emergencies only
+        let shared = SwapFileConfig { shared_slow_disk: true, ..config_with_layout(&[]) };
+        assert_eq!(shared.expansion_trigger(&calm), None);
+        assert_eq!(shared.expansion_trigger(&critical), Some(Expansion::Emergency));
+    }
+
+    /// Removal safety over random sets of swap files
+    mod removal {
+        use super::*;
+        use proptest::prelude::*;
+
+        /// Up to 12 files: size 256M..8G, use in percent, priority
+        fn files() -> impl Strategy<Value = Vec<SwapFileInfo>> {
+            prop::collection::vec((256u64..8192, 0u64..=100, -2000i32..0), 1..12).prop_map(|specs| {
+                specs
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, (size_mb, used_pct, priority))| SwapFileInfo {
+                        path: PathBuf::from(format!("/swapfile/{}", i + 1)),
+                        size_bytes: size_mb * MB,
+                        used_bytes: size_mb * MB * used_pct / 100,
+                        priority,
+                    })
+                    .collect()
+            })
+        }
+
+        fn config(min_count: u32, shrink_threshold: u8, safe_headroom: u8) -> SwapFileConfig {
+            SwapFileConfig {
+                min_count,
+                shrink_threshold,
+                safe_headroom,
+                ..config_with_layout(&[])
+            }
+        }
+
+        /// Free space the other files keep after taking `target`'s data
+        fn room_after(target: &SwapFileInfo, files: &[SwapFileInfo]) -> (u64, u64) {
+            let others = files.iter().filter(|f| f.path != target.path);
+            let size: u64 = others.clone().map(|f| f.size_bytes).sum();
+            let used: u64 = others.map(|f| f.used_bytes).sum();
+            (size, size.saturating_sub(used).saturating_sub(target.used_bytes))
+        }
+
+        proptest! {
+            #[test]
+            fn candidate_respects_every_limit(
+                files in files(),
+                min_count in 0u32..6,
+                shrink_threshold in 10u8..=50,
+                safe_headroom in 20u8..=60,
+                pinned in prop::collection::vec(any::<bool>(), 12),
+            ) {
+                let config = config(min_count, shrink_threshold, safe_headroom);
+                let removable = |f: &SwapFileInfo| {
+                    let idx: usize = f.path.file_name().unwrap().to_str().unwrap().parse().unwrap();
+                    !pinned[idx - 1]
+                };
+                let eligible = |f: &SwapFileInfo| {
+                    f.is_nearly_empty(shrink_threshold) && removable(f) && config.can_safely_remove(f, &files)
+                };
+
+                match config.removal_candidate(&files, removable) {
+                    Some(candidate) => {
+                        prop_assert!(files.len() > min_count as usize, "never below min_count");
+                        prop_assert!(candidate.is_nearly_empty(shrink_threshold));
+                        prop_assert!(removable(candidate));
+                        let (size, free) = room_after(candidate, &files);
+                        prop_assert!(free * 100 >= size * safe_headroom as u64, "headroom kept after migration");
+                        prop_assert!(
+                            !files.iter().any(|f| f.priority < candidate.priority && eligible(f)),
+                            "lowest priority eligible file goes first"
+                        );
+                    }
+                    None => prop_assert!(
+                        files.len() <= min_count as usize || !files.iter().any(eligible),
+                        "an eligible file was kept"
+                    ),
+                }
+            }
+
+            #[test]
+            fn safe_removal_means_the_data_fits(
+                files in files(),
+                target in 0usize..12,
+                safe_headroom in 20u8..=60,
+            ) {
+                let lower = config(0, 30, safe_headroom - 10);
+                let config = config(0, 30, safe_headroom);
+                let target = &files[target % files.len()];
+                let (size, _) = room_after(target, &files);
+                let used: u64 = files.iter().filter(|f| f.path != target.path).map(|f| f.used_bytes).sum();
+                if config.can_safely_remove(target, &files) {
+                    let left = size.checked_sub(used + target.used_bytes);
+                    prop_assert!(left.is_some(), "the data fits on the other files");
+                    prop_assert!(left.unwrap_or(0) >= size * safe_headroom as u64 / 100, "headroom is kept");
+                    prop_assert!(lower.can_safely_remove(target, &files), "less headroom allows it too");
+                }
+                prop_assert!(!config.can_safely_remove(target, std::slice::from_ref(target)), "last file stays");
+            }
+
+            #[test]
+            fn an_empty_file_never_makes_removal_unsafe(
+                files in files(),
+                target in 0usize..12,
+                extra_mb in 256u64..8192,
+            ) {
+                let config = config(0, 30, 40);
+                let target = files[target % files.len()].clone();
+                let mut more = files.clone();
+                more.push(SwapFileInfo {
+                    path: PathBuf::from("/swapfile/extra"),
+                    size_bytes: extra_mb * MB,
+                    used_bytes: 0,
+                    priority: -3000,
+                });
+                if config.can_safely_remove(&target, &files) {
+                    prop_assert!(config.can_safely_remove(&target, &more));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,154 @@
+// Watch for swap set up by others, for systemd-swap
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// An admin's swapon of a partition, a zram device added by another tool or a
+// swapoff changes the swap the daemon coordinates. /proc/swaps is compared
+// every POLL_INTERVAL seconds. An area is external unless a swap unit the
+// daemon wrote (X-SystemdSwap-Tag) activates it. For each change:
+//   event     swap_added / swap_removed with the area, its tier, size and
+//             priority, and a state snapshot
+//   priority  an added area the kernel fills before a faster tier is warned
+//             about (tiers::misordered); its priority is the admin's to change
+//   policy    the swap file monitor re-checks at its next tick whether its
+//             directory shares a spinning disk with other swap (disks.rs)
+// Capacity needs no bookkeeping: the monitors read SwapTotal and SwapFree,
+// which count an external area as soon as it is active.
+
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use crate::absorb::{read_swaps, SwapArea};
+use crate::events::{Event, EventKind};
+use crate::helpers::{find_swap_units, format_size, get_tag_from_swap_unit, get_what_from_swap_unit};
+use crate::tiers::{self, Tier};
+use crate::{info, is_shutdown, warn};
+
+/// Seconds between /proc/swaps checks
+const POLL_INTERVAL: u64 = 5;
+
+/// Bumped whenever the set of active swap areas changes
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Changes of the active swap areas seen so far; compare to notice one
+pub fn generation() -> u64 {
+    GENERATION.load(Ordering::Acquire)
+}
+
+/// Devices and files activated by swap units the daemon wrote
+fn own_areas() -> HashSet<String> {
+    find_swap_units()
+        .into_iter()
+        .filter(|unit| get_tag_from_swap_unit(unit).is_some())
+        .filter_map(get_what_from_swap_unit)
+        .collect()
+}
+
+/// The areas among `areas` set up by others
+pub fn external(areas: Vec<SwapArea>) -> Vec<SwapArea> {
+    let own = own_areas();
+    areas.into_iter().filter(|a| !own.contains(&a.path)).collect()
+}
+
+/// Areas in `after` but not `before`, and in `before` but not `after`
+fn diff<'a>(before: &'a [SwapArea], after: &'a [SwapArea]) -> (Vec<&'a SwapArea>, Vec<&'a SwapArea>) {
+    let paths = |areas: &[SwapArea]| areas.iter().map(|a| a.path.clone()).collect::<HashSet<_>>();
+    let (old, new) = (paths(before), paths(after));
+    (
+        after.iter().filter(|a| !old.contains(&a.path)).collect(),
+        before.iter().filter(|a| !new.contains(&a.path)).collect(),
+    )
+}
+
+/// "/dev/sda2 (T3 HDD/partition swap, 8.0 GiB, priority -2)"
+pub fn describe(tier: Tier, area: &SwapArea) -> String {
+    format!("{} ({} {}, {}, priority {})", area.path, tier.label(), tier.name(), format_size(area.size), area.priority)
+}
+
+fn record(kind: EventKind, tier: Tier, area: &SwapArea) {
+    let mut details = vec![
+        ("area".to_string(), area.path.clone()),
+        ("tier".to_string(), tier.label().to_string()),
+        ("size".to_string(), area.size.to_string()),
+        ("priority".to_string(), area.priority.to_string()),
+    ];
+    details.extend(crate::events::snapshot());
+    if let Err(e) = crate::events::record(&Event::new(kind, details)) {
+        warn!("Swap watch: cannot record {} event: {}", kind.as_str(), e);
+    }
+}
+
+/// Report every change of the swap set up by others until shutdown
+pub fn spawn_watcher() {
+    crate::context::spawn(|| {
+        let mut last: Vec<String> = read_swaps().into_iter().map(|a| a.path).collect();
+        let mut known = external(read_swaps());
+        for (tier, area) in tiers::classify(known.clone()) {
+            info!("Swap watch: {} set up by others", describe(tier, &area));
+        }
+        loop {
+            for _ in 0..POLL_INTERVAL {
+                if is_shutdown() {
+                    return;
+                }
+                thread::sleep(Duration::from_secs(1));
+            }
+            let areas = read_swaps();
+            let paths: Vec<String> = areas.iter().map(|a| a.path.clone()).collect();
+            if paths == last {
+                continue;
+            }
+            last = paths;
+            GENERATION.fetch_add(1, Ordering::AcqRel);
+
+            let current = external(areas.clone());
+            let (added, removed) = diff(&known, &current);
+            for area in removed {
+                let tier = tiers::classify(vec![area.clone()])[0].0;
+                info!("Swap watch: {} was deactivated", describe(tier, area));
+                record(EventKind::SwapRemoved, tier, area);
+            }
+            if !added.is_empty() {
+                let classified = tiers::classify(areas);
+                let notes = tiers::misordered(&classified);
+                for area in added {
+                    let Some((tier, _)) = classified.iter().find(|(_, a)| a.path == area.path) else {
+                        continue;
+                    };
+                    info!("Swap watch: {} was activated by others", describe(*tier, area));
+                    record(EventKind::SwapAdded, *tier, area);
+                    for note in notes.iter().filter(|n| n.starts_with(&format!("{} ", area.path))) {
+                        warn!("Swap watch: {}", note);
+                    }
+                }
+            }
+            known = current;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn area(path: &str) -> SwapArea {
+        SwapArea {
+            path: path.to_string(),
+            kind: "partition".to_string(),
+            size: 1 << 30,
+            used: 0,
+            priority: -2,
+        }
+    }
+
+    #[test]
+    fn added_and_removed_by_path() {
+        let before = [area("/dev/sda2"), area("/dev/zram5")];
+        let after = [area("/dev/zram5"), area("/dev/nvme0n1p3")];
+        let (added, removed) = diff(&before, &after);
+        assert_eq!(added, [&after[1]]);
+        assert_eq!(removed, [&before[0]]);
+        assert_eq!(diff(&after, &after), (vec![], vec![]));
+    }
+}
@@ -0,0 +1,220 @@
+// Kernel sysctl tuning with backup/restore for systemd-swap
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use thiserror::Error;
+
+use crate::config::Config;
+use crate::context::work_dir;
+use crate::defaults;
+use crate::helpers::{makedirs, write_file, write_sysfs};
+use crate::modes::SwapMode;
+use crate::{info, warn};
+
+#[derive(Error, Debug)]
+pub enum SysctlError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Helper error: {0}")]
+    Helper(#[from] crate::helpers::HelperError),
+    #[error("Invalid value for {0}: {1}")]
+    InvalidValue(String, String),
+}
+
+pub type Result<T> = std::result::Result<T, SysctlError>;
+
+pub const MIN_FREE_KBYTES: &str = "vm.min_free_kbytes";
+
+/// Lower/upper bounds for the automatic min_free_kbytes value (64MB..1GB)
+const MIN_FREE_KBYTES_FLOOR: u64 = 64 * 1024;
+const MIN_FREE_KBYTES_CAP: u64 = 1024 * 1024;
+
+fn proc_path(name: &str) -> PathBuf {
+    Path::new("/proc/sys").join(name.replace('.', "/"))
+}
+
+fn backup_dir() -> String {
+    format!("{}/sysctl_backup", work_dir())
+}
+
+/// Read a sysctl value (dotted name, e.g. "vm.min_free_kbytes")
+pub fn read(name: &str) -> Result<String> {
+    Ok(fs::read_to_string(proc_path(name))?.trim().to_string())
+}
+
+/// Read a numeric sysctl value
+pub fn read_u64(name: &str) -> Result<u64> {
+    let value = read(name)?;
+    value
+        .parse()
+        .map_err(|_| SysctlError::InvalidValue(name.to_string(), value))
+}
+
+/// Write a sysctl value, saving the original first so `restore_all` can undo it.
+/// The first backup wins: repeated writes keep the pre-daemon value.
+pub fn write_with_backup(name: &str, value: &str) -> Result<()> {
+    makedirs(backup_dir())?;
+    let backup = Path::new(&backup_dir()).join(name);
+    if !backup.exists() {
+        write_file(&backup, &read(name)?)?;
+    }
+    write_sysfs(proc_path(name), value)?;
+    Ok(())
+}
+
+/// Original value saved before the daemon changed a sysctl, if any
+pub fn backup_value(name: &str) -> Option<String> {
+    fs::read_to_string(Path::new(&backup_dir()).join(name))
+        .ok()
+        .map(|v| v.trim().to_string())
+}
+
+/// Every sysctl changed through `write_with_backup`, with its saved original value
+pub fn backups() -> Vec<(String, String)> {
+    let Ok(entries) = fs::read_dir(backup_dir()) else {
+        return Vec::new();
+    };
+    let mut backups: Vec<(String, String)> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let value = fs::read_to_string(entry.path()).ok()?;
+            Some((entry.file_name().to_string_lossy().to_string(), value.trim().to_string()))
+        })
+        .collect();
+    backups.sort();
+    backups
+}
+
+/// Restore every sysctl changed through `write_with_backup`
+pub fn restore_all() {
+    let Ok(entries) = fs::read_dir(backup_dir()) else {
+        return;
+    };
+    info!("Sysctl: restore configuration: start");
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        match fs::read_to_string(entry.path()) {
+            Ok(value) => {
+                if let Err(e) = write_sysfs(proc_path(&name), value.trim()) {
+                    warn!("Sysctl: failed to restore {}: {}", name, e);
+                }
+            }
+            Err(e) => warn!("Sysctl: failed to read backup of {}: {}", name, e),
+        }
+    }
+    let _ = fs::remove_dir_all(backup_dir());
+    info!("Sysctl: restore configuration: complete");
+}
+
+/// Recommended vm.min_free_kbytes for a mode, or None when the mode keeps
+/// no compressed pool worth reserving for. Manual mode goes by the backends
+/// it enables.
+///
+/// zram allocates compressed pages from the reclaim path itself, so it gets
+/// the larger reserve; zswap falls back to disk when it cannot allocate.
+pub fn recommended_min_free_kbytes(ram_bytes: u64, mode: SwapMode, config: &Config) -> Option<u64> {
+    let percent = if mode.uses_zram(config) {
+        4
+    } else if mode.uses_zswap(config) {
+        3
+    } else {
+        return None;
+    };
+    let kbytes = ram_bytes / 1024 * percent / 100;
+    Some(kbytes.clamp(MIN_FREE_KBYTES_FLOOR, MIN_FREE_KBYTES_CAP))
+}
+
+/// Raise vm.min_free_kbytes for heavy-swap modes when `min_free_kbytes_auto` is set.
+/// Never lowers a value that is already higher (e.g. set by the admin).
+pub fn apply_min_free_kbytes(config: &Config, mode: SwapMode) {
+    let enabled = match config.get_opt("min_free_kbytes_auto") {
+        Some(_) => config.get_bool("min_free_kbytes_auto"),
+        None => defaults::MIN_FREE_KBYTES_AUTO,
+    };
+    if !enabled {
+        return;
+    }
+
+    let Ok(ram) = crate::meminfo::get_ram_size() else {
+        return;
+    };
+    let Some(target) = recommended_min_free_kbytes(ram, mode, config) else {
+        return;
+    };
+    let current = match read_u64(MIN_FREE_KBYTES) {
+        Ok(v) => v,
+        Err(e) => {
+            warn!("Sysctl: cannot read {}: {}", MIN_FREE_KBYTES, e);
+            return;
+        }
+    };
+    if current >= target {
+        info!(
+            "Sysctl: {} already {} (recommended {}), leaving as is",
+            MIN_FREE_KBYTES, current, target
+        );
+        return;
+    }
+
+    match write_with_backup(MIN_FREE_KBYTES, &target.to_string()) {
+        Ok(()) => info!("Sysctl: {} {} → {}", MIN_FREE_KBYTES, current, target),
+        Err(e) => warn!("Sysctl: failed to set {}: {}", MIN_FREE_KBYTES, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    const GIB: u64 = 1024 * 1024 * 1024;
+
+    #[test]
+    fn proc_path_from_dotted_name() {
+        assert_eq!(
+            proc_path("vm.min_free_kbytes"),
+            PathBuf::from("/proc/sys/vm/min_free_kbytes")
+        );
+    }
+
+    fn config(pairs: &[(&str, &str)]) -> Config {
+        Config::from_values(pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect::<HashMap<_, _>>())
+    }
+
+    #[test]
+    fn min_free_kbytes_scales_with_mode() {
+        let none = config(&[]);
+        let zram = recommended_min_free_kbytes(16 * GIB, SwapMode::ZramOnly, &none).unwrap();
+        let zswap = recommended_min_free_kbytes(16 * GIB, SwapMode::ZswapSwapfc, &none).unwrap();
+        assert_eq!(zram, 16 * 1024 * 1024 * 4 / 100);
+        assert!(zswap < zram);
+        assert_eq!(recommended_min_free_kbytes(16 * GIB, SwapMode::Manual, &none), None);
+        assert_eq!(recommended_min_free_kbytes(16 * GIB, SwapMode::Disabled, &none), None);
+    }
+
+    #[test]
+    fn manual_mode_follows_its_enabled_backends() {
+        let manual = |pairs: &[(&str, &str)]| recommended_min_free_kbytes(16 * GIB, SwapMode::Manual, &config(pairs));
+        let zram = recommended_min_free_kbytes(16 * GIB, SwapMode::ZramOnly, &config(&[]));
+        let zswap = recommended_min_free_kbytes(16 * GIB, SwapMode::ZswapSwapfc, &config(&[]));
+        assert_eq!(manual(&[("zram_enabled", "1")]), zram);
+        assert_eq!(manual(&[("zswap_enabled", "1")]), zswap);
+        assert_eq!(manual(&[("zram_enabled", "1"), ("zswap_enabled", "1")]), zram);
+        assert_eq!(manual(&[("swapfile_enabled", "1")]), None);
+    }
+
+    #[test]
+    fn min_free_kbytes_is_clamped() {
+        let none = config(&[]);
+        assert_eq!(
+            recommended_min_free_kbytes(GIB, SwapMode::ZramOnly, &none),
+            Some(MIN_FREE_KBYTES_FLOOR)
+        );
+        assert_eq!(
+            recommended_min_free_kbytes(256 * GIB, SwapMode::ZramSwapfc, &none),
+            Some(MIN_FREE_KBYTES_CAP)
+        );
+    }
+}
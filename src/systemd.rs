@@ -13,7 +13,8 @@ use std::process::{Command, Stdio};
 use thiserror::Error;
 
 use crate::config::RUN_SYSD;
-use crate::helpers::{makedirs, relative_symlink, write_file};
+use crate::exec::TimedCommand;
+use crate::helpers::{makedirs, relative_symlink, write_file, UNIT_TAG_KEY};
 use crate::info;
 
 /// Typed systemctl sub-commands used by this daemon.
@@ -24,6 +25,7 @@ use crate::info;
 pub enum SystemctlAction {
     Start,
     Stop,
+    Restart,
     DaemonReload,
 }
 
@@ -32,6 +34,7 @@ impl SystemctlAction {
         match self {
             Self::Start => "start",
             Self::Stop => "stop",
+            Self::Restart => "restart",
             Self::DaemonReload => "daemon-reload",
         }
     }
@@ -53,16 +56,31 @@ pub type Result<T> = std::result::Result<T, SystemdError>;
 
 /// Notify systemd that we're ready
 pub fn notify_ready() {
-    let _ = libsystemd::daemon::notify(false, &[libsystemd::daemon::NotifyState::Ready]);
+    let mut state = vec![libsystemd::daemon::NotifyState::Ready];
+    // Show the mode and version in `systemctl status`
+    let daemon = crate::state::DaemonState::load();
+    if let Some(ref daemon) = daemon {
+        state.push(libsystemd::daemon::NotifyState::Status(daemon.summary()));
+    }
+    if crate::context::notify_systemd() {
+        let _ = libsystemd::daemon::notify(false, &state);
+    }
+    crate::hooks::run_after_start(daemon.as_ref().map_or("", |d| d.mode.as_str()));
 }
 
 /// Notify systemd that we're stopping
 pub fn notify_stopping() {
+    if !crate::context::notify_systemd() {
+        return;
+    }
     let _ = libsystemd::daemon::notify(false, &[libsystemd::daemon::NotifyState::Stopping]);
 }
 
 /// Notify status message
 pub fn notify_status(status: &str) {
+    if !crate::context::notify_systemd() {
+        return;
+    }
     let _ = libsystemd::daemon::notify(
         false,
         &[(libsystemd::daemon::NotifyState::Status(status.to_string()))],
@@ -81,7 +99,7 @@ pub fn systemctl(action: SystemctlAction, unit: &str) -> Result<()> {
         cmd.arg(action_str).arg(unit);
     }
 
-    let status = cmd.status()?;
+    let status = cmd.status_timed()?;
 
     if status.success() {
         Ok(())
@@ -93,6 +111,29 @@ pub fn systemctl(action: SystemctlAction, unit: &str) -> Result<()> {
     }
 }
 
+/// Start several units with one systemctl call; systemd runs their jobs in
+/// parallel and the call returns once all of them finished
+pub fn start_units(units: &[String]) -> Result<()> {
+    if units.is_empty() {
+        return Ok(());
+    }
+    let status = Command::new("systemctl")
+        .arg("start")
+        .args(units)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status_timed()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(SystemdError::CommandFailed(format!(
+            "systemctl start {} failed with {}",
+            units.join(" "),
+            status
+        )))
+    }
+}
+
 /// Device type for swap unit
 #[derive(Debug, Clone, Copy)]
 pub enum DeviceType {
@@ -136,25 +177,32 @@ pub fn gen_swap_unit(
     let unit_name = Command::new("systemd-escape")
         .args(["-p", "--suffix=swap", &what_str])
         .stdout(Stdio::piped())
-        .output()
+        .output_timed()
         .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())?;
 
     let unit_path = format!("{}/system/{}", RUN_SYSD, unit_name);
 
+    // Loop devices keep their number (see loopdev), so name the backing file
+    let description = match what_str.strip_prefix("/dev/") {
+        Some(dev) if dev.starts_with("loop") => fs::read_to_string(format!("/sys/block/{}/loop/backing_file", dev))
+            .map(|backing| format!("{} {} on {}", device_type, backing.trim(), what_str))
+            .unwrap_or_else(|_| device_type.to_string()),
+        _ => device_type.to_string(),
+    };
+
     // Build unit content
     let mut content = format!(
         r#"[Unit]
 Description=Swap {}
 Documentation=https://github.com/Nefelim4ag/systemd-swap
-
 # Generated by systemd-swap
-# Tag={}
+{}={}
 
 [Swap]
 What={}
 TimeoutSec=1h
 "#,
-        device_type, tag, what_str
+        description, UNIT_TAG_KEY, tag, what_str
     );
 
     if let Some(prio) = priority {
@@ -0,0 +1,236 @@
+// Swap tiers for systemd-swap
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// The backends form layers the kernel fills from the fastest down:
+//   T0  zram               compressed RAM, zram_prio (32767)
+//   T1  zswap pool         compressed cache in front of the swap areas below
+//   T2  NVMe/SSD swap      swap files on flash, priorities 2000-3999
+//   T3  HDD/partition swap swap files on spinning disks and other partitions,
+//                          priorities 1999 and below
+// The daemon assigns priorities inside these bands (zram_prio, and per
+// storage type for swap files, see StorageType::priority_top), so the kernel's
+// order matches the tiers. Swap areas set up by others are placed by the
+// device they live on; one whose priority puts it ahead of a faster tier is
+// reported, since the kernel then fills it first.
+
+use std::collections::HashMap;
+use std::fs;
+use std::ops::RangeInclusive;
+use std::path::Path;
+
+use crate::absorb::SwapArea;
+use crate::swapfile::StorageType;
+use crate::warn;
+
+/// Swap tiers, fastest first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Tier {
+    Zram,
+    Zswap,
+    Fast,
+    Slow,
+}
+
+impl Tier {
+    pub const ALL: [Tier; 4] = [Tier::Zram, Tier::Zswap, Tier::Fast, Tier::Slow];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Tier::Zram => "T0",
+            Tier::Zswap => "T1",
+            Tier::Fast => "T2",
+            Tier::Slow => "T3",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Tier::Zram => "zram",
+            Tier::Zswap => "zswap pool",
+            Tier::Fast => "NVMe/SSD swap",
+            Tier::Slow => "HDD/partition swap",
+        }
+    }
+
+    /// Priorities the daemon gives swap areas of this tier. The zswap pool
+    /// is not a swap area and has none.
+    pub fn priorities(self) -> Option<RangeInclusive<i32>> {
+        match self {
+            Tier::Zram => Some(4000..=i16::MAX as i32),
+            Tier::Zswap => None,
+            Tier::Fast => Some(2000..=3999),
+            Tier::Slow => Some(i32::MIN..=1999),
+        }
+    }
+
+    pub fn for_storage(storage: StorageType) -> Self {
+        match storage {
+            StorageType::Nvme | StorageType::Ssd => Tier::Fast,
+            StorageType::Hdd | StorageType::Unknown => Tier::Slow,
+        }
+    }
+
+    /// Tier of a swap area from /proc/swaps. `storage_of` resolves the
+    /// storage type of a directory, so callers can share lookups.
+    fn of_area(path: &str, storage_of: &mut impl FnMut(&Path) -> StorageType) -> Self {
+        let Some(dev) = path.strip_prefix("/dev/") else {
+            let dir = Path::new(path).parent().unwrap_or(Path::new("/"));
+            return Self::for_storage(storage_of(dir));
+        };
+        if dev.starts_with("zram") {
+            return Tier::Zram;
+        }
+        if dev.starts_with("loop") {
+            // Loop-backed swap file: the disk under its backing file
+            let backing = fs::read_to_string(format!("/sys/block/{}/loop/backing_file", dev)).unwrap_or_default();
+            let dir = Path::new(backing.trim()).parent().unwrap_or(Path::new("/"));
+            return Self::for_storage(storage_of(dir));
+        }
+        // /dev/mapper/* and other links resolve to the kernel name
+        let name = fs::canonicalize(path)
+            .ok()
+            .and_then(|p| p.file_name().map(|n| n.to_string_lossy().into_owned()))
+            .unwrap_or_else(|| dev.to_string());
+        Self::for_storage(StorageType::of_device(name))
+    }
+}
+
+/// Warn when a configured priority takes a backend out of its tier's band
+pub fn check_priority(tier: Tier, priority: i32, key: &str) {
+    let Some(band) = tier.priorities() else {
+        return;
+    };
+    if !band.contains(&priority) {
+        warn!(
+            "{}={} is outside tier {} ({}: {}..{}), the kernel will not fill the tiers in order",
+            key,
+            priority,
+            tier.label(),
+            tier.name(),
+            (*band.start()).max(0),
+            band.end()
+        );
+    }
+}
+
+/// Place every active swap area in its tier
+pub fn classify(areas: Vec<SwapArea>) -> Vec<(Tier, SwapArea)> {
+    let mut storage: HashMap<std::path::PathBuf, StorageType> = HashMap::new();
+    let mut storage_of = |dir: &Path| *storage.entry(dir.to_path_buf()).or_insert_with(|| StorageType::detect(dir));
+    areas
+        .into_iter()
+        .map(|area| (Tier::of_area(&area.path, &mut storage_of), area))
+        .collect()
+}
+
+/// Capacity and use of one tier
+#[derive(Debug, Clone, PartialEq)]
+pub struct TierUsage {
+    pub tier: Tier,
+    pub areas: usize,
+    pub capacity: u64,
+    pub used: u64,
+    /// Lowest and highest priority of its swap areas
+    pub priorities: Option<(i32, i32)>,
+}
+
+/// Per-tier totals in tier order, leaving out tiers without a backend.
+/// `zswap` is the pool limit and the pool size in RAM, if zswap is on.
+pub fn summarize(areas: &[(Tier, SwapArea)], zswap: Option<(u64, u64)>) -> Vec<TierUsage> {
+    Tier::ALL
+        .iter()
+        .filter_map(|&tier| {
+            if tier == Tier::Zswap {
+                let (capacity, used) = zswap?;
+                return Some(TierUsage {
+                    tier,
+                    areas: 0,
+                    capacity,
+                    used,
+                    priorities: None,
+                });
+            }
+            let members: Vec<&SwapArea> = areas.iter().filter(|(t, _)| *t == tier).map(|(_, a)| a).collect();
+            let lowest = members.iter().map(|a| a.priority).min()?;
+            let highest = members.iter().map(|a| a.priority).max()?;
+            Some(TierUsage {
+                tier,
+                areas: members.len(),
+                capacity: members.iter().map(|a| a.size).sum(),
+                used: members.iter().map(|a| a.used).sum(),
+                priorities: Some((lowest, highest)),
+            })
+        })
+        .collect()
+}
+
+/// Swap areas the kernel fills before an area of a faster tier, one note
+/// per area
+pub fn misordered(areas: &[(Tier, SwapArea)]) -> Vec<String> {
+    areas
+        .iter()
+        .filter_map(|(tier, area)| {
+            let (faster_tier, faster) = areas
+                .iter()
+                .filter(|(t, a)| t < tier && a.priority < area.priority)
+                .min_by_key(|(t, _)| *t)?;
+            Some(format!(
+                "{} ({}) has priority {}, above {} ({}, {}); the kernel fills it first",
+                area.path,
+                tier.label(),
+                area.priority,
+                faster.path,
+                faster_tier.label(),
+                faster.priority
+            ))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::defaults;
+    use crate::swapfile::PriorityPolicy;
+
+    fn area(path: &str, size: u64, used: u64, priority: i32) -> SwapArea {
+        SwapArea {
+            path: path.to_string(),
+            kind: "file".to_string(),
+            size,
+            used,
+            priority,
+        }
+    }
+
+    #[test]
+    fn default_priorities_stay_in_their_tier() {
+        assert!(Tier::Zram.priorities().unwrap().contains(&defaults::ZRAM_PRIO));
+        for storage in [StorageType::Nvme, StorageType::Ssd, StorageType::Hdd, StorageType::Unknown] {
+            let band = Tier::for_storage(storage).priorities().unwrap();
+            for file in [1, 28] {
+                assert!(band.contains(&PriorityPolicy::Auto.for_file(storage, file).unwrap()));
+            }
+        }
+    }
+
+    #[test]
+    fn sums_per_tier_and_flags_misordered_areas() {
+        let areas = vec![
+            (Tier::Zram, area("/dev/zram0", 8 << 30, 1 << 30, 32767)),
+            (Tier::Fast, area("/swapfile/1", 2 << 30, 0, 3999)),
+            (Tier::Fast, area("/swapfile/2", 2 << 30, 0, 3998)),
+            (Tier::Slow, area("/dev/sda2", 4 << 30, 1 << 20, 5000)),
+        ];
+        let tiers = summarize(&areas, Some((3 << 30, 1 << 28)));
+        let labels: Vec<&str> = tiers.iter().map(|t| t.tier.label()).collect();
+        assert_eq!(labels, ["T0", "T1", "T2", "T3"]);
+        assert_eq!(tiers[2].capacity, 4 << 30);
+        assert_eq!(tiers[2].priorities, Some((3998, 3999)));
+        assert!(summarize(&areas[..1], None).iter().all(|t| t.tier == Tier::Zram));
+
+        let notes = misordered(&areas);
+        assert_eq!(notes.len(), 1);
+        assert!(notes[0].starts_with("/dev/sda2 (T3) has priority 5000, above /swapfile/1 (T2"));
+    }
+}
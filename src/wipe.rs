@@ -0,0 +1,228 @@
+// Wiping of removed swap files for systemd-swap
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// With swapfile_wipe_on_remove=1 a swap file that is removed is not simply
+// unlinked: its blocks would keep swapped-out secrets in free space. The file
+// is renamed to .wipe-* in the swap directory and a background thread clears
+// it at WIPE_RATE, so contraction never causes an I/O spike, then unlinks it.
+// Spinning disks are overwritten with zeros; on SSD/NVMe an overwrite lands
+// in fresh flash pages anyway, so the blocks are punched out instead and the
+// filesystem discards them (at once with the discard mount option, else at
+// the next fstrim). Swap directories on dm-crypt are never wiped: their
+// blocks only ever held ciphertext. Wipes cut short by shutdown are resumed
+// at the next start. Discard wipes hold back around fstrim.timer's runs
+// (swapfile_trim_window, see fstrim.rs).
+
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::os::unix::fs::FileExt;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use nix::fcntl::{fallocate, FallocateFlags};
+
+use crate::fstrim::{Gate, TrimGate};
+use crate::helpers::now_secs;
+use crate::swapfile::StorageType;
+use crate::{info, is_shutdown, warn};
+
+/// Prefix of files waiting to be wiped in the swap file directory
+pub const WIPE_PREFIX: &str = ".wipe-";
+/// Bytes cleared per second
+const WIPE_RATE: u64 = 32 * 1024 * 1024;
+/// Bytes cleared per write or hole punch
+const WIPE_CHUNK: u64 = 4 * 1024 * 1024;
+
+/// Wipes run one at a time so several removals do not add up their I/O
+static WIPE_LOCK: Mutex<()> = Mutex::new(());
+
+/// How a removed swap file's blocks are cleared
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WipeMethod {
+    /// Write zeros over the file in place
+    Overwrite,
+    /// Punch the blocks out so the filesystem discards them
+    Discard,
+}
+
+impl WipeMethod {
+    /// Pick the method for the swap directory's storage. Copy-on-write files
+    /// (btrfs without NOCOW) are never overwritten in place, so only
+    /// discarding can reach their old blocks.
+    pub fn for_storage(storage: StorageType, cow: bool) -> Self {
+        match storage {
+            StorageType::Hdd | StorageType::Unknown if !cow => WipeMethod::Overwrite,
+            _ => WipeMethod::Discard,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            WipeMethod::Overwrite => "zero overwrite",
+            WipeMethod::Discard => "discard",
+        }
+    }
+}
+
+/// Whether a block device is dm-crypt or stacked on it (e.g. LVM on LUKS)
+pub fn is_encrypted(device: &str) -> bool {
+    let sys = Path::new("/sys/class/block").join(device);
+    let uuid = fs::read_to_string(sys.join("dm/uuid")).unwrap_or_default();
+    if uuid.starts_with("CRYPT-") {
+        return true;
+    }
+    let Ok(slaves) = fs::read_dir(sys.join("slaves")) else {
+        return false;
+    };
+    slaves
+        .flatten()
+        .any(|s| is_encrypted(&s.file_name().to_string_lossy()))
+}
+
+/// Wait while fstrim is due or running. Returns false if shutdown came first.
+fn wait_for_fstrim(gate: &mut TrimGate, path: &Path) -> bool {
+    loop {
+        match gate.check() {
+            Gate::Open => return true,
+            Gate::Wait(Some(reason)) => info!(
+                "swapFC: {}, deferring discard of {} until it has run",
+                reason,
+                path.display()
+            ),
+            Gate::Wait(None) => {}
+            Gate::Released(delay) => info!(
+                "swapFC: fstrim has run, resuming discard of {} in {}s",
+                path.display(),
+                delay.as_secs()
+            ),
+        }
+        if is_shutdown() {
+            return false;
+        }
+        thread::sleep(Duration::from_secs(1));
+    }
+}
+
+/// Clear a file chunk by chunk at `rate` bytes/s and unlink it. Discards
+/// pause around fstrim runs when `trim_window` (seconds) is set.
+/// Returns false, leaving the file in place, if shutdown interrupted it.
+fn wipe_file(path: &Path, method: WipeMethod, rate: u64, trim_window: u64) -> io::Result<bool> {
+    let file: File = OpenOptions::new().write(true).open(path)?;
+    let len = file.metadata()?.len();
+    let zeros = vec![0u8; WIPE_CHUNK as usize];
+    let mut gate = TrimGate::new(trim_window);
+    let mut started = Instant::now();
+    let mut offset = 0;
+    while offset < len {
+        if is_shutdown() {
+            return Ok(false);
+        }
+        if method == WipeMethod::Discard {
+            // Time spent waiting does not count towards the rate
+            let paused = Instant::now();
+            if !wait_for_fstrim(&mut gate, path) {
+                return Ok(false);
+            }
+            started += paused.elapsed();
+        }
+        let n = WIPE_CHUNK.min(len - offset);
+        match method {
+            WipeMethod::Overwrite => {
+                file.write_all_at(&zeros[..n as usize], offset)?;
+                file.sync_data()?;
+            }
+            WipeMethod::Discard => {
+                fallocate(
+                    &file,
+                    FallocateFlags::FALLOC_FL_PUNCH_HOLE | FallocateFlags::FALLOC_FL_KEEP_SIZE,
+                    offset as i64,
+                    n as i64,
+                )
+                .map_err(io::Error::from)?;
+            }
+        }
+        offset += n;
+        let due = Duration::from_secs_f64(offset as f64 / rate as f64);
+        if let Some(wait) = due.checked_sub(started.elapsed()) {
+            thread::sleep(wait);
+        }
+    }
+    drop(file);
+    fs::remove_file(path)?;
+    Ok(true)
+}
+
+fn run(path: PathBuf, method: WipeMethod, trim_window: u64) {
+    let _guard = WIPE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let started = Instant::now();
+    match wipe_file(&path, method, WIPE_RATE, trim_window) {
+        Ok(true) => info!(
+            "swapFC: wiped {} ({}) in {}s",
+            path.display(),
+            method.name(),
+            started.elapsed().as_secs()
+        ),
+        Ok(false) => info!("swapFC: wipe of {} interrupted, resuming at next start", path.display()),
+        Err(e) => warn!("swapFC: wipe of {} failed: {}", path.display(), e),
+    }
+}
+
+/// Move a removed swap file out of the way and wipe it in the background
+pub fn schedule(path: &Path, method: WipeMethod, trim_window: u64) -> io::Result<()> {
+    let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    let stamp = now_secs();
+    let pending = path.with_file_name(format!("{}{}-{}", WIPE_PREFIX, name, stamp));
+    fs::rename(path, &pending)?;
+    crate::context::spawn(move || run(pending, method, trim_window));
+    Ok(())
+}
+
+/// Pick up wipes left over from a previous run. Without a method (wiping
+/// was turned off since) the leftovers are just removed.
+pub fn resume(dir: &Path, method: Option<WipeMethod>, trim_window: u64) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        if !entry.file_name().to_string_lossy().starts_with(WIPE_PREFIX) {
+            continue;
+        }
+        let path = entry.path();
+        match method {
+            Some(method) => {
+                info!("swapFC: resuming wipe of {}", path.display());
+                crate::context::spawn(move || run(path, method, trim_window));
+            }
+            None => {
+                let _ = fs::remove_file(&path);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn method_follows_storage() {
+        assert_eq!(WipeMethod::for_storage(StorageType::Hdd, false), WipeMethod::Overwrite);
+        assert_eq!(WipeMethod::for_storage(StorageType::Hdd, true), WipeMethod::Discard);
+        assert_eq!(WipeMethod::for_storage(StorageType::Nvme, false), WipeMethod::Discard);
+    }
+
+    #[test]
+    fn overwrite_clears_and_unlinks() {
+        let path = std::env::temp_dir().join(format!("systemd-swap-wipe-{}", std::process::id()));
+        fs::write(&path, vec![0xaau8; (WIPE_CHUNK + 4096) as usize]).unwrap();
+        // Check the zeros through a second handle before the unlink
+        let check = File::open(&path).unwrap();
+        assert!(wipe_file(&path, WipeMethod::Overwrite, u64::MAX, 0).unwrap());
+        assert!(!path.exists());
+        let mut buf = vec![0xffu8; 4096];
+        check.read_exact_at(&mut buf, WIPE_CHUNK).unwrap();
+        assert!(buf.iter().all(|&b| b == 0));
+    }
+}
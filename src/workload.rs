@@ -0,0 +1,310 @@
+// Workload class detection for systemd-swap
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// Hardware alone does not tell a developer laptop from a build host with the
+// same RAM and disk. The daemon samples memory use once a minute:
+//   bursts       MemAvailable fell by BURST_DROP_PERCENT of RAM or more since
+//                the previous sample (parallel compile and link jobs)
+//   swap rhythm  pages swapped out and in (pswpout/pswpin in /proc/vmstat);
+//                build hosts write out and seldom read back, desktops page
+//                idle applications back in when the user returns to them
+// The counts are kept in state_dir/workload across reboots and halved once
+// they span DECAY_SAMPLES, so the class follows a change of use. With the core
+// count and whether the host runs a graphical session they make the class:
+//   desktop  a display manager is enabled or a graphical session is open
+//   build    headless, and at least two of: bursty, swap written more than
+//            SWAP_OUT_RATIO times what is read back, BUILD_MIN_CPUS cores
+//   server   any other headless host
+// Usage counts only after MIN_SAMPLES; before that a headless host is a server.
+// Autoconfig tunes the swap file thresholds by class (RecommendedConfig).
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::meminfo::get_mem_stats;
+use crate::{is_shutdown, warn};
+
+/// Seconds between samples
+const SAMPLE_INTERVAL: u64 = 60;
+
+/// Samples between writes of the observations
+const SAVE_EVERY: u64 = 10;
+
+/// Observations needed before usage counts (an hour of samples)
+const MIN_SAMPLES: u64 = 60;
+
+/// Counts are halved when they span this many samples (a week)
+const DECAY_SAMPLES: u64 = 7 * 24 * 60;
+
+/// A drop of MemAvailable by this much of RAM within one sample is a burst
+const BURST_DROP_PERCENT: u64 = 5;
+
+/// Share of samples with a burst that makes a host bursty
+const BURSTY_PERCENT: u64 = 2;
+
+/// Swap written per page read back that makes swap write-mostly
+const SWAP_OUT_RATIO: u64 = 4;
+
+/// Cores from which a headless host counts towards a build host
+const BUILD_MIN_CPUS: usize = 8;
+
+/// What the host is used for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WorkloadClass {
+    #[default]
+    Desktop,
+    Build,
+    Server,
+}
+
+impl WorkloadClass {
+    pub fn name(self) -> &'static str {
+        match self {
+            WorkloadClass::Desktop => "desktop",
+            WorkloadClass::Build => "build",
+            WorkloadClass::Server => "server",
+        }
+    }
+
+    /// Class of a host with `cpus` cores and these observations
+    pub fn classify(cpus: usize, graphical: bool, observed: &Observations) -> Self {
+        if graphical {
+            return WorkloadClass::Desktop;
+        }
+        let signals = [
+            observed.is_bursty(),
+            observed.is_write_mostly(),
+            cpus >= BUILD_MIN_CPUS,
+        ];
+        if signals.iter().filter(|s| **s).count() >= 2 {
+            WorkloadClass::Build
+        } else {
+            WorkloadClass::Server
+        }
+    }
+
+    /// Class of this host from its sessions and the recorded observations
+    pub fn detect(cpus: usize, config: &Config) -> Self {
+        Self::classify(cpus, is_graphical(), &Observations::load(&observations_file(config)))
+    }
+}
+
+impl fmt::Display for WorkloadClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Memory usage patterns seen so far
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Observations {
+    pub samples: u64,
+    /// Samples in which MemAvailable dropped by BURST_DROP_PERCENT of RAM
+    pub bursts: u64,
+    /// Pages swapped in and out over the samples
+    pub swap_in: u64,
+    pub swap_out: u64,
+}
+
+impl Observations {
+    pub fn load(path: &Path) -> Self {
+        let content = fs::read_to_string(path).unwrap_or_default();
+        let mut observed = Self::default();
+        for line in content.lines() {
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let value: u64 = value.trim().parse().unwrap_or(0);
+            match key.trim() {
+                "samples" => observed.samples = value,
+                "bursts" => observed.bursts = value,
+                "swap_in" => observed.swap_in = value,
+                "swap_out" => observed.swap_out = value,
+                _ => {}
+            }
+        }
+        observed
+    }
+
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        let tmp = path.with_extension("tmp");
+        fs::write(
+            &tmp,
+            format!(
+                "samples={}\nbursts={}\nswap_in={}\nswap_out={}\n",
+                self.samples, self.bursts, self.swap_in, self.swap_out
+            ),
+        )?;
+        fs::rename(&tmp, path)
+    }
+
+    /// Add one sample: the drop of MemAvailable in percent of RAM and the
+    /// pages swapped in and out since the previous sample
+    pub fn record(&mut self, drop_percent: u64, swap_in: u64, swap_out: u64) {
+        self.samples += 1;
+        self.bursts += u64::from(drop_percent >= BURST_DROP_PERCENT);
+        self.swap_in = self.swap_in.saturating_add(swap_in);
+        self.swap_out = self.swap_out.saturating_add(swap_out);
+        if self.samples >= DECAY_SAMPLES {
+            self.samples /= 2;
+            self.bursts /= 2;
+            self.swap_in /= 2;
+            self.swap_out /= 2;
+        }
+    }
+
+    fn is_bursty(&self) -> bool {
+        self.samples >= MIN_SAMPLES && self.bursts * 100 >= self.samples * BURSTY_PERCENT
+    }
+
+    fn is_write_mostly(&self) -> bool {
+        self.samples >= MIN_SAMPLES && self.swap_out > 0 && self.swap_out >= self.swap_in * SWAP_OUT_RATIO
+    }
+}
+
+impl fmt::Display for Observations {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.samples < MIN_SAMPLES {
+            return write!(f, "{} of {} samples, usage not counted yet", self.samples, MIN_SAMPLES);
+        }
+        write!(
+            f,
+            "{} samples, {}% with bursts, {} pages swapped out, {} in",
+            self.samples,
+            self.bursts * 100 / self.samples,
+            self.swap_out,
+            self.swap_in
+        )
+    }
+}
+
+/// Observations file below the persistent state directory
+pub fn observations_file(config: &Config) -> PathBuf {
+    crate::config::state_dir(config).join("workload")
+}
+
+/// A display manager is enabled, or logind tracks an X11 or Wayland session.
+/// The display manager covers the boot, when nobody has logged in yet.
+pub fn is_graphical() -> bool {
+    if Path::new("/etc/systemd/system/display-manager.service").exists() {
+        return true;
+    }
+    fs::read_dir("/run/systemd/sessions")
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter_map(|e| fs::read_to_string(e.path()).ok())
+                .any(|session| is_graphical_session(&session))
+        })
+        .unwrap_or(false)
+}
+
+/// Whether a logind session file describes a graphical session
+fn is_graphical_session(content: &str) -> bool {
+    content
+        .lines()
+        .any(|line| matches!(line.trim(), "TYPE=x11" | "TYPE=wayland" | "TYPE=mir"))
+}
+
+/// MemAvailable and MemTotal in bytes, and pswpin/pswpout in pages
+fn read_counters() -> Option<(u64, u64, u64, u64)> {
+    let mem = get_mem_stats(&["MemAvailable", "MemTotal"]).ok()?;
+    let vmstat = fs::read_to_string("/proc/vmstat").ok()?;
+    let counter = |key: &str| {
+        vmstat
+            .lines()
+            .find_map(|l| l.strip_prefix(key)?.strip_prefix(' ')?.trim().parse::<u64>().ok())
+    };
+    Some((mem["MemAvailable"], mem["MemTotal"], counter("pswpin")?, counter("pswpout")?))
+}
+
+/// Sample once a minute until shutdown, writing the observations every
+/// SAVE_EVERY samples
+pub fn spawn_observer(config: &Config) {
+    let path = observations_file(config);
+    crate::context::spawn(move || {
+        let mut observed = Observations::load(&path);
+        let mut last = read_counters();
+        let mut pending = 0;
+        let mut warned = false;
+        while !is_shutdown() {
+            thread::sleep(Duration::from_secs(SAMPLE_INTERVAL));
+            let Some(now) = read_counters() else {
+                continue;
+            };
+            if let Some((avail, _, swap_in, swap_out)) = last {
+                let (avail_now, total, swap_in_now, swap_out_now) = now;
+                let drop_percent = avail.saturating_sub(avail_now) * 100 / total.max(1);
+                observed.record(
+                    drop_percent,
+                    swap_in_now.saturating_sub(swap_in),
+                    swap_out_now.saturating_sub(swap_out),
+                );
+                pending += 1;
+            }
+            last = Some(now);
+            if pending >= SAVE_EVERY {
+                pending = 0;
+                if let Err(e) = observed.save(&path) {
+                    if !warned {
+                        warn!("Workload: cannot write {}: {}", path.display(), e);
+                        warned = true;
+                    }
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn observed(samples: u64, bursts: u64, swap_in: u64, swap_out: u64) -> Observations {
+        Observations {
+            samples,
+            bursts,
+            swap_in,
+            swap_out,
+        }
+    }
+
+    #[test]
+    fn classifies_by_session_cores_and_usage() {
+        let compiling = observed(600, 60, 1_000, 50_000);
+        assert_eq!(WorkloadClass::classify(32, true, &compiling), WorkloadClass::Desktop);
+        assert_eq!(WorkloadClass::classify(32, false, &compiling), WorkloadClass::Build);
+        assert_eq!(WorkloadClass::classify(4, false, &compiling), WorkloadClass::Build);
+        // Steady, reads its swap back: a server whatever the core count
+        let steady = observed(600, 0, 40_000, 50_000);
+        assert_eq!(WorkloadClass::classify(32, false, &steady), WorkloadClass::Server);
+        // Too little observed: only the cores count
+        assert_eq!(WorkloadClass::classify(32, false, &observed(10, 10, 0, 500)), WorkloadClass::Server);
+    }
+
+    #[test]
+    fn records_bursts_and_decays() {
+        let mut observed = Observations::default();
+        observed.record(BURST_DROP_PERCENT, 1, 8);
+        observed.record(1, 0, 0);
+        assert_eq!((observed.samples, observed.bursts, observed.swap_out), (2, 1, 8));
+        observed.samples = DECAY_SAMPLES - 1;
+        observed.record(0, 0, 0);
+        assert_eq!((observed.samples, observed.swap_out), (DECAY_SAMPLES / 2, 4));
+
+        let path = std::env::temp_dir().join(format!("workload-{}", std::process::id()));
+        observed.save(&path).unwrap();
+        assert_eq!(Observations::load(&path), observed);
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn graphical_sessions_by_type() {
+        assert!(is_graphical_session("# This is private data.\nUID=1000\nTYPE=wayland\nCLASS=user\n"));
+        assert!(!is_graphical_session("UID=0\nTYPE=tty\nCLASS=user\n"));
+    }
+}
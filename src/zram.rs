@@ -2,18 +2,29 @@
 // Dynamic multi-ZRAM pool with adaptive expansion/contraction
 // SPDX-License-Identifier: GPL-3.0-or-later
 
+use std::fs::OpenOptions;
+use std::os::unix::fs::FileExt;
 use std::path::Path;
 use std::process::{Command, Stdio};
-use std::thread;
 use std::time::{Duration, Instant};
 
 use thiserror::Error;
 
-use crate::config::{Config, WORK_DIR};
+use crate::config::Config;
+use crate::context::work_dir;
 use crate::defaults;
-use crate::helpers::{makedirs, parse_size, read_file};
-use crate::systemd::{gen_swap_unit, systemctl, SystemctlAction};
-use crate::{error, info, warn};
+use crate::events::{Event, EventKind};
+use crate::exec::TimedCommand;
+use crate::execwatch::ExecWatch;
+use crate::helpers::{force_remove, makedirs, read_file, selected_option, write_sysfs};
+use crate::meminfo::{FreeRam, FreeRamTracker};
+use crate::profiler::{Phase, TickProfiler};
+use crate::psi::PressureWaiter;
+use crate::recovery::WorkDirWatch;
+use crate::state::{write_shared, write_shared_all};
+use crate::systemd::{gen_swap_unit, swapoff, systemctl, SystemctlAction};
+use crate::tiers::Tier;
+use crate::{debug, error, info, warn};
 
 const ZRAM_MODULE: &str = "/sys/module/zram";
 const ZRAM_HOT_ADD: &str = "/sys/class/zram-control/hot_add";
@@ -27,6 +38,8 @@ pub enum ZramError {
     Helper(#[from] crate::helpers::HelperError),
     #[error("Systemd error: {0}")]
     Systemd(#[from] crate::systemd::SystemdError),
+    #[error("Config error: {0}")]
+    Config(#[from] crate::config::ConfigError),
     #[error("Zram module not available")]
     NotAvailable,
     #[error("No free zram device found")]
@@ -41,6 +54,55 @@ pub enum ZramError {
 
 pub type Result<T> = std::result::Result<T, ZramError>;
 
+/// Bytes between warm-up writes. One 4K page per MiB touches every page of the
+/// zram slot table (16 bytes per slot, 256 slots per table page).
+const WARMUP_STRIDE: u64 = 1024 * 1024;
+const WARMUP_PAGE: usize = 4096;
+
+/// Pre-fault a fresh zram device before mkswap: write one compressible page per
+/// WARMUP_STRIDE and discard them all again, so the slot table and the
+/// compression streams are allocated before the first real swap-out.
+fn warm_up_device(dev_path: &str, disksize: u64) -> Result<Duration> {
+    let started = Instant::now();
+
+    // Not a same-filled page, so it goes through the compressor and zsmalloc
+    let page: Vec<u8> = (0..WARMUP_PAGE).map(|i| (i % 251) as u8).collect();
+    let dev = OpenOptions::new().write(true).open(dev_path)?;
+    let mut offset = 0;
+    while offset + WARMUP_PAGE as u64 <= disksize {
+        dev.write_at(&page, offset)?;
+        offset += WARMUP_STRIDE;
+    }
+    dev.sync_all()?;
+    drop(dev);
+
+    let status = Command::new("blkdiscard")
+        .arg(dev_path)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status_timed()?;
+    if !status.success() {
+        return Err(ZramError::ZramctlFailed(format!("blkdiscard {} failed", dev_path)));
+    }
+    Ok(started.elapsed())
+}
+
+/// Run the warm-up if enabled and log its cost; failures only cost the optimization
+fn maybe_warm_up(enabled: bool, dev_path: &str, disksize: u64, ctx: &str) {
+    if !enabled {
+        return;
+    }
+    match warm_up_device(dev_path, disksize) {
+        Ok(elapsed) => info!(
+            "{}: warm-up touched {} pages in {}ms",
+            ctx,
+            disksize.div_ceil(WARMUP_STRIDE),
+            elapsed.as_millis()
+        ),
+        Err(e) => warn!("{}: warm-up failed: {}", ctx, e),
+    }
+}
+
 /// Check if zram is available
 pub fn is_available() -> bool {
     Path::new(ZRAM_MODULE).is_dir()
@@ -49,11 +111,16 @@ pub fn is_available() -> bool {
 /// Set comp_algorithm for a ZRAM device.
 fn configure_zram_algorithm(sysfs: &str, comp_alg: &str, ctx: &str) {
     let comp_path = format!("{}/comp_algorithm", sysfs);
-    if let Err(e) = std::fs::write(&comp_path, comp_alg) {
+    if let Err(e) = write_sysfs(&comp_path, comp_alg) {
         warn!("{}: failed to set comp_algorithm: {}", ctx, e);
     }
 }
 
+/// Algorithm in use, from comp_algorithm's "lzo [lz4] zstd" listing
+fn current_algorithm(sysfs: &str) -> Option<String> {
+    selected_option(&std::fs::read_to_string(format!("{}/comp_algorithm", sysfs)).ok()?)
+}
+
 /// Start zram swap
 pub fn start(config: &Config) -> Result<()> {
     crate::systemd::notify_status("Setting up Zram...");
@@ -64,17 +131,17 @@ pub fn start(config: &Config) -> Result<()> {
     }
     info!("Zram: module found!");
 
-    makedirs(format!("{}/zram", WORK_DIR))?;
+    makedirs(format!("{}/zram", work_dir()))?;
 
     // Parse config values
-    let zram_size = parse_size(config.get("zram_size").unwrap_or(defaults::ZRAM_SIZE)).map_err(ZramError::ZramctlFailed)?;
+    let zram_size = match ratio_disksize(config)? {
+        Some(size) => size,
+        None => config.get_size_or("zram_size", defaults::ZRAM_SIZE)?,
+    };
     let zram_alg = config.get("zram_alg").unwrap_or(defaults::ZRAM_ALG);
     let zram_prio: i32 = config.get_as("zram_prio").unwrap_or(defaults::ZRAM_PRIO);
 
-    let zram_mem_limit = config
-        .get_opt("zram_mem_limit")
-        .and_then(|s| parse_size(s).ok())
-        .unwrap_or(0);
+    let zram_mem_limit = config.get_size_or("zram_mem_limit", "0")?;
 
     if zram_size == 0 {
         warn!("Zram: size is 0, skipping");
@@ -86,6 +153,7 @@ pub fn start(config: &Config) -> Result<()> {
         zram_size,
         zram_size / (1024 * 1024)
     );
+    crate::tiers::check_priority(Tier::Zram, zram_prio, "zram_prio");
 
     info!("Zram: trying to initialize free device");
     if !Path::new(ZRAM_HOT_ADD).exists() {
@@ -99,9 +167,9 @@ pub fn start(config: &Config) -> Result<()> {
     configure_zram_algorithm(&zram_sysfs, zram_alg, "Zram");
 
     let disksize_path = format!("{}/disksize", zram_sysfs);
-    if let Err(e) = std::fs::write(&disksize_path, zram_size.to_string()) {
+    if let Err(e) = write_sysfs(&disksize_path, zram_size.to_string()) {
         error!("Zram: failed to set disksize: {}", e);
-        let _ = std::fs::write(format!("{}/reset", zram_sysfs), "1");
+        let _ = write_sysfs(format!("{}/reset", zram_sysfs), "1");
         return Err(ZramError::ZramctlFailed(
             "Failed to set disksize".to_string(),
         ));
@@ -110,7 +178,7 @@ pub fn start(config: &Config) -> Result<()> {
     if zram_mem_limit > 0 {
         let mem_limit_path = format!("{}/mem_limit", zram_sysfs);
         if Path::new(&mem_limit_path).exists() {
-            match std::fs::write(&mem_limit_path, zram_mem_limit.to_string()) {
+            match write_sysfs(&mem_limit_path, zram_mem_limit.to_string()) {
                 Ok(_) => info!(
                     "Zram: mem_limit = {} MiB (RAM protection)",
                     zram_mem_limit / (1024 * 1024)
@@ -120,17 +188,19 @@ pub fn start(config: &Config) -> Result<()> {
         }
     }
 
+    maybe_warm_up(config.get_bool("zram_warmup"), &zram_dev, zram_size, "Zram");
+
     // Run mkswap
     let mkswap_status = Command::new("mkswap")
         .arg(&zram_dev)
         .stdout(Stdio::null())
         .stderr(Stdio::null())
-        .status()?;
+        .status_timed()?;
 
     if !mkswap_status.success() {
         // Clean up the zram device on mkswap failure
         let zram_id = zram_dev.trim_start_matches("/dev/zram");
-        let _ = std::fs::write(format!("/sys/block/zram{}/reset", zram_id), "1");
+        let _ = write_sysfs(format!("/sys/block/zram{}/reset", zram_id), "1");
         return Err(ZramError::ZramctlFailed("mkswap failed".to_string()));
     }
 
@@ -149,7 +219,7 @@ pub fn start(config: &Config) -> Result<()> {
     let zram_id = zram_dev.trim_start_matches("/dev/zram");
     let zram_sysfs = format!("/sys/block/zram{}", zram_id);
     let zram_info = format!("{}\n{}", zram_dev, zram_sysfs);
-    let _ = std::fs::write(format!("{}/zram/device", WORK_DIR), &zram_info);
+    let _ = write_shared(format!("{}/zram/device", work_dir()), &zram_info);
 
     crate::systemd::notify_status("Zram setup finished");
     Ok(())
@@ -161,7 +231,7 @@ pub fn release(device: &str) -> Result<()> {
         .args(["-r", device])
         .stdout(Stdio::null())
         .stderr(Stdio::null())
-        .status()?;
+        .status_timed()?;
 
     if !status.success() {
         return Err(ZramError::ZramctlFailed(format!(
@@ -173,7 +243,7 @@ pub fn release(device: &str) -> Result<()> {
     // Hot-remove the device from the kernel to avoid orphaned zram entries
     let dev_id = device.trim_start_matches("/dev/zram");
     if Path::new(ZRAM_HOT_REMOVE).exists() {
-        let _ = std::fs::write(ZRAM_HOT_REMOVE, dev_id);
+        let _ = write_sysfs(ZRAM_HOT_REMOVE, dev_id);
     }
 
     Ok(())
@@ -207,6 +277,170 @@ struct ZramDevice {
     state: ZramDeviceState,
     /// Swapoff attempt count while in Draining state
     drain_attempts: u32,
+    /// Swap priority the device is currently active with
+    priority: i32,
+    /// Compression algorithm the device was set up with
+    algorithm: String,
+    /// I/O failures (io_stat) already reported
+    io_failures: u64,
+}
+
+impl ZramDevice {
+    /// Whether the device runs another algorithm than `expected`. An unknown
+    /// algorithm (unreadable comp_algorithm) is not counted.
+    fn is_diverged(&self, expected: &str) -> bool {
+        !self.algorithm.is_empty() && self.algorithm != expected
+    }
+}
+
+/// Seconds between attempts to fix priorities of adopted devices
+const PRIORITY_REBALANCE_INTERVAL: u64 = 60;
+
+/// Seconds between rolling recreates of devices on an outdated algorithm
+const RECREATE_INTERVAL: u64 = 60;
+
+/// Scheduled writeback of incompressible and cold pages to the backing device
+#[derive(Debug, Clone, Copy)]
+pub struct WritebackConfig {
+    /// Seconds between writeback passes
+    pub interval: u64,
+    /// Seconds a page must stay untouched to count as idle (0 = huge pages only)
+    pub idle_age: u64,
+    /// Huge pages (bytes) a device must hold before they are written back
+    pub min_huge: u64,
+}
+
+impl WritebackConfig {
+    /// None unless zram_huge_idle_writeback is on and a backing_dev is set
+    fn from_config(config: &Config) -> Result<Option<Self>> {
+        if !config.get_bool("zram_huge_idle_writeback") {
+            return Ok(None);
+        }
+        if config.get("zram_backing_dev").unwrap_or("").is_empty() {
+            warn!("Zram: zram_huge_idle_writeback needs a zram_backing_dev, disabled");
+            return Ok(None);
+        }
+        Ok(Some(Self {
+            interval: config
+                .get_as::<u64>("zram_writeback_interval")
+                .unwrap_or(defaults::ZRAM_WRITEBACK_INTERVAL)
+                .clamp(60, 86400),
+            idle_age: config
+                .get_as::<u64>("zram_writeback_idle_age")
+                .unwrap_or(defaults::ZRAM_WRITEBACK_IDLE_AGE),
+            min_huge: config.get_size_or("zram_writeback_min_huge", defaults::ZRAM_WRITEBACK_MIN_HUGE)?,
+        }))
+    }
+}
+
+/// Allocate a new zram device through hot_add; returns its id
+fn hot_add() -> Result<u32> {
+    if !Path::new(ZRAM_HOT_ADD).exists() {
+        return Err(ZramError::ZramctlFailed(
+            "Kernel doesn't support hot_add".to_string(),
+        ));
+    }
+    read_file(ZRAM_HOT_ADD)?
+        .trim()
+        .parse()
+        .map_err(|_| ZramError::ZramctlFailed("Invalid hot_add response".to_string()))
+}
+
+/// Give back a device that did not join the pool: reset it, hot_remove it
+/// and drop its swap unit, if one was written
+fn discard_device(id: u32, unit_name: Option<&str>) {
+    let _ = write_sysfs(format!("/sys/block/zram{}/reset", id), "1");
+    if Path::new(ZRAM_HOT_REMOVE).exists() {
+        let _ = write_sysfs(ZRAM_HOT_REMOVE, id.to_string());
+    }
+    if let Some(unit_name) = unit_name {
+        force_remove(format!("/run/systemd/system/{}", unit_name), false);
+    }
+}
+
+/// Run `prepare(id, backing)` for every id, returning the results in order.
+/// A backing dev backs a single device, so with `backing` the ids are tried
+/// one at a time until one prepares successfully and keeps it; the rest then
+/// run in parallel without it.
+fn prepare_devices<T: Send>(
+    ids: &[u32],
+    backing: bool,
+    prepare: impl Fn(u32, bool) -> Result<T> + Sync,
+) -> Vec<Result<T>> {
+    let mut results = Vec::with_capacity(ids.len());
+    if backing {
+        for &id in ids {
+            let result = prepare(id, true);
+            let done = result.is_ok();
+            results.push(result);
+            if done {
+                break;
+            }
+        }
+        if !results.iter().any(|r| r.is_ok()) {
+            warn!("ZramPool: no device could be set up, zram_backing_dev left unused");
+        }
+    }
+    let ctx = crate::context::current();
+    let prepare = &prepare;
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = ids[results.len()..]
+            .iter()
+            .map(|&id| {
+                let ctx = ctx.clone();
+                scope.spawn(move || {
+                    if let Some(ctx) = ctx {
+                        crate::context::enter(ctx);
+                    }
+                    prepare(id, false)
+                })
+            })
+            .collect();
+        results.extend(
+            handles
+                .into_iter()
+                .map(|h| h.join().unwrap_or_else(|_| Err(ZramError::ZramctlFailed("setup thread panicked".to_string())))),
+        );
+    });
+    results
+}
+
+/// Backing device of a zram device, None when it has none
+fn backing_dev(sysfs: &str) -> Option<String> {
+    let dev = std::fs::read_to_string(format!("{}/backing_dev", sysfs)).ok()?;
+    let dev = dev.trim();
+    (!dev.is_empty() && dev != "none").then(|| dev.to_string())
+}
+
+/// Pages written to the backing device so far (bd_stat bd_writes)
+fn bd_writes(sysfs: &str) -> u64 {
+    std::fs::read_to_string(format!("{}/bd_stat", sysfs))
+        .ok()
+        .and_then(|s| s.split_whitespace().nth(2).and_then(|v| v.parse().ok()))
+        .unwrap_or(0)
+}
+
+/// Huge pages (stored uncompressed) of a device, from mm_stat
+fn huge_pages(sysfs: &str) -> u64 {
+    std::fs::read_to_string(format!("{}/mm_stat", sysfs))
+        .ok()
+        .and_then(|s| s.split_whitespace().nth(7).and_then(|v| v.parse().ok()))
+        .unwrap_or(0)
+}
+
+/// Look up a swap device in /proc/swaps, returning (used bytes, priority)
+fn read_swap_entry(dev_path: &str) -> Option<(u64, i32)> {
+    let swaps = std::fs::read_to_string("/proc/swaps").ok()?;
+    // Skip header: Filename Type Size Used Priority
+    swaps.lines().skip(1).find_map(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 5 || fields[0] != dev_path {
+            return None;
+        }
+        let used_kb: u64 = fields[3].parse().ok()?;
+        let priority: i32 = fields[4].parse().ok()?;
+        Some((used_kb * 1024, priority))
+    })
 }
 
 /// Aggregated statistics from all active ZRAM devices in the pool
@@ -222,6 +456,29 @@ pub struct ZramPoolStats {
     pub phys_usage_percent: u8,
     pub total_same_pages: u64,
     pub total_pages_compacted: u64,
+    pub io: IoStat,
+}
+
+/// Disksize as zram_expected_ratio × zram_mem_limit when zram_size_mode=ratio.
+///
+/// zram can never hold more than mem_limit times the compression ratio, so
+/// this gives /proc/swaps a capacity it can actually reach. None means
+/// zram_size applies (ram mode, or ratio mode without a mem_limit).
+pub fn ratio_disksize(config: &Config) -> Result<Option<u64>> {
+    let mode = config.get("zram_size_mode").unwrap_or(defaults::ZRAM_SIZE_MODE);
+    if !mode.eq_ignore_ascii_case("ratio") {
+        return Ok(None);
+    }
+    let mem_limit = config.get_size_or("zram_mem_limit", "0")?;
+    if mem_limit == 0 {
+        warn!("Zram: zram_size_mode=ratio needs a zram_mem_limit, using zram_size");
+        return Ok(None);
+    }
+    let ratio = config
+        .get_as::<f64>("zram_expected_ratio")
+        .unwrap_or(defaults::ZRAM_EXPECTED_RATIO)
+        .clamp(1.0, 10.0);
+    Ok(Some((mem_limit as f64 * ratio) as u64))
 }
 
 /// Configuration for the ZramPool
@@ -229,16 +486,18 @@ pub struct ZramPoolStats {
 pub struct ZramPoolConfig {
     /// Maximum number of ZRAM devices (1-8)
     pub max_devices: u8,
-    /// Initial ZRAM size as percentage of RAM (first device)
-    pub initial_size_percent: u32,
+    /// Total initial disksize in bytes, split across the initial devices
+    pub initial_size: u64,
+    /// initial_size comes from zram_size_mode=ratio rather than a RAM percentage
+    pub sized_by_ratio: bool,
     /// Compression algorithm
     pub algorithm: String,
     /// Swap priority (all devices same = round-robin)
     pub priority: i32,
     /// Minimum compression ratio to allow pool expansion
     pub expand_min_ratio: f64,
-    /// Per-device mem_limit as percentage of RAM (0 = unlimited)
-    pub mem_limit_percent: u32,
+    /// Pool-wide mem_limit in bytes, split per device (0 = unlimited)
+    pub mem_limit: u64,
     /// Pool utilization % that triggers expansion
     pub expand_threshold: u8,
     /// Pool utilization % below which to contract
@@ -251,20 +510,40 @@ pub struct ZramPoolConfig {
     pub min_free_ram_percent: u8,
     /// Seconds between monitor checks
     pub check_interval: u64,
+    /// Pre-fault new devices before mkswap (zram_warmup)
+    pub warmup: bool,
+    /// Recreate adopted devices whose algorithm differs, one at a time
+    pub recreate_diverged: bool,
+    /// Block device new devices write back to (zram_backing_dev)
+    pub backing_dev: Option<String>,
+    /// Huge/idle page writeback schedule, when enabled
+    pub writeback: Option<WritebackConfig>,
 }
 
 impl ZramPoolConfig {
-    pub fn from_config(config: &Config) -> Self {
-        Self {
+    /// Disksize the pool starts with on `ram_total` bytes of RAM: at least
+    /// 50% of it, unless the size follows mem_limit, where more would be
+    /// capacity the pool can never fill
+    pub fn initial_disksize(&self, ram_total: u64) -> u64 {
+        if self.sized_by_ratio {
+            self.initial_size
+        } else {
+            self.initial_size.max(ram_total / 2)
+        }
+    }
+
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let ratio_size = ratio_disksize(config)?;
+        Ok(Self {
             max_devices: config
                 .get_as::<u8>("zram_max_devices")
                 .unwrap_or(defaults::ZRAM_MAX_DEVICES)
                 .clamp(1, 8),
-            initial_size_percent: config
-                .get_opt("zram_size")
-                .and_then(|s| s.strip_suffix('%'))
-                .and_then(|s| s.parse().ok())
-                .unwrap_or(50),
+            initial_size: match ratio_size {
+                Some(size) => size,
+                None => config.get_size_or("zram_size", defaults::ZRAM_POOL_SIZE)?,
+            },
+            sized_by_ratio: ratio_size.is_some(),
             algorithm: config.get("zram_alg").unwrap_or(defaults::ZRAM_ALG).to_string(),
             priority: config.get_as("zram_prio").unwrap_or(defaults::ZRAM_PRIO),
             expand_min_ratio: config
@@ -295,12 +574,16 @@ impl ZramPoolConfig {
                 .get_as::<u64>("zram_check_interval")
                 .unwrap_or(defaults::ZRAM_CHECK_INTERVAL)
                 .clamp(3, 300),
-            mem_limit_percent: config
-                .get_opt("zram_mem_limit")
-                .and_then(|s| s.strip_suffix('%'))
-                .and_then(|s| s.parse().ok())
-                .unwrap_or(0),
-        }
+            mem_limit: config.get_size_or("zram_mem_limit", "0")?,
+            warmup: config.get_bool("zram_warmup"),
+            recreate_diverged: config.get_bool("zram_recreate_diverged"),
+            backing_dev: config
+                .get("zram_backing_dev")
+                .ok()
+                .filter(|d| !d.is_empty())
+                .map(str::to_string),
+            writeback: WritebackConfig::from_config(config)?,
+        })
     }
 }
 
@@ -312,6 +595,31 @@ pub struct ZramPool {
     last_expansion: Option<Instant>,
     last_contraction: Option<Instant>,
     low_util_since: Option<Instant>,
+    last_rebalance: Option<Instant>,
+    last_recreate: Option<Instant>,
+    last_writeback: Option<Instant>,
+    /// Devices were marked idle by a pass that could not use an idle age;
+    /// the next pass writes back what is still idle
+    idle_marked: bool,
+    waiter: PressureWaiter,
+    /// Notices when WORK_DIR was cleared under us
+    work_dir_watch: WorkDirWatch,
+    /// Free RAM corrected for zram growth MemAvailable lags behind
+    free_ram: FreeRamTracker,
+    /// Launches of memory-heavy applications (zram_exec_watch)
+    exec_watch: Option<ExecWatch>,
+    /// Device added ahead of a launch, until its demand shows or the grace ends
+    prespawn: Option<Prespawn>,
+}
+
+/// A device added on a launch hint
+#[derive(Debug)]
+struct Prespawn {
+    id: u32,
+    app: String,
+    at: Instant,
+    /// Pool disksize before the device was added
+    capacity_before: u64,
 }
 
 impl ZramPool {
@@ -324,14 +632,12 @@ impl ZramPool {
         let ram_total = crate::meminfo::get_ram_size()
             .map_err(|e| ZramError::ZramctlFailed(format!("Failed to get RAM size: {}", e)))?;
 
-        let mut pool_config = ZramPoolConfig::from_config(config);
+        let mut pool_config = ZramPoolConfig::from_config(config)?;
 
-        // Enforce minimum initial_size_percent
-        if pool_config.initial_size_percent < 50 {
-            pool_config.initial_size_percent = 50;
-        }
+        pool_config.initial_size = pool_config.initial_disksize(ram_total);
 
-        makedirs(format!("{}/zram", WORK_DIR))?;
+        makedirs(format!("{}/zram", work_dir()))?;
+        crate::tiers::check_priority(Tier::Zram, pool_config.priority, "zram_prio");
 
         Ok(Self {
             devices: Vec::new(),
@@ -340,6 +646,15 @@ impl ZramPool {
             last_expansion: None,
             last_contraction: None,
             low_util_since: None,
+            last_rebalance: None,
+            last_recreate: None,
+            last_writeback: None,
+            idle_marked: false,
+            waiter: PressureWaiter::new(config, "ZramPool"),
+            work_dir_watch: WorkDirWatch::new(),
+            free_ram: FreeRamTracker::new(),
+            exec_watch: ExecWatch::from_config(config),
+            prespawn: None,
         })
     }
 
@@ -349,7 +664,7 @@ impl ZramPool {
     pub fn start_primary(&mut self) -> Result<()> {
         crate::systemd::notify_status("Setting up ZramPool...");
 
-        let total_disksize = self.ram_total * self.config.initial_size_percent as u64 / 100;
+        let total_disksize = self.config.initial_size;
         if total_disksize == 0 {
             warn!("ZramPool: calculated disksize is 0, skipping");
             return Ok(());
@@ -365,6 +680,13 @@ impl ZramPool {
                 "ZramPool: adopted {} existing device(s), need {} total",
                 adopted, INITIAL_DEVICES
             );
+            let pending = self.rebalance_priorities();
+            if pending > 0 {
+                info!(
+                    "ZramPool: {} adopted device(s) keep a mismatched priority until RAM allows re-activation",
+                    pending
+                );
+            }
         }
 
         let remaining = (INITIAL_DEVICES as usize).saturating_sub(self.devices.len());
@@ -376,9 +698,7 @@ impl ZramPool {
                 self.config.algorithm,
                 self.config.max_devices
             );
-            for _ in 0..remaining {
-                self.create_device(per_device_size)?;
-            }
+            self.create_devices(remaining, per_device_size)?;
         }
 
         self.save_device_info()?;
@@ -423,16 +743,14 @@ impl ZramPool {
             }
 
             // Check if it's an active swap device via /proc/swaps
-            let Ok(swaps) = std::fs::read_to_string("/proc/swaps") else {
+            let Some((_, priority)) = read_swap_entry(&dev_path) else {
                 continue;
             };
-            if !swaps.contains(&dev_path) {
-                continue;
-            }
 
             // Find its systemd swap unit if one exists
             let expected_unit = dev_path.trim_start_matches('/').replace('/', "-") + ".swap";
             let unit_name = expected_unit;
+            let algorithm = current_algorithm(&sysfs_path).unwrap_or_default();
 
             let device = ZramDevice {
                 id,
@@ -442,12 +760,26 @@ impl ZramPool {
                 unit_name,
                 state: ZramDeviceState::Active,
                 drain_attempts: 0,
+                priority,
+                algorithm,
+                io_failures: 0,
             };
             info!(
-                "ZramPool: adopted existing zram{} (disksize={}MB)",
+                "ZramPool: adopted existing zram{} (disksize={}MB, priority={}, alg={})",
                 id,
-                disksize / (1024 * 1024)
+                disksize / (1024 * 1024),
+                priority,
+                device.algorithm
             );
+            if device.is_diverged(&self.config.algorithm) {
+                warn!(
+                    "ZramPool: zram{} uses {} but zram_alg is {} (set up by an earlier session or kernel){}",
+                    id,
+                    device.algorithm,
+                    self.config.algorithm,
+                    if self.config.recreate_diverged { ", will recreate it" } else { "" }
+                );
+            }
             self.devices.push(device);
             adopted += 1;
         }
@@ -459,23 +791,138 @@ impl ZramPool {
         if self.active_count() >= self.config.max_devices as usize {
             return Err(ZramError::PoolMaxDevices);
         }
+        let new_id = hot_add()?;
+        let unit_name = self.init_device(new_id, disksize, self.devices.len() + 1)?;
+        self.add_device(new_id, disksize, unit_name);
+        Ok(())
+    }
 
-        if !Path::new(ZRAM_HOT_ADD).exists() {
-            return Err(ZramError::ZramctlFailed(
-                "Kernel doesn't support hot_add".to_string(),
-            ));
+    /// Create `count` devices at once: hot_add them in turn, set them up in
+    /// parallel, then activate them with one daemon-reload and one systemctl
+    /// start. They join the pool in hot_add order. Devices that could not be
+    /// set up or started are reset and hot-removed; the first such error is
+    /// returned once the others are active.
+    fn create_devices(&mut self, count: usize, disksize: u64) -> Result<()> {
+        let room = (self.config.max_devices as usize).saturating_sub(self.active_count());
+        if room == 0 {
+            return Err(ZramError::PoolMaxDevices);
+        }
+        let mut first_error = None;
+        let mut ids = Vec::new();
+        for _ in 0..count.min(room) {
+            match hot_add() {
+                Ok(id) => ids.push(id),
+                Err(e) => {
+                    warn!("ZramPool: hot_add failed after {} device(s): {}", ids.len(), e);
+                    first_error = Some(e);
+                    break;
+                }
+            }
         }
 
-        let new_id: u32 = read_file(ZRAM_HOT_ADD)?
-            .trim()
-            .parse()
-            .map_err(|_| ZramError::ZramctlFailed("Invalid hot_add response".to_string()))?;
+        let pool_size = self.devices.len() + ids.len();
+        let pool = &*self;
+        let prepared = prepare_devices(&ids, self.backing_dev_free(), |id, backing| {
+            pool.prepare_device(id, disksize, pool_size, backing)
+        });
+
+        let mut ready = Vec::new();
+        for (id, result) in ids.into_iter().zip(prepared) {
+            match result {
+                Ok(unit) => ready.push((id, unit)),
+                Err(e) => {
+                    warn!("ZramPool: zram{} setup failed: {}", id, e);
+                    discard_device(id, None);
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+        if !ready.is_empty() {
+            let units: Vec<String> = ready.iter().map(|(_, unit)| unit.clone()).collect();
+            let started = systemctl(SystemctlAction::DaemonReload, "")
+                .and_then(|_| crate::systemd::start_units(&units));
+            if let Err(e) = started {
+                // Units that did come up are swap now and join the pool
+                let (active, failed): (Vec<_>, Vec<_>) = ready
+                    .into_iter()
+                    .partition(|(id, _)| read_swap_entry(&format!("/dev/zram{}", id)).is_some());
+                for (id, unit) in &failed {
+                    warn!("ZramPool: zram{} did not start: {}", id, e);
+                    discard_device(*id, Some(unit));
+                }
+                if !failed.is_empty() {
+                    let _ = systemctl(SystemctlAction::DaemonReload, "");
+                }
+                first_error.get_or_insert(e.into());
+                ready = active;
+            }
+        }
+        for (id, unit) in ready {
+            self.add_device(id, disksize, unit);
+        }
+        first_error.map_or(Ok(()), Err)
+    }
 
-        let sysfs_path = format!("/sys/block/zram{}", new_id);
-        let dev_path = format!("/dev/zram{}", new_id);
+    /// Register an activated device with the pool
+    fn add_device(&mut self, id: u32, disksize: u64, unit_name: String) {
+        info!(
+            "ZramPool: zram{} created (disksize={}MB) — pool now has {} device(s)",
+            id,
+            disksize / (1024 * 1024),
+            self.devices.len() + 1
+        );
+        self.devices.push(ZramDevice {
+            id,
+            disksize,
+            sysfs_path: format!("/sys/block/zram{}", id),
+            dev_path: format!("/dev/zram{}", id),
+            unit_name,
+            state: ZramDeviceState::Active,
+            drain_attempts: 0,
+            priority: self.config.priority,
+            algorithm: self.config.algorithm.clone(),
+            io_failures: 0,
+        });
+    }
+
+    /// Whether zram_backing_dev is set and backs none of the pool's devices
+    fn backing_dev_free(&self) -> bool {
+        self.config.backing_dev.as_ref().is_some_and(|bd| {
+            !self
+                .devices
+                .iter()
+                .any(|d| backing_dev(&d.sysfs_path).as_deref() == Some(bd.as_str()))
+        })
+    }
+
+    /// Set up a reset device (algorithm, disksize, mem_limit, mkswap) and
+    /// activate it through a swap unit. `pool_size` is the device count the
+    /// mem_limit is split across. Returns the unit name.
+    fn init_device(&self, id: u32, disksize: u64, pool_size: usize) -> Result<String> {
+        let backing = self
+            .config
+            .backing_dev
+            .as_ref()
+            .is_some_and(|bd| {
+                !self
+                    .devices
+                    .iter()
+                    .any(|d| d.id != id && backing_dev(&d.sysfs_path).as_deref() == Some(bd.as_str()))
+            });
+        let unit_name = self.prepare_device(id, disksize, pool_size, backing)?;
+        systemctl(SystemctlAction::DaemonReload, "")?;
+        systemctl(SystemctlAction::Start, &unit_name)?;
+        Ok(unit_name)
+    }
+
+    /// `init_device` up to the swap unit, without activating it; `backing`
+    /// gives the device zram_backing_dev
+    fn prepare_device(&self, id: u32, disksize: u64, pool_size: usize, backing: bool) -> Result<String> {
+        let sysfs_path = format!("/sys/block/zram{}", id);
+        let dev_path = format!("/dev/zram{}", id);
 
         // Set comp algorithm BEFORE disksize (kernel 6.1+ requires this order)
-        let ctx = format!("ZramPool: zram{}", new_id);
+        let ctx = format!("ZramPool: zram{}", id);
         configure_zram_algorithm(
             &sysfs_path,
             &self.config.algorithm,
@@ -486,80 +933,291 @@ impl ZramPool {
         if self.config.algorithm == "zstd" {
             let params_path = format!("{}/algorithm_params", sysfs_path);
             if Path::new(&params_path).exists() {
-                let _ = std::fs::write(&params_path, "level=3");
+                let _ = write_sysfs(&params_path, "level=3");
+            }
+        }
+
+        // backing_dev must also precede disksize. A block device backs a
+        // single zram device, so it goes to the first one that finds it free.
+        if let Some(bd) = self.config.backing_dev.as_ref().filter(|_| backing) {
+            match write_sysfs(format!("{}/backing_dev", sysfs_path), bd) {
+                Ok(_) => info!("ZramPool: zram{} backing_dev = {}", id, bd),
+                Err(e) => warn!("ZramPool: failed to set backing_dev {} for zram{}: {}", bd, id, e),
             }
         }
 
         // Set disksize
         let disksize_path = format!("{}/disksize", sysfs_path);
-        if let Err(e) = std::fs::write(&disksize_path, disksize.to_string()) {
-            error!("ZramPool: failed to set disksize for zram{}: {}", new_id, e);
-            let _ = std::fs::write(format!("{}/reset", sysfs_path), "1");
+        if let Err(e) = write_sysfs(&disksize_path, disksize.to_string()) {
+            error!("ZramPool: failed to set disksize for zram{}: {}", id, e);
+            let _ = write_sysfs(format!("{}/reset", sysfs_path), "1");
             return Err(ZramError::ZramctlFailed(
                 "Failed to set disksize".to_string(),
             ));
         }
 
         // Per-device mem_limit: caps physical RAM usage per device
-        if self.config.mem_limit_percent > 0 {
-            let total_limit = self.ram_total * self.config.mem_limit_percent as u64 / 100;
-            let device_count = (self.devices.len() as u64 + 1).max(4);
+        if self.config.mem_limit > 0 {
+            let total_limit = self.config.mem_limit;
+            let device_count = (pool_size as u64).max(4);
             let per_device_limit = total_limit / device_count;
             let mem_limit_path = format!("{}/mem_limit", sysfs_path);
             if Path::new(&mem_limit_path).exists() {
-                match std::fs::write(&mem_limit_path, per_device_limit.to_string()) {
+                match write_sysfs(&mem_limit_path, per_device_limit.to_string()) {
                     Ok(_) => info!(
                         "ZramPool: zram{} mem_limit = {}MB",
-                        new_id,
+                        id,
                         per_device_limit / (1024 * 1024)
                     ),
-                    Err(e) => warn!("ZramPool: failed to set mem_limit for zram{}: {}", new_id, e),
+                    Err(e) => warn!("ZramPool: failed to set mem_limit for zram{}: {}", id, e),
                 }
             }
         }
 
+        maybe_warm_up(self.config.warmup, &dev_path, disksize, &ctx);
+
         // mkswap
         let mkswap_status = Command::new("mkswap")
             .arg(&dev_path)
             .stdout(Stdio::null())
             .stderr(Stdio::null())
-            .status()?;
+            .status_timed()?;
 
         if !mkswap_status.success() {
-            let _ = std::fs::write(format!("{}/reset", sysfs_path), "1");
+            let _ = write_sysfs(format!("{}/reset", sysfs_path), "1");
             return Err(ZramError::ZramctlFailed("mkswap failed".to_string()));
         }
 
-        // Generate systemd swap unit and activate
-        let unit_name = gen_swap_unit(
+        // Generate the systemd swap unit
+        Ok(gen_swap_unit(
             Path::new(&dev_path),
             Some(self.config.priority),
             Some("discard"),
             "zram",
-        )?;
-
-        systemctl(SystemctlAction::DaemonReload, "")?;
-        systemctl(SystemctlAction::Start, &unit_name)?;
+        )?)
+    }
 
-        let device = ZramDevice {
-            id: new_id,
-            disksize,
-            sysfs_path,
-            dev_path,
-            unit_name,
-            state: ZramDeviceState::Active,
-            drain_attempts: 0,
+    /// Rolling recreate: move one active device whose algorithm differs from
+    /// zram_alg to the configured one. Its data goes back to RAM on swapoff,
+    /// so this waits until it fits comfortably; the other devices keep
+    /// serving swap meanwhile. Returns true if a device was recreated.
+    fn recreate_diverged(&mut self) -> bool {
+        self.last_recreate = Some(Instant::now());
+        let Some(idx) = self
+            .devices
+            .iter()
+            .position(|d| d.state == ZramDeviceState::Active && d.is_diverged(&self.config.algorithm))
+        else {
+            return false;
         };
+        let dev_id = self.devices[idx].id;
+        let dev_path = self.devices[idx].dev_path.clone();
+        let used = read_swap_entry(&dev_path).map(|(used, _)| used).unwrap_or(0);
+        let available = crate::meminfo::get_mem_stats(&["MemAvailable"])
+            .map(|s| s["MemAvailable"])
+            .unwrap_or(0);
+        if used > 0 && used.saturating_mul(2) > available {
+            info!(
+                "ZramPool: deferring recreate of zram{} ({}MB stored, {}MB available)",
+                dev_id,
+                used / (1024 * 1024),
+                available / (1024 * 1024)
+            );
+            return false;
+        }
 
         info!(
-            "ZramPool: zram{} created (disksize={}MB) — pool now has {} device(s)",
-            new_id,
-            disksize / (1024 * 1024),
-            self.devices.len() + 1
+            "ZramPool: recreating zram{} with {} (was {})",
+            dev_id, self.config.algorithm, self.devices[idx].algorithm
         );
+        if let Err(e) = swapoff(&dev_path) {
+            warn!("ZramPool: swapoff zram{} failed, keeping {}: {}", dev_id, self.devices[idx].algorithm, e);
+            return false;
+        }
+        let sysfs_path = self.devices[idx].sysfs_path.clone();
+        let _ = write_sysfs(format!("{}/reset", sysfs_path), "1");
+        let disksize = self.devices[idx].disksize;
+        match self.init_device(dev_id, disksize, self.devices.len()) {
+            Ok(unit_name) => {
+                let dev = &mut self.devices[idx];
+                dev.unit_name = unit_name;
+                dev.priority = self.config.priority;
+                dev.algorithm = self.config.algorithm.clone();
+            }
+            Err(e) => {
+                warn!("ZramPool: recreating zram{} failed, removing it from pool: {}", dev_id, e);
+                if Path::new(ZRAM_HOT_REMOVE).exists() {
+                    let _ = write_sysfs(ZRAM_HOT_REMOVE, dev_id.to_string());
+                }
+                self.devices.remove(idx);
+            }
+        }
+        let _ = self.save_device_info();
+        true
+    }
 
-        self.devices.push(device);
-        Ok(())
+    /// Write back huge pages, then idle ones, of every active device with a
+    /// backing device. The idle age is written to `idle` so only pages older
+    /// than it are marked; kernels without entry time tracking refuse that,
+    /// so all pages are marked instead and written back on the next pass if
+    /// still untouched by then. Returns the pages written back.
+    fn writeback_pass(&mut self, wb: WritebackConfig) -> u64 {
+        self.last_writeback = Some(Instant::now());
+        let page_size = crate::meminfo::get_page_size();
+        let mut written = 0;
+        let mut marked_all = false;
+
+        for dev in self.devices.iter().filter(|d| d.state == ZramDeviceState::Active) {
+            let sysfs = &dev.sysfs_path;
+            if backing_dev(sysfs).is_none() {
+                continue;
+            }
+            let writeback = format!("{}/writeback", sysfs);
+            let before = bd_writes(sysfs);
+
+            if huge_pages(sysfs) * page_size >= wb.min_huge.max(1) {
+                if let Err(e) = write_sysfs(&writeback, "huge") {
+                    warn!("ZramPool: huge page writeback on zram{} failed: {}", dev.id, e);
+                }
+            }
+
+            if wb.idle_age > 0 {
+                let idle = format!("{}/idle", sysfs);
+                let aged = write_sysfs(&idle, wb.idle_age.to_string()).is_ok();
+                if aged || self.idle_marked {
+                    if let Err(e) = write_sysfs(&writeback, "idle") {
+                        warn!("ZramPool: idle page writeback on zram{} failed: {}", dev.id, e);
+                    }
+                }
+                if !aged {
+                    marked_all |= write_sysfs(&idle, "all").is_ok();
+                }
+            }
+
+            let done = bd_writes(sysfs).saturating_sub(before);
+            if done > 0 {
+                info!(
+                    "ZramPool: zram{} wrote {}MB back to {}",
+                    dev.id,
+                    done * page_size / (1024 * 1024),
+                    backing_dev(sysfs).unwrap_or_default()
+                );
+            }
+            written += done;
+        }
+
+        self.idle_marked = marked_all;
+        written
+    }
+
+    /// Re-activate adopted devices whose swap priority differs from the
+    /// configured one, so the kernel round-robins across the whole pool again.
+    ///
+    /// Changing a priority needs swapoff + swapon, which pulls the device's data
+    /// back into RAM; devices whose data would not fit comfortably in free RAM
+    /// are left alone and retried from the monitor loop.
+    /// Returns the number of devices that still have a mismatched priority.
+    fn rebalance_priorities(&mut self) -> usize {
+        self.last_rebalance = Some(Instant::now());
+        let target = self.config.priority;
+        let mut pending = 0;
+        let mut changed = false;
+        let mut idx = 0;
+
+        while idx < self.devices.len() {
+            let dev = &self.devices[idx];
+            if dev.state != ZramDeviceState::Active || dev.priority == target {
+                idx += 1;
+                continue;
+            }
+
+            let dev_id = dev.id;
+            let dev_path = dev.dev_path.clone();
+            let used = read_swap_entry(&dev_path).map(|(used, _)| used).unwrap_or(0);
+            let available = crate::meminfo::get_mem_stats(&["MemAvailable"])
+                .map(|s| s["MemAvailable"])
+                .unwrap_or(0);
+            if used > 0 && used.saturating_mul(2) > available {
+                info!(
+                    "ZramPool: zram{} priority {} ≠ {}, deferring re-activation ({}MB stored, {}MB available)",
+                    dev_id,
+                    dev.priority,
+                    target,
+                    used / (1024 * 1024),
+                    available / (1024 * 1024)
+                );
+                pending += 1;
+                idx += 1;
+                continue;
+            }
+
+            info!(
+                "ZramPool: zram{} priority {} ≠ {}, re-activating",
+                dev_id, dev.priority, target
+            );
+
+            if let Err(e) = swapoff(&dev_path) {
+                warn!("ZramPool: swapoff zram{} failed, keeping old priority: {}", dev_id, e);
+                pending += 1;
+                idx += 1;
+                continue;
+            }
+
+            // Rewritten only now, so a failed swapoff leaves the unit matching
+            // the active device; if it cannot be, the old unit swaps on again
+            let (unit_name, priority) = match gen_swap_unit(
+                Path::new(&dev_path),
+                Some(target),
+                Some("discard"),
+                "zram",
+            ) {
+                Ok(name) => (name, target),
+                Err(e) => {
+                    warn!("ZramPool: cannot regenerate unit for zram{}, keeping old priority: {}", dev_id, e);
+                    (self.devices[idx].unit_name.clone(), self.devices[idx].priority)
+                }
+            };
+
+            let restarted = systemctl(SystemctlAction::DaemonReload, "")
+                .and_then(|_| systemctl(SystemctlAction::Start, &unit_name));
+            changed = true;
+            match restarted {
+                Ok(()) => {
+                    if priority != target {
+                        pending += 1;
+                    }
+                    let dev = &mut self.devices[idx];
+                    dev.unit_name = unit_name;
+                    dev.priority = priority;
+                    idx += 1;
+                }
+                Err(e) => {
+                    // Device is no longer swap; drop it so the pool can re-expand cleanly
+                    warn!(
+                        "ZramPool: swapon zram{} failed after swapoff, removing it from pool: {}",
+                        dev_id, e
+                    );
+                    let sysfs_path = self.devices[idx].sysfs_path.clone();
+                    let _ = write_sysfs(format!("{}/reset", sysfs_path), "1");
+                    if Path::new(ZRAM_HOT_REMOVE).exists() {
+                        let _ = write_sysfs(ZRAM_HOT_REMOVE, dev_id.to_string());
+                    }
+                    self.devices.remove(idx);
+                }
+            }
+        }
+
+        if changed {
+            let _ = self.save_device_info();
+        }
+        pending
+    }
+
+    /// Whether any active device still runs with a non-configured priority
+    fn has_priority_mismatch(&self) -> bool {
+        self.devices
+            .iter()
+            .any(|d| d.state == ZramDeviceState::Active && d.priority != self.config.priority)
     }
 
     /// Number of active (non-draining) devices
@@ -578,6 +1236,7 @@ impl ZramPool {
         let mut total_phys: u64 = 0;
         let mut total_same: u64 = 0;
         let mut total_compacted: u64 = 0;
+        let mut io = IoStat::default();
         let mut count: u8 = 0;
 
         for dev in &self.devices {
@@ -591,6 +1250,7 @@ impl ZramPool {
                 total_phys += stats.mem_used_total;
                 total_same += stats.same_pages;
                 total_compacted += stats.pages_compacted;
+                io.add(&stats.io);
                 count += 1;
             }
         }
@@ -628,17 +1288,54 @@ impl ZramPool {
             phys_usage_percent: phys_pct,
             total_same_pages: total_same,
             total_pages_compacted: total_compacted,
+            io,
         })
     }
 
+    /// Alert on I/O failures a device reported since the last check. Pages
+    /// live in RAM (or on the backing device after writeback), so a failed
+    /// read or write points at memory corruption, a driver race or a failing
+    /// backing device, never at ordinary pressure.
+    fn check_io_failures(&mut self) {
+        for dev in &mut self.devices {
+            let Some(io) = IoStat::read(&dev.sysfs_path) else {
+                continue;
+            };
+            let failures = io.failures();
+            if failures > dev.io_failures {
+                error!(
+                    "ZramPool: zram{} I/O failures (+{}): failed_reads={} failed_writes={} invalid_io={} - possible memory corruption or driver bug",
+                    dev.id,
+                    failures - dev.io_failures,
+                    io.failed_reads,
+                    io.failed_writes,
+                    io.invalid_io
+                );
+                let event = Event::new(
+                    EventKind::ZramIoFailure,
+                    vec![
+                        ("device".into(), format!("zram{}", dev.id)),
+                        ("failed_reads".into(), io.failed_reads.to_string()),
+                        ("failed_writes".into(), io.failed_writes.to_string()),
+                        ("invalid_io".into(), io.invalid_io.to_string()),
+                    ],
+                );
+                if let Err(e) = crate::events::record(&event) {
+                    warn!("ZramPool: cannot record {} event: {}", event.kind.as_str(), e);
+                }
+            }
+            dev.io_failures = failures;
+        }
+    }
+
     /// Calculate disksize for the next device
     fn calculate_next_disksize(&self, _stats: &ZramPoolStats) -> u64 {
         // Expansion devices use the same per-device size as initial ones
-        let total_disksize = self.ram_total * self.config.initial_size_percent as u64 / 100;
+        let total_disksize = self.config.initial_size;
         let min_size = self.ram_total * 5 / 100;
         (total_disksize / 4).max(min_size)
     }
-    fn should_expand(&self, stats: &ZramPoolStats) -> bool {
+    fn should_expand(&self, stats: &ZramPoolStats, free_ram: Option<FreeRam>) -> bool {
         // 1. Not at device limit
         if self.active_count() >= self.config.max_devices as usize {
             return false;
@@ -670,7 +1367,8 @@ impl ZramPool {
         // 5. Enough free RAM (adaptive: higher ratio = lower minimum needed)
         // When compression is good, expanding ZRAM is better than letting
         // pages spill to slow disk swap — ZRAM is ~100x faster than HDD.
-        if let Ok(free) = crate::meminfo::get_free_ram_percent() {
+        if let Some(free_ram) = free_ram {
+            let free = free_ram.adjusted;
             let adaptive_min = if stats.compression_ratio >= 10.0 {
                 2_u8 // Excellent: 2% free RAM is enough
             } else if stats.compression_ratio >= 5.0 {
@@ -684,8 +1382,8 @@ impl ZramPool {
             };
             if free < adaptive_min {
                 info!(
-                    "ZramPool: expansion skipped — free RAM {}% < min {}% (ratio {:.1}x)",
-                    free, adaptive_min, stats.compression_ratio
+                    "ZramPool: expansion skipped — free RAM {}% (MemAvailable {}%) < min {}% (ratio {:.1}x)",
+                    free, free_ram.raw, adaptive_min, stats.compression_ratio
                 );
                 return false;
             }
@@ -702,15 +1400,16 @@ impl ZramPool {
     }
 
     /// Expand the pool by adding a new ZRAM device
-    fn expand(&mut self, stats: &ZramPoolStats) -> Result<()> {
+    fn expand(&mut self, stats: &ZramPoolStats, free_ram: Option<FreeRam>) -> Result<()> {
         let disksize = self.calculate_next_disksize(stats);
 
         info!(
-            "ZramPool: expanding — adding device (disksize={}MB, pool_util={}%, ratio={:.2}x, phys={}%)",
+            "ZramPool: expanding — adding device (disksize={}MB, pool_util={}%, ratio={:.2}x, phys={}%{})",
             disksize / (1024 * 1024),
             stats.utilization_percent,
             stats.compression_ratio,
-            stats.phys_usage_percent
+            stats.phys_usage_percent,
+            free_ram.map_or(String::new(), |f| format!(", free RAM {}% (MemAvailable {}%)", f.adjusted, f.raw))
         );
 
         self.create_device(disksize)?;
@@ -720,6 +1419,81 @@ impl ZramPool {
         Ok(())
     }
 
+    /// Act on launch hints: add one device when a watched application
+    /// starts, and take it back if the pool's data never needs it
+    fn exec_watch_tick(&mut self, stats: &ZramPoolStats, free_ram: Option<FreeRam>) {
+        let Some(watch) = self.exec_watch.as_mut() else {
+            return;
+        };
+        let launched = watch.poll();
+        let grace = watch.grace;
+
+        if let Some(pre) = &self.prespawn {
+            // Demand arrived once the data would have expanded the pool anyway
+            if stats.total_orig_data * 100 >= pre.capacity_before * self.config.expand_threshold as u64 {
+                info!("ZramPool: {} demand arrived, keeping zram{} added ahead of it", pre.app, pre.id);
+                self.prespawn = None;
+            } else if pre.at.elapsed() >= grace {
+                let (id, app) = (pre.id, pre.app.clone());
+                self.prespawn = None;
+                let last_idx = self.devices.len().saturating_sub(1);
+                let is_last = self.devices.last().is_some_and(|d| d.id == id && d.state == ZramDeviceState::Active);
+                if is_last {
+                    info!(
+                        "ZramPool: no demand from {} within {}s, removing zram{} again",
+                        app,
+                        grace.as_secs(),
+                        id
+                    );
+                    self.devices[last_idx].state = ZramDeviceState::Draining;
+                    self.devices[last_idx].drain_attempts = 0;
+                    if let Err(e) = self.try_drain_device(last_idx) {
+                        warn!("ZramPool: removing zram{} failed: {}", id, e);
+                    }
+                }
+            }
+            return;
+        }
+
+        let Some(app) = launched.into_iter().next() else {
+            return;
+        };
+        let draining = self.devices.iter().any(|d| d.state == ZramDeviceState::Draining);
+        let cooling = self
+            .last_expansion
+            .is_some_and(|t| t.elapsed().as_secs() < self.config.expand_cooldown);
+        let low_ram = free_ram.is_some_and(|f| f.adjusted < self.config.min_free_ram_percent);
+        if self.active_count() >= self.config.max_devices as usize || draining || cooling || low_ram {
+            debug!("ZramPool: {} launched, no room to expand ahead of it", app);
+            return;
+        }
+
+        let disksize = self.calculate_next_disksize(stats);
+        info!(
+            "ZramPool: {} launched — adding device ahead of its demand (disksize={}MB, pool_util={}%)",
+            app,
+            disksize / (1024 * 1024),
+            stats.utilization_percent
+        );
+        match self.create_device(disksize) {
+            Ok(()) => {
+                self.last_expansion = Some(Instant::now());
+                if let Err(e) = self.save_device_info() {
+                    warn!("ZramPool: {}", e);
+                }
+                if let Some(dev) = self.devices.last() {
+                    self.prespawn = Some(Prespawn {
+                        id: dev.id,
+                        app,
+                        at: Instant::now(),
+                        capacity_before: stats.total_disksize,
+                    });
+                }
+            }
+            Err(e) => warn!("ZramPool: expansion ahead of {} failed: {}", app, e),
+        }
+    }
+
     /// Check if pool should contract (remove last device)
     fn should_contract(&self, stats: &ZramPoolStats) -> bool {
         // 1. Keep at least INITIAL_DEVICES (4) devices running at all times
@@ -771,13 +1545,22 @@ impl ZramPool {
         let dev_path = self.devices[idx].dev_path.clone();
         let dev_id = self.devices[idx].id;
 
-        let succeeded = Command::new("swapoff")
+        // A swapoff killed at its timeout leaves the device active (the kernel
+        // re-enables it on the interrupted swapoff); it counts as one attempt
+        let succeeded = match Command::new("swapoff")
             .arg(&dev_path)
             .stdout(Stdio::null())
             .stderr(Stdio::null())
-            .status()
-            .map(|s| s.success())
-            .unwrap_or(false);
+            .status_timed()
+        {
+            Ok(status) => status.success(),
+            Err(e) => {
+                if crate::exec::is_timeout(&e) {
+                    info!("ZramPool: swapoff zram{} timed out, will retry", dev_id);
+                }
+                false
+            }
+        };
 
         if !succeeded {
             self.devices[idx].drain_attempts += 1;
@@ -788,12 +1571,12 @@ impl ZramPool {
         let unit_name = self.devices[idx].unit_name.clone();
 
         let _ = systemctl(SystemctlAction::Stop, &unit_name);
-        let _ = std::fs::write(format!("{}/reset", sysfs_path), "1");
+        let _ = write_sysfs(format!("{}/reset", sysfs_path), "1");
         if Path::new(ZRAM_HOT_REMOVE).exists() {
-            let _ = std::fs::write(ZRAM_HOT_REMOVE, dev_id.to_string());
+            let _ = write_sysfs(ZRAM_HOT_REMOVE, dev_id.to_string());
         }
         let unit_path = format!("/run/systemd/system/{}", unit_name);
-        let _ = std::fs::remove_file(unit_path);
+        force_remove(unit_path, false);
         let _ = systemctl(SystemctlAction::DaemonReload, "");
 
         self.devices.remove(idx);
@@ -869,15 +1652,18 @@ impl ZramPool {
             .collect();
 
         let info = active.join("\n---\n");
-        std::fs::write(format!("{}/zram/device", WORK_DIR), &info)?;
 
-        // Also save pool metadata
+        // Also save pool metadata, in the same write
         let meta = format!(
-            "devices={}\nmax_devices={}",
+            "devices={}\nmax_devices={}\nalgorithm={}",
             self.active_count(),
-            self.config.max_devices
+            self.config.max_devices,
+            self.config.algorithm
         );
-        std::fs::write(format!("{}/zram/pool_meta", WORK_DIR), &meta)?;
+        write_shared_all(&[
+            (format!("{}/zram/device", work_dir()), info.as_str()),
+            (format!("{}/zram/pool_meta", work_dir()), meta.as_str()),
+        ])?;
 
         Ok(())
     }
@@ -893,18 +1679,39 @@ impl ZramPool {
 
         let check_interval = self.config.check_interval;
         let mut log_counter: u64 = 0;
+        let mut profiler = TickProfiler::new("zram", "ZramPool");
 
         loop {
-            thread::sleep(Duration::from_secs(check_interval));
+            self.waiter.wait(Duration::from_secs(check_interval));
 
             if crate::is_shutdown() {
                 break;
             }
+            let mut tick = profiler.begin(Duration::from_secs(check_interval));
+
+            // Re-publish the device list if WORK_DIR was cleared
+            if self.work_dir_watch.recreated() {
+                let restored = makedirs(format!("{}/zram", work_dir()))
+                    .map_err(ZramError::from)
+                    .and_then(|_| self.save_device_info());
+                match restored {
+                    Ok(()) => info!("ZramPool: working directory restored ({} device(s))", self.active_count()),
+                    Err(e) => warn!("ZramPool: cannot restore working directory: {}", e),
+                }
+            }
 
             let stats = match self.get_pool_stats() {
                 Some(s) => s,
                 None => continue,
             };
+            if stats.io.failures() > 0 {
+                self.check_io_failures();
+            }
+            tick.mark(Phase::Decision);
+            if tick.overrun() {
+                warn!("ZramPool: reading pool stats outlasted the check interval - skipping decisions on stale stats");
+                continue;
+            }
 
             // Periodic log (every ~30s)
             log_counter += 1;
@@ -931,12 +1738,51 @@ impl ZramPool {
             }
 
             // Expansion decision
-            if self.should_expand(&stats) {
-                if let Err(e) = self.expand(&stats) {
+            let free_ram = self.free_ram.sample().ok();
+            if self.should_expand(&stats, free_ram) {
+                tick.mark(Phase::Action);
+                if let Err(e) = self.expand(&stats, free_ram) {
                     warn!("ZramPool: expansion failed: {}", e);
                 }
             }
 
+            // Launch hints from zram_exec_watch
+            self.exec_watch_tick(&stats, free_ram);
+
+            // Retry priority fixes for adopted devices that were deferred
+            let rebalance_due = self
+                .last_rebalance
+                .map(|t| t.elapsed().as_secs() >= PRIORITY_REBALANCE_INTERVAL)
+                .unwrap_or(true);
+            if rebalance_due && self.has_priority_mismatch() {
+                self.rebalance_priorities();
+            }
+
+            // Converge devices left on an older algorithm, one per interval
+            let recreate_due = self
+                .last_recreate
+                .map(|t| t.elapsed().as_secs() >= RECREATE_INTERVAL)
+                .unwrap_or(true);
+            if self.config.recreate_diverged
+                && recreate_due
+                && !self.devices.iter().any(|d| d.state == ZramDeviceState::Draining)
+            {
+                tick.mark(Phase::Action);
+                self.recreate_diverged();
+            }
+
+            // Push incompressible and cold pages out to the backing device
+            if let Some(wb) = self.config.writeback {
+                let writeback_due = self
+                    .last_writeback
+                    .map(|t| t.elapsed().as_secs() >= wb.interval)
+                    .unwrap_or(true);
+                if writeback_due {
+                    tick.mark(Phase::Action);
+                    self.writeback_pass(wb);
+                }
+            }
+
             // Resume pending drain
             if let Err(e) = self.retry_draining() {
                 warn!("ZramPool: drain retry failed: {}", e);
@@ -944,6 +1790,7 @@ impl ZramPool {
 
             // Contraction decision
             if self.should_contract(&stats) {
+                tick.mark(Phase::Action);
                 if let Err(e) = self.contract() {
                     warn!("ZramPool: contraction failed: {}", e);
                 }
@@ -969,6 +1816,58 @@ pub struct ZramStats {
     pub disksize: u64,
     pub same_pages: u64,
     pub pages_compacted: u64,
+    /// Pages stored uncompressed (incompressible)
+    pub huge_pages: u64,
+    /// Pages currently on the backing device
+    pub bd_count: u64,
+    /// Pages written to the backing device since setup
+    pub bd_writes: u64,
+    pub io: IoStat,
+}
+
+/// Failure counters of a device, from io_stat
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct IoStat {
+    /// Reads that returned an error
+    pub failed_reads: u64,
+    /// Writes that returned an error
+    pub failed_writes: u64,
+    /// Requests not aligned to the zram page size
+    pub invalid_io: u64,
+    /// Slots freed by swap free notifications (not a failure)
+    pub notify_free: u64,
+}
+
+impl IoStat {
+    /// Parse io_stat: failed_reads failed_writes invalid_io notify_free
+    fn parse(content: &str) -> Option<Self> {
+        let fields: Vec<u64> = content.split_whitespace().filter_map(|s| s.parse().ok()).collect();
+        if fields.len() < 3 {
+            return None;
+        }
+        Some(Self {
+            failed_reads: fields[0],
+            failed_writes: fields[1],
+            invalid_io: fields[2],
+            notify_free: fields.get(3).copied().unwrap_or(0),
+        })
+    }
+
+    fn read(sysfs: &str) -> Option<Self> {
+        Self::parse(&std::fs::read_to_string(format!("{}/io_stat", sysfs)).ok()?)
+    }
+
+    /// Requests that failed; zero on a healthy device
+    pub fn failures(&self) -> u64 {
+        self.failed_reads + self.failed_writes + self.invalid_io
+    }
+
+    fn add(&mut self, other: &Self) {
+        self.failed_reads += other.failed_reads;
+        self.failed_writes += other.failed_writes;
+        self.invalid_io += other.invalid_io;
+        self.notify_free += other.notify_free;
+    }
 }
 
 impl ZramStats {
@@ -991,7 +1890,7 @@ impl ZramStats {
 
 /// Get aggregated zram stats from saved device info (for status command)
 pub fn get_zram_stats() -> Option<ZramStats> {
-    let device_info = format!("{}/zram/device", WORK_DIR);
+    let device_info = format!("{}/zram/device", work_dir());
     if !Path::new(&device_info).exists() {
         return None;
     }
@@ -1007,6 +1906,10 @@ pub fn get_zram_stats() -> Option<ZramStats> {
     let mut mem_limit: u64 = 0;
     let mut total_same: u64 = 0;
     let mut total_compacted: u64 = 0;
+    let mut total_huge: u64 = 0;
+    let mut total_bd_count: u64 = 0;
+    let mut total_bd_writes: u64 = 0;
+    let mut total_io = IoStat::default();
     let mut found = false;
 
     for section in &sections {
@@ -1030,6 +1933,10 @@ pub fn get_zram_stats() -> Option<ZramStats> {
             mem_limit = stats.mem_limit; // Use last device's limit
             total_same += stats.same_pages;
             total_compacted += stats.pages_compacted;
+            total_huge += stats.huge_pages;
+            total_bd_count += stats.bd_count;
+            total_bd_writes += stats.bd_writes;
+            total_io.add(&stats.io);
             found = true;
         }
     }
@@ -1046,9 +1953,36 @@ pub fn get_zram_stats() -> Option<ZramStats> {
         disksize: total_disksize,
         same_pages: total_same,
         pages_compacted: total_compacted,
+        huge_pages: total_huge,
+        bd_count: total_bd_count,
+        bd_writes: total_bd_writes,
+        io: total_io,
     })
 }
 
+/// Pool devices whose comp_algorithm differs from the pool's configured one,
+/// as (device, algorithm), from saved device info (for status command)
+pub fn algorithm_divergence() -> Vec<(String, String)> {
+    let Some(expected) = pool_algorithm() else {
+        return Vec::new();
+    };
+    let info = std::fs::read_to_string(format!("{}/zram/device", work_dir())).unwrap_or_default();
+    info.split("---")
+        .filter_map(|section| {
+            let mut lines = section.trim().lines();
+            let dev = lines.next()?.trim();
+            let algorithm = current_algorithm(lines.next()?.trim())?;
+            (algorithm != expected).then(|| (dev.to_string(), algorithm))
+        })
+        .collect()
+}
+
+/// Algorithm new pool devices are set up with, from saved pool metadata
+pub fn pool_algorithm() -> Option<String> {
+    let meta = std::fs::read_to_string(format!("{}/zram/pool_meta", work_dir())).ok()?;
+    meta.lines().find_map(|l| l.strip_prefix("algorithm=")).map(str::to_string)
+}
+
 /// Read stats for a specific ZRAM device by sysfs path
 fn get_device_stats(sysfs_path: &str, disksize: u64) -> Option<ZramStats> {
     let mm_stat_path = format!("{}/mm_stat", sysfs_path);
@@ -1061,6 +1995,12 @@ fn get_device_stats(sysfs_path: &str, disksize: u64) -> Option<ZramStats> {
     if fields.len() < 5 {
         return None;
     }
+    // bd_stat: bd_count bd_reads bd_writes, present with CONFIG_ZRAM_WRITEBACK
+    let bd_stat: Vec<u64> = std::fs::read_to_string(format!("{}/bd_stat", sysfs_path))
+        .unwrap_or_default()
+        .split_whitespace()
+        .filter_map(|s| s.parse().ok())
+        .collect();
 
     Some(ZramStats {
         orig_data_size: fields[0],
@@ -1070,5 +2010,100 @@ fn get_device_stats(sysfs_path: &str, disksize: u64) -> Option<ZramStats> {
         disksize,
         same_pages: fields.get(5).copied().unwrap_or(0),
         pages_compacted: fields.get(6).copied().unwrap_or(0),
+        huge_pages: fields.get(7).copied().unwrap_or(0),
+        bd_count: bd_stat.first().copied().unwrap_or(0),
+        bd_writes: bd_stat.get(2).copied().unwrap_or(0),
+        io: IoStat::read(sysfs_path).unwrap_or_default(),
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn config(pairs: &[(&str, &str)]) -> Config {
+        Config::from_values(pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect::<HashMap<_, _>>())
+    }
+
+    #[test]
+    fn ratio_mode_sizes_from_mem_limit() {
+        let ratio = config(&[
+            ("zram_size_mode", "ratio"),
+            ("zram_mem_limit", "2G"),
+            ("zram_expected_ratio", "2.5"),
+        ]);
+        assert_eq!(ratio_disksize(&ratio).unwrap(), Some(5 * 1024 * 1024 * 1024));
+        let pool = ZramPoolConfig::from_config(&ratio).unwrap();
+        assert!(pool.sized_by_ratio);
+        assert_eq!(pool.initial_disksize(16 << 30), 5 << 30, "no 50% floor");
+
+        // zram_size below half of RAM starts at half
+        let small = ZramPoolConfig::from_config(&config(&[("zram_size", "1G")])).unwrap();
+        assert_eq!(small.initial_disksize(8 << 30), 4 << 30);
+        assert_eq!(small.initial_disksize(1 << 30), 1 << 30);
+
+        // Without a mem_limit the ratio has nothing to scale
+        let no_limit = config(&[("zram_size_mode", "ratio")]);
+        assert_eq!(ratio_disksize(&no_limit).unwrap(), None);
+        assert_eq!(ratio_disksize(&config(&[("zram_mem_limit", "2G")])).unwrap(), None);
+    }
+
+    #[test]
+    fn backing_dev_goes_to_the_first_device_that_prepares() {
+        let given = std::sync::Mutex::new(Vec::new());
+        let prepare = |id: u32, backing: bool| {
+            given.lock().unwrap().push((id, backing));
+            if id == 3 {
+                Err(ZramError::ZramctlFailed("Failed to set disksize".to_string()))
+            } else {
+                Ok(id)
+            }
+        };
+        let results = prepare_devices(&[3, 4, 5], true, prepare);
+        assert!(results[0].is_err());
+        assert_eq!(results[1..].iter().map(|r| *r.as_ref().unwrap()).collect::<Vec<_>>(), vec![4, 5]);
+        let mut given = given.into_inner().unwrap();
+        given.sort();
+        assert_eq!(given, vec![(3, true), (4, true), (5, false)]);
+
+        // Without a free backing dev no device is offered one
+        let results = prepare_devices(&[3, 4], false, |id, backing| {
+            if backing {
+                Err(ZramError::PoolMaxDevices)
+            } else {
+                Ok(id)
+            }
+        });
+        assert!(results.iter().all(|r| r.is_ok()));
+    }
+
+    #[test]
+    fn io_stat_counts_failures_but_not_frees() {
+        let io = IoStat::parse("       2        0        1      5123\n").unwrap();
+        assert_eq!(io.failures(), 3);
+        assert_eq!(io.notify_free, 5123);
+        let mut total = IoStat::default();
+        total.add(&io);
+        total.add(&IoStat::parse("0 4 0").unwrap());
+        assert_eq!((total.failed_writes, total.failures()), (4, 7));
+        assert!(IoStat::parse("").is_none());
+    }
+
+    #[test]
+    fn writeback_needs_a_backing_dev() {
+        assert!(WritebackConfig::from_config(&config(&[("zram_huge_idle_writeback", "1")])).unwrap().is_none());
+        assert!(WritebackConfig::from_config(&config(&[("zram_backing_dev", "/dev/sda3")])).unwrap().is_none());
+
+        let wb = WritebackConfig::from_config(&config(&[
+            ("zram_huge_idle_writeback", "1"),
+            ("zram_backing_dev", "/dev/sda3"),
+            ("zram_writeback_interval", "5"),
+        ]))
+        .unwrap()
+        .unwrap();
+        assert_eq!(wb.interval, 60);
+        assert_eq!(wb.idle_age, defaults::ZRAM_WRITEBACK_IDLE_AGE);
+        assert_eq!(wb.min_huge, 16 * 1024 * 1024);
+    }
+}
@@ -7,14 +7,20 @@ use std::path::Path;
 
 use thiserror::Error;
 
-use crate::config::{Config, WORK_DIR};
+use crate::config::Config;
+use crate::context::work_dir;
 use crate::defaults;
-use crate::helpers::{makedirs, read_file, write_file};
+use crate::helpers::{makedirs, read_file, remove_file, write_file};
 use crate::{error, info, warn};
 
 const ZSWAP_MODULE: &str = "/sys/module/zswap";
 const ZSWAP_PARAMS: &str = "/sys/module/zswap/parameters";
 
+/// Fragment carrying the configured parameters across reboots (zswap_persist)
+pub const MODPROBE_FRAGMENT: &str = "/etc/modprobe.d/systemd-swap-zswap.conf";
+/// First line of the fragment; a file without it is not ours to replace
+const FRAGMENT_HEADER: &str = "# Written by systemd-swap (zswap_persist=1), do not edit.";
+
 #[derive(Error, Debug)]
 pub enum ZswapError {
     #[error("IO error: {0}")]
@@ -33,6 +39,87 @@ pub struct ZswapBackup {
     pub parameters: HashMap<String, String>,
 }
 
+impl ZswapBackup {
+    /// Save the backup under WORK_DIR so `stop` can restore it later
+    pub fn save(&self) -> Result<()> {
+        let backup_path = format!("{}/zswap_backup", work_dir());
+        makedirs(&backup_path)?;
+        for (path, value) in &self.parameters {
+            let filename = Path::new(path).file_name().unwrap_or_default();
+            let save_path = format!("{}/{}", backup_path, filename.to_string_lossy());
+            write_file(&save_path, &format!("{}={}", path, value))?;
+        }
+        Ok(())
+    }
+}
+
+fn baseline_file() -> String {
+    format!("{}/zswap_baseline", work_dir())
+}
+
+/// Cumulative zswap event counters from debugfs (since boot or module load)
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ZswapCounters {
+    pub written_back_pages: u64,
+    pub reject_compress_fail: u64,
+    pub reject_compress_poor: u64,
+    pub reject_reclaim_fail: u64,
+    pub pool_limit_hit: u64,
+}
+
+impl ZswapCounters {
+    /// All rejected stores
+    pub fn rejects(&self) -> u64 {
+        self.reject_compress_fail + self.reject_compress_poor + self.reject_reclaim_fail
+    }
+
+    /// Events since `baseline`. A counter below its baseline means debugfs was
+    /// reset (zswap reloaded), so everything it counts is new.
+    pub fn since(&self, baseline: &Self) -> Self {
+        let delta = |now: u64, then: u64| if now >= then { now - then } else { now };
+        Self {
+            written_back_pages: delta(self.written_back_pages, baseline.written_back_pages),
+            reject_compress_fail: delta(self.reject_compress_fail, baseline.reject_compress_fail),
+            reject_compress_poor: delta(self.reject_compress_poor, baseline.reject_compress_poor),
+            reject_reclaim_fail: delta(self.reject_reclaim_fail, baseline.reject_reclaim_fail),
+            pool_limit_hit: delta(self.pool_limit_hit, baseline.pool_limit_hit),
+        }
+    }
+
+    fn to_file(self) -> String {
+        format!(
+            "written_back_pages={}\nreject_compress_fail={}\nreject_compress_poor={}\n\
+             reject_reclaim_fail={}\npool_limit_hit={}\n",
+            self.written_back_pages,
+            self.reject_compress_fail,
+            self.reject_compress_poor,
+            self.reject_reclaim_fail,
+            self.pool_limit_hit
+        )
+    }
+
+    fn from_file(content: &str) -> Self {
+        let mut counters = Self::default();
+        for (key, value) in content.lines().filter_map(|l| l.split_once('=')) {
+            let value = value.trim().parse().unwrap_or(0);
+            match key.trim() {
+                "written_back_pages" => counters.written_back_pages = value,
+                "reject_compress_fail" => counters.reject_compress_fail = value,
+                "reject_compress_poor" => counters.reject_compress_poor = value,
+                "reject_reclaim_fail" => counters.reject_reclaim_fail = value,
+                "pool_limit_hit" => counters.pool_limit_hit = value,
+                _ => {}
+            }
+        }
+        counters
+    }
+
+    /// Counters recorded when the daemon configured zswap
+    pub fn load_baseline() -> Option<Self> {
+        read_file(baseline_file()).ok().map(|c| Self::from_file(&c))
+    }
+}
+
 /// Check if zswap is available (module loaded)
 pub fn is_available() -> bool {
     Path::new(ZSWAP_MODULE).is_dir()
@@ -60,6 +147,70 @@ fn set_enabled(enable: bool) -> Result<()> {
     Ok(())
 }
 
+/// Disable zswap when using zram
+/// According to kernel documentation, zswap and zram should not be used together
+/// as both perform compression in RAM and can cause:
+/// - Double compression (waste of CPU)
+/// - LRU inversion issues
+/// - Unpredictable memory pressure behavior
+pub fn disable_for_zram() {
+    if is_available() && is_enabled() {
+        info!("Disabling zswap (recommended when using zram)");
+        if let Err(e) = set_enabled(false) {
+            warn!("Failed to disable zswap: {}", e);
+        } else {
+            info!("Zswap disabled successfully");
+        }
+    }
+}
+
+/// Zswap parameters as configured, with the daemon's defaults filled in
+#[derive(Debug, Clone, PartialEq)]
+pub struct ZswapParams {
+    pub enabled: bool,
+    pub compressor: String,
+    pub zpool: String,
+    pub max_pool_percent: u32,
+    pub shrinker_enabled: String,
+    pub accept_threshold: String,
+}
+
+impl ZswapParams {
+    pub fn from_config(config: &Config) -> Self {
+        let enabled = config.get("zswap_enabled").unwrap_or("1").to_lowercase();
+        Self {
+            enabled: matches!(enabled.as_str(), "1" | "y" | "yes"),
+            compressor: config.get("zswap_compressor").unwrap_or(defaults::ZSWAP_COMPRESSOR).to_string(),
+            zpool: config.get("zswap_zpool").unwrap_or(defaults::ZSWAP_ZPOOL).to_string(),
+            // Use config value if set, otherwise fall back to the well-tested default.
+            max_pool_percent: config
+                .get_opt("zswap_max_pool_percent")
+                .and_then(|v| v.parse::<u32>().ok())
+                .unwrap_or(defaults::ZSWAP_MAX_POOL_PERCENT),
+            shrinker_enabled: config
+                .get("zswap_shrinker_enabled")
+                .unwrap_or(defaults::ZSWAP_SHRINKER_ENABLED)
+                .to_string(),
+            accept_threshold: config
+                .get("zswap_accept_threshold")
+                .unwrap_or(defaults::ZSWAP_ACCEPT_THRESHOLD)
+                .to_string(),
+        }
+    }
+
+    /// As module parameters (name, value), `enabled` first
+    pub fn module_params(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("enabled", if self.enabled { "1" } else { "0" }.to_string()),
+            ("compressor", self.compressor.clone()),
+            ("zpool", self.zpool.clone()),
+            ("max_pool_percent", self.max_pool_percent.to_string()),
+            ("shrinker_enabled", self.shrinker_enabled.clone()),
+            ("accept_threshold_percent", self.accept_threshold.clone()),
+        ]
+    }
+}
+
 /// Start and configure zswap
 pub fn start(config: &Config) -> Result<ZswapBackup> {
     crate::systemd::notify_status("Setting up Zswap...");
@@ -69,7 +220,7 @@ pub fn start(config: &Config) -> Result<ZswapBackup> {
     }
 
     info!("Zswap: backup current configuration: start");
-    makedirs(format!("{}/zswap", WORK_DIR))?;
+    makedirs(format!("{}/zswap", work_dir()))?;
 
     // Backup current parameters
     let mut backup = HashMap::new();
@@ -85,18 +236,14 @@ pub fn start(config: &Config) -> Result<ZswapBackup> {
     }
     info!("Zswap: backup current configuration: complete");
 
-    // Get config values with adaptive defaults
-    let enabled = config.get("zswap_enabled").unwrap_or("1");
-    let compressor = config.get("zswap_compressor").unwrap_or(defaults::ZSWAP_COMPRESSOR);
-    let zpool = config.get("zswap_zpool").unwrap_or(defaults::ZSWAP_ZPOOL);
-    let shrinker_enabled = config.get("zswap_shrinker_enabled").unwrap_or(defaults::ZSWAP_SHRINKER_ENABLED);
-    let accept_threshold = config.get("zswap_accept_threshold").unwrap_or(defaults::ZSWAP_ACCEPT_THRESHOLD);
-
-    // Use config value if set, otherwise fall back to the well-tested default.
-    let max_pool_percent = config
-        .get_opt("zswap_max_pool_percent")
-        .and_then(|v| v.parse::<u32>().ok())
-        .unwrap_or(defaults::ZSWAP_MAX_POOL_PERCENT);
+    let ZswapParams {
+        enabled,
+        compressor,
+        zpool,
+        max_pool_percent,
+        shrinker_enabled,
+        accept_threshold,
+    } = ZswapParams::from_config(config);
     let max_pool_str = max_pool_percent.to_string();
 
     info!(
@@ -118,11 +265,11 @@ pub fn start(config: &Config) -> Result<ZswapBackup> {
 
     // Write parameters (except enabled) - order matters for some kernels
     let params = [
-        ("compressor", compressor),
-        ("zpool", zpool),
+        ("compressor", compressor.as_str()),
+        ("zpool", zpool.as_str()),
         ("max_pool_percent", &max_pool_str),
-        ("shrinker_enabled", shrinker_enabled),
-        ("accept_threshold_percent", accept_threshold),
+        ("shrinker_enabled", shrinker_enabled.as_str()),
+        ("accept_threshold_percent", accept_threshold.as_str()),
     ];
 
     for (name, value) in params {
@@ -144,9 +291,7 @@ pub fn start(config: &Config) -> Result<ZswapBackup> {
     }
 
     // Now enable zswap if requested
-    let should_enable =
-        enabled == "1" || enabled.to_lowercase() == "y" || enabled.to_lowercase() == "yes";
-    if should_enable {
+    if enabled {
         if let Err(e) = set_enabled(true) {
             error!("Failed to enable zswap: {}", e);
         }
@@ -156,9 +301,136 @@ pub fn start(config: &Config) -> Result<ZswapBackup> {
 
     info!("Zswap: set new parameters: complete");
 
+    // Baseline so status can tell events since daemon start from older ones
+    if let Some(counters) = get_status().and_then(|s| s.counters()) {
+        if let Err(e) = fs::write(baseline_file(), counters.to_file()) {
+            warn!("Zswap: failed to save counter baseline: {}", e);
+        }
+    }
+
     Ok(ZswapBackup { parameters: backup })
 }
 
+// ── Persistence across reboots ──────────────────────────────────────────────
+//
+// On kernels built with CONFIG_ZSWAP_DEFAULT_ON=n zswap starts disabled, and
+// whatever the daemon set is gone after a reboot until it runs again. With
+// zswap_persist=1 the configured parameters are kept in MODPROBE_FRAGMENT,
+// rewritten when the configuration changes and removed once the mode no
+// longer uses zswap. A zswap loaded as a module reads it; most distributions
+// build zswap in, and then only the kernel command line reaches it at boot,
+// so the fragment also carries the matching zswap.* parameters for it.
+
+/// Whether zswap is built into the kernel: only loadable modules have an
+/// initstate
+pub fn is_builtin() -> bool {
+    is_available() && !Path::new(ZSWAP_MODULE).join("initstate").exists()
+}
+
+/// Whether the kernel turns zswap on at boot: zswap.enabled= on the command
+/// line, else CONFIG_ZSWAP_DEFAULT_ON of the running kernel. None if neither
+/// can be read.
+pub fn enabled_at_boot() -> Option<bool> {
+    let cmdline = fs::read_to_string("/proc/cmdline").unwrap_or_default();
+    if let Some(value) = cmdline.split_whitespace().rev().find_map(|arg| arg.strip_prefix("zswap.enabled=")) {
+        return Some(matches!(value.to_lowercase().as_str(), "1" | "y" | "yes" | "true" | "on"));
+    }
+    let release = fs::read_to_string("/proc/sys/kernel/osrelease").ok()?;
+    let kconfig = fs::read_to_string(format!("/boot/config-{}", release.trim())).ok()?;
+    kconfig.lines().find_map(|line| match line {
+        "CONFIG_ZSWAP_DEFAULT_ON=y" => Some(true),
+        "# CONFIG_ZSWAP_DEFAULT_ON is not set" => Some(false),
+        _ => None,
+    })
+}
+
+/// The same parameters as kernel command line arguments
+pub fn kernel_cmdline(params: &[(&str, String)]) -> String {
+    params
+        .iter()
+        .map(|(name, value)| format!("zswap.{}={}", name, value))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Content of MODPROBE_FRAGMENT for `params`
+pub fn render_fragment(params: &[(&str, String)]) -> String {
+    let options: Vec<String> = params.iter().map(|(name, value)| format!("{}={}", name, value)).collect();
+    format!(
+        "{}\n\
+         # Removed again when swap_mode no longer uses zswap or zswap_persist is unset.\n\
+         # With zswap built into the kernel this file is not read; use the kernel\n\
+         # command line instead:\n\
+         #   {}\n\
+         options zswap {}\n",
+        FRAGMENT_HEADER,
+        kernel_cmdline(params),
+        options.join(" ")
+    )
+}
+
+fn remove_fragment(path: &Path) {
+    match fs::read_to_string(path) {
+        Ok(content) if content.starts_with(FRAGMENT_HEADER) => match remove_file(path) {
+            Ok(()) => info!("Zswap: removed {}", path.display()),
+            Err(e) => warn!("Zswap: cannot remove {}: {}", path.display(), e),
+        },
+        _ => {}
+    }
+}
+
+/// Bring MODPROBE_FRAGMENT in line with the configuration at startup:
+/// written (or rewritten if the parameters changed) while zswap_persist is on
+/// and the mode uses zswap, removed otherwise
+pub fn sync_persistence(config: &Config, in_use: bool) {
+    let path = Path::new(MODPROBE_FRAGMENT);
+    let persist = match config.get_opt("zswap_persist") {
+        Some(_) => config.get_bool("zswap_persist"),
+        None => defaults::ZSWAP_PERSIST,
+    };
+    if !in_use || !persist {
+        remove_fragment(path);
+        return;
+    }
+
+    // Parameters this kernel does not have would only be rejected at boot
+    let mut params = ZswapParams::from_config(config).module_params();
+    if is_available() {
+        params.retain(|(name, _)| Path::new(ZSWAP_PARAMS).join(name).exists());
+    }
+    let content = render_fragment(&params);
+
+    match fs::read_to_string(path) {
+        Ok(existing) if existing == content => {}
+        Ok(existing) if !existing.starts_with(FRAGMENT_HEADER) => {
+            warn!("Zswap: {} exists and was not written by systemd-swap, leaving it", path.display());
+            return;
+        }
+        _ => {
+            let written = path
+                .parent()
+                .map_or(Ok(()), makedirs)
+                .and_then(|()| write_file(path, &content));
+            match written {
+                Ok(()) => info!("Zswap: parameters persisted in {}", path.display()),
+                Err(e) => {
+                    warn!("Zswap: cannot write {}: {}", path.display(), e);
+                    return;
+                }
+            }
+        }
+    }
+
+    if is_builtin() && enabled_at_boot() != Some(true) {
+        info!(
+            "Zswap: built into this kernel and off at boot, {} is not read; to have it on before the \
+             daemon starts add to the kernel command line: {}",
+            path.display(),
+            kernel_cmdline(&params)
+        );
+    }
+}
+
 /// Get zswap status information
 pub fn get_status() -> Option<ZswapStatus> {
     if !is_available() {
@@ -191,7 +463,8 @@ pub fn get_status() -> Option<ZswapStatus> {
     }
 
     // Read debug stats (requires root)
-    if debug_dir.is_dir() {
+    status.debugfs = debug_dir.is_dir();
+    if status.debugfs {
         let read_stat = |name: &str| -> u64 {
             read_file(debug_dir.join(name))
                 .ok()
@@ -214,6 +487,63 @@ pub fn get_status() -> Option<ZswapStatus> {
     Some(status)
 }
 
+/// Start a background thread that periodically logs zswap statistics.
+/// Useful for observing pool growth and compression ratio.
+pub fn spawn_monitor() {
+    use std::thread;
+    use std::time::Duration;
+
+    crate::context::spawn(move || {
+        // Initial delay to let zswap settle
+        thread::sleep(Duration::from_secs(10));
+
+        let mut last_wb_pages: u64 = 0;
+        let mut last_pool_limit: u64 = 0;
+
+        loop {
+            match get_status() {
+                Some(status) => {
+                    status.log_summary();
+
+                    // Warn if zswap shrinker is writing back pages rapidly
+                    if status.written_back_pages > last_wb_pages + 1000 {
+                        info!(
+                            "Zswap: shrinker wrote {} pages to disk swap",
+                            status.written_back_pages - last_wb_pages
+                        );
+                    }
+                    last_wb_pages = status.written_back_pages;
+
+                    // Warn if pool limit is being hit repeatedly. When swap slots
+                    // are what ran out, a larger pool would not help.
+                    if status.pool_limit_hit > last_pool_limit {
+                        let slots = crate::meminfo::get_effective_swap_usage()
+                            .ok()
+                            .filter(|u| u.slots_exhausted());
+                        match slots {
+                            Some(usage) => warn!(
+                                "Zswap: pool limit hit {} more time(s) with only {}% of swap slots free - slots are the limit, not max_pool_percent",
+                                status.pool_limit_hit - last_pool_limit,
+                                usage.slot_free_percent()
+                            ),
+                            None => warn!(
+                                "Zswap: pool limit hit {} more time(s) - consider increasing max_pool_percent",
+                                status.pool_limit_hit - last_pool_limit
+                            ),
+                        }
+                    }
+                    last_pool_limit = status.pool_limit_hit;
+                }
+                None => {
+                    warn!("Zswap monitor: failed to read status");
+                }
+            }
+
+            thread::sleep(Duration::from_secs(30));
+        }
+    });
+}
+
 /// Zswap status information
 #[derive(Debug, Default)]
 pub struct ZswapStatus {
@@ -226,6 +556,8 @@ pub struct ZswapStatus {
     pub accept_threshold_percent: u8,
 
     // Runtime statistics (from debugfs, requires root)
+    /// Whether the statistics below could be read
+    pub debugfs: bool,
     /// Total bytes used by zswap pool in RAM
     pub pool_size: u64,
     /// Pages currently stored in zswap pool
@@ -249,6 +581,17 @@ pub struct ZswapStatus {
 }
 
 impl ZswapStatus {
+    /// Event counters, None without debugfs access
+    pub fn counters(&self) -> Option<ZswapCounters> {
+        self.debugfs.then_some(ZswapCounters {
+            written_back_pages: self.written_back_pages,
+            reject_compress_fail: self.reject_compress_fail,
+            reject_compress_poor: self.reject_compress_poor,
+            reject_reclaim_fail: self.reject_reclaim_fail,
+            pool_limit_hit: self.pool_limit_hit,
+        })
+    }
+
     /// Physical RAM usage by pool (percentage of total RAM)
     pub fn ram_usage_percent(&self) -> f64 {
         let ram_bytes = crate::meminfo::get_ram_size().unwrap_or(1);
@@ -292,3 +635,58 @@ impl ZswapStatus {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fragment_reflects_configured_params() {
+        let config = Config::from_values(HashMap::from([
+            ("zswap_compressor".to_string(), "lz4".to_string()),
+            ("zswap_max_pool_percent".to_string(), "30".to_string()),
+        ]));
+        let params = ZswapParams::from_config(&config).module_params();
+        let fragment = render_fragment(&params);
+        assert!(fragment.starts_with(FRAGMENT_HEADER));
+        assert!(fragment.ends_with(
+            "options zswap enabled=1 compressor=lz4 zpool=zsmalloc max_pool_percent=30 \
+             shrinker_enabled=1 accept_threshold_percent=80\n"
+        ));
+        assert!(fragment.contains("#   zswap.enabled=1 zswap.compressor=lz4 zswap.zpool=zsmalloc"));
+
+        let off = Config::from_values(HashMap::from([("zswap_enabled".to_string(), "no".to_string())]));
+        assert!(!ZswapParams::from_config(&off).enabled);
+    }
+
+    #[test]
+    fn counter_deltas_since_baseline() {
+        let baseline = ZswapCounters {
+            written_back_pages: 500,
+            reject_compress_poor: 20,
+            pool_limit_hit: 3,
+            ..ZswapCounters::default()
+        };
+        let restored = ZswapCounters::from_file(&baseline.to_file());
+        assert_eq!(restored, baseline);
+
+        let now = ZswapCounters {
+            written_back_pages: 800,
+            reject_compress_poor: 25,
+            reject_reclaim_fail: 4,
+            pool_limit_hit: 3,
+            ..ZswapCounters::default()
+        };
+        let delta = now.since(&baseline);
+        assert_eq!(delta.written_back_pages, 300);
+        assert_eq!(delta.rejects(), 9);
+        assert_eq!(delta.pool_limit_hit, 0);
+
+        // zswap reloaded: counters restarted below the baseline
+        let reset = ZswapCounters {
+            written_back_pages: 40,
+            ..ZswapCounters::default()
+        };
+        assert_eq!(reset.since(&baseline).written_back_pages, 40);
+    }
+}